@@ -5,7 +5,9 @@
 //! claude -p --dangerously-skip-permissions --model opus --output-format stream-json
 //! ```
 //!
-//! The prompt is piped via stdin.
+//! The prompt is piped via stdin. With `resume_session`, the session id from
+//! one iteration's JSON output is passed back as `--resume <id>` on the next.
+//! `command_template` replaces this layout when set.
 //!
 //! See: <https://docs.anthropic.com/en/docs/claude-code>
 
@@ -16,18 +18,32 @@ use std::process::Stdio;
 use tokio::io::AsyncWriteExt;
 use tracing::{debug, info, warn};
 
+use super::stream;
+use super::template::HostCommand;
+use super::usage::{parse_usage, TokenUsage};
 use super::AgentProvider;
 use crate::config::ClaudeConfig;
+use crate::sandbox::SandboxError;
 
 /// Claude Code CLI agent provider.
 pub(crate) struct ClaudeProvider {
     config: ClaudeConfig,
+    stream_output: bool,
 }
 
 impl ClaudeProvider {
     /// Creates a new Claude provider with the given configuration.
     pub(crate) fn new(config: ClaudeConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            stream_output: false,
+        }
+    }
+
+    /// Echoes agent stdout to the terminal as it arrives.
+    pub(crate) fn with_stream_output(mut self, stream_output: bool) -> Self {
+        self.stream_output = stream_output;
+        self
     }
 }
 
@@ -37,42 +53,43 @@ impl AgentProvider for ClaudeProvider {
         "Claude"
     }
 
-    async fn invoke(&self, project_dir: &Path, prompt: &str) -> Result<String> {
-        let claude_path = &self.config.path;
+    async fn invoke(
+        &self,
+        project_dir: &Path,
+        prompt: &str,
+        iteration: u32,
+        session: Option<&str>,
+    ) -> Result<String> {
+        let command = if self.config.command_template.is_empty() {
+            HostCommand::new(
+                &self.config.path,
+                build_args(&self.config, iteration, session),
+                true,
+            )
+        } else {
+            HostCommand::from_template(
+                &self.config.command_template,
+                &self.config.path,
+                prompt,
+                self.config.model_for_iteration(iteration),
+                &self.config.output_format,
+            )?
+        };
+        let claude_path = &command.program;
         info!("Running Claude agent: {}", claude_path);
         debug!("Project dir: {}", project_dir.display());
+        debug!("Claude args: {:?}", command.args);
 
-        // Build command arguments
-        // claude -p [--dangerously-skip-permissions] [--model model] [--output-format format]
-        let mut args = vec!["-p".to_string()];
-
-        // Add dangerous skip permissions flag (required for autonomous operation)
-        if self.config.skip_permissions {
-            args.push("--dangerously-skip-permissions".to_string());
-        }
-
-        // Add model if configured
-        if let Some(ref model) = self.config.model {
-            args.push("--model".to_string());
-            args.push(model.clone());
-        }
-
-        // Add output format
-        args.push("--output-format".to_string());
-        args.push(self.config.output_format.clone());
-
-        // Add verbose flag if configured
-        if self.config.verbose {
-            args.push("--verbose".to_string());
-        }
-
-        debug!("Claude args: {:?}", args);
-
-        // Claude reads prompt from stdin
+        // Claude reads prompt from stdin unless the template passes it
         let mut child = tokio::process::Command::new(claude_path)
             .current_dir(project_dir)
-            .args(&args)
-            .stdin(Stdio::piped())
+            .kill_on_drop(true)
+            .args(&command.args)
+            .stdin(if command.prompt_on_stdin {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()
@@ -97,7 +114,7 @@ impl AgentProvider for ClaudeProvider {
             stdin.flush().await?;
         }
 
-        let output = child.wait_with_output().await?;
+        let output = stream::wait_with_output(child, self.stream_output).await?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -118,10 +135,11 @@ impl AgentProvider for ClaudeProvider {
 
             warn!("Agent stderr: {}", stderr);
             warn!("Agent stdout: {}", stdout);
-            anyhow::bail!(
-                "Claude agent failed with exit code {:?}:\n{}",
-                output.status.code(),
-                stderr
+            let code = stream::exit_code(output.status);
+            return Err(
+                anyhow::Error::new(SandboxError::non_zero_exit(code)).context(format!(
+                    "Claude agent failed with exit code {code}:\n{stderr}"
+                )),
             );
         }
 
@@ -131,6 +149,85 @@ impl AgentProvider for ClaudeProvider {
 
         Ok(stdout)
     }
+
+    fn token_usage(&self, output: &str) -> TokenUsage {
+        parse_usage(&self.config.output_format, output)
+    }
+
+    fn session_id(&self, output: &str) -> Option<String> {
+        if self.config.resume_session {
+            parse_session_id(output)
+        } else {
+            None
+        }
+    }
+}
+
+/// Session id from `json` or `stream-json` output.
+///
+/// Takes the whole output as one envelope, else the last NDJSON line with a
+/// `session_id`. Ids are checked to be plain tokens, since they end up in a
+/// shell command inside the sandbox.
+pub(crate) fn parse_session_id(output: &str) -> Option<String> {
+    let output = output.trim();
+    envelope_session_id(output)
+        .or_else(|| output.lines().rev().find_map(envelope_session_id))
+        .filter(|id| is_valid_session_id(id))
+}
+
+/// Whether `id` is non-empty and only letters, digits, `-`, and `_`.
+pub(crate) fn is_valid_session_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+// -----------------------------------------------------------------------------
+// Helper functions
+// -----------------------------------------------------------------------------
+
+/// Builds the CLI arguments:
+/// `-p [--dangerously-skip-permissions] [--model m] --output-format f [--verbose] [--resume id]`.
+fn build_args(config: &ClaudeConfig, iteration: u32, session: Option<&str>) -> Vec<String> {
+    let mut args = vec!["-p".to_string()];
+
+    // Add dangerous skip permissions flag (required for autonomous operation)
+    if config.skip_permissions {
+        args.push("--dangerously-skip-permissions".to_string());
+    }
+
+    // Add model if configured
+    if let Some(model) = config.model_for_iteration(iteration) {
+        args.push("--model".to_string());
+        args.push(model.to_string());
+    }
+
+    // Add output format
+    args.push("--output-format".to_string());
+    args.push(config.output_format.clone());
+
+    // Add verbose flag if configured
+    if config.verbose {
+        args.push("--verbose".to_string());
+    }
+
+    // Continue the previous iteration's conversation
+    if let (true, Some(id)) = (config.resume_session, session) {
+        args.push("--resume".to_string());
+        args.push(id.to_string());
+    }
+
+    args
+}
+
+/// Top-level `session_id` of a JSON envelope, if present.
+fn envelope_session_id(json: &str) -> Option<String> {
+    serde_json::from_str::<serde_json::Value>(json)
+        .ok()?
+        .get("session_id")?
+        .as_str()
+        .map(String::from)
 }
 
 #[cfg(test)]
@@ -149,10 +246,13 @@ mod tests {
         let config = ClaudeConfig {
             path: "/custom/claude".to_string(),
             model: Some("sonnet".to_string()),
+            models: Vec::new(),
             skip_permissions: false,
             output_format: "json".to_string(),
             verbose: true,
             timeout_minutes: Some(90),
+            resume_session: true,
+            command_template: Vec::new(),
         };
         let provider = ClaudeProvider::new(config.clone());
         assert_eq!(provider.config.path, "/custom/claude");
@@ -173,28 +273,10 @@ mod tests {
         assert!(!config.verbose);
     }
 
-    /// Test that `build_args` produces correct arguments
-    fn build_args(config: &ClaudeConfig) -> Vec<String> {
-        let mut args = vec!["-p".to_string()];
-        if config.skip_permissions {
-            args.push("--dangerously-skip-permissions".to_string());
-        }
-        if let Some(ref model) = config.model {
-            args.push("--model".to_string());
-            args.push(model.clone());
-        }
-        args.push("--output-format".to_string());
-        args.push(config.output_format.clone());
-        if config.verbose {
-            args.push("--verbose".to_string());
-        }
-        args
-    }
-
     #[test]
     fn test_build_args_default() {
         let config = ClaudeConfig::default();
-        let args = build_args(&config);
+        let args = build_args(&config, 1, None);
         assert!(args.contains(&"-p".to_string()));
         assert!(args.contains(&"--dangerously-skip-permissions".to_string()));
         assert!(args.contains(&"--model".to_string()));
@@ -210,7 +292,7 @@ mod tests {
             skip_permissions: false,
             ..Default::default()
         };
-        let args = build_args(&config);
+        let args = build_args(&config, 1, None);
         assert!(!args.contains(&"--dangerously-skip-permissions".to_string()));
     }
 
@@ -220,7 +302,7 @@ mod tests {
             model: None,
             ..Default::default()
         };
-        let args = build_args(&config);
+        let args = build_args(&config, 1, None);
         assert!(!args.contains(&"--model".to_string()));
     }
 
@@ -230,7 +312,7 @@ mod tests {
             verbose: true,
             ..Default::default()
         };
-        let args = build_args(&config);
+        let args = build_args(&config, 1, None);
         assert!(args.contains(&"--verbose".to_string()));
     }
 
@@ -240,10 +322,92 @@ mod tests {
             output_format: "text".to_string(),
             ..Default::default()
         };
-        let args = build_args(&config);
+        let args = build_args(&config, 1, None);
         assert!(args.contains(&"text".to_string()));
     }
 
+    #[test]
+    fn test_build_args_with_resume() {
+        let config = ClaudeConfig {
+            output_format: "json".to_string(),
+            resume_session: true,
+            ..Default::default()
+        };
+        let args = build_args(&config, 2, Some("4f1c2a9e-0b7d-4e3a-9c11-2d5e8f6a7b30"));
+        assert_eq!(
+            &args[args.len() - 2..],
+            ["--resume", "4f1c2a9e-0b7d-4e3a-9c11-2d5e8f6a7b30"]
+        );
+
+        // First iteration: nothing to resume yet
+        assert!(!build_args(&config, 1, None).contains(&"--resume".to_string()));
+    }
+
+    #[test]
+    fn test_build_args_resume_disabled_ignores_session() {
+        let config = ClaudeConfig::default();
+        let args = build_args(&config, 2, Some("abc"));
+        assert!(!args.contains(&"--resume".to_string()));
+    }
+
+    #[test]
+    fn test_parse_session_id_json_envelope() {
+        let output = r#"{"type":"result","subtype":"success","is_error":false,"result":"Done.","session_id":"4f1c2a9e-0b7d-4e3a-9c11-2d5e8f6a7b30","usage":{"input_tokens":10,"output_tokens":5}}"#;
+        assert_eq!(
+            parse_session_id(output).as_deref(),
+            Some("4f1c2a9e-0b7d-4e3a-9c11-2d5e8f6a7b30")
+        );
+    }
+
+    #[test]
+    fn test_parse_session_id_stream_json() {
+        let output = "{\"type\":\"system\",\"subtype\":\"init\",\"session_id\":\"sess-1\"}\n\
+                      {\"type\":\"assistant\",\"message\":{}}\n\
+                      {\"type\":\"result\",\"session_id\":\"sess-1\"}\n";
+        assert_eq!(parse_session_id(output).as_deref(), Some("sess-1"));
+    }
+
+    #[test]
+    fn test_parse_session_id_missing_or_unsafe() {
+        assert_eq!(parse_session_id("plain text output"), None);
+        assert_eq!(parse_session_id(r#"{"type":"result","result":"ok"}"#), None);
+        assert_eq!(parse_session_id(r#"{"session_id":"x; rm -rf /"}"#), None);
+        assert_eq!(parse_session_id(r#"{"session_id":""}"#), None);
+    }
+
+    #[test]
+    fn test_session_id_requires_resume_session() {
+        let output = r#"{"type":"result","session_id":"abc-123"}"#;
+        let provider = ClaudeProvider::new(ClaudeConfig::default());
+        assert_eq!(provider.session_id(output), None);
+
+        let provider = ClaudeProvider::new(ClaudeConfig {
+            resume_session: true,
+            output_format: "json".to_string(),
+            ..Default::default()
+        });
+        assert_eq!(provider.session_id(output).as_deref(), Some("abc-123"));
+    }
+
+    #[test]
+    fn test_token_usage_json_output() {
+        let provider = ClaudeProvider::new(ClaudeConfig {
+            output_format: "json".to_string(),
+            ..Default::default()
+        });
+        let output =
+            r#"{"type":"result","result":"ok","usage":{"input_tokens":10,"output_tokens":5}}"#;
+        let usage = provider.token_usage(output);
+        assert_eq!(usage.input_tokens, 10);
+        assert_eq!(usage.output_tokens, 5);
+    }
+
+    #[test]
+    fn test_token_usage_text_output_is_zero() {
+        let provider = ClaudeProvider::new(ClaudeConfig::default());
+        assert_eq!(provider.token_usage("plain text"), TokenUsage::default());
+    }
+
     #[tokio::test]
     async fn test_invoke_nonexistent_binary() {
         let config = ClaudeConfig {
@@ -252,7 +416,7 @@ mod tests {
         };
         let provider = ClaudeProvider::new(config);
         let result = provider
-            .invoke(std::path::Path::new("/tmp"), "test prompt")
+            .invoke(std::path::Path::new("/tmp"), "test prompt", 1, None)
             .await;
         assert!(result.is_err());
         let err = result.unwrap_err().to_string();
@@ -280,7 +444,7 @@ mod tests {
         let provider = ClaudeProvider::new(config);
 
         let result = provider
-            .invoke(temp_dir.path(), "test prompt from stdin")
+            .invoke(temp_dir.path(), "test prompt from stdin", 1, None)
             .await;
 
         assert!(result.is_ok(), "Expected success, got: {result:?}");
@@ -309,7 +473,7 @@ mod tests {
         };
         let provider = ClaudeProvider::new(config);
 
-        let result = provider.invoke(temp_dir.path(), "test").await;
+        let result = provider.invoke(temp_dir.path(), "test", 1, None).await;
 
         assert!(result.is_err());
         let err = result.unwrap_err().to_string();
@@ -340,7 +504,7 @@ mod tests {
         let project_dir = temp_dir.path().join("project");
         std::fs::create_dir(&project_dir).unwrap();
 
-        let result = provider.invoke(&project_dir, "ignored").await;
+        let result = provider.invoke(&project_dir, "ignored", 1, None).await;
 
         assert!(result.is_ok());
         let output = result.unwrap();
@@ -349,4 +513,66 @@ mod tests {
             "Expected working dir to be project, got: {output}"
         );
     }
+
+    #[tokio::test]
+    async fn test_invoke_propagates_exit_code() {
+        // Skip in nix sandbox where shell scripts don't work
+        if crate::agent::is_nix_sandbox() {
+            return;
+        }
+
+        // Output that looks benign must not hide a non-zero exit
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mock_path = temp_dir.path().join("mock-claude-exit");
+        crate::agent::create_mock_executable(
+            &mock_path,
+            b"#!/usr/bin/env sh\necho 'All done'\nexit 3\n",
+        );
+
+        let config = ClaudeConfig {
+            path: mock_path.to_str().unwrap().to_string(),
+            ..Default::default()
+        };
+        let provider = ClaudeProvider::new(config);
+
+        let err = provider
+            .invoke(temp_dir.path(), "test", 1, None)
+            .await
+            .unwrap_err();
+        let sandbox_err = err.downcast_ref::<SandboxError>().unwrap();
+        assert!(sandbox_err.is_non_zero_exit());
+        assert_eq!(sandbox_err.exit_code(), Some(3));
+        assert!(err.to_string().contains("exit code 3"));
+    }
+
+    #[tokio::test]
+    async fn test_invoke_rotates_models_by_iteration() {
+        // Skip in nix sandbox where shell scripts don't work
+        if crate::agent::is_nix_sandbox() {
+            return;
+        }
+
+        // Mock binary that prints its arguments
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mock_path = temp_dir.path().join("mock-claude-args");
+        crate::agent::create_mock_executable(&mock_path, b"#!/usr/bin/env sh\necho \"$@\"\n");
+
+        let config = ClaudeConfig {
+            path: mock_path.to_str().unwrap().to_string(),
+            models: vec!["gpt-5".to_string(), "sonnet".to_string()],
+            ..Default::default()
+        };
+        let provider = ClaudeProvider::new(config);
+
+        let first = provider
+            .invoke(temp_dir.path(), "p", 1, None)
+            .await
+            .unwrap();
+        let second = provider
+            .invoke(temp_dir.path(), "p", 2, None)
+            .await
+            .unwrap();
+        assert!(first.contains("--model gpt-5"), "got: {first}");
+        assert!(second.contains("--model sonnet"), "got: {second}");
+    }
 }
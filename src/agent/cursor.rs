@@ -5,47 +5,68 @@
 //! agent -p "prompt" --output-format text
 //! ```
 //!
+//! `command_template` replaces this layout when set.
+//!
 //! See: <https://cursor.com/docs/cli/overview>
 
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use std::path::Path;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
 use tracing::{debug, info, warn};
 
+use super::stream;
+use super::template::HostCommand;
+use super::usage::{parse_usage, TokenUsage};
 use super::AgentProvider;
 use crate::config::CursorConfig;
+use crate::sandbox::SandboxError;
 
 /// Cursor CLI agent provider.
 pub(crate) struct CursorProvider {
     config: CursorConfig,
+    stream_output: bool,
 }
 
 impl CursorProvider {
     /// Creates a new Cursor provider with the given configuration.
     pub(crate) fn new(config: CursorConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            stream_output: false,
+        }
     }
-}
 
-#[async_trait]
-impl AgentProvider for CursorProvider {
-    fn name(&self) -> &'static str {
-        "Cursor"
+    /// Echoes agent stdout to the terminal as it arrives.
+    pub(crate) fn with_stream_output(mut self, stream_output: bool) -> Self {
+        self.stream_output = stream_output;
+        self
     }
+}
 
-    async fn invoke(&self, project_dir: &Path, prompt: &str) -> Result<String> {
-        let agent_path = &self.config.path;
-        info!("Running Cursor agent: {}", agent_path);
-        debug!("Project dir: {}", project_dir.display());
+impl CursorProvider {
+    /// The command line for `iteration`, from `command_template` when set.
+    fn command(&self, prompt: &str, iteration: u32) -> Result<HostCommand> {
+        let model = self.config.model_for_iteration(iteration);
+        if !self.config.command_template.is_empty() {
+            return HostCommand::from_template(
+                &self.config.command_template,
+                &self.config.path,
+                prompt,
+                model,
+                &self.config.output_format,
+            );
+        }
 
         // Build command arguments for print mode
         // agent -p "prompt" [--model "model"] [--sandbox mode] --output-format text
         let mut args = vec!["-p".to_string(), prompt.to_string()];
 
         // Add model if configured
-        if let Some(ref model) = self.config.model {
+        if let Some(model) = model {
             args.push("--model".to_string());
-            args.push(model.clone());
+            args.push(model.to_string());
         }
 
         // Add sandbox mode (disabled by default to allow shell access for validation)
@@ -58,13 +79,41 @@ impl AgentProvider for CursorProvider {
         args.push("--output-format".to_string());
         args.push(self.config.output_format.clone());
 
-        debug!("Agent args: {:?}", args);
+        Ok(HostCommand::new(&self.config.path, args, false))
+    }
+}
+
+#[async_trait]
+impl AgentProvider for CursorProvider {
+    fn name(&self) -> &'static str {
+        "Cursor"
+    }
+
+    async fn invoke(
+        &self,
+        project_dir: &Path,
+        prompt: &str,
+        iteration: u32,
+        _session: Option<&str>,
+    ) -> Result<String> {
+        let command = self.command(prompt, iteration)?;
+        let agent_path = &command.program;
+        info!("Running Cursor agent: {}", agent_path);
+        debug!("Project dir: {}", project_dir.display());
+        debug!("Agent args: {:?}", command.args);
 
-        let output = tokio::process::Command::new(agent_path)
+        let mut child = tokio::process::Command::new(agent_path)
             .current_dir(project_dir)
-            .args(&args)
-            .output()
-            .await
+            .kill_on_drop(true)
+            .args(&command.args)
+            .stdin(if command.prompt_on_stdin {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
             .with_context(|| {
                 format!(
                     "Failed to run Cursor agent '{agent_path}'. \n\
@@ -78,6 +127,14 @@ impl AgentProvider for CursorProvider {
                 )
             })?;
 
+        // Only a command template without {prompt} or {prompt_file} reads stdin
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(prompt.as_bytes()).await?;
+            stdin.flush().await?;
+        }
+
+        let output = stream::wait_with_output(child, self.stream_output).await?;
+
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
             let stdout = String::from_utf8_lossy(&output.stdout);
@@ -98,10 +155,11 @@ impl AgentProvider for CursorProvider {
 
             warn!("Agent stderr: {}", stderr);
             warn!("Agent stdout: {}", stdout);
-            anyhow::bail!(
-                "Cursor agent failed with exit code {:?}:\n{}",
-                output.status.code(),
-                stderr
+            let code = stream::exit_code(output.status);
+            return Err(
+                anyhow::Error::new(SandboxError::non_zero_exit(code)).context(format!(
+                    "Cursor agent failed with exit code {code}:\n{stderr}"
+                )),
             );
         }
 
@@ -111,6 +169,10 @@ impl AgentProvider for CursorProvider {
 
         Ok(stdout)
     }
+
+    fn token_usage(&self, output: &str) -> TokenUsage {
+        parse_usage(&self.config.output_format, output)
+    }
 }
 
 #[cfg(test)]
@@ -129,9 +191,11 @@ mod tests {
         let config = CursorConfig {
             path: "/custom/agent".to_string(),
             model: Some("gpt-4".to_string()),
+            models: Vec::new(),
             sandbox: "on".to_string(),
             output_format: "json".to_string(),
             timeout_minutes: Some(30),
+            command_template: Vec::new(),
         };
         let provider = CursorProvider::new(config.clone());
         assert_eq!(provider.config.path, "/custom/agent");
@@ -150,6 +214,25 @@ mod tests {
         assert_eq!(config.output_format, "text");
     }
 
+    #[test]
+    fn test_token_usage_json_output() {
+        let provider = CursorProvider::new(CursorConfig {
+            output_format: "json".to_string(),
+            ..Default::default()
+        });
+        let output =
+            r#"{"type":"result","result":"ok","usage":{"inputTokens":30,"outputTokens":7}}"#;
+        let usage = provider.token_usage(output);
+        assert_eq!(usage.input_tokens, 30);
+        assert_eq!(usage.output_tokens, 7);
+    }
+
+    #[test]
+    fn test_token_usage_text_output_is_zero() {
+        let provider = CursorProvider::new(CursorConfig::default());
+        assert_eq!(provider.token_usage("plain text"), TokenUsage::default());
+    }
+
     /// Test argument building logic
     fn build_args(config: &CursorConfig, prompt: &str) -> Vec<String> {
         let mut args = vec!["-p".to_string(), prompt.to_string()];
@@ -226,7 +309,7 @@ mod tests {
         };
         let provider = CursorProvider::new(config);
         let result = provider
-            .invoke(std::path::Path::new("/tmp"), "test prompt")
+            .invoke(std::path::Path::new("/tmp"), "test prompt", 1, None)
             .await;
         assert!(result.is_err());
         let err = result.unwrap_err().to_string();
@@ -255,7 +338,7 @@ mod tests {
         let provider = CursorProvider::new(config);
 
         let result = provider
-            .invoke(temp_dir.path(), "test prompt from args")
+            .invoke(temp_dir.path(), "test prompt from args", 1, None)
             .await;
 
         assert!(result.is_ok(), "Expected success, got: {result:?}");
@@ -263,6 +346,40 @@ mod tests {
         assert_eq!(result.unwrap().trim(), "test prompt from args");
     }
 
+    #[tokio::test]
+    async fn test_invoke_with_command_template() {
+        // Skip in nix sandbox where shell scripts don't work
+        if crate::agent::is_nix_sandbox() {
+            return;
+        }
+
+        // The template passes no prompt, so it arrives on stdin
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mock_path = temp_dir.path().join("mock-cursor");
+        crate::agent::create_mock_executable(&mock_path, b"#!/usr/bin/env sh\necho \"$@\"\ncat\n");
+
+        let config = CursorConfig {
+            path: mock_path.to_str().unwrap().to_string(),
+            command_template: [
+                "{path}",
+                "--format",
+                "{output_format}",
+                "--model",
+                "{model}",
+            ]
+            .map(String::from)
+            .to_vec(),
+            ..Default::default()
+        };
+        let provider = CursorProvider::new(config);
+
+        let output = provider
+            .invoke(temp_dir.path(), "prompt via stdin", 1, None)
+            .await
+            .unwrap();
+        assert_eq!(output, "--format text\nprompt via stdin");
+    }
+
     #[tokio::test]
     async fn test_invoke_with_mock_binary_failure() {
         // Skip in nix sandbox where shell scripts don't work
@@ -285,7 +402,7 @@ mod tests {
         };
         let provider = CursorProvider::new(config);
 
-        let result = provider.invoke(temp_dir.path(), "test").await;
+        let result = provider.invoke(temp_dir.path(), "test", 1, None).await;
 
         assert!(result.is_err());
         let err = result.unwrap_err().to_string();
@@ -316,7 +433,7 @@ mod tests {
         let project_dir = temp_dir.path().join("workspace");
         std::fs::create_dir(&project_dir).unwrap();
 
-        let result = provider.invoke(&project_dir, "ignored").await;
+        let result = provider.invoke(&project_dir, "ignored", 1, None).await;
 
         assert!(result.is_ok());
         let output = result.unwrap();
@@ -325,4 +442,66 @@ mod tests {
             "Expected working dir to be workspace, got: {output}"
         );
     }
+
+    #[tokio::test]
+    async fn test_invoke_propagates_exit_code() {
+        // Skip in nix sandbox where shell scripts don't work
+        if crate::agent::is_nix_sandbox() {
+            return;
+        }
+
+        // Output that looks benign must not hide a non-zero exit
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mock_path = temp_dir.path().join("mock-cursor-exit");
+        crate::agent::create_mock_executable(
+            &mock_path,
+            b"#!/usr/bin/env sh\necho 'All done'\nexit 3\n",
+        );
+
+        let config = CursorConfig {
+            path: mock_path.to_str().unwrap().to_string(),
+            ..Default::default()
+        };
+        let provider = CursorProvider::new(config);
+
+        let err = provider
+            .invoke(temp_dir.path(), "test", 1, None)
+            .await
+            .unwrap_err();
+        let sandbox_err = err.downcast_ref::<SandboxError>().unwrap();
+        assert!(sandbox_err.is_non_zero_exit());
+        assert_eq!(sandbox_err.exit_code(), Some(3));
+        assert!(err.to_string().contains("exit code 3"));
+    }
+
+    #[tokio::test]
+    async fn test_invoke_rotates_models_by_iteration() {
+        // Skip in nix sandbox where shell scripts don't work
+        if crate::agent::is_nix_sandbox() {
+            return;
+        }
+
+        // Mock binary that prints its arguments
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mock_path = temp_dir.path().join("mock-cursor-args");
+        crate::agent::create_mock_executable(&mock_path, b"#!/usr/bin/env sh\necho \"$@\"\n");
+
+        let config = CursorConfig {
+            path: mock_path.to_str().unwrap().to_string(),
+            models: vec!["gpt-5".to_string(), "sonnet".to_string()],
+            ..Default::default()
+        };
+        let provider = CursorProvider::new(config);
+
+        let first = provider
+            .invoke(temp_dir.path(), "p", 1, None)
+            .await
+            .unwrap();
+        let second = provider
+            .invoke(temp_dir.path(), "p", 2, None)
+            .await
+            .unwrap();
+        assert!(first.contains("--model gpt-5"), "got: {first}");
+        assert!(second.contains("--model sonnet"), "got: {second}");
+    }
 }
@@ -1,7 +1,8 @@
-//! Mock agent provider for testing.
+//! Mock agent provider for testing and `ralph loop --simulate`.
 //!
 //! Provides a configurable mock that returns predetermined responses
-//! for E2E loop testing without invoking real agent CLIs.
+//! for E2E loop testing without invoking real agent CLIs. The simulated
+//! agent cycles through canned outputs and never touches the project.
 
 use anyhow::Result;
 use async_trait::async_trait;
@@ -11,6 +12,12 @@ use std::sync::Arc;
 
 use super::AgentProvider;
 
+/// Outputs the simulated agent returns, one per iteration in turn.
+const SIMULATED_OUTPUTS: &[&str] = &[
+    "[simulated] Read the prompt and the implementation plan.",
+    "[simulated] Nothing left to do; making no changes.",
+];
+
 /// A mock agent provider for testing.
 ///
 /// Returns configurable responses and tracks invocations for test assertions.
@@ -26,6 +33,7 @@ pub(crate) struct MockAgentProvider {
 
 /// A single mock response configuration.
 #[derive(Debug, Clone)]
+#[cfg_attr(not(test), allow(dead_code))] // Only `Success` is used outside tests
 pub(crate) enum MockResponse {
     /// Return a successful response with the given output.
     Success(String),
@@ -35,6 +43,8 @@ pub(crate) enum MockResponse {
     Timeout,
     /// Return an error that looks like a rate limit.
     RateLimit,
+    /// Fail as if the agent exited with the given non-zero code.
+    Crash(i64),
     /// Succeed but also cancel the loop (sets state.active = false).
     /// Used to test external cancellation during loop execution.
     SuccessAndCancel(String, PathBuf),
@@ -52,17 +62,33 @@ impl MockAgentProvider {
         }
     }
 
+    /// Create the deterministic agent used by `ralph loop --simulate`.
+    pub fn simulated() -> Self {
+        Self {
+            name: "Simulated",
+            ..Self::new(
+                SIMULATED_OUTPUTS
+                    .iter()
+                    .map(|output| MockResponse::Success((*output).to_string()))
+                    .collect(),
+            )
+        }
+    }
+
     /// Create a mock that always succeeds with the given output.
+    #[cfg(test)]
     pub fn always_succeed(output: &str) -> Self {
         Self::new(vec![MockResponse::Success(output.to_string())])
     }
 
     /// Create a mock that always fails with the given error.
+    #[cfg(test)]
     pub fn always_fail(error: &str) -> Self {
         Self::new(vec![MockResponse::Error(error.to_string())])
     }
 
     /// Get the number of times `invoke` was called.
+    #[cfg(test)]
     pub fn invocation_count(&self) -> usize {
         self.invocation_count.load(Ordering::SeqCst)
     }
@@ -74,7 +100,13 @@ impl AgentProvider for MockAgentProvider {
         self.name
     }
 
-    async fn invoke(&self, _project_dir: &Path, _prompt: &str) -> Result<String> {
+    async fn invoke(
+        &self,
+        _project_dir: &Path,
+        _prompt: &str,
+        _iteration: u32,
+        _session: Option<&str>,
+    ) -> Result<String> {
         let count = self.invocation_count.fetch_add(1, Ordering::SeqCst);
         let response = &self.responses[count % self.responses.len()];
 
@@ -85,6 +117,9 @@ impl AgentProvider for MockAgentProvider {
             MockResponse::RateLimit => {
                 anyhow::bail!("rate limit exceeded (resource_exhausted)")
             }
+            MockResponse::Crash(code) => {
+                Err(crate::sandbox::SandboxError::non_zero_exit(*code).into())
+            }
             MockResponse::SuccessAndCancel(output, project_dir) => {
                 // Simulate external cancellation by setting state.active = false
                 if let Some(mut state) = crate::state::RalphState::load(project_dir)? {
@@ -107,11 +142,25 @@ mod tests {
         assert_eq!(provider.name(), "Mock");
     }
 
+    #[tokio::test]
+    async fn test_simulated_provider_cycles_canned_outputs() {
+        let provider = MockAgentProvider::simulated();
+        assert_eq!(provider.name(), "Simulated");
+
+        let dir = Path::new("/tmp");
+        let first = provider.invoke(dir, "p", 1, None).await.unwrap();
+        let second = provider.invoke(dir, "p", 2, None).await.unwrap();
+        let third = provider.invoke(dir, "p", 3, None).await.unwrap();
+        assert_eq!(first, SIMULATED_OUTPUTS[0]);
+        assert_eq!(second, SIMULATED_OUTPUTS[1]);
+        assert_eq!(third, first);
+    }
+
     #[tokio::test]
     async fn test_mock_provider_success() {
         let provider = MockAgentProvider::always_succeed("test output");
         let result = provider
-            .invoke(Path::new("/tmp"), "test prompt")
+            .invoke(Path::new("/tmp"), "test prompt", 1, None)
             .await
             .unwrap();
         assert_eq!(result, "test output");
@@ -120,7 +169,9 @@ mod tests {
     #[tokio::test]
     async fn test_mock_provider_error() {
         let provider = MockAgentProvider::always_fail("test error");
-        let result = provider.invoke(Path::new("/tmp"), "test prompt").await;
+        let result = provider
+            .invoke(Path::new("/tmp"), "test prompt", 1, None)
+            .await;
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("test error"));
     }
@@ -132,9 +183,18 @@ mod tests {
             MockResponse::Success("second".to_string()),
         ]);
 
-        let r1 = provider.invoke(Path::new("/tmp"), "").await.unwrap();
-        let r2 = provider.invoke(Path::new("/tmp"), "").await.unwrap();
-        let r3 = provider.invoke(Path::new("/tmp"), "").await.unwrap();
+        let r1 = provider
+            .invoke(Path::new("/tmp"), "", 1, None)
+            .await
+            .unwrap();
+        let r2 = provider
+            .invoke(Path::new("/tmp"), "", 1, None)
+            .await
+            .unwrap();
+        let r3 = provider
+            .invoke(Path::new("/tmp"), "", 1, None)
+            .await
+            .unwrap();
 
         assert_eq!(r1, "first");
         assert_eq!(r2, "second");
@@ -146,17 +206,17 @@ mod tests {
         let provider = MockAgentProvider::always_succeed("ok");
         assert_eq!(provider.invocation_count(), 0);
 
-        let _ = provider.invoke(Path::new("/tmp"), "").await;
+        let _ = provider.invoke(Path::new("/tmp"), "", 1, None).await;
         assert_eq!(provider.invocation_count(), 1);
 
-        let _ = provider.invoke(Path::new("/tmp"), "").await;
+        let _ = provider.invoke(Path::new("/tmp"), "", 1, None).await;
         assert_eq!(provider.invocation_count(), 2);
     }
 
     #[tokio::test]
     async fn test_mock_provider_timeout() {
         let provider = MockAgentProvider::new(vec![MockResponse::Timeout]);
-        let result = provider.invoke(Path::new("/tmp"), "").await;
+        let result = provider.invoke(Path::new("/tmp"), "", 1, None).await;
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("timed out"));
     }
@@ -164,7 +224,7 @@ mod tests {
     #[tokio::test]
     async fn test_mock_provider_rate_limit() {
         let provider = MockAgentProvider::new(vec![MockResponse::RateLimit]);
-        let result = provider.invoke(Path::new("/tmp"), "").await;
+        let result = provider.invoke(Path::new("/tmp"), "", 1, None).await;
         assert!(result.is_err());
         assert!(result
             .unwrap_err()
@@ -3,16 +3,24 @@
 //! This module provides a unified interface for invoking different AI agent CLIs:
 //! - Cursor: `agent -p "prompt"`
 //! - Claude: `claude -p --dangerously-skip-permissions`
+//! - Ollama: HTTP `POST /api/generate` against a local Ollama server
 //!
 //! The provider is selected via `[agent].provider` in ralph.toml.
+//! CLI providers echo stdout live when `[monitoring].stream_output` is set,
+//! and `command_template` can replace their built-in flag layout.
 
 mod claude;
 mod cursor;
-#[cfg(test)]
 pub(crate) mod mock;
+mod ollama;
+pub(crate) mod stream;
+pub(crate) mod template;
+mod usage;
 
-pub(crate) use claude::ClaudeProvider;
+pub(crate) use claude::{is_valid_session_id, ClaudeProvider};
 pub(crate) use cursor::CursorProvider;
+pub(crate) use ollama::OllamaProvider;
+pub(crate) use usage::TokenUsage;
 
 use anyhow::Result;
 use async_trait::async_trait;
@@ -25,7 +33,30 @@ pub(crate) trait AgentProvider: Send + Sync {
     fn name(&self) -> &'static str;
 
     /// Invokes the agent with a prompt and returns the output.
-    async fn invoke(&self, project_dir: &Path, prompt: &str) -> Result<String>;
+    ///
+    /// `iteration` picks the model when the provider rotates through several.
+    /// `session` continues an earlier conversation, for providers that can.
+    async fn invoke(
+        &self,
+        project_dir: &Path,
+        prompt: &str,
+        iteration: u32,
+        session: Option<&str>,
+    ) -> Result<String>;
+
+    /// Extracts token usage from the output of a previous `invoke`.
+    ///
+    /// Defaults to zero for providers that do not report usage.
+    fn token_usage(&self, _output: &str) -> TokenUsage {
+        TokenUsage::default()
+    }
+
+    /// Extracts the session to resume on the next `invoke`.
+    ///
+    /// Defaults to `None` for providers without resumable sessions.
+    fn session_id(&self, _output: &str) -> Option<String> {
+        None
+    }
 }
 
 /// Supported agent providers.
@@ -36,6 +67,8 @@ pub(crate) enum Provider {
     Cursor,
     /// Claude Code CLI agent.
     Claude,
+    /// Local Ollama server (HTTP API, no CLI binary).
+    Ollama,
 }
 
 impl std::fmt::Display for Provider {
@@ -43,6 +76,7 @@ impl std::fmt::Display for Provider {
         match self {
             Self::Cursor => write!(f, "cursor"),
             Self::Claude => write!(f, "claude"),
+            Self::Ollama => write!(f, "ollama"),
         }
     }
 }
@@ -54,7 +88,8 @@ impl std::str::FromStr for Provider {
         match s.to_lowercase().as_str() {
             "cursor" => Ok(Self::Cursor),
             "claude" => Ok(Self::Claude),
-            _ => anyhow::bail!("Unknown agent provider: '{s}'. Supported: cursor, claude"),
+            "ollama" => Ok(Self::Ollama),
+            _ => anyhow::bail!("Unknown agent provider: '{s}'. Supported: cursor, claude, ollama"),
         }
     }
 }
@@ -104,6 +139,7 @@ mod tests {
     fn test_provider_display() {
         assert_eq!(format!("{}", Provider::Cursor), "cursor");
         assert_eq!(format!("{}", Provider::Claude), "claude");
+        assert_eq!(format!("{}", Provider::Ollama), "ollama");
     }
 
     #[test]
@@ -111,6 +147,7 @@ mod tests {
         assert_eq!("cursor".parse::<Provider>().unwrap(), Provider::Cursor);
         assert_eq!("claude".parse::<Provider>().unwrap(), Provider::Claude);
         assert_eq!("Claude".parse::<Provider>().unwrap(), Provider::Claude);
+        assert_eq!("ollama".parse::<Provider>().unwrap(), Provider::Ollama);
         assert!("unknown".parse::<Provider>().is_err());
     }
 }
@@ -0,0 +1,346 @@
+//! Ollama agent provider
+//!
+//! Talks to a local Ollama server over its HTTP API instead of spawning a CLI:
+//! ```text
+//! POST http://localhost:11434/api/generate
+//! {"model": "llama3.1", "prompt": "...", "stream": true}
+//! ```
+//!
+//! The streamed response is newline-delimited JSON; the `response` field of
+//! every chunk is concatenated into the returned output.
+//!
+//! See: <https://github.com/ollama/ollama/blob/main/docs/api.md>
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tracing::{debug, info, warn};
+
+use super::AgentProvider;
+use crate::config::OllamaConfig;
+
+/// Ollama HTTP API agent provider.
+pub(crate) struct OllamaProvider {
+    config: OllamaConfig,
+    client: reqwest::Client,
+}
+
+impl OllamaProvider {
+    /// Creates a new Ollama provider with the given configuration.
+    pub(crate) fn new(config: OllamaConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Returns the full URL of the generate endpoint.
+    fn generate_url(&self) -> String {
+        format!("{}/api/generate", self.config.host.trim_end_matches('/'))
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Internal types
+// -----------------------------------------------------------------------------
+
+/// Request body for `POST /api/generate`.
+#[derive(Debug, Serialize)]
+struct GenerateRequest<'a> {
+    model: &'a str,
+    prompt: &'a str,
+    stream: bool,
+}
+
+/// A single chunk of the streamed generate response.
+#[derive(Debug, Deserialize)]
+struct GenerateChunk {
+    #[serde(default)]
+    response: String,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+#[async_trait]
+impl AgentProvider for OllamaProvider {
+    fn name(&self) -> &'static str {
+        "Ollama"
+    }
+
+    #[allow(tail_expr_drop_order)] // Drop order changes are harmless for HTTP responses
+    async fn invoke(
+        &self,
+        project_dir: &Path,
+        prompt: &str,
+        _iteration: u32,
+        _session: Option<&str>,
+    ) -> Result<String> {
+        let url = self.generate_url();
+        info!("Running Ollama model '{}' at {}", self.config.model, url);
+        debug!("Project dir: {}", project_dir.display());
+
+        let request = GenerateRequest {
+            model: &self.config.model,
+            prompt,
+            stream: true,
+        };
+
+        let mut response = self
+            .client
+            .post(&url)
+            .json(&request)
+            .send()
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to reach Ollama at '{}'.\n\
+                     \n\
+                     Make sure the Ollama server is running:\n\
+                     - Install: https://ollama.com/download\n\
+                     - Start: ollama serve\n\
+                     - Or configure the host: [agent.ollama].host = \"http://host:11434\"",
+                    self.config.host
+                )
+            })?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            warn!("Ollama response body: {}", body);
+            anyhow::bail!(
+                "Ollama request failed with status {}:\n{}\n\
+                 \n\
+                 Make sure the model is available: ollama pull {}",
+                status,
+                body,
+                self.config.model
+            );
+        }
+
+        let mut output = String::new();
+        let mut buffer = String::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .context("Failed to read Ollama response stream")?
+        {
+            buffer.push_str(&String::from_utf8_lossy(&chunk));
+            while let Some(pos) = buffer.find('\n') {
+                let line: String = buffer.drain(..=pos).collect();
+                append_chunk(&mut output, &line)?;
+            }
+        }
+        append_chunk(&mut output, &buffer)?;
+
+        info!("Ollama agent completed successfully");
+        debug!("Output length: {} bytes", output.len());
+
+        Ok(output)
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Helper functions
+// -----------------------------------------------------------------------------
+
+/// Parses one NDJSON line of the generate stream and appends its `response` text.
+/// Blank lines are ignored; an `error` field is surfaced as a failure.
+fn append_chunk(output: &mut String, line: &str) -> Result<()> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(());
+    }
+
+    let chunk: GenerateChunk = serde_json::from_str(line)
+        .with_context(|| format!("Invalid Ollama response chunk: {line}"))?;
+
+    if let Some(error) = chunk.error {
+        anyhow::bail!("Ollama returned an error: {error}");
+    }
+
+    output.push_str(&chunk.response);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Starts a one-shot HTTP server that replies with `status` and `body`.
+    /// Returns the server's base URL and a handle resolving to the raw request.
+    async fn mock_server(
+        status: &'static str,
+        body: &'static str,
+    ) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+
+            // Read until headers and the full Content-Length body have arrived
+            loop {
+                let n = socket.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some(header_end) = text.find("\r\n\r\n") {
+                    let content_length = text[..header_end]
+                        .lines()
+                        .find_map(|l| {
+                            l.to_lowercase()
+                                .strip_prefix("content-length:")
+                                .map(|v| v.trim().parse::<usize>().unwrap())
+                        })
+                        .unwrap_or(0);
+                    if request.len() >= header_end + 4 + content_length {
+                        break;
+                    }
+                }
+            }
+
+            let response = format!(
+                "HTTP/1.1 {status}\r\nContent-Type: application/x-ndjson\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            socket.shutdown().await.unwrap();
+
+            String::from_utf8_lossy(&request).to_string()
+        });
+
+        (format!("http://{addr}"), handle)
+    }
+
+    fn provider_for(host: String) -> OllamaProvider {
+        OllamaProvider::new(OllamaConfig {
+            host,
+            model: "test-model".to_string(),
+            timeout_minutes: None,
+        })
+    }
+
+    #[test]
+    fn test_ollama_provider_name() {
+        let provider = OllamaProvider::new(OllamaConfig::default());
+        assert_eq!(provider.name(), "Ollama");
+    }
+
+    #[test]
+    fn test_generate_url() {
+        let provider = provider_for("http://localhost:11434".to_string());
+        assert_eq!(
+            provider.generate_url(),
+            "http://localhost:11434/api/generate"
+        );
+
+        let provider = provider_for("http://localhost:11434/".to_string());
+        assert_eq!(
+            provider.generate_url(),
+            "http://localhost:11434/api/generate"
+        );
+    }
+
+    #[test]
+    fn test_append_chunk() {
+        let mut output = String::new();
+        append_chunk(&mut output, r#"{"response":"Hello","done":false}"#).unwrap();
+        append_chunk(&mut output, "  \n").unwrap();
+        append_chunk(&mut output, r#"{"response":" world","done":true}"#).unwrap();
+        assert_eq!(output, "Hello world");
+    }
+
+    #[test]
+    fn test_append_chunk_error_field() {
+        let mut output = String::new();
+        let err = append_chunk(&mut output, r#"{"error":"model not found"}"#).unwrap_err();
+        assert!(err.to_string().contains("model not found"));
+    }
+
+    #[test]
+    fn test_append_chunk_invalid_json() {
+        let mut output = String::new();
+        assert!(append_chunk(&mut output, "not json").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_invoke_collects_streamed_response() {
+        let (host, request) = mock_server(
+            "200 OK",
+            "{\"response\":\"Hello\",\"done\":false}\n{\"response\":\", world\",\"done\":false}\n{\"response\":\"\",\"done\":true}\n",
+        )
+        .await;
+
+        let provider = provider_for(host);
+        let output = provider
+            .invoke(Path::new("/tmp"), "say hello", 1, None)
+            .await
+            .unwrap();
+        assert_eq!(output, "Hello, world");
+
+        let request = request.await.unwrap();
+        assert!(request.starts_with("POST /api/generate"));
+        assert!(request.contains("\"model\":\"test-model\""));
+        assert!(request.contains("\"prompt\":\"say hello\""));
+        assert!(request.contains("\"stream\":true"));
+    }
+
+    #[tokio::test]
+    async fn test_invoke_http_error() {
+        let (host, _request) = mock_server(
+            "404 Not Found",
+            "{\"error\":\"model 'test-model' not found\"}",
+        )
+        .await;
+
+        let provider = provider_for(host);
+        let err = provider
+            .invoke(Path::new("/tmp"), "prompt", 1, None)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("404"));
+        assert!(err.contains("not found"));
+        assert!(err.contains("ollama pull test-model"));
+    }
+
+    #[tokio::test]
+    async fn test_invoke_stream_error() {
+        let (host, _request) = mock_server(
+            "200 OK",
+            "{\"response\":\"partial\",\"done\":false}\n{\"error\":\"out of memory\"}\n",
+        )
+        .await;
+
+        let provider = provider_for(host);
+        let err = provider
+            .invoke(Path::new("/tmp"), "prompt", 1, None)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("out of memory"));
+    }
+
+    #[tokio::test]
+    async fn test_invoke_server_unreachable() {
+        // Bind then drop to get a port nothing is listening on
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let provider = provider_for(format!("http://{addr}"));
+        let err = provider
+            .invoke(Path::new("/tmp"), "prompt", 1, None)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Failed to reach Ollama"));
+    }
+}
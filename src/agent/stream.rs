@@ -0,0 +1,168 @@
+//! Live echo of agent output
+//!
+//! When `[monitoring].stream_output` is enabled, agent stdout is written to
+//! the terminal chunk by chunk as it arrives. The full output is still
+//! accumulated so completion detection and usage parsing work unchanged.
+
+use std::io::Write;
+use std::process::{ExitStatus, Output};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::Child;
+
+/// Read buffer size for piped child stdout.
+const READ_CHUNK_SIZE: usize = 8192;
+
+/// Accumulates output chunks, optionally echoing each one to a writer.
+pub(crate) struct OutputTee<W: Write> {
+    buf: Vec<u8>,
+    echo: Option<W>,
+}
+
+impl OutputTee<std::io::Stdout> {
+    /// Creates a tee that echoes to stdout when `stream` is true.
+    pub(crate) fn stdout(stream: bool) -> Self {
+        Self::new(stream.then(std::io::stdout))
+    }
+}
+
+impl<W: Write> OutputTee<W> {
+    /// Creates a tee echoing to `echo`, or only accumulating when `None`.
+    pub(crate) fn new(echo: Option<W>) -> Self {
+        Self {
+            buf: Vec::new(),
+            echo,
+        }
+    }
+
+    /// Appends `chunk` and echoes it immediately.
+    ///
+    /// Echo failures (e.g. a closed terminal) are ignored so they never fail
+    /// the iteration.
+    pub(crate) fn push(&mut self, chunk: &[u8]) {
+        self.buf.extend_from_slice(chunk);
+        if let Some(echo) = self.echo.as_mut() {
+            let _ = echo.write_all(chunk).and_then(|()| echo.flush());
+        }
+    }
+
+    /// Reads `reader` to EOF, pushing each chunk as soon as it is read.
+    pub(crate) async fn read_from<R: AsyncRead + Unpin>(
+        &mut self,
+        mut reader: R,
+    ) -> std::io::Result<()> {
+        let mut chunk = vec![0u8; READ_CHUNK_SIZE];
+        loop {
+            let n = reader.read(&mut chunk).await?;
+            if n == 0 {
+                return Ok(());
+            }
+            self.push(&chunk[..n]);
+        }
+    }
+
+    /// Returns the accumulated output as (lossy) UTF-8.
+    pub(crate) fn into_string(self) -> String {
+        String::from_utf8_lossy(&self.buf).into_owned()
+    }
+}
+
+/// Like `Child::wait_with_output`, but echoes stdout live when `stream` is true.
+///
+/// Stdout and stderr are drained concurrently so neither pipe can fill up
+/// and stall the child.
+pub(crate) async fn wait_with_output(mut child: Child, stream: bool) -> std::io::Result<Output> {
+    let mut stdout = OutputTee::stdout(stream);
+    let mut stderr = Vec::new();
+    let stdout_pipe = child.stdout.take();
+    let stderr_pipe = child.stderr.take();
+
+    tokio::try_join!(
+        async {
+            match stdout_pipe {
+                Some(pipe) => stdout.read_from(pipe).await,
+                None => Ok(()),
+            }
+        },
+        async {
+            match stderr_pipe {
+                Some(mut pipe) => pipe.read_to_end(&mut stderr).await.map(|_| ()),
+                None => Ok(()),
+            }
+        },
+    )?;
+
+    Ok(Output {
+        status: child.wait().await?,
+        stdout: stdout.buf,
+        stderr,
+    })
+}
+
+/// Exit code of a finished process, or -1 if it was killed by a signal.
+pub(crate) fn exit_code(status: ExitStatus) -> i64 {
+    status.code().map_or(-1, i64::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use tokio::io::ReadBuf;
+
+    /// Reader that yields one queued chunk per read call.
+    struct ChunkedReader(VecDeque<&'static [u8]>);
+
+    impl AsyncRead for ChunkedReader {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            if let Some(chunk) = self.0.pop_front() {
+                buf.put_slice(chunk);
+            }
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    /// Writer recording each write call separately.
+    #[derive(Default)]
+    struct RecordingWriter(Vec<Vec<u8>>);
+
+    impl Write for &mut RecordingWriter {
+        fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+            self.0.push(data.to_vec());
+            Ok(data.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_from_echoes_chunks_incrementally() {
+        let reader = ChunkedReader(VecDeque::from([&b"first\n"[..], b"sec", b"ond\n"]));
+        let mut writer = RecordingWriter::default();
+
+        let mut tee = OutputTee::new(Some(&mut writer));
+        tee.read_from(reader).await.unwrap();
+        let output = tee.into_string();
+
+        assert_eq!(output, "first\nsecond\n");
+        assert_eq!(
+            writer.0,
+            vec![b"first\n".to_vec(), b"sec".to_vec(), b"ond\n".to_vec()]
+        );
+    }
+
+    #[test]
+    fn test_push_without_echo_only_accumulates() {
+        let mut tee = OutputTee::<Vec<u8>>::new(None);
+        tee.push(b"a");
+        tee.push(b"b");
+        assert_eq!(tee.into_string(), "ab");
+    }
+}
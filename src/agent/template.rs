@@ -0,0 +1,348 @@
+//! Configurable agent command lines (`[agent.<provider>].command_template`).
+//!
+//! A template lists the program and its arguments with placeholders. When
+//! set it replaces the provider's built-in flag layout, so a CLI whose flags
+//! change can be followed from ralph.toml. A template that uses neither
+//! `{prompt}` nor `{prompt_file}` gets the prompt on stdin.
+
+use anyhow::{bail, Context, Result};
+use std::path::PathBuf;
+
+/// Placeholders a command template may use.
+pub(crate) const PLACEHOLDERS: &[&str] = &[
+    "{path}",
+    "{prompt}",
+    "{prompt_file}",
+    "{model}",
+    "{output_format}",
+];
+
+/// Values substituted into a command template.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TemplateValues<'a> {
+    /// `{path}`: the provider's configured `path`.
+    pub path: &'a str,
+    /// `{prompt}`: the prompt text.
+    pub prompt: &'a str,
+    /// `{prompt_file}`: a file holding the prompt.
+    pub prompt_file: &'a str,
+    /// `{model}`: the model for this iteration, if any.
+    pub model: Option<&'a str>,
+    /// `{output_format}`: the provider's `output_format`.
+    pub output_format: &'a str,
+}
+
+/// Renders `template` with `values`, program first.
+///
+/// Without a model, an argument using `{model}` is left out along with the
+/// flag right before it, so `["--model", "{model}"]` disappears entirely.
+/// Substituted text is never scanned again, so a prompt containing
+/// `{model}` stays as written.
+pub(crate) fn render(template: &[String], values: &TemplateValues) -> Vec<String> {
+    let mut command: Vec<String> = Vec::with_capacity(template.len());
+    let mut previous_kept = false;
+    for (i, arg) in template.iter().enumerate() {
+        if values.model.is_none() && arg.contains("{model}") {
+            if previous_kept && is_plain_flag(&template[i - 1]) {
+                command.pop();
+            }
+            previous_kept = false;
+            continue;
+        }
+        command.push(substitute(arg, values));
+        previous_kept = true;
+    }
+    command
+}
+
+/// True when the template passes the prompt itself rather than on stdin.
+pub(crate) fn takes_prompt(template: &[String]) -> bool {
+    template
+        .iter()
+        .any(|arg| arg.contains("{prompt}") || arg.contains("{prompt_file}"))
+}
+
+/// Returns the first `{name}` in `template` that is not a placeholder.
+pub(crate) fn unknown_placeholder(template: &[String]) -> Option<&str> {
+    template.iter().find_map(|arg| {
+        let mut rest = arg.as_str();
+        while let Some(start) = rest.find('{') {
+            let candidate = &rest[start..];
+            let Some(end) = candidate.find('}') else {
+                break;
+            };
+            let token = &candidate[..=end];
+            let name = &token[1..token.len() - 1];
+            let looks_like_placeholder =
+                !name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase() || c == '_');
+            if looks_like_placeholder && !PLACEHOLDERS.contains(&token) {
+                return Some(token);
+            }
+            rest = &candidate[1..];
+        }
+        None
+    })
+}
+
+/// A rendered template ready to spawn on the host.
+///
+/// For `{prompt_file}` the prompt is written to a temporary file, removed when
+/// the command is dropped.
+#[derive(Debug)]
+pub(crate) struct HostCommand {
+    /// Program to run.
+    pub program: String,
+    /// Arguments after the program.
+    pub args: Vec<String>,
+    /// Whether the prompt is written to the program's stdin.
+    pub prompt_on_stdin: bool,
+    /// Temporary prompt file behind `{prompt_file}`.
+    prompt_file: Option<PathBuf>,
+}
+
+impl HostCommand {
+    /// A built-in command line: `program` with `args`.
+    pub(crate) fn new(program: &str, args: Vec<String>, prompt_on_stdin: bool) -> Self {
+        Self {
+            program: program.to_string(),
+            args,
+            prompt_on_stdin,
+            prompt_file: None,
+        }
+    }
+
+    /// Renders `template` for a run on the host.
+    pub(crate) fn from_template(
+        template: &[String],
+        path: &str,
+        prompt: &str,
+        model: Option<&str>,
+        output_format: &str,
+    ) -> Result<Self> {
+        let prompt_file = if template.iter().any(|arg| arg.contains("{prompt_file}")) {
+            let file =
+                std::env::temp_dir().join(format!("ralph-prompt-{}.md", uuid::Uuid::new_v4()));
+            std::fs::write(&file, prompt)
+                .with_context(|| format!("Failed to write prompt file {}", file.display()))?;
+            Some(file)
+        } else {
+            None
+        };
+
+        let prompt_file_path = prompt_file
+            .as_deref()
+            .map(|file| file.display().to_string())
+            .unwrap_or_default();
+        let values = TemplateValues {
+            path,
+            prompt,
+            prompt_file: &prompt_file_path,
+            model,
+            output_format,
+        };
+        let mut command = render(template, &values).into_iter();
+        let Some(program) = command.next().filter(|program| !program.is_empty()) else {
+            bail!("command_template renders to an empty command");
+        };
+        Ok(Self {
+            program,
+            args: command.collect(),
+            prompt_on_stdin: !takes_prompt(template),
+            prompt_file,
+        })
+    }
+}
+
+impl Drop for HostCommand {
+    fn drop(&mut self) {
+        if let Some(file) = &self.prompt_file {
+            let _ = std::fs::remove_file(file);
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Helper functions
+// -----------------------------------------------------------------------------
+
+/// True for a flag such as `--model` that takes the following argument.
+fn is_plain_flag(arg: &str) -> bool {
+    arg.starts_with('-') && !arg.contains('{')
+}
+
+/// Replaces each placeholder in `arg` in one pass.
+fn substitute(arg: &str, values: &TemplateValues) -> String {
+    let mut out = String::with_capacity(arg.len());
+    let mut rest = arg;
+    'scan: while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        for &placeholder in PLACEHOLDERS {
+            if let Some(after) = rest.strip_prefix(placeholder) {
+                out.push_str(match placeholder {
+                    "{path}" => values.path,
+                    "{prompt}" => values.prompt,
+                    "{prompt_file}" => values.prompt_file,
+                    "{model}" => values.model.unwrap_or_default(),
+                    _ => values.output_format,
+                });
+                rest = after;
+                continue 'scan;
+            }
+        }
+        out.push('{');
+        rest = &rest[1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(args: &[&str]) -> Vec<String> {
+        args.iter().map(ToString::to_string).collect()
+    }
+
+    fn values(model: Option<&str>) -> TemplateValues<'_> {
+        TemplateValues {
+            path: "agent",
+            prompt: "Fix the build",
+            prompt_file: "/workspace/.ralph/prompt.tmp",
+            model,
+            output_format: "json",
+        }
+    }
+
+    #[test]
+    fn test_render_substitutes_placeholders() {
+        let cmd = render(
+            &template(&[
+                "{path}",
+                "-p",
+                "{prompt}",
+                "--model",
+                "{model}",
+                "--format={output_format}",
+                "--file",
+                "{prompt_file}",
+            ]),
+            &values(Some("gpt-5")),
+        );
+        assert_eq!(
+            cmd,
+            [
+                "agent",
+                "-p",
+                "Fix the build",
+                "--model",
+                "gpt-5",
+                "--format=json",
+                "--file",
+                "/workspace/.ralph/prompt.tmp",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_omits_model_when_unset() {
+        let cmd = render(
+            &template(&["{path}", "-p", "{prompt}", "--model", "{model}"]),
+            &values(None),
+        );
+        assert_eq!(cmd, ["agent", "-p", "Fix the build"]);
+
+        // A single `--model={model}` argument goes on its own
+        let cmd = render(
+            &template(&["{path}", "--model={model}", "-p"]),
+            &values(None),
+        );
+        assert_eq!(cmd, ["agent", "-p"]);
+
+        // An argument that is itself a placeholder is not taken as the flag
+        let cmd = render(&template(&["{path}", "{prompt}", "{model}"]), &values(None));
+        assert_eq!(cmd, ["agent", "Fix the build"]);
+    }
+
+    #[test]
+    fn test_render_does_not_rescan_substitutions() {
+        let values = TemplateValues {
+            prompt: "Mention {model} and {unknown} literally",
+            ..values(Some("gpt-5"))
+        };
+        let cmd = render(&template(&["{path}", "{prompt}", "{nope}"]), &values);
+        assert_eq!(
+            cmd,
+            ["agent", "Mention {model} and {unknown} literally", "{nope}"]
+        );
+    }
+
+    #[test]
+    fn test_takes_prompt() {
+        assert!(takes_prompt(&template(&["agent", "-p", "{prompt}"])));
+        assert!(takes_prompt(&template(&["agent", "--file={prompt_file}"])));
+        assert!(!takes_prompt(&template(&[
+            "claude", "-p", "--model", "{model}"
+        ])));
+    }
+
+    #[test]
+    fn test_unknown_placeholder() {
+        assert_eq!(
+            unknown_placeholder(&template(&["{path}", "--model", "{modle}"])),
+            Some("{modle}")
+        );
+        assert_eq!(
+            unknown_placeholder(&template(&[
+                "{path}",
+                "{prompt}",
+                "{prompt_file}",
+                "{model}",
+                "{output_format}",
+                "--json={\"a\": 1}",
+            ])),
+            None
+        );
+    }
+
+    #[test]
+    fn test_host_command_from_template() {
+        let command = HostCommand::from_template(
+            &template(&["{path}", "--file", "{prompt_file}", "--model", "{model}"]),
+            "cursor-agent",
+            "Fix the build",
+            None,
+            "text",
+        )
+        .unwrap();
+        assert_eq!(command.program, "cursor-agent");
+        assert_eq!(command.args[0], "--file");
+        assert_eq!(command.args.len(), 2);
+        assert!(!command.prompt_on_stdin);
+
+        // The prompt file lives as long as the command
+        let file = PathBuf::from(&command.args[1]);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "Fix the build");
+        drop(command);
+        assert!(!file.exists());
+
+        let stdin = HostCommand::from_template(
+            &template(&["claude", "-p", "--output-format", "{output_format}"]),
+            "claude",
+            "Fix the build",
+            None,
+            "json",
+        )
+        .unwrap();
+        assert_eq!(stdin.args, ["-p", "--output-format", "json"]);
+        assert!(stdin.prompt_on_stdin);
+    }
+
+    #[test]
+    fn test_host_command_empty_render_errors() {
+        let err = HostCommand::from_template(&template(&["{model}"]), "agent", "", None, "text")
+            .unwrap_err();
+        assert!(err.to_string().contains("empty command"));
+    }
+}
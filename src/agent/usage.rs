@@ -0,0 +1,145 @@
+//! Token usage parsing for agent output
+//!
+//! Agent CLIs report token usage when run with a JSON output format:
+//! - `json`: a single result envelope with a top-level `usage` object
+//! - `stream-json`: newline-delimited events, the final `result` event
+//!   carries the totals for the run
+//!
+//! Text output carries no usage, so it is reported as zero.
+
+use serde::Deserialize;
+
+/// Tokens consumed by a single agent invocation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct TokenUsage {
+    /// Prompt tokens, including cache reads and writes.
+    pub input_tokens: u64,
+    /// Completion tokens.
+    pub output_tokens: u64,
+}
+
+/// Extracts token usage from agent output produced with `output_format`.
+///
+/// Only `json` and `stream-json` are parsed. Anything unparseable falls back
+/// to zero usage rather than failing the iteration.
+pub(crate) fn parse_usage(output_format: &str, output: &str) -> TokenUsage {
+    match output_format {
+        "json" | "stream-json" => parse_json_usage(output),
+        _ => TokenUsage::default(),
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Internal types
+// -----------------------------------------------------------------------------
+
+/// Result envelope; only the `usage` field is of interest.
+#[derive(Debug, Deserialize)]
+struct Envelope {
+    usage: Option<UsageFields>,
+}
+
+/// Usage object. Claude uses `snake_case`, Cursor uses `camelCase`.
+#[derive(Debug, Deserialize)]
+#[allow(clippy::struct_field_names)] // Field names mirror the JSON keys
+struct UsageFields {
+    #[serde(default, alias = "inputTokens")]
+    input_tokens: u64,
+    #[serde(default, alias = "outputTokens")]
+    output_tokens: u64,
+    #[serde(default, alias = "cacheCreationInputTokens")]
+    cache_creation_input_tokens: u64,
+    #[serde(default, alias = "cacheReadInputTokens", alias = "cacheReadTokens")]
+    cache_read_input_tokens: u64,
+}
+
+impl From<UsageFields> for TokenUsage {
+    fn from(fields: UsageFields) -> Self {
+        Self {
+            input_tokens: fields
+                .input_tokens
+                .saturating_add(fields.cache_creation_input_tokens)
+                .saturating_add(fields.cache_read_input_tokens),
+            output_tokens: fields.output_tokens,
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Helper functions
+// -----------------------------------------------------------------------------
+
+/// Parses a single JSON document, or the last NDJSON line carrying usage.
+fn parse_json_usage(output: &str) -> TokenUsage {
+    let output = output.trim();
+    envelope_usage(output)
+        .or_else(|| output.lines().rev().find_map(envelope_usage))
+        .unwrap_or_default()
+}
+
+/// Returns the top-level usage of a JSON envelope, if present.
+fn envelope_usage(text: &str) -> Option<TokenUsage> {
+    serde_json::from_str::<Envelope>(text.trim())
+        .ok()?
+        .usage
+        .map(TokenUsage::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLAUDE_JSON: &str = r#"{"type":"result","subtype":"success","is_error":false,"duration_ms":12345,"num_turns":4,"result":"Done.","session_id":"abc","total_cost_usd":0.0421,"usage":{"input_tokens":12,"cache_creation_input_tokens":1000,"cache_read_input_tokens":500,"output_tokens":340,"service_tier":"standard"}}"#;
+
+    const CLAUDE_STREAM_JSON: &str = concat!(
+        r#"{"type":"system","subtype":"init","session_id":"abc"}"#,
+        "\n",
+        r#"{"type":"assistant","message":{"content":[{"type":"text","text":"Hi"}],"usage":{"input_tokens":5,"output_tokens":1}}}"#,
+        "\n",
+        r#"{"type":"result","subtype":"success","result":"Hi","usage":{"input_tokens":100,"output_tokens":20}}"#,
+        "\n"
+    );
+
+    const CURSOR_JSON: &str = r#"{"type":"result","subtype":"success","is_error":false,"duration_ms":5000,"result":"Done.","session_id":"xyz","usage":{"inputTokens":2048,"outputTokens":512}}"#;
+
+    #[test]
+    fn test_parse_claude_json_envelope() {
+        let usage = parse_usage("json", CLAUDE_JSON);
+        assert_eq!(usage.input_tokens, 1512);
+        assert_eq!(usage.output_tokens, 340);
+    }
+
+    #[test]
+    fn test_parse_claude_stream_json_uses_result_event() {
+        let usage = parse_usage("stream-json", CLAUDE_STREAM_JSON);
+        assert_eq!(
+            usage,
+            TokenUsage {
+                input_tokens: 100,
+                output_tokens: 20
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_cursor_json_envelope() {
+        let usage = parse_usage("json", CURSOR_JSON);
+        assert_eq!(usage.input_tokens, 2048);
+        assert_eq!(usage.output_tokens, 512);
+    }
+
+    #[test]
+    fn test_parse_text_format_is_zero() {
+        assert_eq!(parse_usage("text", CLAUDE_JSON), TokenUsage::default());
+    }
+
+    #[test]
+    fn test_parse_invalid_json_is_zero() {
+        assert_eq!(parse_usage("json", "not json"), TokenUsage::default());
+        assert_eq!(parse_usage("json", ""), TokenUsage::default());
+        assert_eq!(
+            parse_usage("json", r#"{"type":"result","result":"no usage"}"#),
+            TokenUsage::default()
+        );
+    }
+}
@@ -1,12 +1,19 @@
 //! Cancel an active Ralph loop.
 //!
+//! The loop notices the inactive state between iterations. With `--force`,
+//! the project's running Ralph containers are also killed so an
+//! in-flight agent stops right away.
+//!
 //! Core logic is pure: takes state, returns updated state.
 //! Formatting is pure: returns strings. IO happens only at the top level.
 
 use anyhow::{Context, Result};
 use colored::Colorize;
 use std::fmt::Write;
+use std::path::Path;
 
+use crate::config::Config;
+use crate::sandbox::DockerSandbox;
 use crate::state::RalphState;
 
 // -----------------------------------------------------------------------------
@@ -14,7 +21,10 @@ use crate::state::RalphState;
 // -----------------------------------------------------------------------------
 
 /// Runs the cancel command, deactivating any active loop.
-pub(crate) fn run() -> Result<()> {
+///
+/// `force` also kills the project's running sandbox containers, using the
+/// runtime from `config_file` (`--config`) or `ralph.toml`.
+pub(crate) async fn run(force: bool, config_file: Option<&Path>) -> Result<()> {
     let cwd = std::env::current_dir().context("Failed to get current directory")?;
 
     let state = RalphState::load(&cwd)?;
@@ -28,6 +38,12 @@ pub(crate) fn run() -> Result<()> {
     }
 
     print!("{}", format_result(&result));
+
+    if force {
+        let config = Config::load_from(&cwd, config_file).context("Failed to load ralph.toml")?;
+        let killed = DockerSandbox::kill_project_containers(config.sandbox.runtime, &cwd).await?;
+        print!("{}", format_killed(killed));
+    }
     Ok(())
 }
 
@@ -81,6 +97,19 @@ fn format_result(result: &CancelResult) -> String {
     out
 }
 
+/// Formats the number of containers `--force` killed.
+fn format_killed(killed: u32) -> String {
+    if killed == 0 {
+        format!("{} No running Ralph containers found.\n", "ℹ".blue())
+    } else {
+        format!(
+            "{} Killed {} running container(s).\n",
+            "✓".green(),
+            killed.to_string().cyan()
+        )
+    }
+}
+
 // -----------------------------------------------------------------------------
 // Tests
 // -----------------------------------------------------------------------------
@@ -88,7 +117,7 @@ fn format_result(result: &CancelResult) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::state::Mode;
+    use crate::state::{LoopSettings, Mode};
     use chrono::Utc;
 
     fn make_state(active: bool, iteration: u32) -> RalphState {
@@ -101,9 +130,18 @@ mod tests {
             last_iteration_at: None,
             error_count: 0,
             consecutive_errors: 0,
+            circuit_breaker_cooled_down: false,
             last_error: None,
             last_commit: None,
             idle_iterations: 0,
+            commit_count: None,
+            total_input_tokens: 0,
+            total_output_tokens: 0,
+            initial_commit: None,
+            pid: None,
+            session_id: None,
+            error_history: Vec::new(),
+            settings: LoopSettings::default(),
         }
     }
 
@@ -143,9 +181,18 @@ mod tests {
             last_iteration_at: Some(Utc::now()),
             error_count: 0,
             consecutive_errors: 0,
+            circuit_breaker_cooled_down: false,
             last_error: None,
             last_commit: None,
             idle_iterations: 0,
+            commit_count: None,
+            total_input_tokens: 0,
+            total_output_tokens: 0,
+            initial_commit: None,
+            pid: None,
+            session_id: None,
+            error_history: Vec::new(),
+            settings: LoopSettings::default(),
         };
 
         let (_, updated) = cancel_loop(Some(state.clone()));
@@ -165,6 +212,14 @@ mod tests {
         assert!(output.contains('5'));
     }
 
+    #[test]
+    fn test_format_killed() {
+        assert!(format_killed(0).contains("No running Ralph containers"));
+        let output = format_killed(2);
+        assert!(output.contains("Killed"));
+        assert!(output.contains('2'));
+    }
+
     #[test]
     fn test_format_result_no_loop() {
         let result = CancelResult::NoActiveLoop;
@@ -1,7 +1,10 @@
 //! Remove Ralph state and configuration files.
 //!
-//! Core logic determines which files to remove based on existence.
-//! Formatting is pure. IO happens only at the top level.
+//! `plan_clean` decides what to remove from the flags, which paths exist,
+//! and the current worktrees; `--dry-run` prints that plan instead of
+//! executing it. Worktrees whose loop is still active are skipped unless
+//! `--force` is given. Planning and formatting are pure. IO happens only at
+//! the top level.
 
 use anyhow::{Context, Result};
 use colored::Colorize;
@@ -9,29 +12,165 @@ use std::fmt::Write;
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use crate::commands::loop_cmd::worktree;
+use crate::commands::loop_cmd::{worktree, ITERATIONS_DIR};
+use crate::config::Config;
+use crate::sandbox::DockerSandbox;
+use crate::state::RalphState;
 
 // -----------------------------------------------------------------------------
 // Public API
 // -----------------------------------------------------------------------------
 
+/// Options given to `ralph clean`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)] // Independent CLI flags, not a state machine
+pub(crate) struct CleanFlags {
+    /// Also remove prompt and rules files (`--all`).
+    pub all: bool,
+    /// Remove all worktrees created by Ralph (`--worktrees`).
+    pub worktrees: bool,
+    /// Remove the sandbox cache volumes (`--caches`).
+    pub caches: bool,
+    /// Remove worktrees even while their loop is active (`--force`).
+    pub force: bool,
+}
+
 /// Runs the clean command, removing Ralph state and config files.
-pub(crate) async fn run(all: bool, worktrees: bool) -> Result<()> {
+///
+/// With `dry_run`, prints what would be removed and deletes nothing.
+/// `config_file` is the `--config` path, if any, read for `--caches`.
+pub(crate) async fn run(
+    flags: CleanFlags,
+    dry_run: bool,
+    config_file: Option<&Path>,
+) -> Result<()> {
     let cwd = std::env::current_dir().context("Failed to get current directory")?;
 
-    // Remove worktrees if requested
-    if worktrees {
-        let removed_worktrees = worktree::remove_all_worktrees(&cwd).await?;
-        print!("{}", format_worktree_results(&removed_worktrees));
+    let worktrees = if flags.worktrees {
+        worktree::list_worktrees(&cwd)?
+    } else {
+        Vec::new()
+    };
+    let plan = plan_clean(
+        flags,
+        |path| cwd.join(path).exists(),
+        worktrees,
+        |branch| active_loop_iteration(&worktree::worktree_path(&cwd, branch)),
+    );
+
+    if dry_run {
+        print!("{}", format_plan(&plan));
+        return Ok(());
     }
+    execute_plan(&cwd, &plan, config_file).await
+}
 
-    let removed = clean_files(
-        all,
-        |path| cwd.join(path).exists(),
+// -----------------------------------------------------------------------------
+// Internal types
+// -----------------------------------------------------------------------------
+
+/// Everything one clean run removes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct CleanPlan {
+    /// Existing state files, plus config files with `--all`.
+    files: Vec<PathBuf>,
+    /// Existing state directories, removed with their contents.
+    dirs: Vec<PathBuf>,
+    /// Branches whose worktrees are removed; `None` without `--worktrees`.
+    worktrees: Option<Vec<String>>,
+    /// Worktrees kept because their loop is still active.
+    skipped_worktrees: Vec<SkippedWorktree>,
+    /// Whether the sandbox cache volumes are removed.
+    caches: bool,
+}
+
+/// A worktree left in place because a loop is still building in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SkippedWorktree {
+    /// Branch the worktree belongs to.
+    branch: String,
+    /// Iteration the active loop is on.
+    iteration: u32,
+}
+
+// -----------------------------------------------------------------------------
+// Helper functions
+// -----------------------------------------------------------------------------
+
+/// Decides what a clean run removes.
+///
+/// `exists` checks a path relative to the project; `worktrees` lists the
+/// branches that currently have a worktree, and `active` returns the
+/// iteration of a branch's loop if it is still active. Active worktrees are
+/// skipped unless `flags.force` is set.
+fn plan_clean<E, A>(flags: CleanFlags, exists: E, worktrees: Vec<String>, active: A) -> CleanPlan
+where
+    E: Fn(&Path) -> bool,
+    A: Fn(&str) -> Option<u32>,
+{
+    let mut skipped_worktrees = Vec::new();
+    let worktrees = flags.worktrees.then(|| {
+        worktrees
+            .into_iter()
+            .filter(|branch| match active(branch) {
+                Some(iteration) if !flags.force => {
+                    skipped_worktrees.push(SkippedWorktree {
+                        branch: branch.clone(),
+                        iteration,
+                    });
+                    false
+                }
+                _ => true,
+            })
+            .collect()
+    });
+
+    CleanPlan {
+        files: files_to_clean(flags.all, &exists),
+        dirs: state_dirs().into_iter().filter(|d| exists(d)).collect(),
+        worktrees,
+        skipped_worktrees,
+        caches: flags.caches,
+    }
+}
+
+/// Returns the iteration of the loop in `worktree` if its saved state is
+/// still active.
+///
+/// Unreadable state counts as inactive: no loop could be running from it.
+fn active_loop_iteration(worktree: &Path) -> Option<u32> {
+    RalphState::load(worktree)
+        .ok()
+        .flatten()
+        .filter(|state| state.active)
+        .map(|state| state.iteration)
+}
+
+/// Removes everything in `plan`, printing what was removed.
+async fn execute_plan(cwd: &Path, plan: &CleanPlan, config_file: Option<&Path>) -> Result<()> {
+    if let Some(ref branches) = plan.worktrees {
+        worktree::remove_worktrees(cwd, branches).await?;
+        print!("{}", format_worktree_results(branches));
+    }
+    print!("{}", format_skipped_worktrees(&plan.skipped_worktrees));
+
+    // Remove cache volumes before ralph.toml may be removed
+    if plan.caches {
+        let config = Config::load_from(cwd, config_file).context("Failed to load ralph.toml")?;
+        let removed_volumes = DockerSandbox::remove_cache_volumes(config.sandbox.runtime).await?;
+        print!("{}", format_cache_results(&removed_volumes));
+    }
+
+    let removed = remove_paths(
+        plan,
         |path| {
             fs::remove_file(cwd.join(path))
                 .with_context(|| format!("Failed to remove {}", path.display()))
         },
+        |path| {
+            fs::remove_dir_all(cwd.join(path))
+                .with_context(|| format!("Failed to remove {}", path.display()))
+        },
     )?;
 
     // Clean up empty directories
@@ -49,19 +188,19 @@ pub(crate) async fn run(all: bool, worktrees: bool) -> Result<()> {
 /// Returns true if a directory is empty.
 fn is_dir_empty(path: &Path) -> bool {
     path.read_dir()
-        .map(|mut entries| entries.next().is_none())
-        .unwrap_or(false)
+        .is_ok_and(|mut entries| entries.next().is_none())
 }
 
-// -----------------------------------------------------------------------------
-// Helper functions
-// -----------------------------------------------------------------------------
-
 /// Returns the list of state files that can be cleaned.
 fn state_files() -> Vec<PathBuf> {
     vec![PathBuf::from(".ralph/state.toml")]
 }
 
+/// Returns state directories that are always removed with their contents.
+fn state_dirs() -> Vec<PathBuf> {
+    vec![PathBuf::from(ITERATIONS_DIR)]
+}
+
 /// Returns additional config files cleaned with `--all`.
 fn config_files() -> Vec<PathBuf> {
     vec![
@@ -95,21 +234,71 @@ where
     files.into_iter().filter(|f| exists(f)).collect()
 }
 
-/// Removes files and returns list of removed paths.
-fn clean_files<E, R>(all: bool, exists: E, mut remove: R) -> Result<Vec<PathBuf>>
+/// Removes the plan's files, then its directories, and returns the removed
+/// paths.
+fn remove_paths<F, D>(
+    plan: &CleanPlan,
+    mut remove_file: F,
+    mut remove_dir: D,
+) -> Result<Vec<PathBuf>>
 where
-    E: Fn(&Path) -> bool,
-    R: FnMut(&Path) -> Result<()>,
+    F: FnMut(&Path) -> Result<()>,
+    D: FnMut(&Path) -> Result<()>,
 {
-    let to_remove = files_to_clean(all, &exists);
     let mut removed = Vec::new();
+    for file in &plan.files {
+        remove_file(file)?;
+        removed.push(file.clone());
+    }
+    for dir in &plan.dirs {
+        remove_dir(dir)?;
+        removed.push(dir.clone());
+    }
+    Ok(removed)
+}
+
+/// Formats the `--dry-run` report of what `plan` would remove.
+fn format_plan(plan: &CleanPlan) -> String {
+    let mut out = String::new();
+    writeln!(&mut out, "\n{} Dry run: nothing was removed.", "ℹ".blue()).unwrap();
+
+    match plan.worktrees.as_deref() {
+        Some([]) => writeln!(&mut out, "\nNo worktrees found to remove.").unwrap(),
+        Some(branches) => {
+            writeln!(&mut out, "\nWould remove worktrees:").unwrap();
+            for branch in branches {
+                writeln!(
+                    &mut out,
+                    "  {} .worktrees/{}",
+                    "-".yellow(),
+                    branch.dimmed()
+                )
+                .unwrap();
+            }
+        }
+        None => {}
+    }
+    out.push_str(&format_skipped_worktrees(&plan.skipped_worktrees));
 
-    for file in to_remove {
-        remove(&file)?;
-        removed.push(file);
+    if plan.caches {
+        writeln!(&mut out, "\nWould remove all Ralph sandbox cache volumes.").unwrap();
     }
 
-    Ok(removed)
+    if plan.files.is_empty() && plan.dirs.is_empty() {
+        writeln!(&mut out, "\nNo Ralph files found to clean.").unwrap();
+    } else {
+        writeln!(&mut out, "\nWould remove Ralph files:").unwrap();
+        for path in plan.files.iter().chain(&plan.dirs) {
+            writeln!(
+                &mut out,
+                "  {} {}",
+                "-".yellow(),
+                path.display().to_string().dimmed()
+            )
+            .unwrap();
+        }
+    }
+    out
 }
 
 /// Formats the clean results as a displayable string.
@@ -146,6 +335,55 @@ fn format_worktree_results(removed: &[String]) -> String {
     out
 }
 
+/// Formats the worktrees kept because their loop is active; empty if none.
+fn format_skipped_worktrees(skipped: &[SkippedWorktree]) -> String {
+    let mut out = String::new();
+    if skipped.is_empty() {
+        return out;
+    }
+    writeln!(
+        &mut out,
+        "\n{} Skipped worktrees with an active loop:",
+        "⚠".yellow()
+    )
+    .unwrap();
+    for worktree in skipped {
+        writeln!(
+            &mut out,
+            "  {} .worktrees/{} (loop still active at iteration {})",
+            "-".yellow(),
+            worktree.branch.dimmed(),
+            worktree.iteration
+        )
+        .unwrap();
+    }
+    writeln!(
+        &mut out,
+        "Wait for the build to finish, or use --force to remove them anyway."
+    )
+    .unwrap();
+    out
+}
+
+/// Formats cache volume removal results.
+fn format_cache_results(removed: &[String]) -> String {
+    let mut out = String::new();
+    if removed.is_empty() {
+        writeln!(
+            &mut out,
+            "\n{} No cache volumes found to remove.",
+            "ℹ".blue()
+        )
+        .unwrap();
+    } else {
+        writeln!(&mut out, "\n{} Removed cache volumes:", "✓".green()).unwrap();
+        for volume in removed {
+            writeln!(&mut out, "  {} {}", "✗".red(), volume.dimmed()).unwrap();
+        }
+    }
+    out
+}
+
 // -----------------------------------------------------------------------------
 // Tests
 // -----------------------------------------------------------------------------
@@ -214,35 +452,239 @@ mod tests {
         assert!(to_clean.is_empty());
     }
 
-    #[test]
-    fn test_clean_files_removes_existing() {
-        let existing: HashSet<PathBuf> = [
+    fn existing() -> HashSet<PathBuf> {
+        [
             PathBuf::from(".ralph/state.toml"),
+            PathBuf::from(".ralph/iterations"),
             PathBuf::from("ralph.toml"),
+            PathBuf::from("AGENTS.md"),
         ]
         .into_iter()
-        .collect();
+        .collect()
+    }
 
-        let removed_files = RefCell::new(Vec::new());
+    fn branches() -> Vec<String> {
+        vec!["feature-a".to_string(), "feature-b".to_string()]
+    }
 
-        let removed = clean_files(
-            true,
+    #[test]
+    fn test_plan_clean_default_flags() {
+        let existing = existing();
+        let plan = plan_clean(
+            CleanFlags::default(),
             |p| existing.contains(p),
+            branches(),
+            |_| None,
+        );
+
+        assert_eq!(
+            plan,
+            CleanPlan {
+                files: vec![PathBuf::from(".ralph/state.toml")],
+                dirs: vec![PathBuf::from(".ralph/iterations")],
+                worktrees: None,
+                skipped_worktrees: Vec::new(),
+                caches: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_plan_clean_all() {
+        let existing = existing();
+        let flags = CleanFlags {
+            all: true,
+            ..CleanFlags::default()
+        };
+        let plan = plan_clean(flags, |p| existing.contains(p), branches(), |_| None);
+
+        assert_eq!(
+            plan.files,
+            vec![
+                PathBuf::from(".ralph/state.toml"),
+                PathBuf::from("ralph.toml"),
+                PathBuf::from("AGENTS.md"),
+            ]
+        );
+        assert_eq!(plan.dirs, vec![PathBuf::from(".ralph/iterations")]);
+        assert_eq!(plan.worktrees, None);
+    }
+
+    #[test]
+    fn test_plan_clean_worktrees_and_caches() {
+        let existing = existing();
+        let flags = CleanFlags {
+            all: true,
+            worktrees: true,
+            caches: true,
+            force: false,
+        };
+        let plan = plan_clean(flags, |p| existing.contains(p), branches(), |_| None);
+
+        assert_eq!(plan.worktrees, Some(branches()));
+        assert!(plan.caches);
+        assert_eq!(plan.files.len(), 3);
+
+        // Requested but none exist: still reported, as an empty list
+        let flags = CleanFlags {
+            worktrees: true,
+            ..CleanFlags::default()
+        };
+        let plan = plan_clean(flags, |_| false, Vec::new(), |_| None);
+        assert_eq!(
+            plan,
+            CleanPlan {
+                worktrees: Some(Vec::new()),
+                ..CleanPlan::default()
+            }
+        );
+    }
+
+    /// Fake worktrees: `feature-a` and `feature-c` have an active loop.
+    fn mixed_branches() -> Vec<String> {
+        ["feature-a", "feature-b", "feature-c", "feature-d"]
+            .map(str::to_string)
+            .to_vec()
+    }
+
+    fn fake_active(branch: &str) -> Option<u32> {
+        match branch {
+            "feature-a" => Some(2),
+            "feature-c" => Some(7),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_plan_clean_skips_active_worktrees() {
+        let flags = CleanFlags {
+            worktrees: true,
+            ..CleanFlags::default()
+        };
+        let plan = plan_clean(flags, |_| false, mixed_branches(), fake_active);
+
+        assert_eq!(
+            plan.worktrees,
+            Some(vec!["feature-b".to_string(), "feature-d".to_string()])
+        );
+        assert_eq!(
+            plan.skipped_worktrees,
+            vec![
+                SkippedWorktree {
+                    branch: "feature-a".to_string(),
+                    iteration: 2,
+                },
+                SkippedWorktree {
+                    branch: "feature-c".to_string(),
+                    iteration: 7,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_clean_force_removes_active_worktrees() {
+        let flags = CleanFlags {
+            worktrees: true,
+            force: true,
+            ..CleanFlags::default()
+        };
+        let plan = plan_clean(flags, |_| false, mixed_branches(), fake_active);
+
+        assert_eq!(plan.worktrees, Some(mixed_branches()));
+        assert!(plan.skipped_worktrees.is_empty());
+    }
+
+    #[test]
+    fn test_active_loop_iteration_reads_worktree_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let worktree = |name: &str| dir.path().join(name);
+
+        let active = RalphState {
+            active: true,
+            iteration: 5,
+            ..RalphState::default()
+        };
+        active.save(&worktree("active")).unwrap();
+        RalphState::default().save(&worktree("finished")).unwrap();
+        std::fs::create_dir_all(worktree("corrupt").join(".ralph")).unwrap();
+        std::fs::write(worktree("corrupt").join(".ralph/state.toml"), "not toml").unwrap();
+
+        assert_eq!(active_loop_iteration(&worktree("active")), Some(5));
+        assert_eq!(active_loop_iteration(&worktree("finished")), None);
+        assert_eq!(active_loop_iteration(&worktree("corrupt")), None);
+        assert_eq!(active_loop_iteration(&worktree("no-state")), None);
+    }
+
+    #[test]
+    fn test_format_skipped_worktrees() {
+        assert!(format_skipped_worktrees(&[]).is_empty());
+
+        let output = format_skipped_worktrees(&[SkippedWorktree {
+            branch: "feature-a".to_string(),
+            iteration: 2,
+        }]);
+        assert!(output.contains("Skipped worktrees with an active loop"));
+        assert!(output.contains(".worktrees/feature-a (loop still active at iteration 2)"));
+        assert!(output.contains("--force"));
+    }
+
+    #[test]
+    fn test_remove_paths_removes_plan() {
+        let existing = existing();
+        let flags = CleanFlags {
+            all: true,
+            ..CleanFlags::default()
+        };
+        let plan = plan_clean(flags, |p| existing.contains(p), Vec::new(), |_| None);
+        let removed_files = RefCell::new(Vec::new());
+        let removed_dirs = RefCell::new(Vec::new());
+
+        let removed = remove_paths(
+            &plan,
             |p| {
                 removed_files.borrow_mut().push(p.to_path_buf());
                 Ok(())
             },
+            |p| {
+                removed_dirs.borrow_mut().push(p.to_path_buf());
+                Ok(())
+            },
         )
         .unwrap();
 
-        assert_eq!(removed.len(), 2);
-        assert_eq!(removed_files.borrow().len(), 2);
+        assert_eq!(*removed_files.borrow(), plan.files);
+        assert_eq!(*removed_dirs.borrow(), plan.dirs);
+        assert_eq!(removed.len(), 4);
+        assert!(remove_paths(&CleanPlan::default(), |_| Ok(()), |_| Ok(()))
+            .unwrap()
+            .is_empty());
     }
 
     #[test]
-    fn test_clean_files_empty_when_nothing_exists() {
-        let removed = clean_files(true, |_| false, |_| Ok(())).unwrap();
-        assert!(removed.is_empty());
+    fn test_format_plan() {
+        let plan = CleanPlan {
+            files: vec![PathBuf::from(".ralph/state.toml")],
+            dirs: vec![PathBuf::from(".ralph/iterations")],
+            worktrees: Some(branches()),
+            skipped_worktrees: vec![SkippedWorktree {
+                branch: "feature-c".to_string(),
+                iteration: 4,
+            }],
+            caches: true,
+        };
+        let output = format_plan(&plan);
+        assert!(output.contains("Dry run: nothing was removed"));
+        assert!(output.contains(".worktrees/feature-a"));
+        assert!(output.contains(".worktrees/feature-b"));
+        assert!(output.contains(".worktrees/feature-c (loop still active at iteration 4)"));
+        assert!(output.contains("cache volumes"));
+        assert!(output.contains(".ralph/state.toml"));
+        assert!(output.contains(".ralph/iterations"));
+
+        let output = format_plan(&CleanPlan::default());
+        assert!(output.contains("No Ralph files found"));
+        assert!(!output.contains("worktrees"));
     }
 
     #[test]
@@ -259,4 +701,13 @@ mod tests {
         assert!(output.contains("ralph.toml"));
         assert!(output.contains("AGENTS.md"));
     }
+
+    #[test]
+    fn test_format_cache_results() {
+        assert!(format_cache_results(&[]).contains("No cache volumes found"));
+
+        let output = format_cache_results(&["ralph-cache-0123456789abcdef".to_string()]);
+        assert!(output.contains("Removed cache volumes"));
+        assert!(output.contains("ralph-cache-0123456789abcdef"));
+    }
 }
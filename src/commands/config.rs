@@ -0,0 +1,544 @@
+//! Inspect and validate ralph.toml.
+//!
+//! `validate` reports every problem at once instead of stopping at the first.
+//! The semantic checks are pure. IO happens only at the top level.
+
+use anyhow::{bail, Context, Result};
+use clap::Subcommand;
+use colored::Colorize;
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::config::{Config, NetworkPolicy};
+use crate::sandbox::{
+    parse_memory_limit, validate_cidr, validate_domain, validate_extra_host, validate_proxy_url,
+};
+
+/// Config inspection actions.
+#[derive(Subcommand, Debug)]
+pub enum ConfigAction {
+    /// Check ralph.toml for schema and semantic errors
+    Validate,
+
+    /// Print the effective config, with defaults filled in and secrets redacted
+    Show,
+}
+
+// -----------------------------------------------------------------------------
+// Public API
+// -----------------------------------------------------------------------------
+
+/// Runs a config command on `config_file` (`--config`), or on `ralph.toml`.
+pub(crate) fn run(action: &ConfigAction, config_file: Option<&Path>) -> Result<()> {
+    let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    let (config, expanded_keys) =
+        Config::load_unchecked(&cwd, config_file).context("Failed to load ralph.toml")?;
+
+    match action {
+        ConfigAction::Validate => {
+            let problems = validate(&config);
+            print!("{}", format_problems(&problems));
+            if !problems.is_empty() {
+                bail!("{} problem(s) found in ralph.toml", problems.len());
+            }
+        }
+        ConfigAction::Show => {
+            let mut rendered =
+                toml::Value::try_from(&config).context("Failed to serialize config")?;
+            redact(&mut rendered, "", &expanded_keys);
+            let rendered =
+                toml::to_string_pretty(&rendered).context("Failed to serialize config")?;
+            print!("{rendered}");
+        }
+    }
+
+    Ok(())
+}
+
+// -----------------------------------------------------------------------------
+// Helper functions
+// -----------------------------------------------------------------------------
+
+/// Keys always redacted by `ralph config show`: container environment values.
+const SECRET_KEYS: &[&str] = &["sandbox.env.vars"];
+
+/// Notification targets, whose webhook URLs are credentials.
+const NOTIFICATION_KEYS: &[&str] = &[
+    "monitoring.notifications.on_complete",
+    "monitoring.notifications.on_error",
+    "monitoring.notifications.on_iteration",
+];
+
+/// Proxy URLs, which may carry `user:password@`.
+const PROXY_KEYS: &[&str] = &["sandbox.network.http_proxy", "sandbox.network.https_proxy"];
+
+/// Replaces with `***` every string under `value` (at dotted key `path`)
+/// whose key is in `expanded_keys` (expanded from the environment, so
+/// possibly a `${SECRET}`) or in [`SECRET_KEYS`]. Notification targets keep
+/// their method (`slack:***`) and proxy URLs keep all but their userinfo.
+/// Keys stay visible.
+fn redact(value: &mut toml::Value, path: &str, expanded_keys: &[String]) {
+    if SECRET_KEYS.contains(&path) || expanded_keys.iter().any(|key| key == path) {
+        mask_strings(value);
+        return;
+    }
+    match value {
+        toml::Value::String(text) if NOTIFICATION_KEYS.contains(&path) => {
+            if let Some((method, _)) = text.split_once(':') {
+                *text = format!("{method}:***");
+            }
+        }
+        toml::Value::String(text) if PROXY_KEYS.contains(&path) => {
+            *text = mask_userinfo(text);
+        }
+        toml::Value::Array(items) => {
+            for item in items {
+                redact(item, path, expanded_keys);
+            }
+        }
+        toml::Value::Table(table) => {
+            for (key, item) in table {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                redact(item, &path, expanded_keys);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Replaces the `user:password` in a URL such as `http://user:pw@host` with `***`.
+fn mask_userinfo(url: &str) -> String {
+    let (scheme, rest) = url.split_once("://").unwrap_or(("", url));
+    let authority_end = rest.find('/').unwrap_or(rest.len());
+    match rest[..authority_end].rfind('@') {
+        Some(at) if scheme.is_empty() => format!("***{}", &rest[at..]),
+        Some(at) => format!("{scheme}://***{}", &rest[at..]),
+        None => url.to_string(),
+    }
+}
+
+/// Replaces every string in `value` with `***`.
+fn mask_strings(value: &mut toml::Value) {
+    match value {
+        toml::Value::String(text) => "***".clone_into(text),
+        toml::Value::Array(items) => items.iter_mut().for_each(mask_strings),
+        toml::Value::Table(table) => table.iter_mut().for_each(|(_, item)| mask_strings(item)),
+        _ => {}
+    }
+}
+
+/// Returns every semantic problem with a deserialized config.
+fn validate(config: &Config) -> Vec<String> {
+    let mut problems = config.agent.problems();
+
+    if let Err(e) = config.agent.get_provider() {
+        problems.push(format!("agent.provider: {e}"));
+    }
+    if let Err(e) = config.agent.get_fallback_provider() {
+        problems.push(format!("agent.fallback_provider: {e}"));
+    }
+    if let Err(e) = config.agent.relative_working_dir() {
+        problems.push(format!("agent.working_dir: {e}"));
+    }
+
+    let resources = &config.sandbox.resources;
+    if parse_memory_limit(&resources.memory).is_err() {
+        problems.push(format!(
+            "sandbox.resources.memory: '{}' is not a size like \"8g\" or \"512m\"",
+            resources.memory
+        ));
+    }
+    if !resources
+        .cpus
+        .parse::<f64>()
+        .is_ok_and(|cpus| cpus.is_finite() && cpus > 0.0)
+    {
+        problems.push(format!(
+            "sandbox.resources.cpus: '{}' is not a positive number",
+            resources.cpus
+        ));
+    }
+
+    let network = &config.sandbox.network;
+    for entry in &network.extra_hosts {
+        if validate_extra_host(entry).is_none() {
+            problems.push(format!(
+                "sandbox.network.extra_hosts: '{entry}' is not in hostname:ip form"
+            ));
+        }
+    }
+    for (key, url) in [
+        ("http_proxy", &network.http_proxy),
+        ("https_proxy", &network.https_proxy),
+    ] {
+        if let Some(url) = url
+            .as_deref()
+            .filter(|url| validate_proxy_url(url).is_none())
+        {
+            problems.push(format!(
+                "sandbox.network.{key}: '{url}' is not a proxy URL like \"http://host:port\""
+            ));
+        }
+    }
+    if network.policy == NetworkPolicy::Allowlist {
+        if network.allowed.is_empty() && network.allowed_cidrs.is_empty() {
+            problems.push(
+                "sandbox.network: policy is \"allowlist\" but `allowed` and `allowed_cidrs` are empty"
+                    .to_string(),
+            );
+        }
+        for domain in &network.allowed {
+            if validate_domain(domain).is_none() {
+                problems.push(format!(
+                    "sandbox.network.allowed: '{domain}' is not a valid domain"
+                ));
+            }
+        }
+        for cidr in &network.allowed_cidrs {
+            if validate_cidr(cidr).is_none() {
+                problems.push(format!(
+                    "sandbox.network.allowed_cidrs: '{cidr}' is not a valid CIDR block"
+                ));
+            }
+        }
+    }
+
+    let multiplier = config.backoff.multiplier;
+    if !(multiplier.is_finite() && multiplier >= 1.0) {
+        problems.push(format!(
+            "backoff.multiplier: '{multiplier}' must be a number >= 1"
+        ));
+    }
+
+    let completion = &config.completion;
+    if !completion.strategy.can_complete(
+        completion.promise.is_some(),
+        completion.success_command.is_some(),
+    ) {
+        problems.push(
+            "completion.strategy: can never be satisfied (set `promise`/`success_command` for the checks it needs, and avoid empty `any`/`all`)"
+                .to_string(),
+        );
+    }
+
+    if config.validation.enabled && config.validation.command.trim().is_empty() {
+        problems.push(
+            "validation.command: empty while validation is enabled (set enabled = false to skip)"
+                .to_string(),
+        );
+    }
+
+    if !config.prompt.validation_error_template.contains("{error}") {
+        problems.push(
+            "prompt.validation_error_template: missing `{error}` placeholder (the validation error would be dropped)"
+                .to_string(),
+        );
+    }
+
+    problems
+}
+
+/// Formats the validation report.
+fn format_problems(problems: &[String]) -> String {
+    let mut out = String::new();
+    if problems.is_empty() {
+        writeln!(&mut out, "{} ralph.toml is valid", "✓".green()).unwrap();
+        return out;
+    }
+
+    for problem in problems {
+        writeln!(&mut out, "{} {problem}", "✗".red()).unwrap();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config_is_valid() {
+        assert!(validate(&Config::default()).is_empty());
+    }
+
+    #[test]
+    fn test_template_config_is_valid() {
+        let config: Config = toml::from_str(crate::templates::RALPH_TOML).unwrap();
+        assert!(validate(&config).is_empty());
+    }
+
+    #[test]
+    fn test_reports_all_problems_at_once() {
+        let config: Config = toml::from_str(
+            r#"
+[agent]
+provider = "gpt"
+
+[sandbox.resources]
+memory = "lots"
+cpus = "four"
+
+[validation]
+command = "  "
+"#,
+        )
+        .unwrap();
+
+        let problems = validate(&config);
+        assert_eq!(problems.len(), 4, "{problems:?}");
+        assert!(problems[0].starts_with("agent.provider"));
+        assert!(problems[1].starts_with("sandbox.resources.memory"));
+        assert!(problems[2].starts_with("sandbox.resources.cpus"));
+        assert!(problems[3].starts_with("validation.command"));
+    }
+
+    #[test]
+    fn test_working_dir_outside_project() {
+        let mut config = Config::default();
+        config.agent.working_dir = Some("../sibling".to_string());
+        let problems = validate(&config);
+        assert_eq!(problems.len(), 1, "{problems:?}");
+        assert!(problems[0].starts_with("agent.working_dir"));
+
+        config.agent.working_dir = Some("packages/app".to_string());
+        assert!(validate(&config).is_empty());
+    }
+
+    #[test]
+    fn test_non_positive_cpus() {
+        let mut config = Config::default();
+        for cpus in ["0", "-1", "NaN"] {
+            config.sandbox.resources.cpus = cpus.to_string();
+            assert_eq!(validate(&config).len(), 1, "cpus = {cpus}");
+        }
+    }
+
+    #[test]
+    fn test_empty_allowlist() {
+        let config: Config = toml::from_str(
+            r#"
+[sandbox.network]
+policy = "allowlist"
+"#,
+        )
+        .unwrap();
+
+        let problems = validate(&config);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("allowlist"));
+    }
+
+    #[test]
+    fn test_invalid_allowlist_entries() {
+        let config: Config = toml::from_str(
+            r#"
+[sandbox.network]
+policy = "allowlist"
+allowed = ["github.com", "bad domain"]
+allowed_cidrs = ["10.0.0.0/8", "10.0.0.0/99"]
+"#,
+        )
+        .unwrap();
+
+        let problems = validate(&config);
+        assert_eq!(problems.len(), 2, "{problems:?}");
+        assert!(problems[0].contains("'bad domain'"));
+        assert!(problems[1].contains("'10.0.0.0/99'"));
+    }
+
+    #[test]
+    fn test_invalid_backoff_multiplier() {
+        let mut config = Config::default();
+        config.backoff.multiplier = 0.5;
+        let problems = validate(&config);
+        assert_eq!(problems.len(), 1, "{problems:?}");
+        assert!(problems[0].starts_with("backoff.multiplier"));
+
+        config.backoff.multiplier = 1.0;
+        assert!(validate(&config).is_empty());
+    }
+
+    #[test]
+    fn test_invalid_extra_hosts() {
+        let config: Config = toml::from_str(
+            r#"
+[sandbox.network]
+extra_hosts = ["registry.internal:10.1.2.3", "registry.internal"]
+"#,
+        )
+        .unwrap();
+
+        let problems = validate(&config);
+        assert_eq!(problems.len(), 1, "{problems:?}");
+        assert!(problems[0].contains("'registry.internal'"));
+    }
+
+    #[test]
+    fn test_invalid_proxy_urls() {
+        let config: Config = toml::from_str(
+            r#"
+[sandbox.network]
+http_proxy = "http://proxy.corp:3128"
+https_proxy = "http://proxy.corp:port"
+"#,
+        )
+        .unwrap();
+
+        let problems = validate(&config);
+        assert_eq!(problems.len(), 1, "{problems:?}");
+        assert!(problems[0].starts_with("sandbox.network.https_proxy"));
+    }
+
+    #[test]
+    fn test_unreachable_completion_strategy() {
+        let config: Config = toml::from_str(
+            r#"
+[completion]
+strategy = { all = ["idle", "command"] }
+"#,
+        )
+        .unwrap();
+        let problems = validate(&config);
+        assert_eq!(problems.len(), 1, "{problems:?}");
+        assert!(problems[0].starts_with("completion.strategy"));
+
+        let config: Config = toml::from_str(
+            r#"
+[completion]
+strategy = { all = ["idle", "command"] }
+success_command = "./check_done.sh"
+"#,
+        )
+        .unwrap();
+        assert!(validate(&config).is_empty());
+    }
+
+    #[test]
+    fn test_empty_command_ok_when_validation_disabled() {
+        let mut config = Config::default();
+        config.validation.command = String::new();
+        assert_eq!(validate(&config).len(), 1);
+
+        config.validation.enabled = false;
+        assert!(validate(&config).is_empty());
+    }
+
+    #[test]
+    fn test_invalid_fallback_provider() {
+        let mut config = Config::default();
+        config.agent.fallback_provider = Some("nope".to_string());
+        let problems = validate(&config);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("agent.fallback_provider"));
+    }
+
+    #[test]
+    fn test_validation_error_template_needs_placeholder() {
+        let mut config = Config::default();
+        config.prompt.validation_error_template = "Validation failed: {error}".to_string();
+        assert!(validate(&config).is_empty());
+
+        config.prompt.validation_error_template = "Validation failed.".to_string();
+        let problems = validate(&config);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("prompt.validation_error_template"));
+    }
+
+    #[test]
+    fn test_reports_agent_problems_with_the_rest() {
+        let config: Config = toml::from_str(
+            r#"
+[agent.cursor]
+output_format = "jsn"
+sandbox = "off"
+
+[agent.claude]
+output_format = "JSON"
+
+[backoff]
+multiplier = 0.5
+"#,
+        )
+        .unwrap();
+
+        let problems = validate(&config);
+        assert_eq!(problems.len(), 4, "{problems:?}");
+        assert!(problems[0].starts_with("agent.cursor.output_format"));
+        assert!(problems[1].starts_with("agent.cursor.sandbox"));
+        assert!(problems[2].starts_with("agent.claude.output_format"));
+        assert!(problems[3].starts_with("backoff.multiplier"));
+    }
+
+    #[test]
+    fn test_redact_env_vars_and_expanded_values() {
+        let mut config = Config::default();
+        config
+            .sandbox
+            .env
+            .vars
+            .insert("API_TOKEN".to_string(), "s3cret".to_string());
+        config.sandbox.image = "registry.corp/ralph:token-abc".to_string();
+        let mut value = toml::Value::try_from(&config).unwrap();
+
+        redact(&mut value, "", &["sandbox.image".to_string()]);
+        let rendered = toml::to_string_pretty(&value).unwrap();
+        assert!(rendered.contains("API_TOKEN = \"***\""), "{rendered}");
+        assert!(rendered.contains("image = \"***\""), "{rendered}");
+        assert!(!rendered.contains("s3cret"));
+        assert!(!rendered.contains("token-abc"));
+        // Untouched values stay visible
+        assert!(rendered.contains("provider = \"cursor\""));
+    }
+
+    #[test]
+    fn test_redact_notification_targets_and_proxy_credentials() {
+        let mut config = Config::default();
+        config.monitoring.notifications.on_complete =
+            Some("slack:https://hooks.slack.com/services/T0/B0/xyz".to_string());
+        config.monitoring.notifications.on_error = Some("desktop".to_string());
+        config.sandbox.network.http_proxy = Some("http://alice:pw@proxy.corp:3128".to_string());
+        config.sandbox.network.https_proxy = Some("http://proxy.corp:3128".to_string());
+        let mut value = toml::Value::try_from(&config).unwrap();
+
+        redact(&mut value, "", &[]);
+        let rendered = toml::to_string_pretty(&value).unwrap();
+        assert!(
+            rendered.contains("on_complete = \"slack:***\""),
+            "{rendered}"
+        );
+        assert!(rendered.contains("on_error = \"desktop\""), "{rendered}");
+        assert!(
+            rendered.contains("http_proxy = \"http://***@proxy.corp:3128\""),
+            "{rendered}"
+        );
+        assert!(
+            rendered.contains("https_proxy = \"http://proxy.corp:3128\""),
+            "{rendered}"
+        );
+        assert!(!rendered.contains("hooks.slack.com"));
+        assert!(!rendered.contains("alice"));
+    }
+
+    #[test]
+    fn test_mask_userinfo() {
+        assert_eq!(
+            mask_userinfo("http://user:pw@proxy:3128/path@x"),
+            "http://***@proxy:3128/path@x"
+        );
+        assert_eq!(mask_userinfo("user:pw@proxy:3128"), "***@proxy:3128");
+        assert_eq!(mask_userinfo("http://proxy:3128"), "http://proxy:3128");
+    }
+
+    #[test]
+    fn test_format_problems() {
+        assert!(format_problems(&[]).contains("ralph.toml is valid"));
+
+        let out = format_problems(&["a: bad".to_string(), "b: worse".to_string()]);
+        assert!(out.contains("a: bad"));
+        assert!(out.contains("b: worse"));
+    }
+}
@@ -0,0 +1,292 @@
+//! Show what the loop changed.
+//!
+//! Diffs `HEAD` against the commit the loop started from. The start is the
+//! `initial_commit` recorded in `.ralph/state.toml`, or, for older state
+//! files, the last commit before `started_at`.
+//!
+//! Argument building and `--stat` parsing are pure. Git is invoked only at
+//! the top level.
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use std::fmt::Write;
+use std::path::Path;
+use std::process::Command;
+
+use crate::state::RalphState;
+
+// -----------------------------------------------------------------------------
+// Public API
+// -----------------------------------------------------------------------------
+
+/// How much of the diff to print.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DiffView {
+    /// Per-file `--stat` lines with totals (default).
+    Stat,
+    /// Changed file names only.
+    NameOnly,
+    /// The full patch.
+    Full,
+}
+
+impl DiffView {
+    /// Picks the view from the `--name-only` and `--full` flags.
+    pub(crate) fn from_flags(name_only: bool, full: bool) -> Self {
+        if full {
+            Self::Full
+        } else if name_only {
+            Self::NameOnly
+        } else {
+            Self::Stat
+        }
+    }
+}
+
+/// Runs the diff command, printing changes since the loop started.
+pub(crate) fn run(view: DiffView) -> Result<()> {
+    let cwd = std::env::current_dir().context("Failed to get current directory")?;
+
+    let Some(state) = RalphState::load(&cwd)? else {
+        bail!(
+            "No saved Ralph loop state found.\n\
+             Run 'ralph loop' to start a new loop."
+        );
+    };
+    let base = match state.initial_commit {
+        Some(commit) => commit,
+        None => commit_before(&cwd, state.started_at)?,
+    };
+
+    let output = git(&cwd, &diff_args(&base, view), "Failed to run git diff")?;
+    print!("{}", format_diff(&base, view, &output));
+
+    Ok(())
+}
+
+// -----------------------------------------------------------------------------
+// Internal types
+// -----------------------------------------------------------------------------
+
+/// Totals from the last line of `git diff --stat`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct DiffStat {
+    /// Number of files changed.
+    files_changed: u32,
+    /// Lines added.
+    insertions: u32,
+    /// Lines removed.
+    deletions: u32,
+}
+
+// -----------------------------------------------------------------------------
+// Helper functions
+// -----------------------------------------------------------------------------
+
+/// Hash prefix length shown in the header.
+const SHORT_HASH_LEN: usize = 7;
+
+/// Builds `git diff` arguments for `base..HEAD`.
+fn diff_args(base: &str, view: DiffView) -> Vec<String> {
+    let mut args = vec!["diff".to_string()];
+    match view {
+        DiffView::Stat => args.push("--stat".to_string()),
+        DiffView::NameOnly => args.push("--name-only".to_string()),
+        DiffView::Full => {}
+    }
+    args.push(format!("{base}..HEAD"));
+    args
+}
+
+/// Splits `git diff --stat` output into per-file lines and the totals line.
+fn split_stat(output: &str) -> (Vec<&str>, Option<&str>) {
+    let mut lines: Vec<&str> = output.lines().filter(|l| !l.trim().is_empty()).collect();
+    let summary = lines.pop_if(|line| line.contains(" changed"));
+    (lines, summary)
+}
+
+/// Parses the totals line of `git diff --stat` output.
+///
+/// Git omits the insertions or deletions part when it is zero. Returns the
+/// default (all zero) when there is no totals line, i.e. no changes.
+fn parse_diff_stat(output: &str) -> DiffStat {
+    let Some(summary) = split_stat(output).1 else {
+        return DiffStat::default();
+    };
+
+    let mut stat = DiffStat::default();
+    for part in summary.split(',') {
+        let mut words = part.split_whitespace();
+        let Some(count) = words.next().and_then(|n| n.parse().ok()) else {
+            continue;
+        };
+        match words.next() {
+            Some(w) if w.starts_with("file") => stat.files_changed = count,
+            Some(w) if w.starts_with("insertion") => stat.insertions = count,
+            Some(w) if w.starts_with("deletion") => stat.deletions = count,
+            _ => {}
+        }
+    }
+    stat
+}
+
+/// Formats the diff output under a header naming the loop start commit.
+///
+/// In the stat view the totals move into the header and per-file lines follow.
+fn format_diff(base: &str, view: DiffView, output: &str) -> String {
+    let short = &base[..base.len().min(SHORT_HASH_LEN)];
+    let mut out = String::new();
+
+    if output.trim().is_empty() {
+        writeln!(&mut out, "No changes since loop start ({short})").unwrap();
+        return out;
+    }
+
+    if view == DiffView::Stat {
+        let stat = parse_diff_stat(output);
+        let (files, _) = split_stat(output);
+        writeln!(
+            &mut out,
+            "{} ({short}): {} file(s) changed, {} {}",
+            "Changes since loop start".bold(),
+            stat.files_changed,
+            format!("+{}", stat.insertions).green(),
+            format!("-{}", stat.deletions).red()
+        )
+        .unwrap();
+        for line in files {
+            writeln!(&mut out, "{line}").unwrap();
+        }
+    } else {
+        writeln!(&mut out, "{} ({short})", "Changes since loop start".bold()).unwrap();
+        out.push_str(output);
+    }
+    out
+}
+
+/// Finds the last commit before `started_at`, for state without `initial_commit`.
+fn commit_before(cwd: &Path, started_at: DateTime<Utc>) -> Result<String> {
+    let before = format!("--before={}", started_at.to_rfc3339());
+    let output = git(
+        cwd,
+        &["rev-list", "-1", &before, "HEAD"].map(str::to_string),
+        "Failed to find the loop start commit",
+    )?;
+    let commit = output.trim();
+    if commit.is_empty() {
+        bail!("No commit found before the loop started at {started_at}");
+    }
+    Ok(commit.to_string())
+}
+
+/// Runs git with `args` in `cwd` and returns stdout.
+fn git(cwd: &Path, args: &[String], error: &str) -> Result<String> {
+    let output = Command::new("git")
+        .current_dir(cwd)
+        .args(args)
+        .output()
+        .context(error.to_string())?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("{error}: {}", stderr.trim());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STAT: &str = " src/main.rs   | 12 ++++++++----
+ src/lib.rs    |  3 +++
+ README.md     |  2 --
+ 3 files changed, 11 insertions(+), 6 deletions(-)
+";
+
+    #[test]
+    fn test_view_from_flags() {
+        assert_eq!(DiffView::from_flags(false, false), DiffView::Stat);
+        assert_eq!(DiffView::from_flags(true, false), DiffView::NameOnly);
+        assert_eq!(DiffView::from_flags(false, true), DiffView::Full);
+    }
+
+    #[test]
+    fn test_diff_args() {
+        assert_eq!(
+            diff_args("abc123", DiffView::Stat),
+            vec!["diff", "--stat", "abc123..HEAD"]
+        );
+        assert_eq!(
+            diff_args("abc123", DiffView::NameOnly),
+            vec!["diff", "--name-only", "abc123..HEAD"]
+        );
+        assert_eq!(
+            diff_args("abc123", DiffView::Full),
+            vec!["diff", "abc123..HEAD"]
+        );
+    }
+
+    #[test]
+    fn test_parse_diff_stat() {
+        assert_eq!(
+            parse_diff_stat(STAT),
+            DiffStat {
+                files_changed: 3,
+                insertions: 11,
+                deletions: 6,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_diff_stat_singular_and_missing_parts() {
+        assert_eq!(
+            parse_diff_stat(" a.rs | 1 +\n 1 file changed, 1 insertion(+)\n"),
+            DiffStat {
+                files_changed: 1,
+                insertions: 1,
+                deletions: 0,
+            }
+        );
+        assert_eq!(
+            parse_diff_stat(" 2 files changed, 4 deletions(-)\n"),
+            DiffStat {
+                files_changed: 2,
+                insertions: 0,
+                deletions: 4,
+            }
+        );
+        assert_eq!(parse_diff_stat(""), DiffStat::default());
+    }
+
+    #[test]
+    fn test_format_diff_stat_moves_totals_to_header() {
+        let out = format_diff("0123456789abcdef", DiffView::Stat, STAT);
+
+        let mut lines = out.lines();
+        let header = lines.next().unwrap();
+        assert!(header.contains("(0123456): 3 file(s) changed"));
+        assert!(header.contains("+11"));
+        assert!(header.contains("-6"));
+        assert_eq!(lines.count(), 3); // Per-file lines only
+        assert!(out.contains("src/lib.rs"));
+    }
+
+    #[test]
+    fn test_format_diff_no_changes() {
+        assert_eq!(
+            format_diff("0123456789", DiffView::Full, ""),
+            "No changes since loop start (0123456)\n"
+        );
+    }
+
+    #[test]
+    fn test_format_diff_name_only() {
+        let out = format_diff("abc", DiffView::NameOnly, "src/main.rs\nREADME.md\n");
+        assert!(out.lines().next().unwrap().contains("(abc)"));
+        assert!(out.ends_with("\nsrc/main.rs\nREADME.md\n"));
+    }
+}
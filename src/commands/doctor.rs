@@ -0,0 +1,400 @@
+//! Preflight health check for a Ralph project.
+//!
+//! Each check is a pure function of the condition it inspects, so tests can
+//! supply mock results. IO (config loading, PATH lookup, daemon pings)
+//! happens only at the top level.
+
+use anyhow::{bail, Result};
+use colored::Colorize;
+use std::ffi::OsStr;
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::agent::Provider;
+use crate::commands::loop_cmd::check_gh_available;
+use crate::config::{Config, ContainerRuntime};
+use crate::sandbox::DockerSandbox;
+
+// -----------------------------------------------------------------------------
+// Public API
+// -----------------------------------------------------------------------------
+
+/// Runs all checks, prints a checklist, and fails if any hard check failed.
+///
+/// `config_file` is the `--config` path; without it `ralph.toml` is checked.
+pub(crate) async fn run(config_file: Option<&Path>) -> Result<()> {
+    let cwd = std::env::current_dir()?;
+    let loaded = Config::load_from(&cwd, config_file);
+
+    let mut checks = vec![check_config(&loaded)];
+    let config = loaded.unwrap_or_default();
+
+    let provider = config.agent.get_provider();
+    checks.push(check_provider(&provider));
+
+    if let Ok(provider) = provider {
+        let path = agent_binary(&config, provider);
+        let found = path.is_some_and(|p| find_on_path(p, std::env::var_os("PATH").as_deref()));
+        checks.push(check_agent_binary(
+            provider,
+            path,
+            found,
+            config.sandbox.enabled,
+        ));
+    }
+
+    if config.sandbox.enabled {
+        let runtime = config.sandbox.runtime;
+        let ping = DockerSandbox::ping(runtime).await;
+        let reachable = ping.is_ok();
+        checks.push(check_runtime(runtime, ping));
+
+        if reachable {
+            let image = &config.sandbox.image;
+            let available = DockerSandbox::image_available(runtime, image).await;
+            checks.push(check_image(image, available));
+        }
+    } else {
+        checks.push(Check::skip("Container runtime", "sandbox disabled"));
+    }
+
+    if config.git.auto_pr {
+        checks.push(check_gh(check_gh_available().await));
+    } else {
+        checks.push(Check::skip("GitHub CLI", "auto_pr disabled"));
+    }
+
+    print!("{}", format_checklist(&checks));
+
+    let failed = checks
+        .iter()
+        .filter(|c| c.status == CheckStatus::Fail)
+        .count();
+    if failed > 0 {
+        bail!("{failed} check(s) failed");
+    }
+    Ok(())
+}
+
+// -----------------------------------------------------------------------------
+// Internal types
+// -----------------------------------------------------------------------------
+
+/// Outcome of a single check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CheckStatus {
+    /// Condition met.
+    Pass,
+    /// Problem that may cause trouble but does not block a loop.
+    Warn,
+    /// Problem that will make `ralph loop` fail.
+    Fail,
+    /// Not applicable with the current configuration.
+    Skip,
+}
+
+/// One line of the checklist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Check {
+    name: &'static str,
+    status: CheckStatus,
+    detail: String,
+    hint: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Pass,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn skip(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Skip,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Fail,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            status: CheckStatus::Warn,
+            ..Self::fail(name, detail, hint)
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Helper functions
+// -----------------------------------------------------------------------------
+
+/// Checks that ralph.toml (if present) parses.
+fn check_config(loaded: &Result<Config>) -> Check {
+    match loaded {
+        Ok(_) => Check::pass("Config", "ralph.toml parses"),
+        Err(e) => Check::fail(
+            "Config",
+            format!("{e:#}"),
+            "Fix the syntax in ralph.toml, or run `ralph init --force` to regenerate it",
+        ),
+    }
+}
+
+/// Checks that `[agent].provider` names a known provider.
+fn check_provider(provider: &Result<Provider>) -> Check {
+    match provider {
+        Ok(p) => Check::pass("Agent provider", p.to_string()),
+        Err(e) => Check::fail(
+            "Agent provider",
+            e.to_string(),
+            "Set [agent].provider to cursor, claude, or ollama",
+        ),
+    }
+}
+
+/// Returns the CLI binary the provider invokes, if it has one.
+fn agent_binary(config: &Config, provider: Provider) -> Option<&str> {
+    match provider {
+        Provider::Cursor => Some(&config.agent.cursor.path),
+        Provider::Claude => Some(&config.agent.claude.path),
+        Provider::Ollama => None,
+    }
+}
+
+/// Checks that the agent CLI is installed.
+///
+/// In sandbox mode the agent runs inside the image, so a missing host binary
+/// only warns.
+fn check_agent_binary(
+    provider: Provider,
+    path: Option<&str>,
+    found: bool,
+    sandbox_enabled: bool,
+) -> Check {
+    const NAME: &str = "Agent binary";
+    let Some(path) = path else {
+        return Check::skip(NAME, format!("{provider} is reached over HTTP"));
+    };
+    if found {
+        return Check::pass(NAME, format!("{path} found"));
+    }
+
+    let detail = format!("{path} not found on PATH");
+    let hint = format!("Install the {provider} CLI or set [agent.{provider}].path in ralph.toml");
+    if sandbox_enabled {
+        Check::warn(NAME, detail, hint)
+    } else {
+        Check::fail(NAME, detail, hint)
+    }
+}
+
+/// Checks that the container runtime daemon answered a ping.
+fn check_runtime(runtime: ContainerRuntime, ping: Result<()>) -> Check {
+    const NAME: &str = "Container runtime";
+    match ping {
+        Ok(()) => Check::pass(NAME, format!("{runtime:?} reachable")),
+        Err(e) => {
+            let hint = match runtime {
+                ContainerRuntime::Docker => "Start Docker, or run with --no-sandbox",
+                ContainerRuntime::Podman => {
+                    "Start the socket with `systemctl --user start podman.socket`, or run with --no-sandbox"
+                }
+            };
+            Check::fail(NAME, e.to_string(), hint)
+        }
+    }
+}
+
+/// Checks that the sandbox image exists locally.
+fn check_image(image: &str, available: Result<bool>) -> Check {
+    const NAME: &str = "Sandbox image";
+    match available {
+        Ok(true) => Check::pass(NAME, format!("{image} present")),
+        Ok(false) => Check::fail(
+            NAME,
+            format!("{image} not found"),
+            "Run `ralph image build` or `ralph image pull`",
+        ),
+        Err(e) => Check::fail(
+            NAME,
+            format!("{e:#}"),
+            "Check `ralph image status` for details",
+        ),
+    }
+}
+
+/// Checks that `gh` is installed and authenticated (needed for `auto_pr`).
+fn check_gh(available: bool) -> Check {
+    const NAME: &str = "GitHub CLI";
+    if available {
+        Check::pass(NAME, "gh authenticated")
+    } else {
+        Check::warn(
+            NAME,
+            "gh missing or not authenticated; pull requests will be skipped",
+            "Install gh and run `gh auth login`, or set [git].auto_pr = false",
+        )
+    }
+}
+
+/// Returns true if `binary` is an existing file, either as a path or by
+/// searching the directories in `path_var`.
+fn find_on_path(binary: &str, path_var: Option<&OsStr>) -> bool {
+    if binary.contains('/') {
+        return Path::new(binary).is_file();
+    }
+    path_var.is_some_and(|paths| std::env::split_paths(paths).any(|dir| dir.join(binary).is_file()))
+}
+
+/// Formats the checklist with one line per check and hints for problems.
+fn format_checklist(checks: &[Check]) -> String {
+    let mut out = String::new();
+    writeln!(&mut out, "{}", "Ralph doctor".bold()).unwrap();
+    writeln!(&mut out).unwrap();
+
+    for check in checks {
+        let marker = match check.status {
+            CheckStatus::Pass => "✓".green(),
+            CheckStatus::Warn => "!".yellow(),
+            CheckStatus::Fail => "✗".red(),
+            CheckStatus::Skip => "-".dimmed(),
+        };
+        writeln!(&mut out, "  {marker} {}: {}", check.name, check.detail).unwrap();
+        if let Some(hint) = &check.hint {
+            writeln!(&mut out, "      {}", hint.dimmed()).unwrap();
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_config() {
+        assert_eq!(
+            check_config(&Ok(Config::default())).status,
+            CheckStatus::Pass
+        );
+
+        let check = check_config(&Err(anyhow::anyhow!("expected `=`")));
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert!(check.detail.contains("expected `=`"));
+        assert!(check.hint.is_some());
+    }
+
+    #[test]
+    fn test_check_provider() {
+        assert_eq!(
+            check_provider(&Ok(Provider::Claude)).detail,
+            "claude".to_string()
+        );
+        let check = check_provider(&"gpt".parse());
+        assert_eq!(check.status, CheckStatus::Fail);
+    }
+
+    #[test]
+    fn test_check_agent_binary() {
+        let found = check_agent_binary(Provider::Claude, Some("claude"), true, false);
+        assert_eq!(found.status, CheckStatus::Pass);
+
+        // Missing on the host fails without a sandbox, warns with one
+        let missing = check_agent_binary(Provider::Cursor, Some("agent"), false, false);
+        assert_eq!(missing.status, CheckStatus::Fail);
+        assert!(missing.hint.unwrap().contains("[agent.cursor].path"));
+        let sandboxed = check_agent_binary(Provider::Cursor, Some("agent"), false, true);
+        assert_eq!(sandboxed.status, CheckStatus::Warn);
+
+        let ollama = check_agent_binary(Provider::Ollama, None, false, false);
+        assert_eq!(ollama.status, CheckStatus::Skip);
+    }
+
+    #[test]
+    fn test_check_runtime() {
+        assert_eq!(
+            check_runtime(ContainerRuntime::Docker, Ok(())).status,
+            CheckStatus::Pass
+        );
+
+        let check = check_runtime(
+            ContainerRuntime::Podman,
+            Err(anyhow::anyhow!("connection refused")),
+        );
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert!(check.hint.unwrap().contains("podman.socket"));
+    }
+
+    #[test]
+    fn test_check_image() {
+        assert_eq!(
+            check_image("ralph:latest", Ok(true)).status,
+            CheckStatus::Pass
+        );
+
+        let missing = check_image("ralph:latest", Ok(false));
+        assert_eq!(missing.status, CheckStatus::Fail);
+        assert!(missing.hint.unwrap().contains("ralph image build"));
+
+        let errored = check_image("ralph:latest", Err(anyhow::anyhow!("boom")));
+        assert_eq!(errored.status, CheckStatus::Fail);
+    }
+
+    #[test]
+    fn test_check_gh_is_soft() {
+        assert_eq!(check_gh(true).status, CheckStatus::Pass);
+        assert_eq!(check_gh(false).status, CheckStatus::Warn);
+    }
+
+    #[test]
+    fn test_find_on_path() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("my-agent"), "").unwrap();
+        let path_var =
+            std::env::join_paths(["/nonexistent", dir.path().to_str().unwrap()]).unwrap();
+
+        assert!(find_on_path("my-agent", Some(&path_var)));
+        assert!(!find_on_path("other-agent", Some(&path_var)));
+        assert!(!find_on_path("my-agent", None));
+
+        // Paths with a slash are checked directly
+        let full = dir.path().join("my-agent");
+        assert!(find_on_path(full.to_str().unwrap(), None));
+        assert!(!find_on_path("/nonexistent/my-agent", Some(&path_var)));
+    }
+
+    #[test]
+    fn test_format_checklist() {
+        let checks = vec![
+            Check::pass("Config", "ralph.toml parses"),
+            Check::fail(
+                "Sandbox image",
+                "ralph:latest not found",
+                "Run `ralph image build`",
+            ),
+            Check::skip("GitHub CLI", "auto_pr disabled"),
+        ];
+        let out = format_checklist(&checks);
+
+        assert!(out.contains("Config: ralph.toml parses"));
+        assert!(out.contains("Sandbox image: ralph:latest not found"));
+        assert!(out.contains("Run `ralph image build`"));
+        assert!(out.contains("GitHub CLI: auto_pr disabled"));
+        assert_eq!(out.lines().count(), 2 + checks.len() + 1);
+    }
+}
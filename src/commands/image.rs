@@ -7,24 +7,42 @@ use bollard::Docker;
 use bytes::Bytes;
 use clap::Subcommand;
 use futures_util::StreamExt;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tar::Builder;
 use tracing::{info, warn};
 
-use crate::config::Config;
+use crate::config::{Config, ContainerRuntime};
+use crate::sandbox::{connect_docker, DockerSandbox};
+
+/// Dockerfile used when the project has none and the image must be built.
+const DEFAULT_DOCKERFILE: &str = include_str!("../../Dockerfile");
+
+/// Name of the Dockerfile inside a context built from the embedded default.
+const CONTEXT_DOCKERFILE: &str = "Dockerfile";
+
+/// Context entry for a Dockerfile that lives outside the project directory.
+const EXTERNAL_DOCKERFILE: &str = "Dockerfile.ralph";
 
 /// Image management actions.
 #[derive(Subcommand, Debug)]
 pub enum ImageAction {
-    /// Build the sandbox image (Nix by default, --dockerfile for legacy)
+    /// Build the sandbox image from `sandbox.dockerfile` (or flake.nix with --nix)
     Build {
-        /// Use Dockerfile instead of Nix (legacy mode)
-        #[arg(long)]
-        dockerfile: Option<String>,
+        /// Dockerfile to build (default: sandbox.dockerfile, ".ralph/Dockerfile")
+        #[arg(long, conflicts_with = "nix")]
+        dockerfile: Option<PathBuf>,
 
         /// Image tag (default: from ralph.toml or "ralph:latest")
         #[arg(long)]
         tag: Option<String>,
+
+        /// Build without using cached layers
+        #[arg(long, conflicts_with = "nix")]
+        no_cache: bool,
+
+        /// Build with `nix build .#dockerImage` instead of a Dockerfile
+        #[arg(long)]
+        nix: bool,
     },
 
     /// Pull pre-built image from registry
@@ -47,39 +65,161 @@ pub enum ImageAction {
 }
 
 /// Run image management command.
-pub async fn run(action: ImageAction) -> Result<()> {
+pub async fn run(action: ImageAction, config_file: Option<&Path>) -> Result<()> {
     // Load config to get default image name
     let project_dir = std::env::current_dir().context("Failed to get current directory")?;
-    let config = Config::load(&project_dir)?;
+    let config = Config::load_from(&project_dir, config_file)?;
+    let runtime = config.sandbox.runtime;
 
     match action {
-        ImageAction::Build { dockerfile, tag } => {
+        ImageAction::Build {
+            dockerfile,
+            tag,
+            no_cache,
+            nix,
+        } => {
             let image_tag = tag.unwrap_or_else(|| config.sandbox.image.clone());
-            if let Some(dockerfile_path) = dockerfile {
-                // Legacy Dockerfile build
-                build_image_dockerfile(&dockerfile_path, &image_tag, &project_dir).await?;
+            if nix {
+                build_image_nix(runtime, &image_tag, &project_dir).await?;
             } else {
-                // Default: Nix-based build
-                build_image_nix(&image_tag, &project_dir).await?;
+                let dockerfile = project_dir
+                    .join(dockerfile.unwrap_or_else(|| PathBuf::from(&config.sandbox.dockerfile)));
+                if write_default_dockerfile(&dockerfile)? {
+                    info!("Wrote default Dockerfile to {}", dockerfile.display());
+                }
+                build_image_dockerfile(
+                    runtime,
+                    &dockerfile,
+                    &image_tag,
+                    &project_dir,
+                    no_cache,
+                    Progress::Stdout,
+                )
+                .await?;
             }
         }
         ImageAction::Pull { image, force } => {
             let image_name = image.unwrap_or_else(|| config.sandbox.image.clone());
-            pull_image(&image_name, config.sandbox.use_local_image, force).await?;
+            pull_image(
+                runtime,
+                &image_name,
+                config.sandbox.use_local_image,
+                force,
+                Progress::Stdout,
+            )
+            .await?;
         }
         ImageAction::Status { image } => {
             let image_name = image.unwrap_or_else(|| config.sandbox.image.clone());
-            show_image_status(&image_name).await?;
+            show_image_status(runtime, &image_name).await?;
         }
     }
 
     Ok(())
 }
 
-/// Build Docker image using Nix (default, reproducible builds).
+/// Where build and pull output goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Progress {
+    /// Printed to stdout.
+    Stdout,
+    /// Logged at info level, keeping stdout free for `--events json`.
+    Log,
+}
+
+impl Progress {
+    /// Reports one line of build or pull output.
+    fn report(self, line: &str) {
+        match self {
+            Self::Stdout => println!("{line}"),
+            Self::Log => info!("{line}"),
+        }
+    }
+}
+
+/// Way to obtain a missing sandbox image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ImageSource {
+    /// Build from the project's `sandbox.dockerfile`.
+    ProjectDockerfile,
+    /// Build from the Dockerfile embedded in ralph.
+    DefaultDockerfile,
+    /// Pull from the registry.
+    Registry,
+}
+
+/// Decides how to obtain the sandbox image, in the order to try.
 ///
-/// Runs `nix build .#dockerImage` and loads the result into Docker.
-async fn build_image_nix(tag: &str, project_dir: &Path) -> Result<()> {
+/// Nothing is needed when the image is present. With `use_local_image`, a
+/// build is tried first (`sandbox.dockerfile` if it exists, else the
+/// embedded default), falling back to a pull; otherwise the image is pulled.
+pub(crate) fn image_sources(
+    image_present: bool,
+    use_local_image: bool,
+    has_project_dockerfile: bool,
+) -> Vec<ImageSource> {
+    match (image_present, use_local_image, has_project_dockerfile) {
+        (true, _, _) => Vec::new(),
+        (false, true, true) => vec![ImageSource::ProjectDockerfile, ImageSource::Registry],
+        (false, true, false) => vec![ImageSource::DefaultDockerfile, ImageSource::Registry],
+        (false, false, _) => vec![ImageSource::Registry],
+    }
+}
+
+/// Makes sure `sandbox.image` exists locally before the loop starts,
+/// building or pulling it with `sandbox.runtime` if missing.
+#[allow(tail_expr_drop_order)] // Drop order doesn't matter for async operations
+pub(crate) async fn ensure_image(
+    config: &Config,
+    project_dir: &Path,
+    progress: Progress,
+) -> Result<()> {
+    let image = &config.sandbox.image;
+    let runtime = config.sandbox.runtime;
+    let present = DockerSandbox::image_available(runtime, image)
+        .await
+        .with_context(|| format!("Failed to check for sandbox image '{image}'"))?;
+    let dockerfile = project_dir.join(&config.sandbox.dockerfile);
+    let sources = image_sources(
+        present,
+        config.sandbox.use_local_image,
+        dockerfile.is_file(),
+    );
+    if sources.is_empty() {
+        return Ok(());
+    }
+
+    info!("Sandbox image '{}' not found locally", image);
+    let mut failures = Vec::new();
+    for source in sources {
+        let result = match source {
+            ImageSource::ProjectDockerfile => {
+                build_image_dockerfile(runtime, &dockerfile, image, project_dir, false, progress)
+                    .await
+            }
+            ImageSource::DefaultDockerfile => build_image_default(runtime, image, progress).await,
+            ImageSource::Registry => pull_image(runtime, image, false, true, progress).await,
+        };
+        match result {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                warn!("Could not obtain image via {:?}: {:#}", source, e);
+                failures.push(format!("{source:?}: {e:#}"));
+            }
+        }
+    }
+
+    anyhow::bail!(
+        "Sandbox image '{image}' is not available and could not be built or pulled:\n  {}\n\
+         Run `ralph image build` or `ralph image pull`, or set sandbox.image.",
+        failures.join("\n  ")
+    )
+}
+
+/// Build Docker image using Nix (reproducible builds).
+///
+/// Runs `nix build .#dockerImage` and loads the result into `runtime`.
+async fn build_image_nix(runtime: ContainerRuntime, tag: &str, project_dir: &Path) -> Result<()> {
     info!("Building Docker image with Nix: {}", tag);
 
     // Step 1: Build the Docker image with Nix
@@ -101,14 +241,14 @@ async fn build_image_nix(tag: &str, project_dir: &Path) -> Result<()> {
         .to_string();
     info!("Nix image built at: {}", image_path);
 
-    // Step 2: Load the image into Docker
-    info!("Loading image into Docker...");
-    let docker_load = tokio::process::Command::new("docker")
+    // Step 2: Load the image into the container runtime
+    info!("Loading image into {}...", runtime.command());
+    let docker_load = tokio::process::Command::new(runtime.command())
         .args(["load", "-i", &image_path])
         .current_dir(project_dir)
         .output()
         .await
-        .context("Failed to run docker load. Is Docker running?")?;
+        .with_context(|| format!("Failed to run {} load. Is it running?", runtime.command()))?;
 
     if !docker_load.status.success() {
         let stderr = String::from_utf8_lossy(&docker_load.stderr);
@@ -121,7 +261,7 @@ async fn build_image_nix(tag: &str, project_dir: &Path) -> Result<()> {
     // Step 3: Tag the image if needed (Nix builds as ralph:latest)
     if tag != "ralph:latest" {
         info!("Tagging image as: {}", tag);
-        let docker_tag = tokio::process::Command::new("docker")
+        let docker_tag = tokio::process::Command::new(runtime.command())
             .args(["tag", "ralph:latest", tag])
             .current_dir(project_dir)
             .output()
@@ -138,43 +278,144 @@ async fn build_image_nix(tag: &str, project_dir: &Path) -> Result<()> {
     Ok(())
 }
 
-/// Build Docker image from Dockerfile (legacy mode).
-async fn build_image_dockerfile(dockerfile: &str, tag: &str, project_dir: &Path) -> Result<()> {
+/// Build Docker image from a Dockerfile, using the project as build context.
+#[allow(tail_expr_drop_order)] // Drop order doesn't matter for async operations
+async fn build_image_dockerfile(
+    runtime: ContainerRuntime,
+    dockerfile: &Path,
+    tag: &str,
+    project_dir: &Path,
+    no_cache: bool,
+    progress: Progress,
+) -> Result<()> {
     info!("Building Docker image from Dockerfile: {}", tag);
 
-    let docker = Docker::connect_with_local_defaults()
-        .context("Failed to connect to Docker. Is Docker running?")?;
+    let docker = connect_docker(runtime).await?;
 
-    docker
-        .ping()
-        .await
-        .context("Cannot ping Docker daemon. Is Docker running?")?;
+    if !dockerfile.is_file() {
+        anyhow::bail!("Dockerfile not found: {}", dockerfile.display());
+    }
+
+    let (tar_buf, context_dockerfile) = build_context_tar(project_dir, dockerfile)?;
+
+    info!("Building image from {}...", dockerfile.display());
+    build_from_context(
+        &docker,
+        &context_dockerfile,
+        tag,
+        no_cache,
+        Bytes::from(tar_buf),
+        progress,
+    )
+    .await
+}
 
-    let dockerfile_path = project_dir.join(dockerfile);
-    if !dockerfile_path.exists() {
-        anyhow::bail!("Dockerfile not found: {}", dockerfile_path.display());
+/// Writes the embedded default Dockerfile to `path` unless a file exists.
+///
+/// Returns true if the file was written.
+fn write_default_dockerfile(path: &Path) -> Result<bool> {
+    if path.exists() {
+        return Ok(false);
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
     }
+    std::fs::write(path, DEFAULT_DOCKERFILE)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(true)
+}
 
-    let build_options = BuildImageOptions {
-        dockerfile: dockerfile.to_string(),
-        t: tag.to_string(),
-        ..Default::default()
+/// Tars `project_dir` as a build context.
+///
+/// Returns the archive and the Dockerfile's path inside it. A Dockerfile
+/// outside the project is added at the context root as `Dockerfile.ralph`.
+fn build_context_tar(project_dir: &Path, dockerfile: &Path) -> Result<(Vec<u8>, String)> {
+    let mut tar_buf = Vec::new();
+    let context_dockerfile = {
+        let mut tar = Builder::new(&mut tar_buf);
+        tar.append_dir_all(".", project_dir)
+            .context("Failed to create tarball from project directory")?;
+
+        let inside = dockerfile
+            .strip_prefix(project_dir)
+            .ok()
+            .and_then(Path::to_str)
+            .map(|relative| relative.replace('\\', "/"));
+        let context_dockerfile = if let Some(relative) = inside {
+            relative
+        } else {
+            tar.append_path_with_name(dockerfile, EXTERNAL_DOCKERFILE)
+                .with_context(|| {
+                    format!("Failed to add {} to build context", dockerfile.display())
+                })?;
+            EXTERNAL_DOCKERFILE.to_string()
+        };
+        tar.finish().context("Failed to finalize tarball")?;
+        context_dockerfile
     };
 
-    // Create tarball from project directory
+    Ok((tar_buf, context_dockerfile))
+}
+
+/// Build Docker image from the embedded default Dockerfile.
+#[allow(tail_expr_drop_order)] // Drop order doesn't matter for async operations
+async fn build_image_default(
+    runtime: ContainerRuntime,
+    tag: &str,
+    progress: Progress,
+) -> Result<()> {
+    info!("Building Docker image from the default Dockerfile: {}", tag);
+
+    let docker = connect_docker(runtime).await?;
+
+    // Build context holding only the Dockerfile
     let mut tar_buf = Vec::new();
     {
         let mut tar = Builder::new(&mut tar_buf);
-        tar.append_dir_all(".", project_dir)
-            .context("Failed to create tarball from project directory")?;
+        let mut header = tar::Header::new_gnu();
+        header.set_size(DEFAULT_DOCKERFILE.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        tar.append_data(
+            &mut header,
+            CONTEXT_DOCKERFILE,
+            DEFAULT_DOCKERFILE.as_bytes(),
+        )
+        .context("Failed to create build context")?;
         tar.finish().context("Failed to finalize tarball")?;
     }
-    let tar_bytes = Bytes::from(tar_buf);
+
+    build_from_context(
+        &docker,
+        CONTEXT_DOCKERFILE,
+        tag,
+        false,
+        Bytes::from(tar_buf),
+        progress,
+    )
+    .await
+}
+
+/// Builds `tag` from a tarred build context, streaming build output.
+async fn build_from_context(
+    docker: &Docker,
+    dockerfile: &str,
+    tag: &str,
+    no_cache: bool,
+    context: Bytes,
+    progress: Progress,
+) -> Result<()> {
+    let build_options = BuildImageOptions {
+        dockerfile: dockerfile.to_string(),
+        t: tag.to_string(),
+        nocache: no_cache,
+        ..Default::default()
+    };
 
     // Build image from tarball
-    let mut stream = docker.build_image(build_options, None, Some(tar_bytes));
+    let mut stream = docker.build_image(build_options, None, Some(context));
 
-    info!("Building image from {}...", dockerfile);
     let mut last_output = String::new();
 
     loop {
@@ -185,7 +426,7 @@ async fn build_image_dockerfile(dockerfile: &str, tag: &str, project_dir: &Path)
                 if let Some(stream_text) = &output.stream {
                     let trimmed = stream_text.trim();
                     if !trimmed.is_empty() {
-                        print!("{trimmed}");
+                        progress.report(trimmed);
                         last_output = trimmed.to_string();
                     }
                 } else if let Some(error) = &output.error {
@@ -241,14 +482,14 @@ async fn image_exists_locally(docker: &Docker, image: &str) -> Result<bool> {
 /// Pull Docker image from registry.
 ///
 /// If `use_local_image` is true and image exists locally, skip pull unless forced.
-async fn pull_image(image: &str, use_local_image: bool, force: bool) -> Result<()> {
-    let docker = Docker::connect_with_local_defaults()
-        .context("Failed to connect to Docker. Is Docker running?")?;
-
-    docker
-        .ping()
-        .await
-        .context("Cannot ping Docker daemon. Is Docker running?")?;
+async fn pull_image(
+    runtime: ContainerRuntime,
+    image: &str,
+    use_local_image: bool,
+    force: bool,
+    progress: Progress,
+) -> Result<()> {
+    let docker = connect_docker(runtime).await?;
 
     // Check for local image if configured to prefer local
     if use_local_image && !force && image_exists_locally(&docker, image).await? {
@@ -256,8 +497,8 @@ async fn pull_image(image: &str, use_local_image: bool, force: bool) -> Result<(
             "Image '{}' found locally. Skipping pull (use --force to override).",
             image
         );
-        println!("Image '{image}' already exists locally.");
-        println!("Use --force to pull anyway.");
+        progress.report(&format!("Image '{image}' already exists locally."));
+        progress.report("Use --force to pull anyway.");
         return Ok(());
     }
 
@@ -281,7 +522,7 @@ async fn pull_image(image: &str, use_local_image: bool, force: bool) -> Result<(
                 if let Some(status) = &output.status {
                     let trimmed = status.trim();
                     if !trimmed.is_empty() {
-                        println!("{trimmed}");
+                        progress.report(trimmed);
                         last_output = trimmed.to_string();
                     }
                 } else if let Some(error) = &output.error {
@@ -308,29 +549,24 @@ async fn pull_image(image: &str, use_local_image: bool, force: bool) -> Result<(
 ///
 /// Shows config info first, then attempts to query Docker for details.
 /// Succeeds even if Docker is unavailable (graceful degradation).
-async fn show_image_status(image: &str) -> Result<()> {
+async fn show_image_status(runtime: ContainerRuntime, image: &str) -> Result<()> {
     // Always show configured image name
     println!("Image: {image}");
 
-    // Try to connect to Docker
-    let docker = match Docker::connect_with_local_defaults() {
+    // Try to connect to the container runtime
+    let docker = match connect_docker(runtime).await {
         Ok(d) => d,
         Err(e) => {
-            println!("Status: Unknown (Docker not available)");
+            println!("Status: Unknown ({} not available)", runtime.command());
             println!("\nNote: {e}");
-            println!("\nTo check status, ensure Docker is running.");
+            println!(
+                "\nTo check status, ensure {} is running.",
+                runtime.command()
+            );
             return Ok(());
         }
     };
 
-    // Try to ping Docker daemon
-    if let Err(e) = docker.ping().await {
-        println!("Status: Unknown (Docker daemon not responding)");
-        println!("\nNote: {e}");
-        println!("\nTo check status, ensure Docker daemon is running.");
-        return Ok(());
-    }
-
     // List all images and find matching ones
     let images = docker
         .list_images(Some(ListImagesOptions::<String> {
@@ -407,6 +643,7 @@ fn parse_image_tag(image: &str) -> (&str, &str) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tempfile::TempDir;
 
     #[test]
     fn test_parse_image_tag() {
@@ -442,7 +679,7 @@ mod tests {
     async fn test_show_image_status_no_docker() {
         // This test verifies the function handles Docker unavailability gracefully
         // It will skip if Docker is not available
-        let result = show_image_status("nonexistent:image").await;
+        let result = show_image_status(ContainerRuntime::Docker, "nonexistent:image").await;
 
         // Function should either succeed (returning status) or fail with Docker connection error
         match result {
@@ -492,7 +729,14 @@ mod tests {
     async fn test_pull_image_local_check_no_docker() {
         // This test verifies pull respects use_local_image setting
         // It will gracefully handle Docker unavailability
-        let result = pull_image("nonexistent:image", true, false).await;
+        let result = pull_image(
+            ContainerRuntime::Docker,
+            "nonexistent:image",
+            true,
+            false,
+            Progress::Log,
+        )
+        .await;
 
         match result {
             Ok(()) => {
@@ -508,4 +752,153 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_image_sources_present_needs_nothing() {
+        assert!(image_sources(true, true, true).is_empty());
+        assert!(image_sources(true, false, false).is_empty());
+    }
+
+    #[test]
+    fn test_image_sources_missing_local_builds_first() {
+        assert_eq!(
+            image_sources(false, true, true),
+            vec![ImageSource::ProjectDockerfile, ImageSource::Registry]
+        );
+        assert_eq!(
+            image_sources(false, true, false),
+            vec![ImageSource::DefaultDockerfile, ImageSource::Registry]
+        );
+    }
+
+    #[test]
+    fn test_image_sources_missing_remote_pulls() {
+        assert_eq!(
+            image_sources(false, false, true),
+            vec![ImageSource::Registry]
+        );
+        assert_eq!(
+            image_sources(false, false, false),
+            vec![ImageSource::Registry]
+        );
+    }
+
+    #[test]
+    fn test_default_dockerfile_embedded() {
+        assert!(DEFAULT_DOCKERFILE.contains("FROM "));
+    }
+
+    #[derive(clap::Parser)]
+    struct ImageCli {
+        #[command(subcommand)]
+        action: ImageAction,
+    }
+
+    fn parse(args: &[&str]) -> Result<ImageAction, clap::Error> {
+        use clap::Parser;
+        ImageCli::try_parse_from(std::iter::once("image").chain(args.iter().copied()))
+            .map(|cli| cli.action)
+    }
+
+    #[test]
+    fn test_build_args_default() {
+        let ImageAction::Build {
+            dockerfile,
+            tag,
+            no_cache,
+            nix,
+        } = parse(&["build"]).unwrap()
+        else {
+            panic!("expected build");
+        };
+        assert!(dockerfile.is_none());
+        assert!(tag.is_none());
+        assert!(!no_cache);
+        assert!(!nix);
+    }
+
+    #[test]
+    fn test_build_args_dockerfile_tag_no_cache() {
+        let ImageAction::Build {
+            dockerfile,
+            tag,
+            no_cache,
+            ..
+        } = parse(&[
+            "build",
+            "--dockerfile",
+            "docker/Dockerfile.dev",
+            "--tag",
+            "ralph:dev",
+            "--no-cache",
+        ])
+        .unwrap()
+        else {
+            panic!("expected build");
+        };
+        assert_eq!(dockerfile, Some(PathBuf::from("docker/Dockerfile.dev")));
+        assert_eq!(tag.as_deref(), Some("ralph:dev"));
+        assert!(no_cache);
+    }
+
+    #[test]
+    fn test_build_args_nix_conflicts() {
+        assert!(parse(&["build", "--nix"]).is_ok());
+        assert!(parse(&["build", "--nix", "--no-cache"]).is_err());
+        assert!(parse(&["build", "--nix", "--dockerfile", "Dockerfile"]).is_err());
+    }
+
+    fn tar_entries(tar_buf: &[u8]) -> Vec<String> {
+        let mut archive = tar::Archive::new(tar_buf);
+        archive
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_build_context_tar_dockerfile_in_project() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.path().join(".ralph")).unwrap();
+        std::fs::write(dir.path().join(".ralph/Dockerfile"), "FROM scratch\n").unwrap();
+        std::fs::write(dir.path().join("Cargo.toml"), "").unwrap();
+
+        let (tar_buf, dockerfile) =
+            build_context_tar(dir.path(), &dir.path().join(".ralph/Dockerfile")).unwrap();
+
+        assert_eq!(dockerfile, ".ralph/Dockerfile");
+        let entries = tar_entries(&tar_buf);
+        assert!(entries.iter().any(|e| e == ".ralph/Dockerfile"));
+        assert!(entries.iter().any(|e| e == "Cargo.toml"));
+    }
+
+    #[test]
+    fn test_build_context_tar_dockerfile_outside_project() {
+        let project = TempDir::new().unwrap();
+        let other = TempDir::new().unwrap();
+        std::fs::write(project.path().join("main.rs"), "").unwrap();
+        std::fs::write(other.path().join("Dockerfile"), "FROM scratch\n").unwrap();
+
+        let (tar_buf, dockerfile) =
+            build_context_tar(project.path(), &other.path().join("Dockerfile")).unwrap();
+
+        assert_eq!(dockerfile, EXTERNAL_DOCKERFILE);
+        let entries = tar_entries(&tar_buf);
+        assert!(entries.iter().any(|e| e == EXTERNAL_DOCKERFILE));
+        assert!(entries.iter().any(|e| e == "main.rs"));
+    }
+
+    #[test]
+    fn test_write_default_dockerfile_only_when_missing() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(".ralph/Dockerfile");
+
+        assert!(write_default_dockerfile(&path).unwrap());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), DEFAULT_DOCKERFILE);
+
+        std::fs::write(&path, "FROM custom\n").unwrap();
+        assert!(!write_default_dockerfile(&path).unwrap());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "FROM custom\n");
+    }
 }
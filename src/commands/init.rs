@@ -4,24 +4,54 @@
 //! filesystem operations, making the core logic easily testable.
 
 use anyhow::{Context, Result};
+use clap::ValueEnum;
 use colored::Colorize;
 use std::fs;
 use std::path::{Path, PathBuf};
 use tracing::info;
 
-use crate::templates;
+use crate::templates::{self, ProjectTemplate};
 
 // -----------------------------------------------------------------------------
 // Public API
 // -----------------------------------------------------------------------------
 
+/// Project template selecting the validation command and build prompt.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum InitTemplate {
+    /// Rust: cargo clippy and cargo test.
+    Rust,
+    /// Node.js: npm test.
+    Node,
+    /// Python: pytest.
+    Python,
+    /// Nix: nix flake check (default).
+    #[default]
+    Nix,
+}
+
+impl InitTemplate {
+    /// Returns the template files for this variant.
+    fn project_template(self) -> ProjectTemplate {
+        match self {
+            Self::Rust => templates::RUST,
+            Self::Node => templates::NODE,
+            Self::Python => templates::PYTHON,
+            Self::Nix => templates::NIX,
+        }
+    }
+}
+
 /// Runs the init command, creating Ralph project files.
-pub(crate) fn run(force: bool) -> Result<()> {
+pub(crate) fn run(force: bool, template: InitTemplate) -> Result<()> {
     let cwd = std::env::current_dir().context("Failed to get current directory")?;
 
-    info!("Initializing Ralph in {}", cwd.display());
+    info!(
+        "Initializing Ralph in {} ({template:?} template)",
+        cwd.display()
+    );
 
-    let files = init_files();
+    let files = init_files(template);
 
     let results = init_project(
         &files,
@@ -52,7 +82,7 @@ struct InitFile {
     /// Relative path for the file.
     path: PathBuf,
     /// File content.
-    content: &'static str,
+    content: String,
     /// Human-readable description.
     description: &'static str,
 }
@@ -73,31 +103,32 @@ enum WriteResult {
 // -----------------------------------------------------------------------------
 
 /// Returns the list of files to initialize in a Ralph project.
-fn init_files() -> Vec<InitFile> {
+fn init_files(template: InitTemplate) -> Vec<InitFile> {
+    let project = template.project_template();
     vec![
         InitFile {
             path: PathBuf::from("ralph.toml"),
-            content: templates::RALPH_TOML,
+            content: project.ralph_toml(),
             description: "Project configuration",
         },
         InitFile {
             path: PathBuf::from("PROMPT_plan.md"),
-            content: templates::PROMPT_PLAN,
+            content: templates::PROMPT_PLAN.to_string(),
             description: "Planning mode prompt",
         },
         InitFile {
             path: PathBuf::from("PROMPT_build.md"),
-            content: templates::PROMPT_BUILD,
+            content: project.prompt_build.to_string(),
             description: "Building mode prompt",
         },
         InitFile {
             path: PathBuf::from(".cursor/rules/ralph.mdc"),
-            content: templates::RULES_MDC,
+            content: templates::RULES_MDC.to_string(),
             description: "Ralph rules for Cursor",
         },
         InitFile {
             path: PathBuf::from("AGENTS.md"),
-            content: templates::AGENTS_MD,
+            content: templates::AGENTS_MD.to_string(),
             description: "Operational guide (customize this!)",
         },
     ]
@@ -140,7 +171,7 @@ where
         let result = if exists(&file.path) && !force {
             WriteResult::Skipped
         } else {
-            write_file(&file.path, file.content)?;
+            write_file(&file.path, &file.content)?;
             if exists(&file.path) && force {
                 WriteResult::Overwritten
             } else {
@@ -250,16 +281,48 @@ mod tests {
 
     #[test]
     fn test_init_files_not_empty() {
-        let files = init_files();
+        let files = init_files(InitTemplate::default());
         assert!(!files.is_empty());
         assert!(files
             .iter()
             .any(|f| f.path.as_path() == Path::new("ralph.toml")));
     }
 
+    #[test]
+    fn test_init_files_use_template() {
+        let find = |files: &[InitFile], path: &str| {
+            files
+                .iter()
+                .find(|f| f.path.as_path() == Path::new(path))
+                .unwrap()
+                .content
+                .clone()
+        };
+
+        let nix = init_files(InitTemplate::Nix);
+        assert_eq!(find(&nix, "ralph.toml"), templates::RALPH_TOML);
+        assert_eq!(find(&nix, "PROMPT_build.md"), templates::PROMPT_BUILD);
+
+        let rust = init_files(InitTemplate::Rust);
+        let config: crate::config::Config = toml::from_str(&find(&rust, "ralph.toml")).unwrap();
+        assert_eq!(
+            config.validation.command,
+            "sh -c \"cargo clippy --all-targets -- -D warnings && cargo test\""
+        );
+        assert!(find(&rust, "PROMPT_build.md").contains("cargo"));
+
+        let node = init_files(InitTemplate::Node);
+        let config: crate::config::Config = toml::from_str(&find(&node, "ralph.toml")).unwrap();
+        assert_eq!(config.validation.command, "npm test");
+
+        let python = init_files(InitTemplate::Python);
+        let config: crate::config::Config = toml::from_str(&find(&python, "ralph.toml")).unwrap();
+        assert_eq!(config.validation.command, "python -m pytest");
+    }
+
     #[test]
     fn test_init_project_creates_files() {
-        let files = init_files();
+        let files = init_files(InitTemplate::default());
         let written = RefCell::new(HashMap::new());
         let dirs_created = RefCell::new(HashSet::new());
 
@@ -297,7 +360,7 @@ mod tests {
 
     #[test]
     fn test_init_project_skips_existing_without_force() {
-        let files = init_files();
+        let files = init_files(InitTemplate::default());
         let written = RefCell::new(HashMap::new());
 
         let results = init_project(
@@ -325,7 +388,7 @@ mod tests {
 
     #[test]
     fn test_init_project_overwrites_with_force() {
-        let files = init_files();
+        let files = init_files(InitTemplate::default());
         let written = RefCell::new(HashMap::new());
 
         let results = init_project(
@@ -356,12 +419,12 @@ mod tests {
         let files = vec![
             InitFile {
                 path: PathBuf::from("new.txt"),
-                content: "new",
+                content: "new".to_string(),
                 description: "New file",
             },
             InitFile {
                 path: PathBuf::from("existing.txt"),
-                content: "existing",
+                content: "existing".to_string(),
                 description: "Existing file",
             },
         ];
@@ -393,7 +456,7 @@ mod tests {
     fn test_format_results_created() {
         let files = vec![InitFile {
             path: PathBuf::from("test.txt"),
-            content: "content",
+            content: "content".to_string(),
             description: "Test file",
         }];
         let results = vec![(PathBuf::from("test.txt"), WriteResult::Created)];
@@ -409,7 +472,7 @@ mod tests {
     fn test_format_results_skipped() {
         let files = vec![InitFile {
             path: PathBuf::from("existing.txt"),
-            content: "content",
+            content: "content".to_string(),
             description: "Existing",
         }];
         let results = vec![(PathBuf::from("existing.txt"), WriteResult::Skipped)];
@@ -423,7 +486,7 @@ mod tests {
     fn test_format_results_overwritten() {
         let files = vec![InitFile {
             path: PathBuf::from("old.txt"),
-            content: "new",
+            content: "new".to_string(),
             description: "Overwritten",
         }];
         let results = vec![(PathBuf::from("old.txt"), WriteResult::Overwritten)];
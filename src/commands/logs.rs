@@ -0,0 +1,385 @@
+//! View the structured loop log.
+//!
+//! Reads `monitoring.log_file`, optionally filtering JSON lines by their
+//! `event` field. JSON lines are pretty-printed; text lines stream raw.
+//! With `--follow`, the log is polled for new lines until interrupted.
+//!
+//! Line filtering and formatting are pure. IO happens only at the top level.
+
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde_json::Value;
+use std::fmt::Write as _;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config::Config;
+
+/// How often `--follow` checks the log for new lines.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// -----------------------------------------------------------------------------
+// Public API
+// -----------------------------------------------------------------------------
+
+/// Runs the logs command, printing the loop log.
+///
+/// `event` keeps only lines whose `event` field matches. `follow` keeps
+/// polling for new lines, switching to the newest file when the log rotates.
+pub(crate) fn run(follow: bool, event: Option<&str>, config_file: Option<&Path>) -> Result<()> {
+    let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    let config = Config::load_from(&cwd, config_file).context("Failed to load ralph.toml")?;
+    let monitoring = &config.monitoring;
+
+    if monitoring.log_file.is_empty() {
+        bail!("File logging is disabled (monitoring.log_file is empty in ralph.toml).");
+    }
+
+    let configured = resolve_log_path(&cwd, &monitoring.log_file);
+    let json = monitoring.log_format == "json";
+
+    let mut current = find_latest_log(&configured);
+    if current.is_none() && !follow {
+        bail!(
+            "No log file found at {}.\n\
+             Run 'ralph loop' to start a loop.",
+            configured.display()
+        );
+    }
+
+    let mut offset = 0;
+    loop {
+        if let Some(ref path) = current {
+            offset = print_new_lines(path, offset, json, event)?;
+        }
+        if !follow {
+            return Ok(());
+        }
+
+        std::thread::sleep(POLL_INTERVAL);
+
+        // On rotation, drain the old file before switching to the new one
+        let latest = find_latest_log(&configured);
+        if latest != current {
+            if let Some(ref path) = current {
+                print_new_lines(path, offset, json, event)?;
+            }
+            current = latest;
+            offset = 0;
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Helper functions
+// -----------------------------------------------------------------------------
+
+/// Resolves the configured log path relative to the project root.
+fn resolve_log_path(cwd: &Path, log_file: &str) -> PathBuf {
+    let path = Path::new(log_file);
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        cwd.join(path)
+    }
+}
+
+/// Finds the most recently modified log file for `configured`.
+///
+/// Rotated logs are written as `<file>.<date>`, so both the configured
+/// name and any `<file>.*` siblings are candidates.
+fn find_latest_log(configured: &Path) -> Option<PathBuf> {
+    let parent = configured.parent()?;
+    let name = configured.file_name()?.to_str()?;
+    let rotated_prefix = format!("{name}.");
+
+    std::fs::read_dir(parent)
+        .ok()?
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|n| n == name || n.starts_with(&rotated_prefix))
+        })
+        .filter_map(|entry| {
+            let modified = entry.metadata().ok()?.modified().ok()?;
+            Some((modified, entry.path()))
+        })
+        .max()
+        .map(|(_, path)| path)
+}
+
+/// Prints complete lines after `offset` and returns the new offset.
+///
+/// A trailing partial line is left for the next read. If the file shrank
+/// (truncated or replaced), reading restarts from the beginning.
+fn print_new_lines(path: &Path, offset: u64, json: bool, event: Option<&str>) -> Result<u64> {
+    let mut file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open log file: {}", path.display()))?;
+    let len = file.metadata()?.len();
+    let offset = if len < offset { 0 } else { offset };
+
+    file.seek(SeekFrom::Start(offset))?;
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf)
+        .with_context(|| format!("Failed to read log file: {}", path.display()))?;
+
+    let Some(end) = buf.iter().rposition(|&b| b == b'\n') else {
+        return Ok(offset);
+    };
+    let text = String::from_utf8_lossy(&buf[..=end]);
+    print!("{}", format_lines(&text, json, event));
+
+    Ok(offset + end as u64 + 1)
+}
+
+/// Filters and formats a chunk of log text, one output line per kept line.
+fn format_lines(text: &str, json: bool, event: Option<&str>) -> String {
+    let mut out = String::new();
+    for line in text.lines().filter(|l| !l.trim().is_empty()) {
+        if !matches_event(line, event) {
+            continue;
+        }
+        writeln!(&mut out, "{}", format_line(line, json)).unwrap();
+    }
+    out
+}
+
+/// Returns true if `line` passes the event filter.
+///
+/// JSON lines match on their `event` field. Text lines match on the
+/// `event="<name>"` field as written by the text formatter.
+fn matches_event(line: &str, event: Option<&str>) -> bool {
+    let Some(wanted) = event else {
+        return true;
+    };
+    match serde_json::from_str::<Value>(line) {
+        Ok(value) => event_name(&value) == Some(wanted),
+        Err(_) => line.contains(&format!("event=\"{wanted}\"")),
+    }
+}
+
+/// Extracts the `event` field from a JSON log line.
+///
+/// The tracing JSON formatter nests fields under `fields`; a top-level
+/// `event` is accepted too.
+fn event_name(value: &Value) -> Option<&str> {
+    value
+        .get("fields")
+        .and_then(|f| f.get("event"))
+        .or_else(|| value.get("event"))
+        .and_then(Value::as_str)
+}
+
+/// Formats one log line: JSON is pretty-printed, anything else is raw.
+fn format_line(line: &str, json: bool) -> String {
+    if !json {
+        return line.to_string();
+    }
+    match serde_json::from_str::<Value>(line) {
+        Ok(value) => format_json_line(&value),
+        Err(_) => line.to_string(),
+    }
+}
+
+/// Formats a JSON log record as `timestamp LEVEL event key=value ... message`.
+fn format_json_line(value: &Value) -> String {
+    let timestamp = value
+        .get("timestamp")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let level = value
+        .get("level")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+
+    let mut out = String::new();
+    write!(&mut out, "{} {}", timestamp.dimmed(), colorize_level(level)).unwrap();
+
+    if let Some(event) = event_name(value) {
+        write!(&mut out, " {}", event.cyan()).unwrap();
+    }
+
+    let fields = value.get("fields").and_then(Value::as_object);
+    for (key, field) in fields.into_iter().flatten() {
+        if key == "event" || key == "message" {
+            continue;
+        }
+        write!(&mut out, " {}={}", key.dimmed(), format_value(field)).unwrap();
+    }
+
+    if let Some(message) = fields
+        .and_then(|f| f.get("message"))
+        .and_then(Value::as_str)
+    {
+        write!(&mut out, " {message}").unwrap();
+    }
+
+    out
+}
+
+/// Formats a field value: strings unquoted, everything else as JSON.
+fn format_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Colors a level name by severity.
+fn colorize_level(level: &str) -> String {
+    let padded = format!("{level:>5}");
+    match level {
+        "ERROR" => padded.red().to_string(),
+        "WARN" => padded.yellow().to_string(),
+        "INFO" => padded.green().to_string(),
+        _ => padded.dimmed().to_string(),
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Tests
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const SAMPLE_JSON_LOG: &str = concat!(
+        r#"{"timestamp":"2026-01-01T12:00:00Z","level":"INFO","fields":{"event":"loop_start","mode":"Build","max_iterations":10},"target":"ralph::commands::loop_cmd"}"#,
+        "\n",
+        r#"{"timestamp":"2026-01-01T12:00:01Z","level":"INFO","fields":{"event":"iteration_start","iteration":1},"target":"ralph::commands::loop_cmd"}"#,
+        "\n",
+        r#"{"timestamp":"2026-01-01T12:00:02Z","level":"INFO","fields":{"message":"Starting agent"},"target":"ralph::agent"}"#,
+        "\n",
+        r#"{"timestamp":"2026-01-01T12:05:00Z","level":"ERROR","fields":{"event":"error","iteration":1,"error":"Code validation failed"},"target":"ralph::commands::loop_cmd"}"#,
+        "\n",
+        r#"{"timestamp":"2026-01-01T12:05:01Z","level":"INFO","fields":{"event":"iteration_start","iteration":2},"target":"ralph::commands::loop_cmd"}"#,
+        "\n",
+        r#"{"timestamp":"2026-01-01T12:09:00Z","level":"INFO","fields":{"event":"loop_end","total_iterations":2,"reason":"completion_detected"},"target":"ralph::commands::loop_cmd"}"#,
+        "\n",
+    );
+
+    fn kept_lines(event: Option<&str>) -> Vec<&'static str> {
+        SAMPLE_JSON_LOG
+            .lines()
+            .filter(|line| matches_event(line, event))
+            .collect()
+    }
+
+    #[test]
+    fn test_event_filter_over_json_lines() {
+        let starts = kept_lines(Some("iteration_start"));
+        assert_eq!(starts.len(), 2);
+        assert!(starts[0].contains(r#""iteration":1"#));
+        assert!(starts[1].contains(r#""iteration":2"#));
+
+        let errors = kept_lines(Some("error"));
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("Code validation failed"));
+
+        assert_eq!(kept_lines(Some("loop_end")).len(), 1);
+        assert!(kept_lines(Some("nonexistent")).is_empty());
+    }
+
+    #[test]
+    fn test_no_event_filter_keeps_everything() {
+        assert_eq!(kept_lines(None).len(), 6);
+    }
+
+    #[test]
+    fn test_event_filter_accepts_top_level_event() {
+        let line = r#"{"event":"complete","iteration":3}"#;
+        assert!(matches_event(line, Some("complete")));
+        assert!(!matches_event(line, Some("error")));
+    }
+
+    #[test]
+    fn test_event_filter_over_text_lines() {
+        let line = r#"2026-01-01T12:00:01Z  INFO ralph::commands::loop_cmd: event="iteration_start" iteration=1"#;
+        assert!(matches_event(line, Some("iteration_start")));
+        assert!(!matches_event(line, Some("iteration")));
+        assert!(!matches_event(line, Some("loop_end")));
+    }
+
+    #[test]
+    fn test_format_json_line() {
+        let line = SAMPLE_JSON_LOG.lines().nth(3).unwrap();
+        let output = format_line(line, true);
+        assert!(output.contains("2026-01-01T12:05:00Z"));
+        assert!(output.contains("ERROR"));
+        assert!(output.contains("error"));
+        assert!(output.contains("iteration"));
+        assert!(output.contains("Code validation failed"));
+        assert!(!output.contains('{'));
+    }
+
+    #[test]
+    fn test_format_json_line_appends_message() {
+        let line = SAMPLE_JSON_LOG.lines().nth(2).unwrap();
+        let output = format_line(line, true);
+        assert!(output.ends_with("Starting agent"));
+    }
+
+    #[test]
+    fn test_format_line_text_is_raw() {
+        let line = SAMPLE_JSON_LOG.lines().next().unwrap();
+        assert_eq!(format_line(line, false), line);
+        assert_eq!(format_line("not json", true), "not json");
+    }
+
+    #[test]
+    fn test_format_lines_filters_and_skips_blank_lines() {
+        let text = format!("{SAMPLE_JSON_LOG}\n\n");
+        let output = format_lines(&text, false, Some("loop_end"));
+        assert_eq!(output.lines().count(), 1);
+        assert!(output.contains("completion_detected"));
+    }
+
+    #[test]
+    fn test_resolve_log_path() {
+        let cwd = Path::new("/project");
+        assert_eq!(
+            resolve_log_path(cwd, ".ralph/loop.log"),
+            PathBuf::from("/project/.ralph/loop.log")
+        );
+        assert_eq!(
+            resolve_log_path(cwd, "/var/log/ralph.log"),
+            PathBuf::from("/var/log/ralph.log")
+        );
+    }
+
+    #[test]
+    fn test_find_latest_log_includes_rotated_files() {
+        let dir = tempdir().unwrap();
+        let configured = dir.path().join("loop.log");
+        assert!(find_latest_log(&configured).is_none());
+
+        let rotated = dir.path().join("loop.log.2026-01-01");
+        std::fs::write(&rotated, "line\n").unwrap();
+        std::fs::write(dir.path().join("other.log"), "line\n").unwrap();
+
+        assert_eq!(find_latest_log(&configured), Some(rotated));
+    }
+
+    #[test]
+    fn test_print_new_lines_tracks_offset() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("loop.log");
+
+        std::fs::write(&path, "first\npartial").unwrap();
+        let offset = print_new_lines(&path, 0, false, None).unwrap();
+        assert_eq!(offset, 6); // Partial line left for the next read
+
+        std::fs::write(&path, "first\npartial line\n").unwrap();
+        let offset = print_new_lines(&path, offset, false, None).unwrap();
+        assert_eq!(offset, 19);
+
+        // Truncated file restarts from the beginning
+        std::fs::write(&path, "new\n").unwrap();
+        assert_eq!(print_new_lines(&path, offset, false, None).unwrap(), 4);
+    }
+}
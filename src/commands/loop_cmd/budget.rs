@@ -0,0 +1,238 @@
+//! Budget enforcement for the Ralph loop.
+//!
+//! Two limits from `[budget]` in ralph.toml:
+//! - `max_minutes`: total wall-clock time since the loop started
+//! - `max_iterations_per_hour`: iteration starts within a rolling hour
+//!
+//! Both checks are pure: the caller supplies the current time. The pause
+//! between iterations (`agent.iteration_delay_seconds`) is decided here too,
+//! so it can defer to the rate limiter.
+
+use chrono::{DateTime, Duration, Utc};
+use std::collections::VecDeque;
+
+use crate::state::RalphState;
+
+/// Returns true if the loop has run for at least `max_minutes`.
+pub(crate) fn is_time_budget_exceeded(
+    state: &RalphState,
+    now: DateTime<Utc>,
+    max_minutes: Option<u32>,
+) -> bool {
+    max_minutes.is_some_and(|max| {
+        now.signed_duration_since(state.started_at) >= Duration::minutes(i64::from(max))
+    })
+}
+
+/// Returns how long to pause after a successful iteration for
+/// `agent.iteration_delay_seconds` (0 = never).
+///
+/// There is no pause after the last iteration `max_iterations` allows, or
+/// when `limiter_wait` (the rate limiter's wait before the next start) is at
+/// least as long, since the limiter sleeps then anyway.
+pub(crate) fn iteration_delay(
+    delay_seconds: u64,
+    state: &RalphState,
+    limiter_wait: Option<std::time::Duration>,
+) -> Option<std::time::Duration> {
+    if delay_seconds == 0
+        || state
+            .max_iterations
+            .is_some_and(|max| state.iteration >= max)
+    {
+        return None;
+    }
+    let delay = std::time::Duration::from_secs(delay_seconds);
+    if limiter_wait.is_some_and(|wait| wait >= delay) {
+        return None;
+    }
+    Some(delay)
+}
+
+/// Tracks iteration start times to cap iterations per rolling hour.
+#[derive(Debug, Default)]
+pub(crate) struct IterationRateLimiter {
+    max_per_hour: Option<u32>,
+    starts: VecDeque<DateTime<Utc>>,
+}
+
+impl IterationRateLimiter {
+    /// Creates a limiter allowing at most `max_per_hour` starts per rolling hour.
+    /// `None` or `0` disables limiting.
+    pub(crate) fn new(max_per_hour: Option<u32>) -> Self {
+        Self {
+            max_per_hour: max_per_hour.filter(|&n| n > 0),
+            starts: VecDeque::new(),
+        }
+    }
+
+    /// Returns how long to wait before the next iteration may start, if at all.
+    ///
+    /// Starts older than an hour are dropped. When the window is full, the
+    /// wait lasts until the oldest start leaves the window.
+    pub(crate) fn delay_before_start(&mut self, now: DateTime<Utc>) -> Option<std::time::Duration> {
+        let max = self.max_per_hour?;
+        let window = Duration::hours(1);
+
+        while self
+            .starts
+            .front()
+            .is_some_and(|&start| now.signed_duration_since(start) >= window)
+        {
+            self.starts.pop_front();
+        }
+
+        if self.starts.len() < max as usize {
+            return None;
+        }
+
+        let oldest = *self.starts.front()?;
+        (oldest + window - now).to_std().ok()
+    }
+
+    /// Records that an iteration started at `now`.
+    pub(crate) fn record_start(&mut self, now: DateTime<Utc>) {
+        if self.max_per_hour.is_some() {
+            self.starts.push_back(now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state_started_at(started_at: DateTime<Utc>) -> RalphState {
+        RalphState {
+            started_at,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_time_budget_unlimited() {
+        let now = Utc::now();
+        let state = state_started_at(now - Duration::days(30));
+        assert!(!is_time_budget_exceeded(&state, now, None));
+    }
+
+    #[test]
+    fn test_time_budget_not_exceeded() {
+        let now = Utc::now();
+        let state = state_started_at(now - Duration::minutes(59));
+        assert!(!is_time_budget_exceeded(&state, now, Some(60)));
+    }
+
+    #[test]
+    fn test_time_budget_exceeded() {
+        let now = Utc::now();
+        let state = state_started_at(now - Duration::minutes(60));
+        assert!(is_time_budget_exceeded(&state, now, Some(60)));
+
+        let state = state_started_at(now - Duration::minutes(90));
+        assert!(is_time_budget_exceeded(&state, now, Some(60)));
+    }
+
+    /// Iterations out of 1..=`iterations` that pause before the next one.
+    fn delays_over(iterations: u32, max_iterations: Option<u32>, delay_seconds: u64) -> usize {
+        (1..=iterations)
+            .filter(|&iteration| {
+                let state = RalphState {
+                    iteration,
+                    max_iterations,
+                    ..Default::default()
+                };
+                iteration_delay(delay_seconds, &state, None).is_some()
+            })
+            .count()
+    }
+
+    #[test]
+    fn test_iteration_delay_skips_final_iteration() {
+        assert_eq!(delays_over(5, Some(5), 10), 4);
+        assert_eq!(delays_over(1, Some(1), 10), 0);
+        // Without a limit every iteration may be followed by another
+        assert_eq!(delays_over(5, None, 10), 5);
+        assert_eq!(delays_over(5, Some(5), 0), 0);
+
+        let state = RalphState {
+            iteration: 2,
+            max_iterations: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(
+            iteration_delay(10, &state, None),
+            Some(std::time::Duration::from_secs(10))
+        );
+    }
+
+    #[test]
+    fn test_iteration_delay_defers_to_rate_limiter() {
+        let state = RalphState::default();
+        let secs = std::time::Duration::from_secs;
+
+        // The limiter already holds the next start at least as long
+        assert_eq!(iteration_delay(10, &state, Some(secs(10))), None);
+        assert_eq!(iteration_delay(10, &state, Some(secs(600))), None);
+        // A shorter limiter wait is absorbed by the delay
+        assert_eq!(iteration_delay(10, &state, Some(secs(3))), Some(secs(10)));
+    }
+
+    #[test]
+    fn test_rate_limiter_disabled() {
+        let now = Utc::now();
+        for max in [None, Some(0)] {
+            let mut limiter = IterationRateLimiter::new(max);
+            for _ in 0..100 {
+                limiter.record_start(now);
+            }
+            assert!(limiter.delay_before_start(now).is_none());
+        }
+    }
+
+    #[test]
+    fn test_rate_limiter_allows_up_to_max() {
+        let now = Utc::now();
+        let mut limiter = IterationRateLimiter::new(Some(3));
+
+        for i in 0..3 {
+            let t = now + Duration::minutes(i);
+            assert!(limiter.delay_before_start(t).is_none());
+            limiter.record_start(t);
+        }
+    }
+
+    #[test]
+    fn test_rate_limiter_waits_for_oldest_to_expire() {
+        let now = Utc::now();
+        let mut limiter = IterationRateLimiter::new(Some(2));
+        limiter.record_start(now);
+        limiter.record_start(now + Duration::minutes(10));
+
+        // At +20m the window is full; the oldest start expires at +60m
+        let delay = limiter
+            .delay_before_start(now + Duration::minutes(20))
+            .unwrap();
+        assert_eq!(delay, std::time::Duration::from_mins(40));
+    }
+
+    #[test]
+    fn test_rate_limiter_rolling_window() {
+        let now = Utc::now();
+        let mut limiter = IterationRateLimiter::new(Some(2));
+        limiter.record_start(now);
+        limiter.record_start(now + Duration::minutes(10));
+
+        // Once the first start is an hour old, a slot frees up
+        assert!(limiter
+            .delay_before_start(now + Duration::minutes(60))
+            .is_none());
+        limiter.record_start(now + Duration::minutes(60));
+
+        // Window now holds +10m and +60m; next slot frees at +70m
+        let delay = limiter
+            .delay_before_start(now + Duration::minutes(65))
+            .unwrap();
+        assert_eq!(delay, std::time::Duration::from_mins(5));
+    }
+}
@@ -0,0 +1,409 @@
+//! Loop lifecycle events and where they are written.
+//!
+//! The loop reports what it is doing through an [`EventSink`]. The human
+//! sink prints the colored banners and summaries from `format`; the JSON
+//! sink writes one JSON object per line to stdout for wrappers such as TUIs.
+//! Rendering is pure; sinks only print.
+
+use clap::ValueEnum;
+use serde::Serialize;
+use std::path::Path;
+
+use super::format::{
+    format_banner, format_budget_exceeded, format_completion_detected, format_dry_run,
+    format_iteration_header, format_kept_container, format_loop_finished,
+    format_max_iterations_reached, format_progress, format_rate_limited,
+    format_success_command_passed, BannerInfo, ProgressInfo,
+};
+use super::{format_branch_summary, BranchResult};
+
+/// Output format for loop events on stdout.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum EventFormat {
+    /// Colored, human-readable output (default).
+    #[default]
+    Human,
+    /// One JSON object per line.
+    Json,
+}
+
+impl EventFormat {
+    /// Returns the sink that writes events in this format.
+    pub(crate) fn sink(self) -> Box<dyn EventSink> {
+        match self {
+            Self::Human => Box::new(HumanSink),
+            Self::Json => Box::new(JsonSink),
+        }
+    }
+}
+
+/// Something the loop reports while it runs.
+///
+/// Serialized with an `event` tag, e.g. `{"event":"iteration_start","iteration":3}`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub(crate) enum LoopEvent<'a> {
+    /// Branch build mode is starting.
+    BranchBuildStart {
+        /// Number of incomplete branches to build.
+        branches: usize,
+        /// `parallel` or `sequential`.
+        mode: &'a str,
+    },
+    /// Branch build mode finished.
+    BranchBuildFinished {
+        /// Per-branch outcome.
+        results: &'a [BranchResult],
+    },
+    /// Dry run: the prompt the next iteration would send.
+    DryRun {
+        /// Iteration the prompt is for.
+        iteration: u32,
+        /// Prompt file the prompt was read from.
+        prompt_file: &'a Path,
+        /// Fully assembled prompt.
+        prompt: &'a str,
+    },
+    /// The loop switched off a protected branch (`git.auto_branch`).
+    BranchSwitched {
+        /// Protected branch the loop started on.
+        from: &'a str,
+        /// Branch created for the loop.
+        to: &'a str,
+    },
+    /// The loop is starting.
+    LoopStart(&'a BannerInfo),
+    /// An iteration is starting.
+    IterationStart {
+        /// Iteration number.
+        iteration: u32,
+    },
+    /// Progress summary between iterations.
+    Progress(&'a ProgressInfo),
+    /// Iteration starts are rate limited; the loop is waiting.
+    RateLimited {
+        /// Seconds until the next iteration may start.
+        wait_seconds: u64,
+    },
+    /// The iteration limit was reached.
+    MaxIterationsReached {
+        /// Configured limit.
+        max_iterations: u32,
+    },
+    /// The time budget ran out.
+    BudgetExceeded {
+        /// Configured budget in minutes.
+        max_minutes: u32,
+    },
+    /// Idle detection decided the task is complete.
+    CompletionDetected {
+        /// Consecutive idle iterations observed.
+        idle_iterations: u32,
+    },
+    /// `completion.success_command` passed.
+    SuccessCommandPassed {
+        /// The command that passed.
+        command: &'a str,
+    },
+    /// `--keep-container` left the persistent container running.
+    ContainerKept {
+        /// Container name.
+        container: &'a str,
+        /// Command that opens a shell in it.
+        shell_command: &'a str,
+    },
+    /// The loop finished without an error.
+    LoopFinished {
+        /// Final iteration counter.
+        iterations: u32,
+        /// Why the loop ended (same labels as the metrics file).
+        reason: &'a str,
+    },
+}
+
+/// Destination for loop events.
+pub(crate) trait EventSink: Send + Sync {
+    /// Writes one event.
+    fn emit(&self, event: &LoopEvent<'_>);
+}
+
+/// Prints events as colored text.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct HumanSink;
+
+impl EventSink for HumanSink {
+    fn emit(&self, event: &LoopEvent<'_>) {
+        print!("{}", render_human(event));
+    }
+}
+
+/// Prints events as JSON lines.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct JsonSink;
+
+impl EventSink for JsonSink {
+    fn emit(&self, event: &LoopEvent<'_>) {
+        println!("{}", render_json(event));
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Helper functions
+// -----------------------------------------------------------------------------
+
+/// Renders an event as the text the human sink prints.
+fn render_human(event: &LoopEvent<'_>) -> String {
+    match *event {
+        LoopEvent::BranchBuildStart { branches, mode } => {
+            format!("Building {branches} branches in {mode} mode...\n\n")
+        }
+        LoopEvent::BranchBuildFinished { results } => format_branch_summary(results),
+        LoopEvent::DryRun {
+            iteration,
+            prompt_file,
+            prompt,
+        } => format_dry_run(iteration, prompt_file, prompt),
+        LoopEvent::BranchSwitched { from, to } => {
+            format!("Switched from protected branch '{from}' to '{to}'\n")
+        }
+        LoopEvent::LoopStart(banner) => format_banner(banner),
+        LoopEvent::IterationStart { iteration } => {
+            format!("{}\n", format_iteration_header(iteration))
+        }
+        LoopEvent::Progress(progress) => format_progress(progress),
+        LoopEvent::RateLimited { wait_seconds } => format!(
+            "{}\n",
+            format_rate_limited(std::time::Duration::from_secs(wait_seconds))
+        ),
+        LoopEvent::MaxIterationsReached { max_iterations } => {
+            format!("{}\n", format_max_iterations_reached(max_iterations))
+        }
+        LoopEvent::BudgetExceeded { max_minutes } => {
+            format!("{}\n", format_budget_exceeded(max_minutes))
+        }
+        LoopEvent::CompletionDetected { idle_iterations } => {
+            format!("{}\n", format_completion_detected(idle_iterations))
+        }
+        LoopEvent::SuccessCommandPassed { command } => {
+            format!("{}\n", format_success_command_passed(command))
+        }
+        LoopEvent::ContainerKept { shell_command, .. } => format_kept_container(shell_command),
+        LoopEvent::LoopFinished { iterations, .. } => format_loop_finished(iterations),
+    }
+}
+
+/// Renders an event as a single-line JSON object.
+fn render_json(event: &LoopEvent<'_>) -> String {
+    // Events hold only strings, numbers, and options, so this cannot fail
+    serde_json::to_string(event).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn to_value(event: &LoopEvent<'_>) -> Value {
+        let line = render_json(event);
+        assert!(!line.contains('\n'), "JSON events must be one line");
+        serde_json::from_str(&line).unwrap()
+    }
+
+    fn banner() -> BannerInfo {
+        BannerInfo {
+            provider: "claude".to_string(),
+            mode: "Build".to_string(),
+            prompt_file: "PROMPT_build.md".to_string(),
+            iteration: 1,
+            max_iterations: Some(20),
+            sandbox_enabled: true,
+        }
+    }
+
+    fn progress() -> ProgressInfo {
+        ProgressInfo {
+            iteration: 3,
+            mode: "Build".to_string(),
+            elapsed_time: "5m 0s".to_string(),
+            avg_iteration_duration: Some("1m 40s".to_string()),
+            successful_commits: 2,
+            errors: 1,
+            last_commit_message: Some("Add parser".to_string()),
+            input_tokens: 100,
+            output_tokens: 20,
+            memory_mb: Some(256),
+            cpu_percent: Some(12.5),
+            last_agent_ms: Some(61_000),
+            last_validation_ms: Some(2_500),
+            idle_iterations: 1,
+            idle_threshold: 2,
+        }
+    }
+
+    #[test]
+    fn test_loop_start_json() {
+        assert_eq!(
+            to_value(&LoopEvent::LoopStart(&banner())),
+            json!({
+                "event": "loop_start",
+                "provider": "claude",
+                "mode": "Build",
+                "prompt_file": "PROMPT_build.md",
+                "iteration": 1,
+                "max_iterations": 20,
+                "sandbox_enabled": true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_iteration_start_json() {
+        assert_eq!(
+            to_value(&LoopEvent::IterationStart { iteration: 3 }),
+            json!({"event": "iteration_start", "iteration": 3})
+        );
+    }
+
+    #[test]
+    fn test_progress_json() {
+        assert_eq!(
+            to_value(&LoopEvent::Progress(&progress())),
+            json!({
+                "event": "progress",
+                "iteration": 3,
+                "mode": "Build",
+                "elapsed_time": "5m 0s",
+                "avg_iteration_duration": "1m 40s",
+                "successful_commits": 2,
+                "errors": 1,
+                "last_commit_message": "Add parser",
+                "input_tokens": 100,
+                "output_tokens": 20,
+                "memory_mb": 256,
+                "cpu_percent": 12.5,
+                "last_agent_ms": 61000,
+                "last_validation_ms": 2500,
+                "idle_iterations": 1,
+                "idle_threshold": 2,
+            })
+        );
+    }
+
+    #[test]
+    fn test_stop_events_json() {
+        assert_eq!(
+            to_value(&LoopEvent::RateLimited { wait_seconds: 90 }),
+            json!({"event": "rate_limited", "wait_seconds": 90})
+        );
+        assert_eq!(
+            to_value(&LoopEvent::MaxIterationsReached { max_iterations: 20 }),
+            json!({"event": "max_iterations_reached", "max_iterations": 20})
+        );
+        assert_eq!(
+            to_value(&LoopEvent::BudgetExceeded { max_minutes: 60 }),
+            json!({"event": "budget_exceeded", "max_minutes": 60})
+        );
+        assert_eq!(
+            to_value(&LoopEvent::CompletionDetected { idle_iterations: 2 }),
+            json!({"event": "completion_detected", "idle_iterations": 2})
+        );
+        assert_eq!(
+            to_value(&LoopEvent::SuccessCommandPassed {
+                command: "cargo test"
+            }),
+            json!({"event": "success_command_passed", "command": "cargo test"})
+        );
+        assert_eq!(
+            to_value(&LoopEvent::LoopFinished {
+                iterations: 7,
+                reason: "agent_idle"
+            }),
+            json!({"event": "loop_finished", "iterations": 7, "reason": "agent_idle"})
+        );
+        assert_eq!(
+            to_value(&LoopEvent::ContainerKept {
+                container: "ralph-1a2b3c4d",
+                shell_command: "docker exec -it ralph-1a2b3c4d bash"
+            }),
+            json!({
+                "event": "container_kept",
+                "container": "ralph-1a2b3c4d",
+                "shell_command": "docker exec -it ralph-1a2b3c4d bash",
+            })
+        );
+    }
+
+    #[test]
+    fn test_setup_events_json() {
+        assert_eq!(
+            to_value(&LoopEvent::DryRun {
+                iteration: 4,
+                prompt_file: Path::new("PROMPT_plan.md"),
+                prompt: "Plan it.\nCarefully.",
+            }),
+            json!({
+                "event": "dry_run",
+                "iteration": 4,
+                "prompt_file": "PROMPT_plan.md",
+                "prompt": "Plan it.\nCarefully.",
+            })
+        );
+        assert_eq!(
+            to_value(&LoopEvent::BranchSwitched {
+                from: "main",
+                to: "ralph/20240305-070809"
+            }),
+            json!({"event": "branch_switched", "from": "main", "to": "ralph/20240305-070809"})
+        );
+        assert_eq!(
+            to_value(&LoopEvent::BranchBuildStart {
+                branches: 2,
+                mode: "parallel"
+            }),
+            json!({"event": "branch_build_start", "branches": 2, "mode": "parallel"})
+        );
+    }
+
+    #[test]
+    fn test_branch_build_finished_json() {
+        let results = [BranchResult {
+            branch: "feature-auth".to_string(),
+            success: true,
+            iterations: 5,
+            error: None,
+            pr_url: Some("https://github.com/org/repo/pull/1".to_string()),
+        }];
+        assert_eq!(
+            to_value(&LoopEvent::BranchBuildFinished { results: &results }),
+            json!({
+                "event": "branch_build_finished",
+                "results": [{
+                    "branch": "feature-auth",
+                    "success": true,
+                    "iterations": 5,
+                    "error": null,
+                    "pr_url": "https://github.com/org/repo/pull/1",
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn test_render_human_matches_format_functions() {
+        assert_eq!(
+            render_human(&LoopEvent::IterationStart { iteration: 3 }),
+            format!("{}\n", format_iteration_header(3))
+        );
+        assert_eq!(
+            render_human(&LoopEvent::Progress(&progress())),
+            format_progress(&progress())
+        );
+        assert_eq!(
+            render_human(&LoopEvent::BranchBuildStart {
+                branches: 2,
+                mode: "sequential"
+            }),
+            "Building 2 branches in sequential mode...\n\n"
+        );
+    }
+}
@@ -5,17 +5,19 @@
 
 use chrono::{Duration, Utc};
 use colored::Colorize;
+use serde::Serialize;
 use std::fmt::Write;
 use std::path::Path;
 
 use crate::agent::Provider;
 use crate::config::Config;
+use crate::detection::format_idle_progress;
 use crate::state::RalphState;
 
 use super::git::{count_successful_commits, get_last_commit_message};
 
 /// Banner information for display at loop start.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub(crate) struct BannerInfo {
     pub provider: String,
     pub mode: String,
@@ -45,7 +47,7 @@ impl BannerInfo {
 }
 
 /// Progress information for display during loop execution.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub(crate) struct ProgressInfo {
     pub iteration: u32,
     pub mode: String,
@@ -54,10 +56,24 @@ pub(crate) struct ProgressInfo {
     pub successful_commits: u32,
     pub errors: u32,
     pub last_commit_message: Option<String>,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// Sandbox container memory in MiB, when resource usage is shown.
+    pub memory_mb: Option<u64>,
+    /// Sandbox container CPU percent, when resource usage is shown.
+    pub cpu_percent: Option<f64>,
+    /// Milliseconds the last agent run took.
+    pub last_agent_ms: Option<u64>,
+    /// Milliseconds the last validation took.
+    pub last_validation_ms: Option<u64>,
+    /// Consecutive idle iterations so far.
+    pub idle_iterations: u32,
+    /// Idle iterations that complete the loop (`completion.idle_threshold`).
+    pub idle_threshold: u32,
 }
 
 impl ProgressInfo {
-    pub async fn new(state: &RalphState, cwd: &Path) -> Self {
+    pub async fn new(state: &RalphState, cwd: &Path, idle_threshold: u32) -> Self {
         let now = Utc::now();
         let elapsed = now.signed_duration_since(state.started_at);
         let elapsed_time = format_duration(&elapsed);
@@ -81,6 +97,14 @@ impl ProgressInfo {
             successful_commits,
             errors: state.error_count,
             last_commit_message,
+            input_tokens: state.total_input_tokens,
+            output_tokens: state.total_output_tokens,
+            memory_mb: None,
+            cpu_percent: None,
+            last_agent_ms: None,
+            last_validation_ms: None,
+            idle_iterations: state.idle_iterations,
+            idle_threshold,
         }
     }
 }
@@ -180,6 +204,12 @@ pub(crate) fn format_progress(info: &ProgressInfo) -> String {
         info.successful_commits.to_string().green()
     )
     .unwrap();
+    writeln!(
+        &mut out,
+        "  Idle:      {}",
+        format_idle_progress(info.idle_iterations, info.idle_threshold).cyan()
+    )
+    .unwrap();
 
     if info.errors > 0 {
         writeln!(
@@ -190,6 +220,30 @@ pub(crate) fn format_progress(info: &ProgressInfo) -> String {
         .unwrap();
     }
 
+    if info.input_tokens > 0 || info.output_tokens > 0 {
+        writeln!(
+            &mut out,
+            "  Tokens:    {} in / {} out",
+            info.input_tokens.to_string().cyan(),
+            info.output_tokens.to_string().cyan()
+        )
+        .unwrap();
+    }
+
+    if let (Some(memory_mb), Some(cpu_percent)) = (info.memory_mb, info.cpu_percent) {
+        writeln!(
+            &mut out,
+            "  Sandbox:   {} MB, {} CPU",
+            memory_mb.to_string().cyan(),
+            format!("{cpu_percent:.1}%").cyan()
+        )
+        .unwrap();
+    }
+
+    if let Some(timing) = format_phase_timing(info.last_agent_ms, info.last_validation_ms) {
+        writeln!(&mut out, "  Timing:    {timing}").unwrap();
+    }
+
     if let Some(ref commit_msg) = info.last_commit_message {
         writeln!(&mut out, "\n  Current task: {}", commit_msg.dimmed()).unwrap();
         writeln!(&mut out, "  Last commit:  \"{}\"", commit_msg.cyan()).unwrap();
@@ -200,11 +254,70 @@ pub(crate) fn format_progress(info: &ProgressInfo) -> String {
     out
 }
 
+/// Formats the last iteration's phase times, e.g. "agent 12.3s, validation 0.4s".
+fn format_phase_timing(agent_ms: Option<u64>, validation_ms: Option<u64>) -> Option<String> {
+    let parts: Vec<String> = [("agent", agent_ms), ("validation", validation_ms)]
+        .into_iter()
+        .filter_map(|(phase, ms)| {
+            ms.map(|ms| {
+                let seconds = format!("{}.{}s", ms / 1000, ms % 1000 / 100);
+                format!("{phase} {}", seconds.cyan())
+            })
+        })
+        .collect();
+    (!parts.is_empty()).then(|| parts.join(", "))
+}
+
+/// Formats the dry-run output: a header naming the prompt source, then the prompt.
+pub(crate) fn format_dry_run(iteration: u32, prompt_file: &Path, prompt: &str) -> String {
+    let mut out = String::new();
+    writeln!(
+        &mut out,
+        "{} Dry run: prompt for iteration {} ({})",
+        "🔍".cyan(),
+        iteration,
+        prompt_file.display()
+    )
+    .unwrap();
+    writeln!(&mut out, "{}", "─".repeat(63).dimmed()).unwrap();
+    out.push_str(prompt);
+    if !prompt.ends_with('\n') {
+        out.push('\n');
+    }
+    writeln!(&mut out, "{}", "─".repeat(63).dimmed()).unwrap();
+    writeln!(
+        &mut out,
+        "{} No agent was invoked and no state was changed.",
+        "ℹ".blue()
+    )
+    .unwrap();
+    out
+}
+
 /// Formats the max iterations reached message.
 pub(crate) fn format_max_iterations_reached(max: u32) -> String {
     format!("\n{} Max iterations ({}) reached.", "🛑".red(), max)
 }
 
+/// Formats the time budget exceeded message.
+pub(crate) fn format_budget_exceeded(max_minutes: u32) -> String {
+    format!(
+        "\n{} Time budget ({} minutes) exceeded.",
+        "🛑".red(),
+        max_minutes
+    )
+}
+
+/// Formats the rate limit wait message.
+pub(crate) fn format_rate_limited(wait: std::time::Duration) -> String {
+    let wait = Duration::from_std(wait).unwrap_or_default();
+    format!(
+        "\n{} Iteration rate limit reached, waiting {}...",
+        "⏳".yellow(),
+        format_duration(&wait)
+    )
+}
+
 /// Formats the completion detected message.
 pub(crate) fn format_completion_detected(idle_count: u32) -> String {
     format!(
@@ -214,6 +327,25 @@ pub(crate) fn format_completion_detected(idle_count: u32) -> String {
     )
 }
 
+/// Formats the success command passed message.
+pub(crate) fn format_success_command_passed(command: &str) -> String {
+    format!(
+        "\n{} Success command passed ({}) - task complete.",
+        "✅".green(),
+        command
+    )
+}
+
+/// Formats the hint shown when `--keep-container` leaves the persistent
+/// container running.
+pub(crate) fn format_kept_container(shell_command: &str) -> String {
+    format!(
+        "\n{} Kept sandbox container. Open a shell with:\n  {}\n",
+        "🐳".cyan(),
+        shell_command.bold()
+    )
+}
+
 /// Formats the loop finished message.
 pub(crate) fn format_loop_finished(total_iterations: u32) -> String {
     let mut out = String::new();
@@ -230,7 +362,7 @@ pub(crate) fn format_loop_finished(total_iterations: u32) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::state::Mode;
+    use crate::state::{LoopSettings, Mode};
 
     /// Strip ANSI color codes from a string for testing.
     /// This allows tests to work in environments where colors are disabled.
@@ -279,9 +411,18 @@ mod tests {
             last_iteration_at: None,
             error_count: 0,
             consecutive_errors: 0,
+            circuit_breaker_cooled_down: false,
             last_error: None,
             last_commit: None,
             idle_iterations: 0,
+            commit_count: None,
+            total_input_tokens: 0,
+            total_output_tokens: 0,
+            initial_commit: None,
+            pid: None,
+            session_id: None,
+            error_history: Vec::new(),
+            settings: LoopSettings::default(),
         };
         let config = Config::default();
         let prompt = std::path::PathBuf::from("/project/PROMPT_plan.md");
@@ -366,6 +507,41 @@ mod tests {
         assert!(output.contains("10"));
     }
 
+    #[test]
+    fn test_format_dry_run() {
+        let output = strip_ansi_codes(&format_dry_run(
+            3,
+            Path::new("/project/PROMPT_build.md"),
+            "Build the thing.",
+        ));
+        assert!(output.contains("Dry run"));
+        assert!(output.contains("iteration 3"));
+        assert!(output.contains("/project/PROMPT_build.md"));
+        assert!(output.contains("Build the thing.\n"));
+        assert!(output.contains("No agent was invoked"));
+    }
+
+    #[test]
+    fn test_format_budget_exceeded() {
+        let output = format_budget_exceeded(120);
+        assert!(output.contains("Time budget"));
+        assert!(output.contains("120 minutes"));
+    }
+
+    #[test]
+    fn test_format_rate_limited() {
+        let output = strip_ansi_codes(&format_rate_limited(std::time::Duration::from_secs(125)));
+        assert!(output.contains("rate limit"));
+        assert!(output.contains("2m 5s"));
+    }
+
+    #[test]
+    fn test_format_success_command_passed() {
+        let output = strip_ansi_codes(&format_success_command_passed("./check_done.sh"));
+        assert!(output.contains("Success command passed"));
+        assert!(output.contains("./check_done.sh"));
+    }
+
     #[test]
     fn test_format_completion_detected() {
         let output = format_completion_detected(2);
@@ -374,6 +550,15 @@ mod tests {
         assert!(output.contains("task complete"));
     }
 
+    #[test]
+    fn test_format_kept_container() {
+        let output = strip_ansi_codes(&format_kept_container(
+            "docker exec -it ralph-1a2b3c4d bash",
+        ));
+        assert!(output.contains("Kept sandbox container"));
+        assert!(output.contains("  docker exec -it ralph-1a2b3c4d bash\n"));
+    }
+
     #[test]
     fn test_format_loop_finished() {
         let output = format_loop_finished(7);
@@ -412,6 +597,14 @@ mod tests {
             successful_commits: 12,
             errors: 2,
             last_commit_message: Some("Add JWT token validation".to_string()),
+            input_tokens: 120_000,
+            output_tokens: 8_500,
+            memory_mb: Some(512),
+            cpu_percent: Some(37.25),
+            last_agent_ms: Some(12_345),
+            last_validation_ms: Some(400),
+            idle_iterations: 1,
+            idle_threshold: 3,
         };
 
         let output = format_progress(&info);
@@ -423,6 +616,10 @@ mod tests {
         assert!(stripped.contains("12 successful"));
         assert!(stripped.contains("2 (recovered)"));
         assert!(stripped.contains("Add JWT token validation"));
+        assert!(stripped.contains("120000 in / 8500 out"));
+        assert!(stripped.contains("Sandbox:   512 MB, 37.2% CPU"));
+        assert!(stripped.contains("Timing:    agent 12.3s, validation 0.4s"));
+        assert!(stripped.contains("Idle:      1/3\n"));
     }
 
     #[test]
@@ -435,6 +632,14 @@ mod tests {
             successful_commits: 3,
             errors: 0,
             last_commit_message: None,
+            input_tokens: 0,
+            output_tokens: 0,
+            memory_mb: None,
+            cpu_percent: None,
+            last_agent_ms: None,
+            last_validation_ms: None,
+            idle_iterations: 1,
+            idle_threshold: 2,
         };
 
         let output = format_progress(&info);
@@ -444,5 +649,16 @@ mod tests {
         assert!(stripped.contains("Plan"));
         assert!(stripped.contains("3 successful"));
         assert!(!stripped.contains("Errors")); // Should not show errors line when 0
+        assert!(!stripped.contains("Tokens")); // Should not show tokens line when none reported
+        assert!(!stripped.contains("Sandbox")); // Resource usage only when sampled
+        assert!(!stripped.contains("Timing")); // No phase has run yet
+        assert!(stripped.contains("Idle:      1/2 (next idle iteration completes the loop)"));
+    }
+
+    #[test]
+    fn test_format_phase_timing() {
+        assert_eq!(format_phase_timing(None, None), None);
+        let agent_only = strip_ansi_codes(&format_phase_timing(Some(1_500), None).unwrap());
+        assert_eq!(agent_only, "agent 1.5s");
     }
 }
@@ -1,47 +1,168 @@
 //! Git operations for the Ralph loop.
 //!
 //! This module handles all git interactions during loop execution:
-//! push, branch detection, commit hash retrieval, and commit counting.
+//! push, branch detection, commit hash retrieval, commit counting,
+//! auto-committing agent changes, and rolling back failed iterations.
 
 use anyhow::{bail, Context, Result};
 use chrono::{DateTime, Utc};
+use std::future::Future;
 use std::path::Path;
-use tracing::{debug, info};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Delay before the first push retry; doubled for each retry after it.
+const PUSH_RETRY_BASE_DELAY: Duration = Duration::from_secs(2);
+
+/// `git push` stderr fragments that mean retrying cannot help.
+const NON_RETRYABLE_PUSH_ERRORS: &[&str] = &[
+    "rejected",
+    "non-fast-forward",
+    "fetch first",
+    "hook declined",
+    "Permission denied",
+    "Authentication failed",
+    "could not read Username",
+    "does not appear to be a git repository",
+    "No configured push destination",
+];
 
 /// Push current changes to the remote repository.
 ///
-/// Refuses to push to protected branches as a safety measure.
-pub(crate) async fn git_push(cwd: &Path, protected_branches: &[String]) -> Result<()> {
+/// Refuses to push to protected branches as a safety measure. Transient
+/// failures are retried up to `retries` times with exponential backoff;
+/// rejections (e.g. non-fast-forward) fail immediately.
+pub(crate) async fn git_push(
+    cwd: &Path,
+    protected_branches: &[String],
+    retries: u32,
+) -> Result<()> {
     debug!("Pushing to git...");
 
     // Check if current branch is protected
     let branch = get_current_branch(cwd).await?;
-    if protected_branches.iter().any(|b| b == &branch) {
+    if is_protected_branch(&branch, protected_branches) {
         bail!(
             "Refusing to push to protected branch '{branch}'. \
              Remove it from git.protected_branches in ralph.toml to allow pushing."
         );
     }
 
-    let output = tokio::process::Command::new("git")
+    push_with_retry(retries, PUSH_RETRY_BASE_DELAY, || push_once(cwd, &branch)).await?;
+
+    info!("Git push complete");
+    Ok(())
+}
+
+/// A failed push attempt.
+#[derive(Debug)]
+struct PushFailure {
+    /// Git's error output.
+    message: String,
+    /// Whether another attempt might succeed.
+    retryable: bool,
+}
+
+/// Runs `push` until it succeeds, fails with a non-retryable error, or
+/// `retries` retries are spent. Waits `base_delay * 2^n` before retry `n`.
+async fn push_with_retry<F, Fut>(retries: u32, base_delay: Duration, mut push: F) -> Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = std::result::Result<(), PushFailure>>,
+{
+    let mut attempt = 0;
+    loop {
+        let failure = match push().await {
+            Ok(()) => return Ok(()),
+            Err(failure) => failure,
+        };
+        if !failure.retryable {
+            bail!("git push rejected: {}", failure.message);
+        }
+        if attempt >= retries {
+            bail!(
+                "git push failed after {} attempt(s): {}",
+                attempt + 1,
+                failure.message
+            );
+        }
+
+        let delay = base_delay.saturating_mul(1 << attempt.min(16));
+        warn!(
+            "git push failed (attempt {}); retrying in {}s: {}",
+            attempt + 1,
+            delay.as_secs(),
+            failure.message
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// Pushes once, falling back to `push -u origin <branch>` for branches
+/// without an upstream.
+async fn push_once(cwd: &Path, branch: &str) -> std::result::Result<(), PushFailure> {
+    let output = run_push(cwd, &["push"]).await?;
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let output = run_push(cwd, &["push", "-u", "origin", branch]).await?;
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    Err(PushFailure {
+        retryable: is_retryable_push_error(&message),
+        message,
+    })
+}
+
+/// Runs a git push command; failing to spawn git is not retryable.
+async fn run_push(
+    cwd: &Path,
+    args: &[&str],
+) -> std::result::Result<std::process::Output, PushFailure> {
+    tokio::process::Command::new("git")
         .current_dir(cwd)
-        .args(["push"])
+        .args(args)
         .output()
         .await
-        .context("Failed to run git push")?;
+        .map_err(|e| PushFailure {
+            message: format!("Failed to run git push: {e}"),
+            retryable: false,
+        })
+}
 
-    if !output.status.success() {
-        // Try to create upstream branch
-        tokio::process::Command::new("git")
-            .current_dir(cwd)
-            .args(["push", "-u", "origin", &branch])
-            .output()
-            .await
-            .context("Failed to push with upstream")?;
-    }
+/// Whether a push failure with this stderr is worth retrying.
+fn is_retryable_push_error(stderr: &str) -> bool {
+    !NON_RETRYABLE_PUSH_ERRORS
+        .iter()
+        .any(|fragment| stderr.contains(fragment))
+}
 
-    info!("Git push complete");
-    Ok(())
+/// `git diff --stat` of the working tree against `base`, or empty on failure.
+pub(crate) async fn diff_stat_since(cwd: &Path, base: &str) -> String {
+    match tokio::process::Command::new("git")
+        .current_dir(cwd)
+        .args(["diff", "--stat", base])
+        .output()
+        .await
+    {
+        Ok(output) if output.status.success() => {
+            String::from_utf8_lossy(&output.stdout).into_owned()
+        }
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            warn!("git diff --stat failed: {}", stderr.trim());
+            String::new()
+        }
+        Err(e) => {
+            warn!("Failed to run git diff --stat: {e}");
+            String::new()
+        }
+    }
 }
 
 /// Get the name of the current git branch.
@@ -56,6 +177,36 @@ pub(crate) async fn get_current_branch(cwd: &Path) -> Result<String> {
     Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
 }
 
+/// Whether `branch` is listed in `protected_branches`.
+///
+/// An empty name (detached HEAD or not a repository) is never protected.
+pub(crate) fn is_protected_branch(branch: &str, protected_branches: &[String]) -> bool {
+    !branch.is_empty() && protected_branches.iter().any(|b| b == branch)
+}
+
+/// Name for a branch created off a protected branch: `ralph/<timestamp>`.
+pub(crate) fn auto_branch_name(now: DateTime<Utc>) -> String {
+    format!("ralph/{}", now.format("%Y%m%d-%H%M%S"))
+}
+
+/// Create `branch` at HEAD and switch to it.
+pub(crate) async fn create_branch(cwd: &Path, branch: &str) -> Result<()> {
+    let output = tokio::process::Command::new("git")
+        .current_dir(cwd)
+        .args(["checkout", "-q", "-b", branch])
+        .output()
+        .await
+        .context("Failed to run git checkout")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("Failed to create branch '{branch}': {stderr}");
+    }
+
+    info!("Created and switched to branch '{}'", branch);
+    Ok(())
+}
+
 /// Get the last commit message (first line only).
 pub(crate) async fn get_last_commit_message(cwd: &Path) -> Option<String> {
     let output = tokio::process::Command::new("git")
@@ -97,6 +248,221 @@ pub(crate) async fn count_successful_commits(cwd: &Path, started_at: DateTime<Ut
     u32::try_from(count.min(u32::MAX as usize)).unwrap_or(u32::MAX)
 }
 
+/// Git trailer key marking commits made by the loop.
+///
+/// `ralph revert` only touches commits carrying it.
+pub(crate) const ITERATION_TRAILER: &str = "Ralph-Iteration";
+
+/// Append the `Ralph-Iteration: <n>` trailer to a commit message.
+pub(crate) fn with_iteration_trailer(message: &str, iteration: u32) -> String {
+    format!("{}\n\n{ITERATION_TRAILER}: {iteration}", message.trim_end())
+}
+
+/// Render an auto-commit message, filling `{iteration}` and `{mode}`.
+pub(crate) fn render_commit_message(template: &str, iteration: u32, mode: &str) -> String {
+    template
+        .replace("{iteration}", &iteration.to_string())
+        .replace("{mode}", mode)
+}
+
+/// Render a branch-build PR title or body, filling `{branch}`, `{goal}`,
+/// and `{iterations}`.
+///
+/// Placeholders are filled in a single pass, so a goal that happens to
+/// contain `{branch}` is kept as written. Unknown placeholders are left as-is.
+pub(crate) fn render_pr_template(
+    template: &str,
+    branch: &str,
+    goal: &str,
+    iterations: u32,
+) -> String {
+    let iterations = iterations.to_string();
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = [
+            ("{branch}", branch),
+            ("{goal}", goal),
+            ("{iterations}", iterations.as_str()),
+        ]
+        .into_iter()
+        .find(|(placeholder, _)| rest.starts_with(placeholder));
+        if let Some((placeholder, value)) = value {
+            out.push_str(value);
+            rest = &rest[placeholder.len()..];
+        } else {
+            out.push('{');
+            rest = &rest[1..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Commit all changes in the working tree with `message`.
+///
+/// With `sign`, the commit is signed (see [`commit_args`]) and then checked
+/// for a signature. Returns false without committing if the tree is clean.
+pub(crate) async fn auto_commit(
+    cwd: &Path,
+    message: &str,
+    sign: bool,
+    signing_key: Option<&str>,
+) -> Result<bool> {
+    let status = tokio::process::Command::new("git")
+        .current_dir(cwd)
+        .args(["status", "--porcelain"])
+        .output()
+        .await
+        .context("Failed to run git status")?;
+    if !status.status.success() {
+        let stderr = String::from_utf8_lossy(&status.stderr);
+        bail!("git status failed: {stderr}");
+    }
+    if status.stdout.iter().all(u8::is_ascii_whitespace) {
+        debug!("Working tree clean, nothing to auto-commit");
+        return Ok(false);
+    }
+
+    let add = ["add", "-A"].map(str::to_string).to_vec();
+    for args in [add, commit_args(message, sign, signing_key)] {
+        let output = tokio::process::Command::new("git")
+            .current_dir(cwd)
+            .args(&args)
+            .output()
+            .await
+            .with_context(|| format!("Failed to run git {}", args[0]))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if sign && args[0] == "commit" {
+                bail!("{}", format_signing_error(signing_key, &stderr));
+            }
+            bail!("git {} failed: {stderr}", args[0]);
+        }
+    }
+
+    if sign {
+        let output = tokio::process::Command::new("git")
+            .current_dir(cwd)
+            .args(["cat-file", "commit", "HEAD"])
+            .output()
+            .await
+            .context("Failed to run git cat-file")?;
+        if !is_signed_commit(&String::from_utf8_lossy(&output.stdout)) {
+            bail!("Auto-commit was created without a signature; check git's signing setup");
+        }
+    }
+
+    info!("Auto-committed changes: {}", message);
+    Ok(true)
+}
+
+/// Builds the `git commit` arguments for an auto-commit.
+///
+/// With `sign`, adds `--gpg-sign`, naming `signing_key` when set so it
+/// overrides `user.signingkey`. Git picks GPG or SSH from `gpg.format`.
+pub(crate) fn commit_args(message: &str, sign: bool, signing_key: Option<&str>) -> Vec<String> {
+    let mut args = vec!["commit".to_string(), "-q".to_string()];
+    if sign {
+        args.push(signing_key.map_or_else(
+            || "--gpg-sign".to_string(),
+            |key| format!("--gpg-sign={key}"),
+        ));
+    }
+    args.extend(["-m".to_string(), message.to_string()]);
+    args
+}
+
+/// Whether a raw commit object (`git cat-file commit`) carries a signature.
+///
+/// GPG and SSH signatures are both stored in a `gpgsig` header.
+fn is_signed_commit(object: &str) -> bool {
+    object
+        .lines()
+        .take_while(|line| !line.is_empty())
+        .any(|line| line.starts_with("gpgsig ") || line.starts_with("gpgsig-sha256 "))
+}
+
+/// Error for a commit that failed while signing is on.
+///
+/// A missing key is the usual cause, so the message names the key.
+fn format_signing_error(signing_key: Option<&str>, stderr: &str) -> String {
+    let key = signing_key.map_or_else(
+        || "git's user.signingkey".to_string(),
+        |key| format!("signing key '{key}'"),
+    );
+    format!(
+        "Signed commit failed (git.sign_commits is on): {}\n\
+         Check that the {key} is available, or set git.signing_key",
+        stderr.trim()
+    )
+}
+
+/// Hard-reset the working tree to `target`, discarding a failed iteration.
+///
+/// Refuses to move to a commit that is not `initial` or one of its
+/// descendants, so a rollback never rewinds past the loop's starting point.
+pub(crate) async fn git_reset_hard(cwd: &Path, target: &str, initial: Option<&str>) -> Result<()> {
+    if let Some(initial) = initial {
+        let is_descendant = tokio::process::Command::new("git")
+            .current_dir(cwd)
+            .args(["merge-base", "--is-ancestor", initial, target])
+            .output()
+            .await
+            .context("Failed to run git merge-base")?
+            .status
+            .success();
+        if !is_descendant {
+            bail!(
+                "Refusing to reset to {target}: it is not at or after the loop's initial commit {initial}"
+            );
+        }
+    }
+
+    let output = tokio::process::Command::new("git")
+        .current_dir(cwd)
+        .args(["reset", "--hard", target])
+        .output()
+        .await
+        .context("Failed to run git reset")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("git reset --hard {target} failed: {stderr}");
+    }
+
+    info!("Rolled back working tree to {}", target);
+    Ok(())
+}
+
+/// Roll back a failed iteration to `start_commit` when `enabled`.
+///
+/// Returns true if the tree was reset. Failures are logged, never fatal:
+/// the loop carries on and the agent fixes forward instead.
+pub(crate) async fn rollback_iteration(
+    cwd: &Path,
+    enabled: bool,
+    start_commit: Option<&str>,
+    initial_commit: Option<&str>,
+) -> bool {
+    if !enabled {
+        return false;
+    }
+    let Some(target) = start_commit else {
+        warn!("Rollback skipped: no commit recorded at iteration start");
+        return false;
+    };
+    match git_reset_hard(cwd, target, initial_commit).await {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("Rollback failed: {e}");
+            false
+        }
+    }
+}
+
 /// Create a pull request using the `gh` CLI.
 ///
 /// Returns the PR URL on success.
@@ -143,6 +509,303 @@ pub(crate) async fn check_gh_available() -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::process::Command;
+
+    /// Run a git command in `dir`, returning trimmed stdout.
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .current_dir(dir)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {args:?} failed");
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+
+    /// Create a temp git repo with one commit of `file.txt`. Returns None if git is unavailable.
+    fn init_temp_repo() -> Option<tempfile::TempDir> {
+        let dir = tempfile::tempdir().unwrap();
+        let ok = Command::new("git")
+            .current_dir(dir.path())
+            .args(["init", "-q"])
+            .output()
+            .is_ok_and(|o| o.status.success());
+        if !ok {
+            return None;
+        }
+        git(dir.path(), &["config", "user.email", "test@example.com"]);
+        git(dir.path(), &["config", "user.name", "Test"]);
+        git(dir.path(), &["config", "commit.gpgsign", "false"]);
+        commit_file(dir.path(), "initial");
+        Some(dir)
+    }
+
+    /// Write `file.txt` with `content` and commit it. Returns the new HEAD.
+    fn commit_file(dir: &Path, content: &str) -> String {
+        std::fs::write(dir.join("file.txt"), content).unwrap();
+        git(dir, &["add", "file.txt"]);
+        git(dir, &["commit", "-q", "-m", content]);
+        git(dir, &["rev-parse", "HEAD"])
+    }
+
+    #[tokio::test]
+    async fn test_diff_stat_since_includes_commits_and_worktree() {
+        let Some(repo) = init_temp_repo() else {
+            return;
+        };
+        let dir = repo.path();
+        let base = git(dir, &["rev-parse", "HEAD"]);
+        assert!(diff_stat_since(dir, &base).await.is_empty());
+
+        commit_file(dir, "iteration");
+        std::fs::write(dir.join("file.txt"), "uncommitted\nedit\n").unwrap();
+        let stat = diff_stat_since(dir, &base).await;
+        assert!(stat.contains("file.txt"));
+        assert!(stat.contains("1 file changed"));
+
+        assert!(diff_stat_since(dir, "not-a-commit").await.is_empty());
+    }
+
+    #[test]
+    fn test_render_commit_message() {
+        assert_eq!(
+            render_commit_message("ralph: {mode} iteration {iteration}", 7, "build"),
+            "ralph: build iteration 7"
+        );
+        assert_eq!(
+            render_commit_message("{iteration}/{iteration} {unknown}", 2, "plan"),
+            "2/2 {unknown}"
+        );
+        assert_eq!(render_commit_message("wip", 1, "build"), "wip");
+    }
+
+    #[test]
+    fn test_render_pr_template() {
+        assert_eq!(
+            render_pr_template("{branch}: {goal}", "add-auth", "Add login", 4),
+            "add-auth: Add login"
+        );
+        assert_eq!(
+            render_pr_template(
+                "{goal} ({iterations} iterations)\n\n`{branch}`",
+                "fix-ci",
+                "Fix CI",
+                12
+            ),
+            "Fix CI (12 iterations)\n\n`fix-ci`"
+        );
+    }
+
+    #[test]
+    fn test_render_pr_template_missing_and_unknown_placeholders() {
+        // No placeholders: the template is used verbatim
+        assert_eq!(
+            render_pr_template("Ralph build", "b", "g", 1),
+            "Ralph build"
+        );
+        // Unknown or unclosed placeholders are left alone
+        assert_eq!(
+            render_pr_template("{branch} {mode} {goal", "b", "g", 1),
+            "b {mode} {goal"
+        );
+        // Values are not re-scanned for placeholders
+        assert_eq!(
+            render_pr_template("{goal}", "b", "rename {branch}", 1),
+            "rename {branch}"
+        );
+    }
+
+    #[test]
+    fn test_with_iteration_trailer() {
+        assert_eq!(
+            with_iteration_trailer("ralph: build iteration 7", 7),
+            "ralph: build iteration 7\n\nRalph-Iteration: 7"
+        );
+        assert_eq!(
+            with_iteration_trailer("subject\n\nbody\n", 2),
+            "subject\n\nbody\n\nRalph-Iteration: 2"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_auto_commit_commits_dirty_tree() {
+        let Some(repo) = init_temp_repo() else {
+            return; // Git not available
+        };
+        let dir = repo.path();
+        let before = git(dir, &["rev-parse", "HEAD"]);
+
+        std::fs::write(dir.join("file.txt"), "edited").unwrap();
+        std::fs::write(dir.join("new.txt"), "untracked").unwrap();
+
+        assert!(auto_commit(dir, "ralph: build iteration 3", false, None)
+            .await
+            .unwrap());
+        assert_ne!(git(dir, &["rev-parse", "HEAD"]), before);
+        assert_eq!(
+            git(dir, &["log", "-1", "--format=%s"]),
+            "ralph: build iteration 3"
+        );
+        assert!(git(dir, &["status", "--porcelain"]).is_empty());
+    }
+
+    #[test]
+    fn test_commit_args() {
+        assert_eq!(
+            commit_args("msg", false, None),
+            vec!["commit", "-q", "-m", "msg"]
+        );
+        // The key is ignored unless signing is on
+        assert_eq!(
+            commit_args("msg", false, Some("ABCD1234")),
+            vec!["commit", "-q", "-m", "msg"]
+        );
+        assert_eq!(
+            commit_args("msg", true, None),
+            vec!["commit", "-q", "--gpg-sign", "-m", "msg"]
+        );
+        assert_eq!(
+            commit_args("msg", true, Some("~/.ssh/id.pub")),
+            vec!["commit", "-q", "--gpg-sign=~/.ssh/id.pub", "-m", "msg"]
+        );
+    }
+
+    #[test]
+    fn test_is_signed_commit() {
+        let unsigned = "tree abc\nauthor A <a@a> 1 +0000\ncommitter A <a@a> 1 +0000\n\nmsg\n";
+        assert!(!is_signed_commit(unsigned));
+
+        let signed = "tree abc\nauthor A <a@a> 1 +0000\ngpgsig -----BEGIN SSH SIGNATURE-----\n xyz\n -----END SSH SIGNATURE-----\n\nmsg\n";
+        assert!(is_signed_commit(signed));
+
+        // Only headers count, not the message body
+        assert!(!is_signed_commit("tree abc\n\ngpgsig in the message\n"));
+    }
+
+    #[test]
+    fn test_format_signing_error_names_key() {
+        let err = format_signing_error(Some("ABCD1234"), "error: gpg failed to sign the data\n");
+        assert!(err.contains("gpg failed to sign the data"));
+        assert!(err.contains("signing key 'ABCD1234'"));
+        assert!(format_signing_error(None, "").contains("user.signingkey"));
+    }
+
+    #[tokio::test]
+    async fn test_auto_commit_signs_with_ssh_key() {
+        let Some(repo) = init_temp_repo() else {
+            return; // Git not available
+        };
+        let dir = repo.path();
+        let key = dir.join("signing_key");
+        let generated = Command::new("ssh-keygen")
+            .args(["-q", "-t", "ed25519", "-N", "", "-C", "ralph", "-f"])
+            .arg(&key)
+            .output()
+            .is_ok_and(|o| o.status.success());
+        if !generated {
+            return; // ssh-keygen not available
+        }
+        git(dir, &["config", "gpg.format", "ssh"]);
+        std::fs::write(dir.join(".gitignore"), "signing_key*\n").unwrap();
+        std::fs::write(dir.join("file.txt"), "signed").unwrap();
+
+        let key = key.to_string_lossy();
+        assert!(auto_commit(dir, "signed", true, Some(&key)).await.unwrap());
+        assert!(git(dir, &["cat-file", "commit", "HEAD"]).contains("gpgsig "));
+    }
+
+    #[tokio::test]
+    async fn test_auto_commit_missing_signing_key_errors() {
+        let Some(repo) = init_temp_repo() else {
+            return; // Git not available
+        };
+        let dir = repo.path();
+        git(dir, &["config", "gpg.format", "ssh"]);
+        std::fs::write(dir.join("file.txt"), "unsigned").unwrap();
+
+        let missing = dir.join("no-such-key").to_string_lossy().into_owned();
+        let err = auto_commit(dir, "unsigned", true, Some(&missing))
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("Signed commit failed"), "{err}");
+        assert!(err.contains("no-such-key"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_auto_commit_clean_tree_is_noop() {
+        let Some(repo) = init_temp_repo() else {
+            return; // Git not available
+        };
+        let dir = repo.path();
+        let before = git(dir, &["rev-parse", "HEAD"]);
+
+        assert!(!auto_commit(dir, "unused", false, None).await.unwrap());
+        assert_eq!(git(dir, &["rev-parse", "HEAD"]), before);
+    }
+
+    #[tokio::test]
+    async fn test_git_reset_hard_discards_iteration_commits() {
+        let Some(repo) = init_temp_repo() else {
+            return; // Git not available
+        };
+        let dir = repo.path();
+        let initial = git(dir, &["rev-parse", "HEAD"]);
+
+        commit_file(dir, "bad iteration");
+        std::fs::write(dir.join("file.txt"), "uncommitted").unwrap();
+
+        git_reset_hard(dir, &initial, Some(&initial)).await.unwrap();
+
+        assert_eq!(git(dir, &["rev-parse", "HEAD"]), initial);
+        assert_eq!(
+            std::fs::read_to_string(dir.join("file.txt")).unwrap(),
+            "initial"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rollback_iteration_only_when_enabled() {
+        let Some(repo) = init_temp_repo() else {
+            return; // Git not available
+        };
+        let dir = repo.path();
+        let start = git(dir, &["rev-parse", "HEAD"]);
+        let bad = commit_file(dir, "bad iteration");
+
+        // Disabled: tree is left alone
+        assert!(!rollback_iteration(dir, false, Some(&start), Some(&start)).await);
+        assert_eq!(git(dir, &["rev-parse", "HEAD"]), bad);
+
+        // Enabled: reset to the iteration's start commit
+        assert!(rollback_iteration(dir, true, Some(&start), Some(&start)).await);
+        assert_eq!(git(dir, &["rev-parse", "HEAD"]), start);
+    }
+
+    #[tokio::test]
+    async fn test_rollback_iteration_without_start_commit() {
+        let Some(repo) = init_temp_repo() else {
+            return; // Git not available
+        };
+        assert!(!rollback_iteration(repo.path(), true, None, None).await);
+    }
+
+    #[tokio::test]
+    async fn test_git_reset_hard_refuses_to_pass_initial_commit() {
+        let Some(repo) = init_temp_repo() else {
+            return; // Git not available
+        };
+        let dir = repo.path();
+        let before_loop = git(dir, &["rev-parse", "HEAD"]);
+        let initial = commit_file(dir, "loop start");
+        let head = commit_file(dir, "iteration");
+
+        let err = git_reset_hard(dir, &before_loop, Some(&initial))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("initial commit"));
+        assert_eq!(git(dir, &["rev-parse", "HEAD"]), head);
+    }
 
     #[tokio::test]
     async fn test_git_push_rejects_protected_branch() {
@@ -169,7 +832,7 @@ mod tests {
 
         // Call git_push with current branch in protected list - should fail
         let protected = vec![branch.clone()];
-        let result = git_push(&cwd, &protected).await;
+        let result = git_push(&cwd, &protected, 0).await;
 
         assert!(result.is_err());
         let err = result.unwrap_err().to_string();
@@ -208,7 +871,7 @@ mod tests {
 
         // Call git_push - it should not fail due to protected branch check
         // (it may fail for other reasons like no remote, but that's a different error)
-        let result = git_push(&cwd, &protected).await;
+        let result = git_push(&cwd, &protected, 0).await;
 
         // If it failed, it shouldn't be because of protected branch
         if let Err(e) = result {
@@ -219,4 +882,137 @@ mod tests {
         }
         // Success or other failure is fine
     }
+
+    fn transient(message: &str) -> PushFailure {
+        PushFailure {
+            message: message.to_string(),
+            retryable: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_push_with_retry_fails_then_succeeds() {
+        let mut attempts = 0;
+        let result = push_with_retry(3, Duration::ZERO, || {
+            attempts += 1;
+            let outcome = if attempts < 3 {
+                Err(transient("Could not resolve host: github.com"))
+            } else {
+                Ok(())
+            };
+            async move { outcome }
+        })
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(attempts, 3);
+    }
+
+    #[tokio::test]
+    async fn test_push_with_retry_gives_up_after_retries() {
+        let mut attempts = 0;
+        let err = push_with_retry(2, Duration::ZERO, || {
+            attempts += 1;
+            async { Err(transient("Connection reset by peer")) }
+        })
+        .await
+        .unwrap_err();
+
+        assert_eq!(attempts, 3); // First attempt plus 2 retries
+        assert!(err.to_string().contains("after 3 attempt(s)"));
+        assert!(err.to_string().contains("Connection reset by peer"));
+    }
+
+    #[tokio::test]
+    async fn test_push_with_retry_zero_retries_tries_once() {
+        let mut attempts = 0;
+        let result = push_with_retry(0, Duration::ZERO, || {
+            attempts += 1;
+            async { Err(transient("timed out")) }
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn test_push_with_retry_does_not_retry_rejection() {
+        let mut attempts = 0;
+        let err = push_with_retry(3, Duration::ZERO, || {
+            attempts += 1;
+            async {
+                Err(PushFailure {
+                    message: "! [rejected] main -> main (non-fast-forward)".to_string(),
+                    retryable: false,
+                })
+            }
+        })
+        .await
+        .unwrap_err();
+
+        assert_eq!(attempts, 1);
+        assert!(err.to_string().contains("rejected"));
+    }
+
+    #[test]
+    fn test_is_retryable_push_error() {
+        assert!(is_retryable_push_error(
+            "fatal: unable to access 'https://github.com/o/r.git/': Could not resolve host: github.com"
+        ));
+        assert!(is_retryable_push_error(
+            "fatal: the remote end hung up unexpectedly"
+        ));
+        assert!(!is_retryable_push_error(
+            " ! [rejected]        main -> main (non-fast-forward)\nerror: failed to push some refs"
+        ));
+        assert!(!is_retryable_push_error(
+            "hint: Updates were rejected because the remote contains work that you do not\n (fetch first)"
+        ));
+        assert!(!is_retryable_push_error(
+            "fatal: No configured push destination."
+        ));
+        assert!(!is_retryable_push_error(
+            "git@github.com: Permission denied (publickey)."
+        ));
+    }
+
+    #[test]
+    fn test_is_protected_branch() {
+        let protected = vec!["main".to_string(), "production".to_string()];
+        assert!(is_protected_branch("main", &protected));
+        assert!(is_protected_branch("production", &protected));
+        assert!(!is_protected_branch("feature/login", &protected));
+        assert!(!is_protected_branch("main-backup", &protected));
+        assert!(!is_protected_branch("", &protected));
+        assert!(!is_protected_branch("main", &[]));
+    }
+
+    #[test]
+    fn test_auto_branch_name() {
+        let now = DateTime::parse_from_rfc3339("2024-03-05T07:08:09Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(auto_branch_name(now), "ralph/20240305-070809");
+    }
+
+    #[tokio::test]
+    async fn test_create_branch_switches_to_it() {
+        let Some(dir) = init_temp_repo() else {
+            return;
+        };
+
+        create_branch(dir.path(), "ralph/20240305-070809")
+            .await
+            .unwrap();
+        assert_eq!(
+            get_current_branch(dir.path()).await.unwrap(),
+            "ralph/20240305-070809"
+        );
+
+        // Creating it again fails rather than silently reusing it
+        assert!(create_branch(dir.path(), "ralph/20240305-070809")
+            .await
+            .is_err());
+    }
 }
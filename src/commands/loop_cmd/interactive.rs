@@ -0,0 +1,168 @@
+//! Supervised runs (`ralph loop --interactive`).
+//!
+//! After each iteration the loop prints `git diff --stat` against the
+//! pre-iteration commit and asks whether to continue, stop, or revert. The
+//! prompt runs before the completion check and push, so a reverted iteration
+//! never reaches the remote. It is only shown when stdin and stdout are a
+//! terminal; parsing the answer is pure.
+
+use anyhow::{bail, Result};
+use colored::Colorize;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::Path;
+
+use super::git::{diff_stat_since, git_reset_hard};
+use super::signal::{signalled, ShutdownSignal};
+
+/// What to do with the iteration that just finished.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum IterationAction {
+    /// Keep the changes and run the next iteration.
+    Continue,
+    /// Keep the changes and stop the loop.
+    Stop,
+    /// Reset to the pre-iteration commit and run the next iteration.
+    Revert,
+}
+
+/// Whether stdin and stdout are both a terminal, so a prompt can be answered.
+pub(crate) fn is_interactive_terminal() -> bool {
+    std::io::stdin().is_terminal() && std::io::stdout().is_terminal()
+}
+
+/// Shows the iteration's changes and asks what to do next.
+///
+/// `base` is the commit the iteration started from. Returns `None` if a
+/// shutdown signal arrives while waiting; the loop's signal check takes over.
+/// End of input counts as stop.
+pub(crate) async fn review_iteration(
+    cwd: &Path,
+    iteration: u32,
+    base: Option<&str>,
+    shutdown: &mut ShutdownSignal,
+) -> Option<IterationAction> {
+    let stat = match base {
+        Some(base) => diff_stat_since(cwd, base).await,
+        None => String::new(),
+    };
+    print!("{}", format_review(iteration, &stat));
+
+    // A plain thread, not `spawn_blocking`: a read still pending at shutdown
+    // must not keep the runtime alive
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(read_action());
+    });
+
+    tokio::select! {
+        action = rx => Some(action.unwrap_or(IterationAction::Stop)),
+        () = signalled(shutdown) => None,
+    }
+}
+
+/// Maps an answer to the prompt to an action, ignoring case and whitespace.
+///
+/// Accepts the first letter or the full word. Anything else, including an
+/// empty line, is `None` so the prompt is repeated.
+pub(crate) fn parse_action(input: &str) -> Option<IterationAction> {
+    match input.trim().to_ascii_lowercase().as_str() {
+        "c" | "continue" => Some(IterationAction::Continue),
+        "s" | "stop" => Some(IterationAction::Stop),
+        "r" | "revert" => Some(IterationAction::Revert),
+        _ => None,
+    }
+}
+
+/// Resets an iteration the user rejected to the commit it started from.
+pub(crate) async fn revert_iteration(
+    cwd: &Path,
+    base: Option<&str>,
+    initial: Option<&str>,
+) -> Result<()> {
+    let Some(base) = base else {
+        bail!("Cannot revert: the iteration started without a commit");
+    };
+    git_reset_hard(cwd, base, initial).await
+}
+
+// -----------------------------------------------------------------------------
+// Helper functions
+// -----------------------------------------------------------------------------
+
+/// Prompt shown after the diff summary.
+const PROMPT: &str = "[c]ontinue / [s]top / [r]evert? ";
+
+/// Formats the diff summary shown before the prompt.
+fn format_review(iteration: u32, stat: &str) -> String {
+    let header = format!("Iteration {iteration} changes:").bold();
+    if stat.trim().is_empty() {
+        format!("\n{header} none\n")
+    } else {
+        format!("\n{header}\n{}\n", stat.trim_end())
+    }
+}
+
+/// Prompts on stdout and reads answers from stdin until one parses.
+fn read_action() -> IterationAction {
+    let stdin = std::io::stdin();
+    let mut line = String::new();
+    loop {
+        print!("{PROMPT}");
+        let _ = std::io::stdout().flush();
+
+        line.clear();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => return IterationAction::Stop,
+            Ok(_) => {}
+        }
+        if let Some(action) = parse_action(&line) {
+            return action;
+        }
+        println!("Please answer c, s, or r.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_action_keys() {
+        assert_eq!(parse_action("c"), Some(IterationAction::Continue));
+        assert_eq!(parse_action("s"), Some(IterationAction::Stop));
+        assert_eq!(parse_action("r"), Some(IterationAction::Revert));
+    }
+
+    #[test]
+    fn test_parse_action_words_case_and_whitespace() {
+        assert_eq!(parse_action("Continue\n"), Some(IterationAction::Continue));
+        assert_eq!(parse_action("  STOP "), Some(IterationAction::Stop));
+        assert_eq!(parse_action("R\r\n"), Some(IterationAction::Revert));
+    }
+
+    #[test]
+    fn test_parse_action_rejects_other_input() {
+        assert_eq!(parse_action(""), None);
+        assert_eq!(parse_action("\n"), None);
+        assert_eq!(parse_action("x"), None);
+        assert_eq!(parse_action("yes"), None);
+        assert_eq!(parse_action("cs"), None);
+    }
+
+    #[test]
+    fn test_format_review() {
+        let out = format_review(3, " src/main.rs | 2 +-\n 1 file changed\n");
+        assert!(out.contains("Iteration 3 changes:"));
+        assert!(out.ends_with(" src/main.rs | 2 +-\n 1 file changed\n"));
+
+        assert!(format_review(4, "").contains("Iteration 4 changes:"));
+        assert!(format_review(4, "").trim_end().ends_with("none"));
+    }
+
+    #[tokio::test]
+    async fn test_revert_iteration_without_base_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = revert_iteration(dir.path(), None, None).await.unwrap_err();
+        assert!(err.to_string().contains("Cannot revert"));
+    }
+}
@@ -0,0 +1,86 @@
+//! Per-iteration prompt and output capture for debugging.
+//!
+//! When `monitoring.save_iteration_output` is set, each iteration's assembled
+//! prompt and raw agent output are written to `.ralph/iterations/<n>.prompt`
+//! and `.ralph/iterations/<n>.log`. Write failures only warn.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Directory holding saved iterations, relative to the project root.
+pub(crate) const ITERATIONS_DIR: &str = ".ralph/iterations";
+
+/// Saves the prompt sent for `iteration`.
+pub(crate) fn save_prompt(project_dir: &Path, iteration: u32, prompt: &str) {
+    save(project_dir, iteration, "prompt", prompt);
+}
+
+/// Saves the agent output captured for `iteration`.
+pub(crate) fn save_output(project_dir: &Path, iteration: u32, output: &str) {
+    save(project_dir, iteration, "log", output);
+}
+
+// -----------------------------------------------------------------------------
+// Helper functions
+// -----------------------------------------------------------------------------
+
+/// Returns the path for `iteration` with `extension`.
+fn iteration_path(project_dir: &Path, iteration: u32, extension: &str) -> PathBuf {
+    project_dir
+        .join(ITERATIONS_DIR)
+        .join(format!("{iteration}.{extension}"))
+}
+
+/// Writes `content`, warning instead of failing the loop.
+fn save(project_dir: &Path, iteration: u32, extension: &str, content: &str) {
+    let path = iteration_path(project_dir, iteration, extension);
+    if let Err(e) = write_file(&path, content) {
+        warn!("Failed to save iteration {iteration} {extension}: {e:#}");
+    }
+}
+
+/// Creates the parent directory lazily and writes `content` to `path`.
+fn write_file(path: &Path, content: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    std::fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_save_creates_named_files() {
+        let dir = TempDir::new().unwrap();
+        assert!(!dir.path().join(ITERATIONS_DIR).exists());
+
+        save_prompt(dir.path(), 3, "prompt text");
+        save_output(dir.path(), 3, "agent output");
+
+        let iterations = dir.path().join(ITERATIONS_DIR);
+        assert_eq!(
+            std::fs::read_to_string(iterations.join("3.prompt")).unwrap(),
+            "prompt text"
+        );
+        assert_eq!(
+            std::fs::read_to_string(iterations.join("3.log")).unwrap(),
+            "agent output"
+        );
+    }
+
+    #[test]
+    fn test_save_failure_is_not_fatal() {
+        let dir = TempDir::new().unwrap();
+        // A file where the directory should be makes creation fail
+        std::fs::create_dir_all(dir.path().join(".ralph")).unwrap();
+        std::fs::write(dir.path().join(ITERATIONS_DIR), "").unwrap();
+
+        save_output(dir.path(), 1, "output");
+        assert!(dir.path().join(ITERATIONS_DIR).is_file());
+    }
+}
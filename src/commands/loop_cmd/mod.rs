@@ -4,39 +4,76 @@
 //! is separated into submodules for maintainability:
 //! - `format`: Output formatting and progress display
 //! - `git`: Git operations (push, branch, commit)
+//! - `iteration_log`: Saved per-iteration prompts and agent output
+//! - `signal`: SIGINT/SIGTERM handling that leaves state inactive
+//! - `timing`: Per-phase timing spans (agent, validation, git)
 //! - `worktree`: Git worktree management for parallel builds
 
+mod budget;
+mod events;
 mod format;
 mod git;
+mod interactive;
+mod iteration_log;
+mod signal;
+mod summary;
+mod timing;
 pub(crate) mod worktree;
 
 use anyhow::{bail, Context, Result};
 use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use tracing::{debug, info, warn};
 
-use crate::agent::{AgentProvider, ClaudeProvider, CursorProvider, Provider};
-use crate::config::Config;
-use crate::detection::{get_commit_hash, CompletionDetector};
+use crate::agent::mock::MockAgentProvider;
+use crate::agent::{AgentProvider, ClaudeProvider, CursorProvider, OllamaProvider, Provider};
+use crate::commands::image::{ensure_image, Progress};
+use crate::commands::shell::shell_command;
+use crate::config::{
+    AgentConfig, BackoffConfig, CompletionConfig, Config, ContainerRuntime, MonitoringConfig,
+    ValidationConfig,
+};
+use crate::detection::{
+    commit_count_since, get_commit_hash, is_worktree_dirty, success_command_passed,
+    CompletionDetector, CompletionSignals,
+};
+use crate::metrics::{write_metrics, LoopSummary};
 use crate::notifications::{NotificationDetails, NotificationEvent, Notifier};
 use crate::sandbox::{DockerSandbox, Sandbox, SandboxError};
-use crate::state::{Mode, RalphState};
-
-use format::{
-    format_banner, format_completion_detected, format_iteration_header, format_loop_finished,
-    format_max_iterations_reached, format_progress, BannerInfo, ProgressInfo,
+use crate::state::{ErrorKind, LoopLock, LoopSettings, Mode, RalphState};
+
+use budget::{is_time_budget_exceeded, iteration_delay, IterationRateLimiter};
+pub(crate) use events::EventFormat;
+use events::{EventSink, LoopEvent};
+use format::{BannerInfo, ProgressInfo};
+use git::{
+    auto_branch_name, auto_commit, count_successful_commits, create_branch, create_pull_request,
+    get_current_branch, git_push, is_protected_branch, render_commit_message, render_pr_template,
+    rollback_iteration, with_iteration_trailer,
+};
+pub(crate) use git::{check_gh_available, ITERATION_TRAILER};
+use interactive::{is_interactive_terminal, revert_iteration, review_iteration, IterationAction};
+pub(crate) use iteration_log::ITERATIONS_DIR;
+use signal::{
+    is_signalled, signalled, sleep_or_signal, spawn_signal_listener, stop_on_signal, ShutdownSignal,
 };
-use git::{check_gh_available, create_pull_request, git_push};
+use timing::{timed, PhaseTimings};
 use worktree::{
     configure_worktree_identity, copy_plan_to_worktree, create_worktree, enable_worktree_config,
-    parse_implementation_plan, worktree_path, BranchSection,
+    parse_implementation_plan, schedule, worktree_path, BranchSection, PlanDefaults,
 };
 
+/// Consecutive errors (ending in a rate limit) before switching to
+/// `[agent].fallback_provider`.
+const FALLBACK_AFTER_CONSECUTIVE_ERRORS: u32 = 3;
+
 /// Check if a branch section has incomplete tasks.
 ///
 /// Returns true if the branch has any unchecked `- [ ]` tasks before the next
 /// `## Branch:` header.
-fn is_branch_incomplete(plan_content: &str, branch_name: &str) -> bool {
+pub(crate) fn is_branch_incomplete(plan_content: &str, branch_name: &str) -> bool {
     let header = format!("## Branch: {branch_name}");
     let Some(start) = plan_content.find(&header) else {
         return false;
@@ -73,10 +110,15 @@ pub(crate) struct LoopDependencies {
     pub prompt_file: PathBuf,
 }
 
-/// Result from running the loop core, for test verification.
-#[cfg(test)]
+/// Message prefix for loops stopped by the consecutive-error circuit breaker.
+const CIRCUIT_BREAKER_MESSAGE: &str = "Circuit breaker triggered";
+
+/// Message prefix for completed loops whose `validation.final_command` failed.
+const FINAL_VALIDATION_MESSAGE: &str = "Final validation failed";
+
+/// Outcome of a finished loop.
 #[derive(Debug)]
-pub(crate) struct LoopResult {
+pub(crate) struct LoopOutcome {
     /// Final iteration reached.
     pub final_iteration: u32,
     /// How the loop terminated.
@@ -85,30 +127,87 @@ pub(crate) struct LoopResult {
     pub error_count: u32,
 }
 
+impl LoopOutcome {
+    /// Builds the outcome from the loop's final state.
+    fn from_state(state: &RalphState, termination_reason: TerminationReason) -> Self {
+        Self {
+            final_iteration: state.iteration,
+            termination_reason,
+            error_count: state.error_count,
+        }
+    }
+
+    /// Fails if the loop stopped on an error, so the process exits non-zero.
+    pub(crate) fn ensure_success(&self) -> Result<()> {
+        debug!(
+            "Loop ended ({}) at iteration {} with {} error(s)",
+            self.termination_reason.label(),
+            self.final_iteration,
+            self.error_count
+        );
+        match &self.termination_reason {
+            TerminationReason::Error(message) => Err(anyhow::anyhow!("{message}")),
+            _ => Ok(()),
+        }
+    }
+}
+
 /// Why the loop terminated.
-#[cfg(test)]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) enum TerminationReason {
     /// Max iterations reached.
     MaxIterations,
-    /// Completion detected (idle threshold).
+    /// Completion detected (idle threshold or completion promise).
     CompletionDetected,
+    /// `completion.success_command` passed.
+    SuccessCommand,
     /// Loop was cancelled externally.
     Cancelled,
-    /// Fatal error occurred.
+    /// SIGINT or SIGTERM received.
+    Signalled,
+    /// Time budget (`[budget].max_minutes`) exhausted.
+    BudgetExceeded,
+    /// Dry run printed the prompt without running the agent.
+    DryRun,
+    /// Stopped at the `--interactive` prompt.
+    Stopped,
+    /// Fatal error occurred (including the circuit breaker).
     Error(String),
 }
 
+impl TerminationReason {
+    /// Stable label used in logs, notifications, and metrics.
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            Self::MaxIterations => "max_iterations_reached",
+            Self::CompletionDetected => "agent_idle",
+            Self::SuccessCommand => "success_command",
+            Self::Cancelled => "cancelled",
+            Self::Signalled => "cancelled_by_signal",
+            Self::BudgetExceeded => "budget_exceeded",
+            Self::DryRun => "dry_run",
+            Self::Stopped => "stopped_by_user",
+            Self::Error(message) if message.starts_with(CIRCUIT_BREAKER_MESSAGE) => {
+                "circuit_breaker"
+            }
+            Self::Error(message) if message.starts_with(FINAL_VALIDATION_MESSAGE) => {
+                "final_validation_failed"
+            }
+            Self::Error(_) => "error",
+        }
+    }
+}
+
 /// Run the loop with injected dependencies (for testing).
 ///
-/// This is an internal function for E2E testing that allows mocking
-/// the agent and sandbox while testing the full loop orchestration logic.
+/// Drives the same loop as [`run`] with the agent and sandbox swapped out,
+/// skipping the CLI setup around it (lock, banner, signal listener).
 #[cfg(test)]
-#[allow(clippy::too_many_lines, tail_expr_drop_order)]
+#[allow(tail_expr_drop_order)] // Drop order doesn't matter for async operations
 pub(crate) async fn run_loop_core(
     deps: LoopDependencies,
     initial_state: RalphState,
-) -> Result<LoopResult> {
+) -> Result<LoopOutcome> {
     let LoopDependencies {
         agent,
         sandbox,
@@ -121,208 +220,25 @@ pub(crate) async fn run_loop_core(
     state.active = true;
     state.save(&project_dir)?;
 
-    // Initialize completion detector from persisted state for idle detection
-    // continuity across restarts
-    let mut detector = CompletionDetector::from_state(
-        config.completion.idle_threshold,
-        state.last_commit.clone(),
-        state.idle_iterations,
-    );
-
-    // Create persistent container if sandbox is enabled and reuse is configured
-    let persistent_container_name = if let Some(ref sb) = sandbox {
-        if config.sandbox.reuse_container {
-            match sb.create_persistent(&project_dir).await {
-                Ok(name) if !name.is_empty() => Some(name),
-                _ => None,
-            }
-        } else {
-            None
-        }
-    } else {
-        None
+    let session = LoopSession {
+        agent_dir: resolve_agent_dir(&project_dir, &config.agent)?,
+        provider: resolve_provider(&config, None)?,
+        fallback_provider: config.agent.get_fallback_provider()?,
+        config,
+        cwd: project_dir,
+        prompt_file,
+        prompt_append: Vec::new(),
+        agent,
+        sandbox_enabled: sandbox.is_some(),
+        sandbox,
+        keep_container: false,
+        interactive: false,
+        output: None,
+        // The sender is dropped, so the loop is never signalled
+        shutdown: tokio::sync::watch::channel(false).1,
+        sink: EventFormat::Human.sink(),
     };
-
-    let termination_reason;
-
-    // Main loop
-    loop {
-        // Check for external cancellation (e.g., `ralph cancel`)
-        if let Some(loaded) = RalphState::load(&project_dir)? {
-            if !loaded.active {
-                state.active = false;
-                state.save(&project_dir)?;
-                termination_reason = TerminationReason::Cancelled;
-                break;
-            }
-        }
-
-        // Check max iterations
-        if is_max_iterations_reached(&state) {
-            state.active = false;
-            state.save(&project_dir)?;
-            termination_reason = TerminationReason::MaxIterations;
-            break;
-        }
-
-        // Read prompt
-        let mut prompt = std::fs::read_to_string(&prompt_file)
-            .with_context(|| format!("Failed to read prompt file: {}", prompt_file.display()))?;
-
-        // Append validation errors from previous iteration if present
-        if let Some(ref last_error) = state.last_error {
-            if last_error.starts_with("Validation error:") {
-                let error_details = last_error
-                    .strip_prefix("Validation error:")
-                    .unwrap_or(last_error);
-
-                prompt.push_str("\n\n");
-                prompt.push_str("## ⚠️ VALIDATION ERROR FROM PREVIOUS ITERATION\n");
-                prompt.push_str("The following validation error occurred. Please fix it:\n\n");
-                prompt.push_str("```\n");
-                prompt.push_str(error_details.trim());
-                prompt.push_str("\n```\n");
-                prompt.push_str(
-                    "\nFix the issues above and ensure validation passes before proceeding.\n",
-                );
-            }
-        }
-
-        // Run agent
-        let output_result = if let Some(ref sb) = sandbox {
-            sb.run(&project_dir, &prompt, persistent_container_name.as_deref())
-                .await
-        } else {
-            agent.invoke(&project_dir, &prompt).await
-        };
-
-        // Handle agent execution result
-        let _output = match output_result {
-            Ok(out) => out,
-            Err(e) => {
-                let error_msg = e.to_string();
-
-                // Check if this is a recoverable error
-                let is_timeout = error_msg.contains("timed out");
-                let is_rate_limit = error_msg.contains("resource_exhausted")
-                    || error_msg.contains("rate limit")
-                    || error_msg.contains("Rate limit");
-
-                if is_timeout || is_rate_limit {
-                    state.error_count += 1;
-                    state.consecutive_errors += 1;
-                    state.last_error = Some(error_msg);
-                    state.last_iteration_at = Some(chrono::Utc::now());
-                    state.iteration += 1;
-                    state.save(&project_dir)?;
-
-                    // Circuit breaker
-                    if config.monitoring.max_consecutive_errors > 0
-                        && state.consecutive_errors >= config.monitoring.max_consecutive_errors
-                    {
-                        if let (Some(container_name), Some(sb)) =
-                            (&persistent_container_name, &sandbox)
-                        {
-                            let _ = sb.remove_persistent(container_name).await;
-                        }
-                        termination_reason =
-                            TerminationReason::Error("Circuit breaker triggered".to_string());
-                        break;
-                    }
-                    continue;
-                }
-
-                // Non-recoverable error
-                if let (Some(container_name), Some(sb)) = (&persistent_container_name, &sandbox) {
-                    let _ = sb.remove_persistent(container_name).await;
-                }
-                return Err(e).context("Agent execution failed");
-            }
-        };
-
-        // Validate code if enabled
-        if config.validation.enabled {
-            match validate_code(&project_dir, &config.validation.command).await {
-                Ok(()) => {
-                    if let Some(ref last_error) = state.last_error {
-                        if last_error.starts_with("Validation error:") {
-                            state.last_error = None;
-                        }
-                    }
-                }
-                Err(full_error) => {
-                    state.error_count += 1;
-                    state.consecutive_errors += 1;
-                    state.last_error = Some(format!("Validation error:{full_error}"));
-                    state.last_iteration_at = Some(chrono::Utc::now());
-                    state.iteration += 1;
-                    state.save(&project_dir)?;
-
-                    // Circuit breaker
-                    if config.monitoring.max_consecutive_errors > 0
-                        && state.consecutive_errors >= config.monitoring.max_consecutive_errors
-                    {
-                        if let (Some(container_name), Some(sb)) =
-                            (&persistent_container_name, &sandbox)
-                        {
-                            let _ = sb.remove_persistent(container_name).await;
-                        }
-                        termination_reason =
-                            TerminationReason::Error("Circuit breaker triggered".to_string());
-                        break;
-                    }
-                    continue;
-                }
-            }
-        }
-
-        // Successful iteration
-        state.consecutive_errors = 0;
-        state.last_iteration_at = Some(chrono::Utc::now());
-
-        // Check for cancellation again (agent may have been cancelled externally during execution)
-        if let Some(loaded) = RalphState::load(&project_dir)? {
-            if !loaded.active {
-                state.active = false;
-                state.save(&project_dir)?;
-                termination_reason = TerminationReason::Cancelled;
-                break;
-            }
-        }
-
-        state.save(&project_dir)?;
-
-        // Check for completion (idle detection - no real git in tests, so always idle)
-        // In real usage, this compares git commit hashes
-        // check_completion updates detector's internal state
-        let is_complete = detector.check_completion(None);
-
-        // Sync detector state to RalphState for persistence across restarts
-        state.last_commit = detector.last_commit().map(String::from);
-        state.idle_iterations = detector.idle_count();
-
-        if is_complete {
-            state.active = false;
-            state.save(&project_dir)?;
-            termination_reason = TerminationReason::CompletionDetected;
-            break;
-        }
-
-        // Increment iteration
-        state.iteration += 1;
-        state.save(&project_dir)?;
-    }
-
-    // Cleanup
-    if let (Some(container_name), Some(sb)) = (persistent_container_name, &sandbox) {
-        let _ = sb.remove_persistent(&container_name).await;
-    }
-
-    Ok(LoopResult {
-        final_iteration: state.iteration,
-        termination_reason,
-        error_count: state.error_count,
-    })
+    drive_loop(session, state).await
 }
 
 // -----------------------------------------------------------------------------
@@ -330,7 +246,7 @@ pub(crate) async fn run_loop_core(
 // -----------------------------------------------------------------------------
 
 /// Result of building a single branch.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub(crate) struct BranchResult {
     /// Branch name.
     pub branch: String,
@@ -371,16 +287,29 @@ impl BranchResult {
 // -----------------------------------------------------------------------------
 
 /// Execute builds for all branches in parallel or sequential mode.
-#[allow(tail_expr_drop_order)]
+///
+/// `defaults` (the plan frontmatter) fill in any branch without its own
+/// `Provider:` or `MaxIterations:`. With `fail_fast`, the first failed
+/// branch cancels the rest: queued branches never start, running ones stop
+/// after their current iteration, and both are reported as failed.
+#[allow(tail_expr_drop_order, clippy::too_many_arguments)] // CLI options passed through
 async fn execute_branch_builds(
-    branches: Vec<BranchSection>,
+    mut branches: Vec<BranchSection>,
+    defaults: &PlanDefaults,
     config: &Config,
-    max_iterations: Option<u32>,
+    max_iterations: IterationCap,
     no_sandbox: bool,
     provider_override: Option<&str>,
     sequential: bool,
+    fail_fast: bool,
 ) -> Result<Vec<BranchResult>> {
     let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    for branch in &mut branches {
+        defaults.apply(branch);
+    }
+
+    // Order by dependencies before touching any worktree, so a cycle fails fast
+    let stages = schedule(&branches)?;
 
     // Enable worktree config extension
     enable_worktree_config(&cwd).await?;
@@ -403,7 +332,17 @@ async fn execute_branch_builds(
 
         // Configure identity if specified
         if let Some(ref wt_config) = config.git.worktree {
-            if let Err(e) = configure_worktree_identity(&cwd, &branch.name, wt_config).await {
+            let git = &config.git;
+            let signing_key = git.signing_key.as_deref();
+            if let Err(e) = configure_worktree_identity(
+                &cwd,
+                &branch.name,
+                wt_config,
+                git.sign_commits,
+                signing_key,
+            )
+            .await
+            {
                 warn!("Failed to configure identity for '{}': {}", branch.name, e);
             }
         }
@@ -414,124 +353,340 @@ async fn execute_branch_builds(
         }
     }
 
-    // Execute builds
-    let results = if sequential {
-        execute_sequential(
-            &cwd,
-            branches,
-            config,
-            max_iterations,
-            no_sandbox,
-            provider_override,
-            gh_available,
-        )
-        .await
-    } else {
-        execute_parallel(
-            &cwd,
-            branches,
-            config,
-            max_iterations,
-            no_sandbox,
-            provider_override,
-            gh_available,
-        )
-        .await
-    };
+    // Execute builds one stage at a time; branches within a stage run concurrently
+    let mut by_name: HashMap<String, BranchSection> = branches
+        .into_iter()
+        .map(|branch| (branch.name.clone(), branch))
+        .collect();
+    let mut results: Vec<BranchResult> = Vec::new();
+
+    for stage in stages {
+        let stage: Vec<_> = stage
+            .iter()
+            .filter_map(|name| by_name.remove(name))
+            .collect();
+        if fail_fast && results.iter().any(|result| !result.success) {
+            results.extend(stage.iter().map(|branch| cancel_branch(&cwd, &branch.name)));
+            continue;
+        }
 
-    results
+        let (ready, blocked): (Vec<_>, Vec<_>) = stage
+            .into_iter()
+            .partition(|branch| failed_dependency(branch, &results).is_none());
+        for branch in &blocked {
+            let dep = failed_dependency(branch, &results).unwrap_or_default();
+            warn!("Skipping '{}': dependency '{}' failed", branch.name, dep);
+            results.push(BranchResult::failure(
+                &branch.name,
+                0,
+                format!("Dependency '{dep}' failed"),
+            ));
+        }
+
+        let names: Vec<String> = ready.iter().map(|branch| branch.name.clone()).collect();
+        let wave_results = if sequential {
+            execute_sequential(
+                &cwd,
+                ready,
+                config,
+                max_iterations,
+                no_sandbox,
+                provider_override,
+                gh_available,
+                fail_fast,
+            )
+            .await?
+        } else {
+            execute_parallel(
+                &cwd,
+                ready,
+                config,
+                max_iterations,
+                no_sandbox,
+                provider_override,
+                gh_available,
+                fail_fast,
+            )
+            .await?
+        };
+
+        // Branches fail-fast stopped before they finished
+        let cancelled: Vec<_> = names
+            .iter()
+            .filter(|name| fail_fast && !wave_results.iter().any(|r| &r.branch == *name))
+            .map(|name| cancel_branch(&cwd, name))
+            .collect();
+        results.extend(wave_results);
+        results.extend(cancelled);
+    }
+
+    Ok(results)
+}
+
+/// Returns the first dependency of `branch` that has a failed result.
+fn failed_dependency<'a>(branch: &'a BranchSection, results: &[BranchResult]) -> Option<&'a str> {
+    branch
+        .depends_on
+        .iter()
+        .find(|dep| {
+            results
+                .iter()
+                .any(|result| &result.branch == *dep && !result.success)
+        })
+        .map(String::as_str)
+}
+
+/// Records a branch stopped by `--fail-fast` as failed and marks its
+/// worktree's loop inactive, so an aborted build does not look like it is
+/// still running.
+fn cancel_branch(project_dir: &Path, branch: &str) -> BranchResult {
+    let wt_path = worktree_path(project_dir, branch);
+    if let Ok(Some(mut state)) = RalphState::load(&wt_path) {
+        if state.active {
+            state.active = false;
+            if let Err(e) = state.save(&wt_path) {
+                warn!("Failed to mark '{}' inactive: {}", branch, e);
+            }
+        }
+    }
+    warn!(
+        "Cancelled '{}': another branch failed (--fail-fast)",
+        branch
+    );
+    BranchResult::failure(branch, 0, FAIL_FAST_CANCELLED.to_string())
 }
 
-/// Execute branch builds sequentially.
+/// Error recorded for branches cancelled by `--fail-fast`.
+const FAIL_FAST_CANCELLED: &str = "Cancelled after another branch failed (--fail-fast)";
+
+/// Execute branch builds sequentially. With `fail_fast`, stops after the
+/// first failed branch.
+#[allow(clippy::too_many_arguments)] // CLI options passed through
 async fn execute_sequential(
     project_dir: &Path,
     branches: Vec<BranchSection>,
     config: &Config,
-    max_iterations: Option<u32>,
+    max_iterations: IterationCap,
     no_sandbox: bool,
     provider_override: Option<&str>,
     gh_available: bool,
+    fail_fast: bool,
 ) -> Result<Vec<BranchResult>> {
-    let mut results = Vec::with_capacity(branches.len());
+    let stop = |result: &BranchResult| fail_fast && !result.success;
+    // Sequential builds stop between branches, so this is never raised
+    let (_cancel, cancelled) = tokio::sync::watch::channel(false);
+    let results = run_in_order(branches, stop, |branch| {
+        let cancelled = cancelled.clone();
+        async move {
+            info!("Building branch '{}' sequentially...", branch.name);
+            build_single_branch(
+                project_dir,
+                &branch,
+                config,
+                max_iterations,
+                no_sandbox,
+                provider_override,
+                gh_available,
+                cancelled,
+            )
+            .await
+        }
+    })
+    .await;
 
-    for branch in branches {
-        info!("Building branch '{}' sequentially...", branch.name);
-        let result = build_single_branch(
-            project_dir,
-            &branch,
-            config,
-            max_iterations,
-            no_sandbox,
-            provider_override,
-            gh_available,
-        )
-        .await;
+    Ok(results)
+}
+
+/// Runs `build` for each item in turn until `stop` is true for a result;
+/// later items are never started.
+async fn run_in_order<T, R, F, Fut>(items: Vec<T>, stop: impl Fn(&R) -> bool, build: F) -> Vec<R>
+where
+    F: Fn(T) -> Fut,
+    Fut: std::future::Future<Output = R>,
+{
+    let mut results = Vec::with_capacity(items.len());
+    for item in items {
+        let result = build(item).await;
+        let done = stop(&result);
         results.push(result);
+        if done {
+            break;
+        }
     }
-
-    Ok(results)
+    results
 }
 
-/// Execute branch builds in parallel using tokio `JoinSet`.
-#[allow(tail_expr_drop_order)]
+/// Execute branch builds in parallel, at most `git.max_parallel_branches` at
+/// once. With `fail_fast`, the first failed branch cancels the others.
+#[allow(clippy::too_many_arguments)] // CLI options passed through
 async fn execute_parallel(
     project_dir: &Path,
     branches: Vec<BranchSection>,
     config: &Config,
-    max_iterations: Option<u32>,
+    max_iterations: IterationCap,
     no_sandbox: bool,
     provider_override: Option<&str>,
     gh_available: bool,
+    fail_fast: bool,
 ) -> Result<Vec<BranchResult>> {
+    let stop = |result: &BranchResult| fail_fast && !result.success;
+    let limit = config.git.max_parallel_branches;
+    let results = run_bounded(
+        branches,
+        limit,
+        BRANCH_CANCEL_GRACE,
+        stop,
+        |branch, cancelled| {
+            let project_dir = project_dir.to_path_buf();
+            let config = config.clone();
+            let provider_override = provider_override.map(String::from);
+
+            async move {
+                info!("Building branch '{}' in parallel...", branch.name);
+                build_single_branch(
+                    &project_dir,
+                    &branch,
+                    &config,
+                    max_iterations,
+                    no_sandbox,
+                    provider_override.as_deref(),
+                    gh_available,
+                    cancelled,
+                )
+                .await
+            }
+        },
+    )
+    .await;
+
+    Ok(results)
+}
+
+/// How long fail-fast waits for cancelled branches to finish their current
+/// iteration before aborting them.
+const BRANCH_CANCEL_GRACE: std::time::Duration = std::time::Duration::from_mins(5);
+
+/// Runs `build` for each item on a tokio `JoinSet`, with at most `limit`
+/// running at once (a limit of 0 is treated as 1).
+///
+/// Each build gets a cancel signal. Results are collected in completion
+/// order; panicked tasks are logged and skipped. The first result for which
+/// `stop` is true raises the signal: queued items never start, and running
+/// builds are expected to stop at their next check, clean up, and return a
+/// result. Builds still running `grace` later are aborted and yield none.
+#[allow(tail_expr_drop_order)]
+async fn run_bounded<T, R, F, Fut>(
+    items: Vec<T>,
+    limit: usize,
+    grace: std::time::Duration,
+    stop: impl Fn(&R) -> bool,
+    build: F,
+) -> Vec<R>
+where
+    R: Send + 'static,
+    F: Fn(T, ShutdownSignal) -> Fut,
+    Fut: std::future::Future<Output = R> + Send + 'static,
+{
+    use std::sync::Arc;
+    use tokio::sync::{watch, Semaphore};
     use tokio::task::JoinSet;
 
+    let semaphore = Arc::new(Semaphore::new(limit.max(1)));
+    let (cancel, cancelled) = watch::channel(false);
     let mut join_set = JoinSet::new();
 
-    for branch in branches {
-        let project_dir = project_dir.to_path_buf();
-        let config = config.clone();
-        let provider_override = provider_override.map(String::from);
+    for item in items {
+        let semaphore = Arc::clone(&semaphore);
+        let mut cancelled = cancelled.clone();
+        let task = build(item, cancelled.clone());
 
         join_set.spawn(async move {
-            info!("Building branch '{}' in parallel...", branch.name);
-            build_single_branch(
-                &project_dir,
-                &branch,
-                &config,
-                max_iterations,
-                no_sandbox,
-                provider_override.as_deref(),
-                gh_available,
-            )
-            .await
+            // The semaphore is never closed, so acquiring cannot fail
+            let permit = tokio::select! {
+                biased;
+                () = signalled(&mut cancelled) => return None,
+                permit = semaphore.acquire_owned() => permit.ok(),
+            };
+            // The permit comes back with the result, so the next item can
+            // only start once `stop` has seen it
+            Some((task.await, permit))
         });
     }
 
     let mut results = Vec::new();
-    while let Some(result) = join_set.join_next().await {
+    let mut deadline = None;
+    loop {
+        let next = match deadline {
+            Some(at) => {
+                let joined = tokio::time::timeout_at(at, join_set.join_next()).await;
+                let Ok(next) = joined else {
+                    warn!(
+                        "Aborting {} branch(es) still running {}s after cancellation",
+                        join_set.len(),
+                        grace.as_secs()
+                    );
+                    join_set.abort_all();
+                    deadline = None;
+                    continue;
+                };
+                next
+            }
+            None => join_set.join_next().await,
+        };
+        let Some(result) = next else {
+            break;
+        };
         match result {
-            Ok(branch_result) => results.push(branch_result),
+            Ok(Some((value, _permit))) => {
+                if !*cancel.borrow() && stop(&value) {
+                    cancel.send_replace(true);
+                    deadline = Some(tokio::time::Instant::now() + grace);
+                }
+                results.push(value);
+            }
+            Ok(None) => {}
+            Err(e) if e.is_cancelled() => {}
             Err(e) => {
                 warn!("Branch task panicked: {}", e);
             }
         }
     }
 
-    Ok(results)
+    results
 }
 
-/// Build a single branch in its worktree.
+/// Resolves a branch's iteration cap and provider: `--max-iterations` (or
+/// `--unlimited`) and `--provider` win, then the branch's metadata (which
+/// already includes the plan defaults), then the build-mode default cap.
+fn branch_overrides<'a>(
+    branch: &'a BranchSection,
+    max_iterations: IterationCap,
+    provider_override: Option<&'a str>,
+) -> (Option<u32>, Option<&'a str>) {
+    (
+        max_iterations.resolve(LoopMode::Build, branch.max_iterations),
+        provider_override.or(branch.provider.as_deref()),
+    )
+}
+
+/// Build a single branch in its worktree, stopping early once `cancelled`
+/// is raised.
+#[allow(clippy::too_many_arguments)] // CLI options passed through
 async fn build_single_branch(
     project_dir: &Path,
     branch: &BranchSection,
     config: &Config,
-    max_iterations: Option<u32>,
+    max_iterations: IterationCap,
     no_sandbox: bool,
     provider_override: Option<&str>,
     gh_available: bool,
+    cancelled: ShutdownSignal,
 ) -> BranchResult {
     let wt_path = worktree_path(project_dir, &branch.name);
 
+    let (max_iterations, provider_override) =
+        branch_overrides(branch, max_iterations, provider_override);
+
     // Run the loop in the worktree directory
     match run_branch_loop(
         &wt_path,
@@ -539,6 +694,7 @@ async fn build_single_branch(
         max_iterations,
         no_sandbox,
         provider_override,
+        cancelled,
     )
     .await
     {
@@ -549,10 +705,17 @@ async fn build_single_branch(
                     &wt_path,
                     &branch.name,
                     &config.git.pr_base,
-                    &format!("{}: {}", branch.name, branch.goal),
-                    &format!(
-                        "## Summary\n\n{}\n\n## Branch\n\n`{}`\n\n---\n\n🤖 Generated by Ralph",
-                        branch.goal, branch.name
+                    &render_pr_template(
+                        &config.git.pr_title_template,
+                        &branch.name,
+                        &branch.goal,
+                        iterations,
+                    ),
+                    &render_pr_template(
+                        &config.git.pr_body_template,
+                        &branch.name,
+                        &branch.goal,
+                        iterations,
                     ),
                 )
                 .await
@@ -574,6 +737,9 @@ async fn build_single_branch(
 }
 
 /// Run the loop for a single branch (simplified version of the main loop).
+///
+/// `cancelled` (`--fail-fast`) is checked between iterations, so the
+/// iteration in flight finishes and removes its container first.
 #[allow(clippy::too_many_lines, tail_expr_drop_order)]
 async fn run_branch_loop(
     wt_path: &Path,
@@ -581,6 +747,7 @@ async fn run_branch_loop(
     max_iterations: Option<u32>,
     no_sandbox: bool,
     provider_override: Option<&str>,
+    mut cancelled: ShutdownSignal,
 ) -> Result<u32> {
     use crate::detection::{get_commit_hash, CompletionDetector};
 
@@ -593,41 +760,52 @@ async fn run_branch_loop(
         );
     }
 
+    let agent_dir = resolve_agent_dir(wt_path, &config.agent)?;
+
     // Load or create state for this worktree
     let mut state = RalphState::load_or_create(wt_path, Mode::Build)?;
     state.max_iterations = max_iterations;
     state.active = true;
+    if state.initial_commit.is_none() {
+        state.initial_commit = get_commit_hash(wt_path).await;
+    }
     state.save(wt_path)?;
 
     // Get agent provider
     let provider = resolve_provider(config, provider_override)?;
 
     // Create agent
-    let agent: Box<dyn AgentProvider> = match provider {
-        Provider::Cursor => Box::new(CursorProvider::new(config.agent.cursor.clone())),
-        Provider::Claude => Box::new(ClaudeProvider::new(config.agent.claude.clone())),
-    };
+    let agent = create_agent(config, provider);
 
     // Create sandbox if enabled
-    let sandbox: Option<Box<dyn Sandbox>> = if !no_sandbox && config.sandbox.enabled {
-        Some(Box::new(DockerSandbox::new(
-            config.clone(),
-            provider,
-            config.agent.clone(),
-        )))
-    } else {
-        None
-    };
+    let sandbox_enabled = !no_sandbox && config.sandbox.enabled;
+    check_provider_sandbox(provider, sandbox_enabled)?;
+    let sandbox = create_sandbox(config, provider, sandbox_enabled);
 
     // Initialize completion detector
     let mut detector = CompletionDetector::from_state(
-        config.completion.idle_threshold,
+        config.completion.idle_threshold_for(state.mode),
         state.last_commit.clone(),
         state.idle_iterations,
-    );
+    )
+    .with_commit_count(state.commit_count)
+    .with_promise(
+        config.completion.promise.as_deref(),
+        &config.completion.promise_format,
+    )
+    .with_strategy(config.completion.strategy.clone())
+    .with_min_iterations_before_idle(config.completion.min_iterations_before_idle);
+    let mut empty_output_retries = 0;
 
     // Main loop for this branch
     loop {
+        // Another branch failed under --fail-fast
+        if is_signalled(&cancelled) {
+            state.active = false;
+            state.save(wt_path)?;
+            bail!(FAIL_FAST_CANCELLED);
+        }
+
         // Check for cancellation
         if let Some(loaded) = RalphState::load(wt_path)? {
             if !loaded.active {
@@ -649,88 +827,157 @@ async fn run_branch_loop(
 
         // Record commit hash at start
         let start_commit = get_commit_hash(wt_path).await;
-        detector.record_commit(start_commit);
-
-        // Read prompt
-        let mut prompt = std::fs::read_to_string(&prompt_file)
-            .with_context(|| format!("Failed to read prompt file: {}", prompt_file.display()))?;
-
-        // Append validation errors if present
-        if let Some(ref last_error) = state.last_error {
-            if last_error.starts_with("Validation error:") {
-                let error_details = last_error
-                    .strip_prefix("Validation error:")
-                    .unwrap_or(last_error);
-                prompt.push_str("\n\n## ⚠️ VALIDATION ERROR FROM PREVIOUS ITERATION\n");
-                prompt.push_str("The following validation error occurred. Please fix it:\n\n```\n");
-                prompt.push_str(error_details.trim());
-                prompt.push_str("\n```\n\nFix the issues above and ensure validation passes.\n");
+        detector.record_commit(start_commit.clone());
+        detector.record_commit_count(
+            idle_commit_count(wt_path, &config.completion, state.initial_commit.as_deref()).await,
+        );
+
+        // Run pre-iteration hook (failure aborts this iteration)
+        if let Some(ref hook) = config.hooks.pre_iteration {
+            if let Err(hook_error) = run_hook(wt_path, hook).await {
+                record_failed_iteration(
+                    &mut state,
+                    wt_path,
+                    ErrorKind::Hook,
+                    format!("Pre-iteration hook error:{hook_error}"),
+                )?;
+
+                check_branch_circuit_breaker(
+                    &mut state,
+                    wt_path,
+                    &config.monitoring,
+                    "consecutive errors",
+                    &mut cancelled,
+                )
+                .await?;
+                continue;
             }
         }
 
+        // Read prompt, with any validation error from the previous iteration
+        let prompt = read_prompt(&prompt_file, &state, config, &[])?;
+
         // Run agent
         let output_result = if let Some(ref sb) = sandbox {
-            sb.run(wt_path, &prompt, None).await
+            sb.run(
+                wt_path,
+                &prompt,
+                None,
+                state.iteration,
+                state.session_id.as_deref(),
+            )
+            .await
         } else {
             let timeout_mins = resolve_timeout(config, provider);
             let timeout_duration = std::time::Duration::from_secs(u64::from(timeout_mins) * 60);
-            tokio::time::timeout(timeout_duration, agent.invoke(wt_path, &prompt))
-                .await
-                .unwrap_or_else(|_| {
-                    Err(anyhow::anyhow!(
-                        "Agent execution timed out after {timeout_mins} minutes"
-                    ))
-                })
+            tokio::time::timeout(
+                timeout_duration,
+                agent.invoke(
+                    &agent_dir,
+                    &prompt,
+                    state.iteration,
+                    state.session_id.as_deref(),
+                ),
+            )
+            .await
+            .unwrap_or_else(|_| {
+                Err(anyhow::anyhow!(
+                    "Agent execution timed out after {timeout_mins} minutes"
+                ))
+            })
         };
 
         // Handle result
-        match output_result {
-            Ok(_) => {
+        let output = match output_result {
+            Ok(output) => {
+                record_agent_output(&mut state, agent.as_ref(), &output);
                 state.consecutive_errors = 0;
+                output
             }
             Err(e) => {
-                let error_msg = e.to_string();
-                let is_recoverable = error_msg.contains("timed out")
-                    || error_msg.contains("rate limit")
-                    || error_msg.contains("resource_exhausted");
-
-                if is_recoverable {
-                    state.error_count += 1;
-                    state.consecutive_errors += 1;
-                    state.last_error = Some(error_msg);
-                    state.iteration += 1;
-                    state.save(wt_path)?;
-
-                    if config.monitoring.max_consecutive_errors > 0
-                        && state.consecutive_errors >= config.monitoring.max_consecutive_errors
-                    {
-                        state.active = false;
-                        state.save(wt_path)?;
-                        bail!("Circuit breaker triggered");
+                if let Some(recoverable) = RecoverableError::classify(&e) {
+                    if recoverable == RecoverableError::Crash {
+                        // A crashed run may be a session that can no longer be resumed
+                        state.session_id = None;
                     }
+                    record_failed_iteration(&mut state, wt_path, ErrorKind::Agent, e.to_string())?;
+
+                    check_branch_circuit_breaker(
+                        &mut state,
+                        wt_path,
+                        &config.monitoring,
+                        "consecutive errors",
+                        &mut cancelled,
+                    )
+                    .await?;
                     continue;
                 }
+                state.save(wt_path)?;
                 return Err(e);
             }
+        };
+
+        // Empty output with no changes: retry instead of counting it as idle
+        if retry_empty_output(
+            wt_path,
+            &output,
+            start_commit.as_deref(),
+            &mut empty_output_retries,
+            config.agent.max_empty_retries,
+        )
+        .await
+        {
+            warn!("Agent returned empty output; retrying iteration");
+            record_failed_iteration(
+                &mut state,
+                wt_path,
+                ErrorKind::Agent,
+                EMPTY_OUTPUT_ERROR.to_string(),
+            )?;
+
+            check_branch_circuit_breaker(
+                &mut state,
+                wt_path,
+                &config.monitoring,
+                "consecutive errors",
+                &mut cancelled,
+            )
+            .await?;
+            continue;
         }
 
         // Validate if enabled
         if config.validation.enabled {
-            if let Err(validation_error) = validate_code(wt_path, &config.validation.command).await
+            if let Err(validation_error) = validate_code(
+                wt_path,
+                &config.validation.command,
+                config.validation.timeout(),
+            )
+            .await
             {
-                state.error_count += 1;
-                state.consecutive_errors += 1;
-                state.last_error = Some(format!("Validation error:{validation_error}"));
-                state.iteration += 1;
-                state.save(wt_path)?;
-
-                if config.monitoring.max_consecutive_errors > 0
-                    && state.consecutive_errors >= config.monitoring.max_consecutive_errors
-                {
-                    state.active = false;
-                    state.save(wt_path)?;
-                    bail!("Circuit breaker triggered");
-                }
+                record_failed_iteration(
+                    &mut state,
+                    wt_path,
+                    ErrorKind::Validation,
+                    format!("Validation error:{validation_error}"),
+                )?;
+
+                rollback_iteration(
+                    wt_path,
+                    config.validation.rollback_on_failure,
+                    start_commit.as_deref(),
+                    state.initial_commit.as_deref(),
+                )
+                .await;
+
+                check_branch_circuit_breaker(
+                    &mut state,
+                    wt_path,
+                    &config.monitoring,
+                    "consecutive validation errors",
+                    &mut cancelled,
+                )
+                .await?;
                 continue;
             }
             // Clear validation error on success
@@ -746,14 +993,38 @@ async fn run_branch_loop(
         state.last_iteration_at = Some(chrono::Utc::now());
         state.save(wt_path)?;
 
-        // Check completion (idle detection)
+        // Commit leftover agent changes so idle detection sees the work
+        if config.git.auto_commit {
+            commit_iteration_changes(wt_path, config, state.iteration, state.mode).await;
+        }
+
+        // Check completion (idle detection on a clean tree)
         let current_commit = get_commit_hash(wt_path).await;
-        let is_complete = detector.check_completion(current_commit.as_deref());
+        let is_dirty = is_worktree_dirty(wt_path).await;
+        let idle = check_idle(
+            &mut detector,
+            wt_path,
+            &config.completion,
+            state.initial_commit.as_deref(),
+            current_commit.as_deref(),
+            is_dirty,
+        )
+        .await;
+        let completion = check_completion(
+            &detector,
+            state.iteration,
+            wt_path,
+            config.completion.success_command.as_deref(),
+            idle,
+            &output,
+        )
+        .await;
 
         state.last_commit = detector.last_commit().map(String::from);
         state.idle_iterations = detector.idle_count();
+        state.commit_count = detector.commit_count();
 
-        if is_complete {
+        if completion == CompletionCheck::Detected {
             state.active = false;
             state.save(wt_path)?;
             break;
@@ -761,11 +1032,36 @@ async fn run_branch_loop(
 
         // Git push if enabled
         if config.git.auto_push {
-            if let Err(e) = git_push(wt_path, &config.git.protected_branches).await {
+            if let Err(e) = git_push(
+                wt_path,
+                &config.git.protected_branches,
+                config.git.push_retries,
+            )
+            .await
+            {
                 warn!("Git push failed in worktree: {}", e);
             }
         }
 
+        // Run post-iteration hook (failures only warn)
+        if let Some(ref hook) = config.hooks.post_iteration {
+            if let Err(hook_error) = run_hook(wt_path, hook).await {
+                warn!("Post-iteration hook failed in worktree: {}", hook_error);
+            }
+        }
+
+        // Success command passed: stop after pushing this iteration's work
+        if completion == CompletionCheck::SuccessCommand {
+            state.active = false;
+            state.save(wt_path)?;
+            break;
+        }
+
+        // Pause before the next iteration (`agent.iteration_delay_seconds`)
+        if let Some(delay) = iteration_delay(config.agent.iteration_delay_seconds, &state, None) {
+            sleep_or_signal(delay, &mut cancelled).await;
+        }
+
         state.iteration += 1;
         state.save(wt_path)?;
     }
@@ -773,6 +1069,21 @@ async fn run_branch_loop(
     Ok(state.iteration)
 }
 
+/// Combines branch results into one outcome; any failed branch is an error.
+fn branch_outcome(results: &[BranchResult]) -> LoopOutcome {
+    let failed_count = results.iter().filter(|r| !r.success).count();
+    let termination_reason = if failed_count > 0 {
+        TerminationReason::Error(format!("{failed_count} branch(es) failed"))
+    } else {
+        TerminationReason::CompletionDetected
+    };
+    LoopOutcome {
+        final_iteration: results.iter().map(|r| r.iterations).sum(),
+        termination_reason,
+        error_count: u32::try_from(failed_count).unwrap_or(u32::MAX),
+    }
+}
+
 /// Format a summary of branch build results.
 fn format_branch_summary(results: &[BranchResult]) -> String {
     use std::fmt::Write;
@@ -839,30 +1150,84 @@ fn format_branch_summary(results: &[BranchResult]) -> String {
 // -----------------------------------------------------------------------------
 
 /// Runs the main Ralph loop with the specified configuration.
+///
+/// With `dry_run`, prints the prompt the next iteration would send and exits
+/// without invoking the agent, creating containers, or saving state. With
+/// `interactive` on a terminal, asks after each iteration whether to
+/// continue, stop, or revert it. With `simulate`, runs the canned mock agent
+/// on the host with everything that reaches outside the project turned off.
 #[allow(tail_expr_drop_order, clippy::too_many_lines)] // Drop order doesn't matter for async operations
 pub(crate) async fn run(
-    mode: LoopMode,
-    max_iterations: Option<u32>,
-    no_sandbox: bool,
-    custom_prompt: Option<String>,
-    provider_override: Option<String>,
-    sequential: bool,
-) -> Result<()> {
+    options: LoopOptions,
+    events: EventFormat,
+    config_file: Option<&Path>,
+) -> Result<LoopOutcome> {
+    let LoopOptions {
+        mode,
+        max_iterations,
+        settings,
+        dry_run,
+        interactive,
+        simulate,
+        output,
+        keep_container,
+        resume,
+    } = options;
+    let no_sandbox = settings.no_sandbox;
+    let custom_prompt = settings.prompt.as_deref();
+    let provider_override = settings.provider.as_deref();
     let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    let sink = events.sink();
+    // Image build and pull output must not interleave with JSON events
+    let image_progress = match events {
+        EventFormat::Human => Progress::Stdout,
+        EventFormat::Json => Progress::Log,
+    };
+
+    // Load configuration; CLI overrides apply to branch builds too
+    let mut config = Config::load_from(&cwd, config_file).context("Failed to load ralph.toml")?;
+    apply_max_errors_override(&mut config.monitoring, settings.max_errors);
+    apply_validation_override(
+        &mut config.validation,
+        settings.no_validation,
+        settings.validation_cmd.clone(),
+    );
+    if settings.tail_errors.is_some() {
+        config.validation.error_tail_lines = settings.tail_errors;
+    }
+    if settings.workdir.is_some() {
+        config.agent.working_dir.clone_from(&settings.workdir);
+    }
+    if simulate {
+        apply_simulate_overrides(&mut config);
+    }
+    let agent_dir = resolve_agent_dir(&cwd, &config.agent)?;
 
-    // Load configuration
-    let config = Config::load(&cwd).context("Failed to load ralph.toml")?;
+    // Refuse to run alongside another loop; held until run() returns
+    let _loop_lock = if dry_run {
+        None
+    } else {
+        Some(acquire_loop_lock(&cwd)?)
+    };
 
     // Check for branch build mode: build mode + IMPLEMENTATION_PLAN.md with branches
-    if mode == LoopMode::Build && custom_prompt.is_none() {
+    // (skipped in dry-run and simulate mode, which never create worktrees, and
+    // when resuming, which continues the single loop in the saved state)
+    if mode == LoopMode::Build
+        && custom_prompt.is_none()
+        && !dry_run
+        && !simulate
+        && resume.is_none()
+    {
         let plan_path = cwd.join("IMPLEMENTATION_PLAN.md");
         if plan_path.exists() {
             let plan_content = std::fs::read_to_string(&plan_path)
                 .context("Failed to read IMPLEMENTATION_PLAN.md")?;
-            let branches = parse_implementation_plan(&plan_content);
+            let plan = parse_implementation_plan(&plan_content);
 
             // Filter to only incomplete branches (those with unchecked tasks)
-            let incomplete_branches: Vec<_> = branches
+            let incomplete_branches: Vec<_> = plan
+                .branches
                 .into_iter()
                 .filter(|b| {
                     // Check if branch section has unchecked tasks
@@ -876,38 +1241,50 @@ pub(crate) async fn run(
                     "Found {} incomplete branches in IMPLEMENTATION_PLAN.md",
                     incomplete_branches.len()
                 );
-                let mode_str = if sequential { "sequential" } else { "parallel" };
-                println!(
-                    "Building {} branches in {} mode...\n",
-                    incomplete_branches.len(),
-                    mode_str
-                );
+                if interactive {
+                    warn!("--interactive is ignored in branch build mode");
+                }
+                if output.is_some() {
+                    warn!("--output is ignored in branch build mode");
+                }
+                if keep_container {
+                    warn!("--keep-container is ignored in branch build mode");
+                }
+                let mode_str = if settings.sequential {
+                    "sequential"
+                } else {
+                    "parallel"
+                };
+                sink.emit(&LoopEvent::BranchBuildStart {
+                    branches: incomplete_branches.len(),
+                    mode: mode_str,
+                });
+
+                if !no_sandbox && config.sandbox.enabled {
+                    ensure_image(&config, &cwd, image_progress).await?;
+                }
 
                 let results = execute_branch_builds(
                     incomplete_branches,
+                    &plan.defaults,
                     &config,
                     max_iterations,
                     no_sandbox,
-                    provider_override.as_deref(),
-                    sequential,
+                    provider_override,
+                    settings.sequential,
+                    settings.fail_fast,
                 )
                 .await?;
 
-                print!("{}", format_branch_summary(&results));
-
-                // Return error if any branch failed
-                let failed_count = results.iter().filter(|r| !r.success).count();
-                if failed_count > 0 {
-                    bail!("{failed_count} branch(es) failed");
-                }
+                sink.emit(&LoopEvent::BranchBuildFinished { results: &results });
 
-                return Ok(());
+                return Ok(branch_outcome(&results));
             }
         }
     }
 
     // Determine prompt file
-    let prompt_file = determine_prompt_file(&cwd, mode, custom_prompt.as_deref());
+    let prompt_file = determine_prompt_file(&cwd, mode, custom_prompt);
 
     if !prompt_file.exists() {
         bail!(
@@ -916,34 +1293,71 @@ pub(crate) async fn run(
         );
     }
 
-    // Load or create state
-    let state = RalphState::load_or_create(&cwd, mode.into())?;
-    let mut state = prepare_state(state, max_iterations);
+    // Load or create state, unless resuming one
+    let state = match resume {
+        Some(state) => state,
+        None => RalphState::load_or_create(&cwd, mode.into())?,
+    };
+    let mut state = prepare_state(state, max_iterations.resolve(mode, None));
+    state.settings = settings.clone();
+
+    // Dry run: show the next iteration's prompt, leave everything untouched
+    if dry_run {
+        let prompt = read_prompt(&prompt_file, &state, &config, &settings.prompt_append)?;
+        sink.emit(&LoopEvent::DryRun {
+            iteration: state.iteration,
+            prompt_file: &prompt_file,
+            prompt: &prompt,
+        });
+        return Ok(LoopOutcome::from_state(&state, TerminationReason::DryRun));
+    }
+
+    // Never let the agent commit straight to a protected branch (the
+    // simulated agent never commits)
+    if !simulate {
+        guard_protected_branch(
+            &cwd,
+            &config.git.protected_branches,
+            config.git.auto_branch,
+            sink.as_ref(),
+        )
+        .await?;
+    }
+
+    if state.initial_commit.is_none() {
+        state.initial_commit = get_commit_hash(&cwd).await;
+    }
     state.save(&cwd)?;
 
     // Get agent provider: CLI override takes precedence over config
-    let provider = resolve_provider(&config, provider_override.as_deref())?;
-
-    // Print startup banner
-    let banner = BannerInfo::new(&state, &prompt_file, no_sandbox, &config, provider);
-    print!("{}", format_banner(&banner));
+    let provider = resolve_provider(&config, provider_override)?;
+    let fallback_provider = config.agent.get_fallback_provider()?;
 
     // Create the agent provider (for non-sandbox mode)
-    let agent: Box<dyn AgentProvider> = match provider {
-        Provider::Cursor => Box::new(CursorProvider::new(config.agent.cursor.clone())),
-        Provider::Claude => Box::new(ClaudeProvider::new(config.agent.claude.clone())),
+    let agent: Box<dyn AgentProvider> = if simulate {
+        Box::new(MockAgentProvider::simulated())
+    } else {
+        create_agent(&config, provider)
     };
 
+    // Print startup banner
+    let mut banner = BannerInfo::new(&state, &prompt_file, no_sandbox, &config, provider);
+    if simulate {
+        banner.provider = agent.name().to_lowercase();
+    }
+    sink.emit(&LoopEvent::LoopStart(&banner));
+
     // Create sandbox if enabled
-    let sandbox: Option<Box<dyn Sandbox>> = if banner.sandbox_enabled {
-        Some(Box::new(DockerSandbox::new(
-            config.clone(),
-            provider,
-            config.agent.clone(),
-        )))
-    } else {
-        None
-    };
+    check_provider_sandbox(provider, banner.sandbox_enabled)?;
+    if let Some(fallback) = fallback_provider {
+        check_provider_sandbox(fallback, banner.sandbox_enabled)?;
+    }
+    let sandbox = create_sandbox(&config, provider, banner.sandbox_enabled);
+
+    // Build or pull the sandbox image if it is missing
+    if banner.sandbox_enabled {
+        ensure_image(&config, &cwd, image_progress).await?;
+    }
 
     // Clean up orphaned containers if sandbox is enabled
     if let Some(ref sb) = sandbox {
@@ -955,8 +1369,116 @@ pub(crate) async fn run(
         }
     }
 
+    if keep_container && !(banner.sandbox_enabled && config.sandbox.reuse_container) {
+        warn!(
+            "--keep-container needs the sandbox with sandbox.reuse_container = true; ignoring it"
+        );
+    }
+
+    // Stop cleanly on Ctrl+C / SIGTERM instead of leaving state active
+    let shutdown = spawn_signal_listener();
+
+    // Without a terminal nobody can answer the review prompt
+    let interactive = if interactive && !is_interactive_terminal() {
+        warn!("--interactive is ignored: stdin or stdout is not a terminal");
+        false
+    } else {
+        interactive
+    };
+
+    // Log loop start
+    tracing::info!(
+        event = "loop_start",
+        mode = ?state.mode,
+        provider = %banner.provider,
+        max_iterations = state.max_iterations,
+    );
+
+    let session = LoopSession {
+        config,
+        cwd,
+        agent_dir,
+        prompt_file,
+        prompt_append: settings.prompt_append,
+        provider,
+        fallback_provider,
+        agent,
+        sandbox,
+        sandbox_enabled: banner.sandbox_enabled,
+        keep_container,
+        interactive,
+        output,
+        shutdown,
+        sink,
+    };
+    drive_loop(session, state).await
+}
+
+// -----------------------------------------------------------------------------
+// Loop Driver
+// -----------------------------------------------------------------------------
+
+/// What the main loop runs with, set up by [`run`] (or a test).
+struct LoopSession {
+    /// Configuration with CLI overrides applied.
+    config: Config,
+    /// Project directory.
+    cwd: PathBuf,
+    /// Directory the agent runs in on the host.
+    agent_dir: PathBuf,
+    /// Prompt file read at the start of each iteration.
+    prompt_file: PathBuf,
+    /// Files appended to every prompt (`--prompt-append`).
+    prompt_append: Vec<PathBuf>,
+    /// Provider to start with.
+    provider: Provider,
+    /// Provider to switch to when rate limits persist.
+    fallback_provider: Option<Provider>,
+    /// Agent for runs on the host.
+    agent: Box<dyn AgentProvider>,
+    /// Sandbox for runs in containers.
+    sandbox: Option<Box<dyn Sandbox>>,
+    /// Whether the sandbox is enabled, kept on a provider switch.
+    sandbox_enabled: bool,
+    /// Keep the persistent container when the loop ends (`--keep-container`).
+    keep_container: bool,
+    /// Review each iteration at a terminal prompt (`--interactive`).
+    interactive: bool,
+    /// Extra file for the metrics summary (`--output`).
+    output: Option<PathBuf>,
+    /// Raised on SIGINT or SIGTERM.
+    shutdown: ShutdownSignal,
+    /// Where loop events go.
+    sink: Box<dyn EventSink>,
+}
+
+/// Runs iterations until the loop terminates, then cleans up after it.
+///
+/// `state` must already be marked active and saved.
+#[allow(tail_expr_drop_order, clippy::too_many_lines)] // Drop order doesn't matter for async operations
+async fn drive_loop(session: LoopSession, mut state: RalphState) -> Result<LoopOutcome> {
+    let LoopSession {
+        config,
+        cwd,
+        agent_dir,
+        prompt_file,
+        prompt_append,
+        mut provider,
+        fallback_provider,
+        mut agent,
+        mut sandbox,
+        sandbox_enabled,
+        keep_container,
+        interactive,
+        output,
+        mut shutdown,
+        sink,
+    } = session;
+    let mut switched_provider = false;
+
     // Create persistent container if reuse is enabled
-    let persistent_container_name = if banner.sandbox_enabled && config.sandbox.reuse_container {
+    let mut container_recreations = 0;
+    let mut persistent_container_name = if sandbox_enabled && config.sandbox.reuse_container {
         match sandbox.as_ref() {
             Some(sb) => match sb.create_persistent(&cwd).await {
                 Ok(name) if !name.is_empty() => {
@@ -981,24 +1503,49 @@ pub(crate) async fn run(
     // Initialize completion detector from persisted state for idle detection
     // continuity across restarts
     let mut detector = CompletionDetector::from_state(
-        config.completion.idle_threshold,
+        config.completion.idle_threshold_for(state.mode),
         state.last_commit.clone(),
         state.idle_iterations,
-    );
+    )
+    .with_commit_count(state.commit_count)
+    .with_promise(
+        config.completion.promise.as_deref(),
+        &config.completion.promise_format,
+    )
+    .with_strategy(config.completion.strategy.clone())
+    .with_min_iterations_before_idle(config.completion.min_iterations_before_idle);
 
     // Initialize notifier
-    let notifier = Notifier::new(config.monitoring.notifications.clone());
+    let notifier = Notifier::new(config.monitoring.notifications.clone())
+        .with_mode(format!("{:?}", state.mode));
 
-    // Log loop start
-    tracing::info!(
-        event = "loop_start",
-        mode = ?state.mode,
-        provider = %provider,
-        max_iterations = state.max_iterations,
-    );
+    // Track iteration starts for `[budget].max_iterations_per_hour`
+    let mut rate_limiter = IterationRateLimiter::new(config.budget.max_iterations_per_hour);
 
     // Main loop
-    loop {
+    let mut empty_output_retries = 0;
+    let termination_reason = loop {
+        // Check for SIGINT/SIGTERM (interrupts agent runs and sleeps below)
+        if is_signalled(&shutdown) {
+            warn!("Loop cancelled by signal");
+            stop_on_signal(&mut state, &cwd)?;
+
+            tracing::info!(
+                event = "loop_end",
+                total_iterations = state.iteration,
+                reason = "cancelled_by_signal",
+            );
+
+            let details = NotificationDetails::complete(
+                state.iteration,
+                state.iteration,
+                "cancelled_by_signal",
+            );
+            notifier.notify(NotificationEvent::Complete, &details).await;
+
+            break TerminationReason::Signalled;
+        }
+
         // Check for external cancellation (e.g., `ralph cancel`)
         if let Some(loaded) = RalphState::load(&cwd)? {
             if !loaded.active {
@@ -1016,16 +1563,15 @@ pub(crate) async fn run(
                     NotificationDetails::complete(state.iteration, state.iteration, "cancelled");
                 notifier.notify(NotificationEvent::Complete, &details).await;
 
-                break;
+                break TerminationReason::Cancelled;
             }
         }
 
         // Check max iterations
         if is_max_iterations_reached(&state) {
-            println!(
-                "{}",
-                format_max_iterations_reached(state.max_iterations.unwrap())
-            );
+            sink.emit(&LoopEvent::MaxIterationsReached {
+                max_iterations: state.max_iterations.unwrap(),
+            });
             state.active = false;
             state.save(&cwd)?;
 
@@ -1044,83 +1590,187 @@ pub(crate) async fn run(
             );
             notifier.notify(NotificationEvent::Complete, &details).await;
 
-            break;
+            break TerminationReason::MaxIterations;
         }
 
-        println!("{}", format_iteration_header(state.iteration));
+        // Check time budget
+        if is_time_budget_exceeded(&state, chrono::Utc::now(), config.budget.max_minutes) {
+            if let Some(max_minutes) = config.budget.max_minutes {
+                sink.emit(&LoopEvent::BudgetExceeded { max_minutes });
+            }
+            state.active = false;
+            state.save(&cwd)?;
+
+            tracing::info!(
+                event = "loop_end",
+                total_iterations = state.iteration,
+                reason = "budget_exceeded",
+            );
+
+            let details =
+                NotificationDetails::complete(state.iteration, state.iteration, "budget_exceeded");
+            notifier.notify(NotificationEvent::Complete, &details).await;
+
+            break TerminationReason::BudgetExceeded;
+        }
+
+        // Rate limit iteration starts, then re-check cancellation and limits
+        if let Some(wait) = rate_limiter.delay_before_start(chrono::Utc::now()) {
+            sink.emit(&LoopEvent::RateLimited {
+                wait_seconds: wait.as_secs(),
+            });
+            info!("Iteration rate limit reached, sleeping {:?}", wait);
+            sleep_or_signal(wait, &mut shutdown).await;
+            continue;
+        }
+        rate_limiter.record_start(chrono::Utc::now());
+
+        // Per-phase timings of this iteration, logged and shown in progress
+        let mut timings = PhaseTimings::default();
+
+        sink.emit(&LoopEvent::IterationStart {
+            iteration: state.iteration,
+        });
 
         // Log iteration start
         tracing::info!(event = "iteration_start", iteration = state.iteration,);
 
         // Record commit hash at start of iteration (for idle detection)
         let start_commit = get_commit_hash(&cwd).await;
-        detector.record_commit(start_commit);
+        detector.record_commit(start_commit.clone());
+        detector.record_commit_count(
+            idle_commit_count(&cwd, &config.completion, state.initial_commit.as_deref()).await,
+        );
 
-        // Read prompt
-        let mut prompt = std::fs::read_to_string(&prompt_file)
-            .with_context(|| format!("Failed to read prompt file: {}", prompt_file.display()))?;
+        // Run pre-iteration hook (failure aborts this iteration as a recoverable error)
+        if let Some(ref hook) = config.hooks.pre_iteration {
+            if let Err(hook_error) = run_hook(&cwd, hook).await {
+                warn!("Pre-iteration hook failed. Skipping agent for this iteration.");
+
+                record_failed_iteration(
+                    &mut state,
+                    &cwd,
+                    ErrorKind::Hook,
+                    format!("Pre-iteration hook error:{hook_error}"),
+                )?;
+
+                let hook_error_context = serde_json::json!({
+                    "iteration": state.iteration - 1,
+                    "hook": hook,
+                });
+                tracing::error!(
+                    event = "error",
+                    iteration = state.iteration - 1,
+                    error = %hook_error,
+                    ?hook_error_context,
+                );
 
-        // Append validation errors from previous iteration if present
-        if let Some(ref last_error) = state.last_error {
-            if last_error.starts_with("Validation error:") {
-                debug!("Appending validation error to prompt for agent visibility");
+                let error_details = NotificationDetails::error(
+                    Some(state.iteration - 1),
+                    &format!("Pre-iteration hook failed: {hook_error}"),
+                    Some(hook_error_context),
+                );
+                notifier
+                    .notify(NotificationEvent::Error, &error_details)
+                    .await;
 
-                let error_details = last_error
-                    .strip_prefix("Validation error:")
-                    .unwrap_or(last_error);
+                // Circuit breaker: stop if too many consecutive errors
+                if let Some(reason) = check_circuit_breaker(
+                    &mut state,
+                    &cwd,
+                    &config.monitoring,
+                    "consecutive errors",
+                    &notifier,
+                    &mut shutdown,
+                )
+                .await?
+                {
+                    break reason;
+                }
 
-                prompt.push_str("\n\n");
-                prompt.push_str("## ⚠️ VALIDATION ERROR FROM PREVIOUS ITERATION\n");
-                prompt.push_str("The following validation error occurred. Please fix it:\n\n");
-                prompt.push_str("```\n");
-                prompt.push_str(error_details.trim());
-                prompt.push_str("\n```\n");
-                prompt.push_str(
-                    "\nFix the issues above and ensure validation passes before proceeding.\n",
-                );
+                print_progress(
+                    &state,
+                    &cwd,
+                    &config,
+                    persistent_container_name.as_deref(),
+                    timings,
+                    sink.as_ref(),
+                )
+                .await;
+                continue;
             }
         }
 
+        // Read prompt, with any validation error from the previous iteration
+        let prompt = read_prompt(&prompt_file, &state, &config, &prompt_append)?;
+        if config.monitoring.save_iteration_output {
+            iteration_log::save_prompt(&cwd, state.iteration, &prompt);
+        }
+
         // Run agent (in sandbox if enabled, otherwise directly)
         info!(
             "Running {} agent iteration {}",
             agent.name(),
             state.iteration
         );
-        let output_result = if let Some(ref sb) = sandbox {
-            sb.run(&cwd, &prompt, persistent_container_name.as_deref())
+        let agent_run = timed("agent", state.iteration, async {
+            if let Some(ref sb) = sandbox {
+                run_in_sandbox(
+                    sb.as_ref(),
+                    &cwd,
+                    &prompt,
+                    &mut persistent_container_name,
+                    &mut container_recreations,
+                    state.iteration,
+                    state.session_id.as_deref(),
+                )
                 .await
-        } else {
-            // Non-sandbox mode: apply timeout (provider-specific > global)
-            let timeout_mins = resolve_timeout(&config, provider);
-            let timeout_duration = std::time::Duration::from_secs(u64::from(timeout_mins) * 60);
-            tokio::time::timeout(timeout_duration, agent.invoke(&cwd, &prompt))
+            } else {
+                // Non-sandbox mode: apply timeout (provider-specific > global)
+                let timeout_mins = resolve_timeout(&config, provider);
+                let timeout_duration = std::time::Duration::from_secs(u64::from(timeout_mins) * 60);
+                tokio::time::timeout(
+                    timeout_duration,
+                    agent.invoke(
+                        &agent_dir,
+                        &prompt,
+                        state.iteration,
+                        state.session_id.as_deref(),
+                    ),
+                )
                 .await
                 .unwrap_or_else(|_| {
                     Err(anyhow::anyhow!(
                         "Agent execution timed out after {timeout_mins} minutes"
                     ))
                 })
+            }
+        });
+        let output_result = tokio::select! {
+            result = agent_run => Some(result),
+            () = signalled(&mut shutdown) => None,
+        };
+        let Some((output_result, agent_ms)) = output_result else {
+            // Interrupted mid-iteration; the check at the top of the loop stops it
+            warn!(
+                "Signal received, interrupting iteration {}",
+                state.iteration
+            );
+            continue;
         };
 
+        timings.agent_ms = Some(agent_ms);
+
         // Handle agent execution result (including timeouts)
-        let _output = match output_result {
+        let output = match output_result {
             Ok(out) => out,
             Err(e) => {
                 let error_msg = e.to_string();
 
                 // Check if this is a recoverable error (timeout, rate limit, etc.)
-                // Use typed error checking for sandbox errors
-                let is_timeout = e
-                    .downcast_ref::<SandboxError>()
-                    .is_some_and(SandboxError::is_timeout)
-                    || error_msg.contains("timed out"); // Fallback for non-sandbox timeouts
-                let is_rate_limit = error_msg.contains("resource_exhausted")
-                    || error_msg.contains("rate limit")
-                    || error_msg.contains("Rate limit")
-                    || error_msg.contains("429")
-                    || error_msg.contains("quota")
-                    || error_msg.contains("Quota");
+                let recoverable = RecoverableError::classify(&e);
+                let is_timeout = recoverable == Some(RecoverableError::Timeout);
+                let is_rate_limit = recoverable == Some(RecoverableError::RateLimit);
 
                 // Log error
                 let error_context = serde_json::json!({
@@ -1128,6 +1778,9 @@ pub(crate) async fn run(
                     "provider": provider.to_string(),
                     "timeout": is_timeout,
                     "rate_limit": is_rate_limit,
+                    "exit_code": e
+                        .downcast_ref::<SandboxError>()
+                        .and_then(SandboxError::exit_code),
                 });
                 tracing::error!(
                     event = "error",
@@ -1146,14 +1799,8 @@ pub(crate) async fn run(
                     .notify(NotificationEvent::Error, &error_details)
                     .await;
 
-                // For recoverable errors (timeout, rate limit), continue to next iteration
-                if is_timeout || is_rate_limit {
-                    let error_type = if is_rate_limit {
-                        "rate limit"
-                    } else {
-                        "timeout"
-                    };
-
+                // For recoverable errors (timeout, rate limit, crash), continue to next iteration
+                if let Some(recoverable) = recoverable {
                     // Check if this is a consecutive rate limit error (likely hard cap)
                     let consecutive_rate_limits = if is_rate_limit {
                         // Count consecutive rate limit errors in recent iterations
@@ -1165,17 +1812,34 @@ pub(crate) async fn run(
                         false
                     };
 
-                    if is_rate_limit {
+                    let fallback = fallback_provider_to_switch_to(
+                        is_rate_limit,
+                        state.consecutive_errors + 1,
+                        provider,
+                        fallback_provider,
+                        switched_provider,
+                    );
+
+                    if let Some(next) = fallback {
+                        // Persistent rate limit: try the fallback instead of backing off
+                        warn!(
+                            event = "provider_switch",
+                            iteration = state.iteration,
+                            from = %provider,
+                            to = %next,
+                            "Rate limit persists on {}; switching to fallback provider {}",
+                            provider,
+                            next
+                        );
+                        provider = next;
+                        agent = create_agent(&config, provider);
+                        sandbox = create_sandbox(&config, provider, sandbox_enabled);
+                        switched_provider = true;
+                    } else if is_rate_limit {
                         if consecutive_rate_limits {
                             // Likely hit a hard cap (daily/hourly quota)
-                            // Use exponential backoff: 30s, 1m, 2m, 5m, 10m
-                            let backoff_seconds = match state.consecutive_errors {
-                                0..=1 => 30,
-                                2 => 60,
-                                3 => 120,
-                                4 => 300,
-                                _ => 600, // 10 minutes for 5+ consecutive errors
-                            };
+                            let backoff_seconds =
+                                backoff_seconds(state.consecutive_errors, &config.backoff);
 
                             warn!(
                                 "Rate limit error (likely daily/hourly quota). Waiting {} seconds before retry...",
@@ -1185,50 +1849,70 @@ pub(crate) async fn run(
                                 "If this persists, you may have hit a hard quota limit. Consider:\n\
                                  - Waiting several hours before retrying\n\
                                  - Switching to Claude provider: ralph loop build --provider claude\n\
+                                 - Setting [agent].fallback_provider in ralph.toml\n\
                                  - Reducing iteration frequency"
                             );
 
-                            tokio::time::sleep(std::time::Duration::from_secs(backoff_seconds))
-                                .await;
+                            sleep_or_signal(
+                                std::time::Duration::from_secs(backoff_seconds),
+                                &mut shutdown,
+                            )
+                            .await;
                         } else {
                             // First rate limit error - short delay
+                            let backoff_seconds = backoff_seconds(0, &config.backoff);
                             info!(
-                                "Waiting 30 seconds before retry to allow rate limit to reset..."
+                                "Waiting {} seconds before retry to allow rate limit to reset...",
+                                backoff_seconds
                             );
-                            tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+                            sleep_or_signal(
+                                std::time::Duration::from_secs(backoff_seconds),
+                                &mut shutdown,
+                            )
+                            .await;
                         }
                     }
 
-                    state.error_count += 1;
-                    state.consecutive_errors += 1;
-                    state.last_error = Some(format!("Agent {error_type}: {error_msg}"));
+                    state.record_error(
+                        ErrorKind::Agent,
+                        format!("Agent {}: {error_msg}", recoverable.label()),
+                    );
+                    if recoverable == RecoverableError::Crash {
+                        // A crashed run may be a session that can no longer be resumed
+                        state.session_id = None;
+                    }
+                    if fallback.is_some() {
+                        // The fallback provider starts with a clean slate
+                        state.consecutive_errors = 0;
+                    }
                     state.last_iteration_at = Some(chrono::Utc::now());
                     state.iteration += 1;
                     state.save(&cwd)?;
 
                     // Circuit breaker: stop if too many consecutive errors
-                    if config.monitoring.max_consecutive_errors > 0
-                        && state.consecutive_errors >= config.monitoring.max_consecutive_errors
+                    if let Some(reason) = check_circuit_breaker(
+                        &mut state,
+                        &cwd,
+                        &config.monitoring,
+                        "consecutive errors",
+                        &notifier,
+                        &mut shutdown,
+                    )
+                    .await?
                     {
-                        // Clean up persistent container before bailing
-                        if let (Some(container_name), Some(sb)) =
-                            (&persistent_container_name, &sandbox)
-                        {
-                            let _ = sb.remove_persistent(container_name).await;
-                        }
-                        bail!(
-                            "Circuit breaker triggered: {} consecutive errors (limit: {}). \
-                             Increase monitoring.max_consecutive_errors in ralph.toml to continue.",
-                            state.consecutive_errors,
-                            config.monitoring.max_consecutive_errors
-                        );
+                        break reason;
                     }
 
                     // Show progress if enabled
-                    if config.monitoring.show_progress {
-                        let progress = ProgressInfo::new(&state, &cwd).await;
-                        print!("{}", format_progress(&progress));
-                    }
+                    print_progress(
+                        &state,
+                        &cwd,
+                        &config,
+                        persistent_container_name.as_deref(),
+                        timings,
+                        sink.as_ref(),
+                    )
+                    .await;
 
                     // Continue to next iteration
                     continue;
@@ -1236,15 +1920,88 @@ pub(crate) async fn run(
 
                 // For other errors, fail the loop (but cleanup container first)
                 if let (Some(container_name), Some(sb)) = (&persistent_container_name, &sandbox) {
-                    let _ = sb.remove_persistent(container_name).await;
+                    release_persistent_container(
+                        sb.as_ref(),
+                        container_name,
+                        keep_container.then_some(config.sandbox.runtime),
+                        sink.as_ref(),
+                    )
+                    .await;
                 }
+                state.save(&cwd)?;
+                export_metrics(&cwd, &config, &state, "error", output.as_deref()).await;
                 return Err(e).context("Agent execution failed");
             }
         };
+        if config.monitoring.save_iteration_output {
+            iteration_log::save_output(&cwd, state.iteration, &output);
+        }
+
+        // Empty output with no changes is usually a CLI hiccup: retry it as a
+        // recoverable error rather than letting it count as an idle iteration
+        if retry_empty_output(
+            &cwd,
+            &output,
+            start_commit.as_deref(),
+            &mut empty_output_retries,
+            config.agent.max_empty_retries,
+        )
+        .await
+        {
+            warn!(
+                "Agent returned empty output; retrying ({}/{})",
+                empty_output_retries, config.agent.max_empty_retries
+            );
+            record_failed_iteration(
+                &mut state,
+                &cwd,
+                ErrorKind::Agent,
+                EMPTY_OUTPUT_ERROR.to_string(),
+            )?;
+
+            // Circuit breaker: stop if too many consecutive errors
+            if let Some(reason) = check_circuit_breaker(
+                &mut state,
+                &cwd,
+                &config.monitoring,
+                "consecutive errors",
+                &notifier,
+                &mut shutdown,
+            )
+            .await?
+            {
+                break reason;
+            }
+
+            print_progress(
+                &state,
+                &cwd,
+                &config,
+                persistent_container_name.as_deref(),
+                timings,
+                sink.as_ref(),
+            )
+            .await;
+            continue;
+        }
+
+        // Accumulate token usage (zero if unavailable) and the session to resume
+        record_agent_output(&mut state, agent.as_ref(), &output);
 
         // Validate code compiles before proceeding (if enabled)
         if config.validation.enabled {
-            match validate_code(&cwd, &config.validation.command).await {
+            let (validation, validation_ms) = timed(
+                "validation",
+                state.iteration,
+                validate_code(
+                    &cwd,
+                    &config.validation.command,
+                    config.validation.timeout(),
+                ),
+            )
+            .await;
+            timings.validation_ms = Some(validation_ms);
+            match validation {
                 Ok(()) => {
                     // Clear validation error if validation now passes (agent fixed it)
                     if let Some(ref last_error) = state.last_error {
@@ -1255,19 +2012,21 @@ pub(crate) async fn run(
                     }
                 }
                 Err(full_error) => {
-                    warn!("Code validation failed. Agent should fix this in next iteration.");
-
-                    // Truncate for logging/notifications (full error goes in state)
-                    let error_summary: String =
-                        full_error.lines().take(5).collect::<Vec<_>>().join("\n");
+                    // Excerpt for the terminal/notifications (full error goes in state)
+                    let error_summary =
+                        validation_error_excerpt(&full_error, config.validation.error_tail_lines);
+                    warn!(
+                        "Code validation failed. Agent should fix this in next iteration.\n{}",
+                        error_summary
+                    );
 
                     // Store full error in state for next iteration's prompt
-                    state.error_count += 1;
-                    state.consecutive_errors += 1;
-                    state.last_error = Some(format!("Validation error:{full_error}"));
-                    state.last_iteration_at = Some(chrono::Utc::now());
-                    state.iteration += 1;
-                    state.save(&cwd)?;
+                    record_failed_iteration(
+                        &mut state,
+                        &cwd,
+                        ErrorKind::Validation,
+                        format!("Validation error:{full_error}"),
+                    )?;
 
                     // Log validation error
                     let validation_error_context = serde_json::json!({
@@ -1291,29 +2050,45 @@ pub(crate) async fn run(
                         .notify(NotificationEvent::Error, &error_details)
                         .await;
 
-                    // Circuit breaker: stop if too many consecutive errors
-                    if config.monitoring.max_consecutive_errors > 0
-                        && state.consecutive_errors >= config.monitoring.max_consecutive_errors
+                    // Discard the broken iteration's commits (if enabled)
+                    if rollback_iteration(
+                        &cwd,
+                        config.validation.rollback_on_failure,
+                        start_commit.as_deref(),
+                        state.initial_commit.as_deref(),
+                    )
+                    .await
                     {
-                        // Clean up persistent container before bailing
-                        if let (Some(container_name), Some(sb)) =
-                            (&persistent_container_name, &sandbox)
-                        {
-                            let _ = sb.remove_persistent(container_name).await;
-                        }
-                        bail!(
-                            "Circuit breaker triggered: {} consecutive validation errors (limit: {}). \
-                             Increase monitoring.max_consecutive_errors in ralph.toml to continue.",
-                            state.consecutive_errors,
-                            config.monitoring.max_consecutive_errors
+                        warn!(
+                            "Rolled back to {} after validation failure.",
+                            start_commit.as_deref().unwrap_or_default()
                         );
                     }
 
-                    // Continue to next iteration (let agent fix it)
-                    if config.monitoring.show_progress {
-                        let progress = ProgressInfo::new(&state, &cwd).await;
-                        print!("{}", format_progress(&progress));
+                    // Circuit breaker: stop if too many consecutive errors
+                    if let Some(reason) = check_circuit_breaker(
+                        &mut state,
+                        &cwd,
+                        &config.monitoring,
+                        "consecutive validation errors",
+                        &notifier,
+                        &mut shutdown,
+                    )
+                    .await?
+                    {
+                        break reason;
                     }
+
+                    // Continue to next iteration (let agent fix it)
+                    print_progress(
+                        &state,
+                        &cwd,
+                        &config,
+                        persistent_container_name.as_deref(),
+                        timings,
+                        sink.as_ref(),
+                    )
+                    .await;
                     continue;
                 }
             }
@@ -1340,25 +2115,87 @@ pub(crate) async fn run(
                     NotificationDetails::complete(state.iteration, state.iteration, "cancelled");
                 notifier.notify(NotificationEvent::Complete, &details).await;
 
-                break;
+                break TerminationReason::Cancelled;
             }
         }
 
         state.save(&cwd)?;
 
+        // Commit leftover agent changes so idle detection sees the work
+        if config.git.auto_commit {
+            let ((), git_ms) = timed(
+                "git",
+                state.iteration,
+                commit_iteration_changes(&cwd, &config, state.iteration, state.mode),
+            )
+            .await;
+            timings.add_git(git_ms);
+        }
+
+        // Supervised runs: review the iteration before it is checked or pushed
+        let mut stop_requested = false;
+        if interactive {
+            match review_iteration(
+                &cwd,
+                state.iteration,
+                start_commit.as_deref(),
+                &mut shutdown,
+            )
+            .await
+            {
+                None => continue, // Signalled; handled at the top of the loop
+                Some(IterationAction::Continue) => {}
+                Some(IterationAction::Stop) => stop_requested = true,
+                Some(IterationAction::Revert) => {
+                    revert_iteration(
+                        &cwd,
+                        start_commit.as_deref(),
+                        state.initial_commit.as_deref(),
+                    )
+                    .await?;
+                    info!("Reverted iteration {}", state.iteration);
+                    state.iteration += 1;
+                    state.save(&cwd)?;
+                    continue;
+                }
+            }
+        }
+
         // Get commit hash after agent execution (may have created commits)
         let current_commit = get_commit_hash(&cwd).await;
+        let is_dirty = is_worktree_dirty(&cwd).await;
 
-        // Check for completion: validation passed + agent idle (no new commits)
-        // check_completion updates detector's internal state (last_commit, idle_count)
-        let is_complete = detector.check_completion(current_commit.as_deref());
+        // Check for completion: validation passed + agent idle (no new commits,
+        // clean tree). Failed validation never reaches this point, so tests pass.
+        // Updates detector's internal state (last_commit, idle_count)
+        let idle = check_idle(
+            &mut detector,
+            &cwd,
+            &config.completion,
+            state.initial_commit.as_deref(),
+            current_commit.as_deref(),
+            is_dirty,
+        )
+        .await;
+        let completion = check_completion(
+            &detector,
+            state.iteration,
+            &cwd,
+            config.completion.success_command.as_deref(),
+            idle,
+            &output,
+        )
+        .await;
 
         // Sync detector state to RalphState for persistence across restarts
         state.last_commit = detector.last_commit().map(String::from);
         state.idle_iterations = detector.idle_count();
+        state.commit_count = detector.commit_count();
 
-        if is_complete {
-            println!("{}", format_completion_detected(detector.idle_count()));
+        if completion == CompletionCheck::Detected {
+            sink.emit(&LoopEvent::CompletionDetected {
+                idle_iterations: detector.idle_count(),
+            });
             state.active = false;
             state.save(&cwd)?;
 
@@ -1375,19 +2212,32 @@ pub(crate) async fn run(
                 NotificationDetails::complete(state.iteration, state.iteration, "agent_idle");
             notifier.notify(NotificationEvent::Complete, &details).await;
 
-            break;
+            break TerminationReason::CompletionDetected;
         }
 
         let commit_hash = current_commit;
 
         // Git operations
         if config.git.auto_push {
-            if let Err(e) = git_push(&cwd, &config.git.protected_branches).await {
+            let (pushed, git_ms) = timed(
+                "git",
+                state.iteration,
+                git_push(
+                    &cwd,
+                    &config.git.protected_branches,
+                    config.git.push_retries,
+                ),
+            )
+            .await;
+            timings.add_git(git_ms);
+            if let Err(e) = pushed {
                 warn!("Git push failed: {e}");
                 state.error_count += 1;
                 // Note: Git push failures don't increment consecutive_errors because
                 // the iteration itself succeeded. The agent produced valid code.
-                state.last_error = Some(format!("Git push failed: {e}"));
+                let push_error = format!("Git push failed: {e}");
+                state.push_error_history(ErrorKind::Push, &push_error);
+                state.last_error = Some(push_error);
                 state.save(&cwd)?;
                 // Log git push error
                 let git_error_context = serde_json::json!({
@@ -1412,47 +2262,140 @@ pub(crate) async fn run(
             }
         }
 
+        // Run post-iteration hook (failures only warn)
+        if let Some(ref hook) = config.hooks.post_iteration {
+            if let Err(hook_error) = run_hook(&cwd, hook).await {
+                warn!("Post-iteration hook failed: {}", hook_error);
+            }
+        }
+
         // Log iteration complete
         tracing::info!(
             event = "iteration_complete",
             iteration = state.iteration,
             commit = ?commit_hash,
+            agent_ms = timings.agent_ms,
+            validation_ms = timings.validation_ms,
+            git_ms = timings.git_ms,
+        );
+
+        // Heartbeat notification (no-op unless `on_iteration` is configured)
+        let elapsed = chrono::Utc::now().signed_duration_since(state.started_at);
+        let details = NotificationDetails::iteration_complete(
+            state.iteration,
+            commit_hash.as_deref(),
+            elapsed.num_seconds(),
         );
+        notifier
+            .notify(NotificationEvent::IterationComplete, &details)
+            .await;
+
+        // Stop chosen at the review prompt: this iteration's work is kept and pushed
+        if stop_requested {
+            info!("Loop stopped at the interactive prompt");
+            state.active = false;
+            state.save(&cwd)?;
+
+            tracing::info!(
+                event = "loop_end",
+                total_iterations = state.iteration,
+                reason = "stopped_by_user",
+            );
+
+            let details =
+                NotificationDetails::complete(state.iteration, state.iteration, "stopped_by_user");
+            notifier.notify(NotificationEvent::Complete, &details).await;
+
+            break TerminationReason::Stopped;
+        }
+
+        // Success command passed: finish after pushing this iteration's work
+        if let (CompletionCheck::SuccessCommand, Some(command)) =
+            (completion, &config.completion.success_command)
+        {
+            sink.emit(&LoopEvent::SuccessCommandPassed { command });
+            state.active = false;
+            state.save(&cwd)?;
+
+            tracing::info!(
+                event = "loop_end",
+                total_iterations = state.iteration,
+                reason = "success_command",
+            );
+
+            let details =
+                NotificationDetails::complete(state.iteration, state.iteration, "success_command");
+            notifier.notify(NotificationEvent::Complete, &details).await;
+
+            break TerminationReason::SuccessCommand;
+        }
 
         // Show progress display between iterations (if enabled)
-        if config.monitoring.show_progress {
-            let progress = ProgressInfo::new(&state, &cwd).await;
-            print!("{}", format_progress(&progress));
+        print_progress(
+            &state,
+            &cwd,
+            &config,
+            persistent_container_name.as_deref(),
+            timings,
+            sink.as_ref(),
+        )
+        .await;
+
+        // Pause before the next iteration (`agent.iteration_delay_seconds`);
+        // a rate limiter wait at least as long replaces it
+        let limiter_wait = rate_limiter.delay_before_start(chrono::Utc::now());
+        if let Some(delay) =
+            iteration_delay(config.agent.iteration_delay_seconds, &state, limiter_wait)
+        {
+            info!("Sleeping {:?} before the next iteration", delay);
+            sleep_or_signal(delay, &mut shutdown).await;
         }
 
         // Increment iteration
         state.iteration += 1;
         state.save(&cwd)?;
-    }
+    };
 
-    // Log loop end if not already logged
+    let termination_reason =
+        final_validation_gate(&cwd, &config.validation, &mut state, termination_reason).await?;
+
+    // Log loop end if not already logged (circuit breaker)
     if state.active {
         tracing::info!(
             event = "loop_end",
             total_iterations = state.iteration,
-            reason = "max_iterations_reached",
+            reason = termination_reason.label(),
         );
     }
 
     // Clean up persistent container if it was created
     if let (Some(container_name), Some(sb)) = (persistent_container_name, &sandbox) {
-        info!("Cleaning up persistent container: {}", container_name);
-        if let Err(e) = sb.remove_persistent(&container_name).await {
-            warn!(
-                "Failed to remove persistent container {}: {}",
-                container_name, e
-            );
-        }
+        release_persistent_container(
+            sb.as_ref(),
+            &container_name,
+            keep_container.then_some(config.sandbox.runtime),
+            sink.as_ref(),
+        )
+        .await;
     }
 
-    print!("{}", format_loop_finished(state.iteration));
+    export_metrics(
+        &cwd,
+        &config,
+        &state,
+        termination_reason.label(),
+        output.as_deref(),
+    )
+    .await;
 
-    Ok(())
+    if !matches!(termination_reason, TerminationReason::Error(_)) {
+        sink.emit(&LoopEvent::LoopFinished {
+            iterations: state.iteration,
+            reason: termination_reason.label(),
+        });
+    }
+
+    Ok(LoopOutcome::from_state(&state, termination_reason))
 }
 
 // -----------------------------------------------------------------------------
@@ -1477,6 +2420,82 @@ impl From<LoopMode> for Mode {
     }
 }
 
+impl From<Mode> for LoopMode {
+    fn from(mode: Mode) -> Self {
+        match mode {
+            Mode::Plan => Self::Plan,
+            Mode::Build => Self::Build,
+        }
+    }
+}
+
+impl LoopMode {
+    /// Iteration cap when nothing else sets one: 10 for plan, 20 for build.
+    fn default_max_iterations(self) -> u32 {
+        match self {
+            Self::Plan => 10,
+            Self::Build => 20,
+        }
+    }
+}
+
+/// Iteration cap requested on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum IterationCap {
+    /// No flag: a branch or plan cap if there is one, else the mode default.
+    Default,
+    /// `--max-iterations <n>`.
+    Max(u32),
+    /// `--unlimited`.
+    Unlimited,
+}
+
+impl IterationCap {
+    /// Builds the cap from `--max-iterations` and `--unlimited` (which wins).
+    pub(crate) fn from_flags(max_iterations: Option<u32>, unlimited: bool) -> Self {
+        match (max_iterations, unlimited) {
+            (_, true) => Self::Unlimited,
+            (Some(max), false) => Self::Max(max),
+            (None, false) => Self::Default,
+        }
+    }
+
+    /// Resolves the cap: the flag, then `configured` (a branch's
+    /// `MaxIterations:` or the plan frontmatter), then the mode default.
+    fn resolve(self, mode: LoopMode, configured: Option<u32>) -> Option<u32> {
+        match self {
+            Self::Max(max) => Some(max),
+            Self::Unlimited => None,
+            Self::Default => Some(configured.unwrap_or_else(|| mode.default_max_iterations())),
+        }
+    }
+}
+
+/// Command-line options for [`run`].
+#[derive(Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)] // One per CLI flag
+pub(crate) struct LoopOptions {
+    /// Plan or build.
+    pub mode: LoopMode,
+    /// `--max-iterations` / `--unlimited`.
+    pub max_iterations: IterationCap,
+    /// Options saved with the state for `ralph resume`.
+    pub settings: LoopSettings,
+    /// `--dry-run`.
+    pub dry_run: bool,
+    /// `--interactive`.
+    pub interactive: bool,
+    /// `--simulate`.
+    pub simulate: bool,
+    /// `--output`.
+    pub output: Option<PathBuf>,
+    /// `--keep-container`.
+    pub keep_container: bool,
+    /// Stopped loop to continue instead of loading or creating one, set by
+    /// `ralph resume`. Branch-build mode is skipped for it.
+    pub resume: Option<RalphState>,
+}
+
 // -----------------------------------------------------------------------------
 // Helper functions
 // -----------------------------------------------------------------------------
@@ -1496,9 +2515,286 @@ fn determine_prompt_file(cwd: &Path, mode: LoopMode, custom_prompt: Option<&str>
 fn prepare_state(mut state: RalphState, max_iterations: Option<u32>) -> RalphState {
     state.max_iterations = max_iterations;
     state.active = true;
+    state.pid = Some(std::process::id());
     state
 }
 
+/// Prints the progress display between iterations if `monitoring.show_progress`
+/// is set, adding the persistent container's CPU and memory usage when
+/// `monitoring.show_resource_usage` is also set.
+async fn print_progress(
+    state: &RalphState,
+    cwd: &Path,
+    config: &Config,
+    persistent_container: Option<&str>,
+    timings: PhaseTimings,
+    sink: &dyn EventSink,
+) {
+    if !config.monitoring.show_progress {
+        return;
+    }
+    let mut progress =
+        ProgressInfo::new(state, cwd, config.completion.idle_threshold_for(state.mode)).await;
+    progress.last_agent_ms = timings.agent_ms;
+    progress.last_validation_ms = timings.validation_ms;
+    if let (true, Some(container_name)) =
+        (config.monitoring.show_resource_usage, persistent_container)
+    {
+        match DockerSandbox::container_resource_usage(config.sandbox.runtime, container_name).await
+        {
+            Ok(usage) => {
+                progress.memory_mb = Some(usage.memory_mb);
+                progress.cpu_percent = Some(usage.cpu_percent);
+            }
+            Err(e) => debug!("Could not read sandbox resource usage: {e:#}"),
+        }
+    }
+    sink.emit(&LoopEvent::Progress(&progress));
+}
+
+/// Refuses to run on a protected branch, or with `auto_branch` switches to
+/// a fresh `ralph/<timestamp>` branch first. Outside a git repository, or
+/// on a detached HEAD, there is no branch to protect.
+async fn guard_protected_branch(
+    cwd: &Path,
+    protected_branches: &[String],
+    auto_branch: bool,
+    sink: &dyn EventSink,
+) -> Result<()> {
+    let Ok(branch) = get_current_branch(cwd).await else {
+        return Ok(());
+    };
+    if !is_protected_branch(&branch, protected_branches) {
+        return Ok(());
+    }
+    if !auto_branch {
+        bail!(
+            "Refusing to run the loop on protected branch '{branch}'.\n\
+             Create a feature branch first (git switch -c <name>), set git.auto_branch = true \
+             to have ralph create one, or remove '{branch}' from git.protected_branches."
+        );
+    }
+
+    let new_branch = auto_branch_name(chrono::Utc::now());
+    create_branch(cwd, &new_branch).await?;
+    sink.emit(&LoopEvent::BranchSwitched {
+        from: &branch,
+        to: &new_branch,
+    });
+    Ok(())
+}
+
+/// Takes the loop lock for `cwd`, failing with the running loop's PID if
+/// another process holds it.
+fn acquire_loop_lock(cwd: &Path) -> Result<LoopLock> {
+    if let Some(lock) = LoopLock::try_acquire(cwd)? {
+        return Ok(lock);
+    }
+    let pid = RalphState::load(cwd).ok().flatten().and_then(|s| s.pid);
+    bail!("{}", format_loop_already_running(pid))
+}
+
+/// Error shown when another loop already holds the lock.
+fn format_loop_already_running(pid: Option<u32>) -> String {
+    let owner = pid.map_or_else(|| "unknown PID".to_string(), |pid| format!("PID {pid}"));
+    format!(
+        "Another ralph loop is already running in this directory ({owner}).\n\
+         Stop it first, or wait for it to finish."
+    )
+}
+
+/// Auto-commits a dirty working tree using `git.commit_message_template`,
+/// tagged with the `Ralph-Iteration` trailer.
+/// Failures only warn: the iteration already succeeded.
+async fn commit_iteration_changes(cwd: &Path, config: &Config, iteration: u32, mode: Mode) {
+    let mode = format!("{mode:?}").to_lowercase();
+    let message = with_iteration_trailer(
+        &render_commit_message(&config.git.commit_message_template, iteration, &mode),
+        iteration,
+    );
+    let git = &config.git;
+    if let Err(e) = auto_commit(cwd, &message, git.sign_commits, git.signing_key.as_deref()).await {
+        warn!("Auto-commit failed: {e}");
+    }
+}
+
+/// Writes the end-of-loop summary to `monitoring.metrics_file` and, as
+/// markdown, to the `--output` file, if either is set.
+/// Failures only warn so they never mask the loop's own outcome.
+async fn export_metrics(
+    cwd: &Path,
+    config: &Config,
+    state: &RalphState,
+    reason: &str,
+    output: Option<&Path>,
+) {
+    let metrics_path = config
+        .monitoring
+        .metrics_file
+        .as_deref()
+        .map(|f| cwd.join(f));
+    if metrics_path.is_none() && output.is_none() {
+        return;
+    }
+
+    let elapsed = chrono::Utc::now() - state.started_at;
+    let summary = LoopSummary {
+        mode: format!("{:?}", state.mode).to_lowercase(),
+        iterations: state.iteration,
+        error_count: state.error_count,
+        elapsed_seconds: u64::try_from(elapsed.num_seconds()).unwrap_or(0),
+        commits: count_successful_commits(cwd, state.started_at).await,
+        termination_reason: reason.to_string(),
+    };
+
+    if let Some(path) = metrics_path {
+        match write_metrics(&path, config.monitoring.metrics_format, &summary) {
+            Ok(()) => debug!("Wrote loop metrics to {}", path.display()),
+            Err(e) => warn!("Failed to write metrics file: {e:#}"),
+        }
+    }
+    if let Some(path) = output {
+        match summary::write_summary(path, &summary, state) {
+            Ok(()) => info!("Wrote run summary to {}", path.display()),
+            Err(e) => warn!("Failed to write run summary: {e:#}"),
+        }
+    }
+}
+
+/// Reads the prompt file and assembles the prompt for the current iteration.
+///
+/// `prompt_append` files are re-read every call so edits between iterations
+/// take effect. With `prompt.substitute`, placeholders in the file contents
+/// are filled in before the validation error is appended. With
+/// `prompt.commit_trailer`, the agent is asked to tag its own commits with the
+/// `Ralph-Iteration` trailer so `ralph revert` can find them.
+fn read_prompt(
+    prompt_file: &Path,
+    state: &RalphState,
+    config: &Config,
+    prompt_append: &[PathBuf],
+) -> Result<String> {
+    let base = std::fs::read_to_string(prompt_file)
+        .with_context(|| format!("Failed to read prompt file: {}", prompt_file.display()))?;
+    let mut base = append_prompt_files(&base, prompt_append, |path| std::fs::read_to_string(path));
+    if config.prompt.substitute {
+        base = substitute_placeholders(&base, state);
+    }
+    let prompt = assemble_prompt(
+        &base,
+        state.last_error.as_deref(),
+        config.validation.max_error_chars,
+        &config.prompt.validation_error_template,
+    );
+    if !config.prompt.commit_trailer {
+        return Ok(prompt);
+    }
+    Ok(format!(
+        "{prompt}\n\n## Commit Trailer\n\
+         End every commit message with the trailer line \
+         `{ITERATION_TRAILER}: {}` so Ralph can tell its commits apart.\n",
+        state.iteration
+    ))
+}
+
+/// Appends the contents of each file in `files` to `base`, in order.
+/// Files that cannot be read are skipped with a warning.
+fn append_prompt_files(
+    base: &str,
+    files: &[PathBuf],
+    read: impl Fn(&Path) -> std::io::Result<String>,
+) -> String {
+    let mut prompt = base.to_string();
+    for path in files {
+        match read(path) {
+            Ok(content) => {
+                prompt.push_str("\n\n");
+                prompt.push_str(content.trim_end());
+            }
+            Err(e) => warn!("Skipping --prompt-append file {}: {}", path.display(), e),
+        }
+    }
+    prompt
+}
+
+/// Replaces each `{iteration}`, `{mode}`, `{max_iterations}`, and
+/// `{last_error}` in `prompt` with its value from `state`.
+///
+/// Unknown `{...}` text is left alone. Inserted values are not rescanned, so
+/// an error message containing a placeholder is inserted verbatim.
+fn substitute_placeholders(prompt: &str, state: &RalphState) -> String {
+    let mut out = String::with_capacity(prompt.len());
+    let mut rest = prompt;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        rest = &rest[open..];
+        let token = rest.find('}').map(|close| &rest[1..close]);
+        let Some((token, value)) =
+            token.and_then(|t| placeholder_value(t, state).map(|value| (t, value)))
+        else {
+            out.push('{');
+            rest = &rest[1..];
+            continue;
+        };
+        out.push_str(&value);
+        rest = &rest[token.len() + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Value of the prompt placeholder `name`, or `None` if it is not one.
+fn placeholder_value(name: &str, state: &RalphState) -> Option<String> {
+    match name {
+        "iteration" => Some(state.iteration.to_string()),
+        "mode" => Some(format!("{:?}", state.mode).to_lowercase()),
+        "max_iterations" => Some(
+            state
+                .max_iterations
+                .map_or_else(|| "unlimited".to_string(), |max| max.to_string()),
+        ),
+        "last_error" => Some(state.last_error.clone().unwrap_or_default()),
+        _ => None,
+    }
+}
+
+/// Appends the previous iteration's validation error to `base`, if any,
+/// so the agent sees what to fix. Other errors are not appended.
+///
+/// The error fills the `{error}` placeholder of `template`
+/// (`prompt.validation_error_template`). Errors longer than
+/// `max_error_chars` (0 = unlimited) are cut down to their head and tail so
+/// a huge dump cannot overflow the agent's context.
+fn assemble_prompt(
+    base: &str,
+    last_error: Option<&str>,
+    max_error_chars: usize,
+    template: &str,
+) -> String {
+    let Some(error_details) = last_error.and_then(|e| e.strip_prefix("Validation error:")) else {
+        return base.to_string();
+    };
+    debug!("Appending validation error to prompt for agent visibility");
+
+    let error = truncate_middle(error_details.trim(), max_error_chars);
+    format!("{base}\n\n{}", template.replace("{error}", &error))
+}
+
+/// Keeps the first and last `max / 2` chars of `text`, replacing the middle
+/// with a `...[truncated N chars]...` marker. `max == 0` disables truncation.
+fn truncate_middle(text: &str, max: usize) -> String {
+    let total = text.chars().count();
+    if max == 0 || total <= max {
+        return text.to_string();
+    }
+
+    let head_len = max / 2;
+    let tail_len = max - head_len;
+    let head: String = text.chars().take(head_len).collect();
+    let tail: String = text.chars().skip(total - tail_len).collect();
+    format!("{head}\n...[truncated {} chars]...\n{tail}", total - max)
+}
+
 /// Checks if max iterations has been reached.
 fn is_max_iterations_reached(state: &RalphState) -> bool {
     state
@@ -1513,64 +2809,715 @@ fn resolve_provider(config: &Config, provider_override: Option<&str>) -> Result<
     resolve_provider_with_env(config, provider_override, env_provider.as_deref())
 }
 
-/// Resolves the timeout for the given provider.
-/// Priority: provider-specific timeout > global sandbox timeout.
-fn resolve_timeout(config: &Config, provider: Provider) -> u32 {
-    config
-        .agent
-        .get_provider_timeout(provider)
-        .unwrap_or(config.sandbox.resources.timeout_minutes)
+/// Creates the host-mode agent for `provider`.
+fn create_agent(config: &Config, provider: Provider) -> Box<dyn AgentProvider> {
+    match provider {
+        Provider::Cursor => Box::new(
+            CursorProvider::new(config.agent.cursor.clone())
+                .with_stream_output(config.monitoring.stream_output),
+        ),
+        Provider::Claude => Box::new(
+            ClaudeProvider::new(config.agent.claude.clone())
+                .with_stream_output(config.monitoring.stream_output),
+        ),
+        Provider::Ollama => Box::new(OllamaProvider::new(config.agent.ollama.clone())),
+    }
 }
 
-/// Internal helper for provider resolution with explicit env var value.
-/// Enables testing without modifying actual environment.
-fn resolve_provider_with_env(
+/// Creates the Docker sandbox for `provider` when sandboxing is enabled.
+fn create_sandbox(
     config: &Config,
-    provider_override: Option<&str>,
-    env_provider: Option<&str>,
-) -> Result<Provider> {
-    // 1. CLI flag takes highest precedence
-    if let Some(p) = provider_override {
-        debug!("Using CLI provider override: {}", p);
-        return p.parse();
-    }
+    provider: Provider,
+    sandbox_enabled: bool,
+) -> Option<Box<dyn Sandbox>> {
+    sandbox_enabled.then(|| {
+        Box::new(DockerSandbox::new(
+            config.clone(),
+            provider,
+            config.agent.clone(),
+        )) as Box<dyn Sandbox>
+    })
+}
 
-    // 2. RALPH_PROVIDER env var takes precedence over config
-    if let Some(env_val) = env_provider {
-        if !env_val.is_empty() {
-            debug!("Using RALPH_PROVIDER env var: {}", env_val);
-            return env_val.parse();
-        }
+/// Returns the fallback provider to switch to after a rate-limit error, if any.
+///
+/// `consecutive_errors` includes the current error. The switch happens once
+/// per run (`already_switched`) and only after
+/// `FALLBACK_AFTER_CONSECUTIVE_ERRORS` errors in a row.
+fn fallback_provider_to_switch_to(
+    is_rate_limit: bool,
+    consecutive_errors: u32,
+    current: Provider,
+    fallback: Option<Provider>,
+    already_switched: bool,
+) -> Option<Provider> {
+    if !is_rate_limit || already_switched || consecutive_errors < FALLBACK_AFTER_CONSECUTIVE_ERRORS
+    {
+        return None;
     }
-
-    // 3. Fall back to config file
-    config.agent.get_provider()
+    fallback.filter(|&next| next != current)
 }
 
-// -----------------------------------------------------------------------------
-// Validation
-// -----------------------------------------------------------------------------
+/// Circuit-breaker decision after a failed iteration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BreakerAction {
+    /// Under the limit: keep going.
+    Continue,
+    /// First trip with a cool-down configured: pause, then keep going.
+    CoolDown(std::time::Duration),
+    /// Stop the loop.
+    Trip,
+}
 
-/// Validates code by running the configured validation command.
-/// Returns the full error message if validation fails.
-async fn validate_code(cwd: &Path, command: &str) -> Result<(), String> {
-    debug!("Validating code with command: {}", command);
+/// Replaces `monitoring.max_consecutive_errors` with the `--max-errors` value, if given.
+fn apply_max_errors_override(monitoring: &mut MonitoringConfig, max_errors: Option<u32>) {
+    if let Some(limit) = max_errors {
+        monitoring.max_consecutive_errors = limit;
+    }
+}
 
-    // Parse command using shell-words to handle quoted arguments properly
-    // e.g., `sh -c "cmd1 && cmd2"` becomes ["sh", "-c", "cmd1 && cmd2"]
-    let parts = shell_words::split(command)
-        .map_err(|e| format!("Failed to parse validation command: {e}"))?;
+/// Resolves `agent.working_dir` to the directory the agent runs in.
+///
+/// The directory must exist and, after following symlinks, stay inside
+/// `project_dir`. Without a working dir this is `project_dir` itself.
+fn resolve_agent_dir(project_dir: &Path, agent: &AgentConfig) -> Result<PathBuf> {
+    let relative = agent
+        .relative_working_dir()
+        .context("Invalid agent.working_dir")?;
+    if relative.as_os_str().is_empty() {
+        return Ok(project_dir.to_path_buf());
+    }
+
+    let dir = project_dir.join(&relative);
+    if !dir.is_dir() {
+        bail!("Agent working directory not found: {}", relative.display());
+    }
+    let root = project_dir
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve {}", project_dir.display()))?;
+    let resolved = dir
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve {}", dir.display()))?;
+    if !resolved.starts_with(&root) {
+        bail!(
+            "Agent working directory {} resolves outside the project",
+            relative.display()
+        );
+    }
+    Ok(dir)
+}
+
+/// Turns off everything `--simulate` must not reach: the sandbox, validation,
+/// the success command, auto-commit, push, and the fallback provider.
+fn apply_simulate_overrides(config: &mut Config) {
+    config.sandbox.enabled = false;
+    config.validation.enabled = false;
+    config.validation.final_command = None;
+    config.completion.success_command = None;
+    config.git.auto_commit = false;
+    config.git.auto_push = false;
+    config.agent.fallback_provider = None;
+}
+
+/// Commits since the loop's start commit, with `completion.idle_by_commit_count`.
+///
+/// `None` when counting is off, there is no start commit, or git fails.
+async fn idle_commit_count(
+    cwd: &Path,
+    completion: &CompletionConfig,
+    initial_commit: Option<&str>,
+) -> Option<u32> {
+    if !completion.idle_by_commit_count {
+        return None;
+    }
+    commit_count_since(cwd, initial_commit?).await
+}
+
+/// Checks whether the finished iteration was idle on a clean tree: by commit
+/// count with `completion.idle_by_commit_count`, otherwise by HEAD hash.
+async fn check_idle(
+    detector: &mut CompletionDetector,
+    cwd: &Path,
+    completion: &CompletionConfig,
+    initial_commit: Option<&str>,
+    current_commit: Option<&str>,
+    is_dirty: bool,
+) -> bool {
+    if completion.idle_by_commit_count {
+        let count = idle_commit_count(cwd, completion, initial_commit).await;
+        detector.check_completion_by_count(count, is_dirty, true)
+    } else {
+        detector.check_completion_with_worktree(current_commit, is_dirty, true)
+    }
+}
+
+/// How `completion.strategy` judged a finished iteration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompletionCheck {
+    /// Not done; run another iteration.
+    Pending,
+    /// Done by idle detection or the promise; stop before pushing.
+    Detected,
+    /// Done once the success command passed; stop after pushing.
+    SuccessCommand,
+}
+
+/// Evaluates `completion.strategy` for the finished iteration.
+///
+/// The success command only runs when the strategy consults it and the idle
+/// and promise checks alone do not finish the loop.
+async fn check_completion(
+    detector: &CompletionDetector,
+    iteration: u32,
+    cwd: &Path,
+    success_command: Option<&str>,
+    idle: bool,
+    output: &str,
+) -> CompletionCheck {
+    let signals = CompletionSignals {
+        idle,
+        promise: detector.promise_in(output),
+        command: false,
+    };
+    if detector.is_complete(iteration, signals) {
+        return CompletionCheck::Detected;
+    }
+    if !detector.uses_command() {
+        return CompletionCheck::Pending;
+    }
+
+    let command = success_command_passed(cwd, success_command).await;
+    if command && detector.is_complete(iteration, CompletionSignals { command, ..signals }) {
+        CompletionCheck::SuccessCommand
+    } else {
+        CompletionCheck::Pending
+    }
+}
+
+/// Runs `validation.final_command` once after a completed loop (idle
+/// detection, promise, or success command) and returns the reason the run
+/// is reported with.
+///
+/// A failure is recorded as a validation error, so a resumed loop sees it
+/// in the prompt, and turns the outcome into an error so the process exits
+/// non-zero. Other outcomes, or no final command, pass through unchanged.
+async fn final_validation_gate(
+    cwd: &Path,
+    validation: &ValidationConfig,
+    state: &mut RalphState,
+    reason: TerminationReason,
+) -> Result<TerminationReason> {
+    let Some(command) = validation.final_command.as_deref() else {
+        return Ok(reason);
+    };
+    if !matches!(
+        reason,
+        TerminationReason::CompletionDetected | TerminationReason::SuccessCommand
+    ) {
+        return Ok(reason);
+    }
+
+    info!("Running final validation: {}", command);
+    let Err(error) = validate_code(cwd, command, validation.timeout()).await else {
+        info!("Final validation passed");
+        return Ok(reason);
+    };
+    state.record_error(ErrorKind::Validation, format!("Validation error:{error}"));
+    state.save(cwd)?;
+    Ok(TerminationReason::Error(format!(
+        "{FINAL_VALIDATION_MESSAGE} ({command}):\n{}",
+        validation_error_excerpt(&error, validation.error_tail_lines)
+    )))
+}
+
+/// Formats a timeout for error messages: whole minutes as "N minutes",
+/// shorter or uneven limits in seconds, and sub-second limits in ms.
+fn format_time_limit(limit: std::time::Duration) -> String {
+    let secs = limit.as_secs();
+    match (secs / 60, secs % 60) {
+        (1, 0) => "1 minute".to_string(),
+        (minutes, 0) if minutes > 0 => format!("{minutes} minutes"),
+        _ if secs > 0 => format!("{secs} seconds"),
+        _ => format!("{} ms", limit.as_millis()),
+    }
+}
+
+/// Lines of a validation error shown when `validation.error_tail_lines` is unset.
+const VALIDATION_SUMMARY_LINES: usize = 5;
+
+/// Picks the part of a validation error shown in the terminal and notifications.
+///
+/// Unset `tail_lines` keeps the first 5 lines, which name the failing
+/// command; `Some(0)` keeps every line; `Some(n)` keeps the last `n`.
+fn validation_error_excerpt(full_error: &str, tail_lines: Option<usize>) -> String {
+    let lines: Vec<&str> = full_error.lines().collect();
+    let selected = match tail_lines {
+        None => &lines[..lines.len().min(VALIDATION_SUMMARY_LINES)],
+        Some(0) => &lines[..],
+        Some(n) => &lines[lines.len().saturating_sub(n)..],
+    };
+    selected.join("\n")
+}
+
+/// Applies `--validation-cmd` and `--no-validation` to the validation config.
+///
+/// A command override also turns validation on; `--no-validation` wins over
+/// both and also skips the final validation gate.
+fn apply_validation_override(
+    validation: &mut ValidationConfig,
+    no_validation: bool,
+    validation_cmd: Option<String>,
+) {
+    if let Some(command) = validation_cmd {
+        validation.command = command;
+        validation.enabled = true;
+    }
+    if no_validation {
+        validation.enabled = false;
+        validation.final_command = None;
+    }
+}
+
+/// Decides what the circuit breaker does at the current error streak.
+///
+/// The cool-down is granted once per run; a second trip stops the loop.
+fn circuit_breaker_action(state: &RalphState, monitoring: &MonitoringConfig) -> BreakerAction {
+    let limit = monitoring.max_consecutive_errors;
+    if limit == 0 || state.consecutive_errors < limit {
+        BreakerAction::Continue
+    } else if monitoring.circuit_breaker_cooldown_minutes > 0 && !state.circuit_breaker_cooled_down
+    {
+        BreakerAction::CoolDown(std::time::Duration::from_secs(
+            monitoring
+                .circuit_breaker_cooldown_minutes
+                .saturating_mul(60),
+        ))
+    } else {
+        BreakerAction::Trip
+    }
+}
+
+/// Notification details for a trip, naming the iteration that failed last.
+///
+/// Failure paths bump `state.iteration` before the breaker check, so the
+/// failed iteration is the one before it. The last error is cut to its first
+/// lines, like other error notifications.
+fn circuit_breaker_details(state: &RalphState) -> NotificationDetails {
+    let last_error = state
+        .last_error
+        .as_deref()
+        .map(|error| error.lines().take(5).collect::<Vec<_>>().join("\n"));
+    NotificationDetails::circuit_breaker(
+        state.iteration.saturating_sub(1),
+        state.consecutive_errors,
+        last_error.as_deref(),
+    )
+}
+
+/// Spends the one cool-down: resets the error streak and marks it used.
+fn begin_cooldown(state: &mut RalphState) {
+    state.circuit_breaker_cooled_down = true;
+    state.consecutive_errors = 0;
+}
+
+/// Applies the circuit breaker after a failed iteration.
+///
+/// Returns the reason to stop, if any. A trip sends a circuit breaker
+/// notification. On a cool-down, sleeps (returning early on a signal) and
+/// lets the loop carry on with a fresh error streak.
+async fn check_circuit_breaker(
+    state: &mut RalphState,
+    cwd: &Path,
+    monitoring: &MonitoringConfig,
+    errors: &str,
+    notifier: &Notifier,
+    shutdown: &mut signal::ShutdownSignal,
+) -> Result<Option<TerminationReason>> {
+    match circuit_breaker_action(state, monitoring) {
+        BreakerAction::Continue => Ok(None),
+        BreakerAction::Trip => {
+            notifier
+                .notify(
+                    NotificationEvent::CircuitBreaker,
+                    &circuit_breaker_details(state),
+                )
+                .await;
+            Ok(Some(TerminationReason::Error(circuit_breaker_message(
+                state, monitoring, errors,
+            ))))
+        }
+        BreakerAction::CoolDown(duration) => {
+            cool_down(state, cwd, monitoring, errors, duration, shutdown).await?;
+            Ok(None)
+        }
+    }
+}
+
+/// Applies the circuit breaker after a failed iteration in a branch build.
+///
+/// Like [`check_circuit_breaker`], but a trip fails the branch, and a
+/// fail-fast cancellation cuts a cool-down short.
+async fn check_branch_circuit_breaker(
+    state: &mut RalphState,
+    wt_path: &Path,
+    monitoring: &MonitoringConfig,
+    errors: &str,
+    cancelled: &mut ShutdownSignal,
+) -> Result<()> {
+    match circuit_breaker_action(state, monitoring) {
+        BreakerAction::Continue => Ok(()),
+        BreakerAction::Trip => {
+            state.active = false;
+            state.save(wt_path)?;
+            bail!(circuit_breaker_message(state, monitoring, errors))
+        }
+        BreakerAction::CoolDown(duration) => {
+            cool_down(state, wt_path, monitoring, errors, duration, cancelled).await
+        }
+    }
+}
+
+/// Explains a circuit breaker trip and how to raise the limit.
+fn circuit_breaker_message(
+    state: &RalphState,
+    monitoring: &MonitoringConfig,
+    errors: &str,
+) -> String {
+    format!(
+        "{CIRCUIT_BREAKER_MESSAGE}: {} {errors} (limit: {}). \
+         Increase monitoring.max_consecutive_errors in ralph.toml (or pass --max-errors) to continue.",
+        state.consecutive_errors, monitoring.max_consecutive_errors
+    )
+}
+
+/// Spends the cool-down: saves the fresh error streak, then sleeps for
+/// `duration`, returning early on `signal`.
+async fn cool_down(
+    state: &mut RalphState,
+    dir: &Path,
+    monitoring: &MonitoringConfig,
+    errors: &str,
+    duration: std::time::Duration,
+    signal: &mut ShutdownSignal,
+) -> Result<()> {
+    warn!(
+        "Circuit breaker tripped after {} {}; cooling down for {} minute(s) before retrying",
+        state.consecutive_errors, errors, monitoring.circuit_breaker_cooldown_minutes
+    );
+    begin_cooldown(state);
+    state.save(dir)?;
+    sleep_or_signal(duration, signal).await;
+    Ok(())
+}
+
+/// Seconds to wait after a rate limit: `min(base * multiplier^consecutive, max)`.
+fn backoff_seconds(consecutive: u32, backoff: &BackoffConfig) -> u64 {
+    let exponent = i32::try_from(consecutive).unwrap_or(i32::MAX);
+    #[allow(clippy::cast_precision_loss)] // Second counts are far below 2^52
+    let (base, max) = (backoff.base_seconds as f64, backoff.max_seconds as f64);
+    let seconds = base * backoff.multiplier.powi(exponent);
+    if seconds.is_nan() || seconds >= max {
+        return backoff.max_seconds;
+    }
+    // In range [0, max_seconds] here, so the cast is exact enough
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let seconds = seconds.max(0.0) as u64;
+    seconds
+}
+
+/// Books what a successful agent run reports: token usage, and the session
+/// to resume next iteration (kept from earlier runs when none is reported).
+fn record_agent_output(state: &mut RalphState, agent: &dyn AgentProvider, output: &str) {
+    state.add_token_usage(agent.token_usage(output));
+    if let Some(id) = agent.session_id(output) {
+        state.session_id = Some(id);
+    }
+}
+
+/// Substrings of agent errors that mean the provider refused the run.
+const RATE_LIMIT_MARKERS: &[&str] = &[
+    "resource_exhausted",
+    "rate limit",
+    "Rate limit",
+    "429",
+    "quota",
+    "Quota",
+];
+
+/// An agent failure the loop retries on the next iteration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RecoverableError {
+    /// The run hit its time limit.
+    Timeout,
+    /// The provider refused the run (rate limit or quota).
+    RateLimit,
+    /// The agent exited with a non-zero code.
+    Crash,
+}
+
+impl RecoverableError {
+    /// Classifies an agent error; `None` means the loop cannot carry on.
+    fn classify(error: &anyhow::Error) -> Option<Self> {
+        let message = error.to_string();
+        let sandbox_error = error.downcast_ref::<SandboxError>();
+        if RATE_LIMIT_MARKERS
+            .iter()
+            .any(|marker| message.contains(marker))
+        {
+            Some(Self::RateLimit)
+        } else if sandbox_error.is_some_and(SandboxError::is_timeout)
+            // Host runs time out without a SandboxError
+            || message.contains("timed out")
+        {
+            Some(Self::Timeout)
+        } else if sandbox_error.is_some_and(SandboxError::is_non_zero_exit) {
+            Some(Self::Crash)
+        } else {
+            None
+        }
+    }
+
+    /// Names the failure in the recorded error, e.g. `Agent timeout: ...`.
+    fn label(self) -> &'static str {
+        match self {
+            Self::Timeout => "timeout",
+            Self::RateLimit => "rate limit",
+            Self::Crash => "failure",
+        }
+    }
+}
+
+/// Books a failed iteration: records the error, then moves on to the next
+/// iteration and saves.
+fn record_failed_iteration(
+    state: &mut RalphState,
+    dir: &Path,
+    kind: ErrorKind,
+    message: String,
+) -> Result<()> {
+    state.record_error(kind, message);
+    state.last_iteration_at = Some(chrono::Utc::now());
+    state.iteration += 1;
+    state.save(dir)
+}
+
+/// Most persistent containers replaced in one run; after that an unhealthy
+/// container fails its iteration like any other sandbox error.
+const MAX_CONTAINER_RECREATIONS: u32 = 3;
+
+/// Runs the agent in `sandbox`, reusing the persistent `container` if set.
+///
+/// If the reused container cannot be recovered (dead, being removed, or
+/// gone), a new persistent container replaces it and the run is retried
+/// once, up to [`MAX_CONTAINER_RECREATIONS`] per run. If the replacement
+/// cannot be created, later iterations use per-iteration containers.
+#[allow(tail_expr_drop_order)] // Drop order doesn't matter for async operations
+async fn run_in_sandbox(
+    sandbox: &dyn Sandbox,
+    project_dir: &Path,
+    prompt: &str,
+    container: &mut Option<String>,
+    recreations: &mut u32,
+    iteration: u32,
+    session: Option<&str>,
+) -> Result<String> {
+    let result = sandbox
+        .run(
+            project_dir,
+            prompt,
+            container.as_deref(),
+            iteration,
+            session,
+        )
+        .await;
+    let Some(old) = container.as_deref() else {
+        return result;
+    };
+    match &result {
+        Err(e) if is_container_unhealthy(e) && *recreations < MAX_CONTAINER_RECREATIONS => {
+            *recreations += 1;
+            warn!(
+                "Persistent container {} is unusable ({}). Recreating it ({}/{})",
+                old, e, recreations, MAX_CONTAINER_RECREATIONS
+            );
+        }
+        _ => return result,
+    }
+
+    let _ = sandbox.remove_persistent(old).await;
+    *container = match sandbox.create_persistent(project_dir).await {
+        Ok(name) if !name.is_empty() => {
+            info!("Created persistent container: {}", name);
+            Some(name)
+        }
+        Ok(_) => None,
+        Err(e) => {
+            warn!(
+                "Failed to recreate persistent container: {}. Falling back to per-iteration containers.",
+                e
+            );
+            None
+        }
+    };
+    sandbox
+        .run(
+            project_dir,
+            prompt,
+            container.as_deref(),
+            iteration,
+            session,
+        )
+        .await
+}
+
+/// Removes the persistent container at loop end. With `keep` (the runtime,
+/// set by `--keep-container`) it is left running and the command to open a
+/// shell in it is reported instead.
+async fn release_persistent_container(
+    sandbox: &dyn Sandbox,
+    name: &str,
+    keep: Option<ContainerRuntime>,
+    sink: &dyn EventSink,
+) {
+    if let Some(runtime) = keep {
+        info!("Keeping persistent container: {}", name);
+        sink.emit(&LoopEvent::ContainerKept {
+            container: name,
+            shell_command: &shell_command(runtime, name).join(" "),
+        });
+        return;
+    }
+    info!("Cleaning up persistent container: {}", name);
+    if let Err(e) = sandbox.remove_persistent(name).await {
+        warn!("Failed to remove persistent container {}: {}", name, e);
+    }
+}
+
+/// Whether `error` is a sandbox container that must be recreated.
+fn is_container_unhealthy(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<SandboxError>()
+        .is_some_and(SandboxError::is_container_unhealthy)
+}
+
+/// Message recorded for an empty-output iteration that is retried.
+const EMPTY_OUTPUT_ERROR: &str = "Agent returned empty output";
+
+/// Whether an iteration should be retried because the agent returned empty
+/// output without touching the tree (`agent.max_empty_retries`).
+///
+/// `retries` counts the empty outputs retried in a row; it is bumped on each
+/// retry and reset by non-empty output. Once retries are used up the empty
+/// output is accepted like any other, so idle detection can end the run.
+async fn retry_empty_output(
+    cwd: &Path,
+    output: &str,
+    start_commit: Option<&str>,
+    retries: &mut u32,
+    max_retries: u32,
+) -> bool {
+    if !output.trim().is_empty() {
+        *retries = 0;
+        return false;
+    }
+    if *retries >= max_retries
+        || get_commit_hash(cwd).await.as_deref() != start_commit
+        || is_worktree_dirty(cwd).await
+    {
+        return false;
+    }
+    *retries += 1;
+    true
+}
+
+/// Resolves the timeout for the given provider.
+/// Priority: provider-specific timeout > `agent.timeout_minutes` > sandbox timeout.
+fn resolve_timeout(config: &Config, provider: Provider) -> u32 {
+    config
+        .agent
+        .get_provider_timeout(provider)
+        .or(config.agent.timeout_minutes)
+        .unwrap_or(config.sandbox.resources.timeout_minutes)
+}
+
+/// Fails fast when the provider cannot run inside the Docker sandbox.
+/// Ollama is reached over HTTP from the host, so it requires `--no-sandbox`.
+fn check_provider_sandbox(provider: Provider, sandbox_enabled: bool) -> Result<()> {
+    if sandbox_enabled && provider == Provider::Ollama {
+        anyhow::bail!(
+            "The Ollama provider does not support the Docker sandbox.\n\
+             Run with --no-sandbox or set [sandbox].enabled = false in ralph.toml"
+        );
+    }
+    Ok(())
+}
+
+/// Internal helper for provider resolution with explicit env var value.
+/// Enables testing without modifying actual environment.
+fn resolve_provider_with_env(
+    config: &Config,
+    provider_override: Option<&str>,
+    env_provider: Option<&str>,
+) -> Result<Provider> {
+    // 1. CLI flag takes highest precedence
+    if let Some(p) = provider_override {
+        debug!("Using CLI provider override: {}", p);
+        return p.parse();
+    }
+
+    // 2. RALPH_PROVIDER env var takes precedence over config
+    if let Some(env_val) = env_provider {
+        if !env_val.is_empty() {
+            debug!("Using RALPH_PROVIDER env var: {}", env_val);
+            return env_val.parse();
+        }
+    }
+
+    // 3. Fall back to config file
+    config.agent.get_provider()
+}
+
+// -----------------------------------------------------------------------------
+// Validation
+// -----------------------------------------------------------------------------
+
+/// Validates code by running the configured validation command.
+/// Returns the full error message if validation fails.
+///
+/// A command still running after `timeout` is killed and reported as a
+/// validation failure.
+async fn validate_code(
+    cwd: &Path,
+    command: &str,
+    timeout: Option<std::time::Duration>,
+) -> Result<(), String> {
+    debug!("Validating code with command: {}", command);
+
+    // Parse command using shell-words to handle quoted arguments properly
+    // e.g., `sh -c "cmd1 && cmd2"` becomes ["sh", "-c", "cmd1 && cmd2"]
+    let parts = shell_words::split(command)
+        .map_err(|e| format!("Failed to parse validation command: {e}"))?;
 
     let (program, args) = parts
         .split_first()
         .ok_or_else(|| "Validation command cannot be empty".to_string())?;
 
-    let output = tokio::process::Command::new(program)
+    // kill_on_drop kills the command when the timeout drops the future
+    let run = tokio::process::Command::new(program)
         .current_dir(cwd)
         .args(args)
-        .output()
-        .await
-        .map_err(|e| format!("Failed to run validation command: {e}"))?;
+        .kill_on_drop(true)
+        .output();
+    let output = match timeout {
+        Some(limit) => tokio::time::timeout(limit, run).await.map_err(|_| {
+            format!(
+                "Validation timed out after {} ({command})",
+                format_time_limit(limit)
+            )
+        })?,
+        None => run.await,
+    }
+    .map_err(|e| format!("Failed to run validation command: {e}"))?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -1590,6 +3537,34 @@ async fn validate_code(cwd: &Path, command: &str) -> Result<(), String> {
     Ok(())
 }
 
+/// Runs a `[hooks]` command in the given directory.
+/// Returns the hook's stderr (or stdout if stderr is empty) on failure.
+async fn run_hook(cwd: &Path, command: &str) -> Result<(), String> {
+    let parts =
+        shell_words::split(command).map_err(|e| format!("Failed to parse hook command: {e}"))?;
+
+    let (program, args) = parts
+        .split_first()
+        .ok_or_else(|| "Hook command cannot be empty".to_string())?;
+
+    debug!("Running hook: {}", command);
+    let output = tokio::process::Command::new(program)
+        .current_dir(cwd)
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run hook command: {e}"))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let error_msg = if stderr.is_empty() { stdout } else { stderr };
+        return Err(format!("Hook failed ({command}):\n{error_msg}"));
+    }
+
+    Ok(())
+}
+
 // -----------------------------------------------------------------------------
 // Tests
 // -----------------------------------------------------------------------------
@@ -1597,6 +3572,7 @@ async fn validate_code(cwd: &Path, command: &str) -> Result<(), String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{CompletionStrategy, NotificationConfig};
     use chrono::Utc;
 
     fn make_state(iteration: u32, max: Option<u32>) -> RalphState {
@@ -1609,9 +3585,18 @@ mod tests {
             last_iteration_at: None,
             error_count: 0,
             consecutive_errors: 0,
+            circuit_breaker_cooled_down: false,
             last_error: None,
             last_commit: None,
             idle_iterations: 0,
+            commit_count: None,
+            total_input_tokens: 0,
+            total_output_tokens: 0,
+            initial_commit: None,
+            pid: None,
+            session_id: None,
+            error_history: Vec::new(),
+            settings: LoopSettings::default(),
         }
     }
 
@@ -1642,40 +3627,361 @@ mod tests {
         assert_eq!(path, PathBuf::from("/custom/prompt.md"));
     }
 
-    #[test]
-    fn test_prepare_state_with_max() {
-        let state = make_state(1, None);
-        let prepared = prepare_state(state, Some(10));
-
-        assert!(prepared.active);
-        assert_eq!(prepared.max_iterations, Some(10));
+    fn default_template() -> String {
+        Config::default().prompt.validation_error_template
     }
 
     #[test]
-    fn test_prepare_state_unlimited() {
-        let state = make_state(1, Some(5));
-        let prepared = prepare_state(state, None);
-
-        assert!(prepared.active);
-        assert_eq!(prepared.max_iterations, None);
+    fn test_assemble_prompt_without_error() {
+        assert_eq!(
+            assemble_prompt("Build the thing.", None, 4000, &default_template()),
+            "Build the thing."
+        );
     }
 
     #[test]
-    fn test_is_max_iterations_reached_under() {
-        let state = make_state(3, Some(10));
-        assert!(!is_max_iterations_reached(&state));
+    fn test_assemble_prompt_appends_validation_error() {
+        let prompt = assemble_prompt(
+            "Build the thing.",
+            Some("Validation error:\n  error[E0382]: borrow of moved value\n"),
+            4000,
+            &default_template(),
+        );
+        assert_eq!(
+            prompt,
+            "Build the thing.\n\n\
+             ## ⚠️ VALIDATION ERROR FROM PREVIOUS ITERATION\n\
+             The following validation error occurred. Please fix it:\n\n\
+             ```\nerror[E0382]: borrow of moved value\n```\n\
+             \nFix the issues above and ensure validation passes before proceeding.\n"
+        );
     }
 
     #[test]
-    fn test_is_max_iterations_reached_at() {
-        let state = make_state(10, Some(10));
-        assert!(!is_max_iterations_reached(&state));
+    fn test_assemble_prompt_custom_template() {
+        let prompt = assemble_prompt(
+            "Build the thing.",
+            Some("Validation error:cargo check failed"),
+            4000,
+            "VALIDATION FAILED. Fix this first:\n{error}\n",
+        );
+        assert_eq!(
+            prompt,
+            "Build the thing.\n\nVALIDATION FAILED. Fix this first:\ncargo check failed\n"
+        );
+
+        // Every placeholder is filled; error text is not rescanned
+        let prompt = assemble_prompt(
+            "Base.",
+            Some("Validation error:missing {error}"),
+            4000,
+            "{error} / {error}",
+        );
+        assert_eq!(prompt, "Base.\n\nmissing {error} / missing {error}");
     }
 
     #[test]
-    fn test_is_max_iterations_reached_over() {
-        let state = make_state(11, Some(10));
-        assert!(is_max_iterations_reached(&state));
+    fn test_read_prompt_uses_configured_template() {
+        let dir = tempfile::tempdir().unwrap();
+        let prompt_file = dir.path().join("PROMPT_build.md");
+        std::fs::write(&prompt_file, "Build the thing.").unwrap();
+        let state = RalphState {
+            last_error: Some("Validation error:tests failed".into()),
+            ..make_state(1, None)
+        };
+        let mut config = Config::default();
+        config.prompt.validation_error_template = "Validation failed:\n{error}".to_string();
+
+        let prompt = read_prompt(&prompt_file, &state, &config, &[]).unwrap();
+        assert_eq!(
+            prompt,
+            "Build the thing.\n\nValidation failed:\ntests failed"
+        );
+        assert!(!prompt.contains("⚠️"));
+    }
+
+    #[test]
+    fn test_assemble_prompt_ignores_other_errors() {
+        let prompt = assemble_prompt(
+            "Build the thing.",
+            Some("Agent execution timed out"),
+            4000,
+            &default_template(),
+        );
+        assert_eq!(prompt, "Build the thing.");
+
+        // Only a leading prefix counts
+        let prompt = assemble_prompt(
+            "Build the thing.",
+            Some("Hook failed. Validation error:x"),
+            4000,
+            &default_template(),
+        );
+        assert_eq!(prompt, "Build the thing.");
+    }
+
+    #[test]
+    fn test_truncate_middle_boundaries() {
+        assert_eq!(truncate_middle("abcdef", 6), "abcdef");
+        assert_eq!(truncate_middle("abcdef", 0), "abcdef");
+        assert_eq!(
+            truncate_middle("abcdefg", 6),
+            "abc\n...[truncated 1 chars]...\nefg"
+        );
+        assert_eq!(
+            truncate_middle("abcdefghij", 5),
+            "ab\n...[truncated 5 chars]...\nhij"
+        );
+        // Counts chars, not bytes
+        assert_eq!(
+            truncate_middle("ééééé", 2),
+            "é\n...[truncated 3 chars]...\né"
+        );
+    }
+
+    #[test]
+    fn test_assemble_prompt_truncates_long_error() {
+        let error = format!("Validation error:HEAD{}TAIL", "x".repeat(5000));
+        let prompt = assemble_prompt("Build.", Some(&error), 100, &default_template());
+
+        assert!(prompt.contains("HEAD"));
+        assert!(prompt.contains("TAIL"));
+        assert!(prompt.contains("...[truncated 4908 chars]..."));
+        assert!(!prompt.contains(&"x".repeat(100)));
+    }
+
+    #[test]
+    fn test_read_prompt_uses_state_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let prompt_file = dir.path().join("PROMPT_build.md");
+        std::fs::write(&prompt_file, "Build the thing.").unwrap();
+        let state = RalphState {
+            last_error: Some("Validation error:cargo check failed".into()),
+            ..make_state(2, None)
+        };
+
+        let prompt = read_prompt(&prompt_file, &state, &Config::default(), &[]).unwrap();
+        assert!(prompt.starts_with("Build the thing.\n\n## ⚠️ VALIDATION ERROR"));
+        assert!(prompt.contains("cargo check failed"));
+        assert!(!prompt.contains("Ralph-Iteration"));
+    }
+
+    #[test]
+    fn test_read_prompt_commit_trailer_opt_in() {
+        let dir = tempfile::tempdir().unwrap();
+        let prompt_file = dir.path().join("PROMPT_build.md");
+        std::fs::write(&prompt_file, "Build the thing.").unwrap();
+        let mut config = Config::default();
+        config.prompt.commit_trailer = true;
+
+        let prompt = read_prompt(&prompt_file, &make_state(2, None), &config, &[]).unwrap();
+        assert!(prompt.starts_with("Build the thing.\n\n## Commit Trailer"));
+        assert!(prompt.contains("`Ralph-Iteration: 2`"));
+    }
+
+    #[test]
+    fn test_read_prompt_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = read_prompt(
+            &dir.path().join("missing.md"),
+            &make_state(1, None),
+            &Config::default(),
+            &[],
+        );
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn test_append_prompt_files_in_order() {
+        let files = [PathBuf::from("NOTES.md"), PathBuf::from("TODO.md")];
+        let prompt = append_prompt_files("Base.", &files, |path| {
+            Ok(format!("Contents of {}\n", path.display()))
+        });
+        assert_eq!(
+            prompt,
+            "Base.\n\nContents of NOTES.md\n\nContents of TODO.md"
+        );
+    }
+
+    #[test]
+    fn test_append_prompt_files_skips_missing() {
+        let files = [PathBuf::from("missing.md"), PathBuf::from("NOTES.md")];
+        let prompt = append_prompt_files("Base.", &files, |path| {
+            if path == Path::new("missing.md") {
+                Err(std::io::Error::from(std::io::ErrorKind::NotFound))
+            } else {
+                Ok("Notes".to_string())
+            }
+        });
+        assert_eq!(prompt, "Base.\n\nNotes");
+    }
+
+    #[test]
+    fn test_read_prompt_appends_before_validation_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let prompt_file = dir.path().join("PROMPT_build.md");
+        let notes = dir.path().join("NOTES.md");
+        std::fs::write(&prompt_file, "Build the thing.").unwrap();
+        std::fs::write(&notes, "Remember the milk.").unwrap();
+        let state = RalphState {
+            last_error: Some("Validation error:tests failed".into()),
+            ..make_state(1, None)
+        };
+
+        let prompt = read_prompt(
+            &prompt_file,
+            &state,
+            &Config::default(),
+            std::slice::from_ref(&notes),
+        )
+        .unwrap();
+        assert!(
+            prompt.starts_with("Build the thing.\n\nRemember the milk.\n\n## ⚠️ VALIDATION ERROR")
+        );
+
+        // Edits between iterations are picked up
+        std::fs::write(&notes, "Buy eggs.").unwrap();
+        let prompt = read_prompt(&prompt_file, &state, &Config::default(), &[notes]).unwrap();
+        assert!(prompt.contains("Buy eggs."));
+        assert!(!prompt.contains("Remember the milk."));
+    }
+
+    fn substitute_config() -> Config {
+        let mut config = Config::default();
+        config.prompt.substitute = true;
+        config
+    }
+
+    #[test]
+    fn test_substitute_iteration_and_mode() {
+        assert_eq!(
+            substitute_placeholders(
+                "This is attempt {iteration} in {mode} mode.",
+                &make_state(3, None)
+            ),
+            "This is attempt 3 in build mode."
+        );
+
+        let plan = RalphState {
+            mode: Mode::Plan,
+            ..make_state(1, None)
+        };
+        assert_eq!(substitute_placeholders("{mode}", &plan), "plan");
+    }
+
+    #[test]
+    fn test_substitute_max_iterations() {
+        assert_eq!(
+            substitute_placeholders("{iteration}/{max_iterations}", &make_state(2, Some(10))),
+            "2/10"
+        );
+        assert_eq!(
+            substitute_placeholders("{max_iterations}", &make_state(2, None)),
+            "unlimited"
+        );
+    }
+
+    #[test]
+    fn test_substitute_last_error() {
+        let state = RalphState {
+            last_error: Some("Validation error: {iteration} not found".into()),
+            ..make_state(4, None)
+        };
+        // Inserted values are not substituted again
+        assert_eq!(
+            substitute_placeholders("Last error: {last_error}", &state),
+            "Last error: Validation error: {iteration} not found"
+        );
+        assert_eq!(
+            substitute_placeholders("[{last_error}]", &make_state(1, None)),
+            "[]"
+        );
+    }
+
+    #[test]
+    fn test_substitute_leaves_other_braces() {
+        let state = make_state(5, None);
+        assert_eq!(
+            substitute_placeholders("fn main() { {iteration} } {unknown} {", &state),
+            "fn main() { 5 } {unknown} {"
+        );
+        assert_eq!(substitute_placeholders("{{iteration}}", &state), "{5}");
+    }
+
+    #[test]
+    fn test_read_prompt_substitutes_when_enabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let prompt_file = dir.path().join("PROMPT_build.md");
+        std::fs::write(&prompt_file, "This is attempt {iteration}.").unwrap();
+        let state = make_state(7, Some(9));
+
+        let prompt = read_prompt(&prompt_file, &state, &substitute_config(), &[]).unwrap();
+        assert!(prompt.starts_with("This is attempt 7."));
+    }
+
+    #[test]
+    fn test_read_prompt_substitution_is_opt_in() {
+        let dir = tempfile::tempdir().unwrap();
+        let prompt_file = dir.path().join("PROMPT_build.md");
+        std::fs::write(&prompt_file, "This is attempt {iteration}.").unwrap();
+
+        let prompt =
+            read_prompt(&prompt_file, &make_state(7, None), &Config::default(), &[]).unwrap();
+        assert!(prompt.starts_with("This is attempt {iteration}."));
+    }
+
+    #[test]
+    fn test_prepare_state_with_max() {
+        let state = make_state(1, None);
+        let prepared = prepare_state(state, Some(10));
+
+        assert!(prepared.active);
+        assert_eq!(prepared.max_iterations, Some(10));
+        assert_eq!(prepared.pid, Some(std::process::id()));
+    }
+
+    #[test]
+    fn test_acquire_loop_lock_reports_running_pid() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = prepare_state(RalphState::default(), None);
+        state.save(dir.path()).unwrap();
+
+        let _held = acquire_loop_lock(dir.path()).unwrap();
+        let err = acquire_loop_lock(dir.path()).unwrap_err().to_string();
+        assert!(err.contains("already running"));
+        assert!(err.contains(&format!("PID {}", std::process::id())));
+    }
+
+    #[test]
+    fn test_format_loop_already_running_without_pid() {
+        assert!(format_loop_already_running(None).contains("unknown PID"));
+    }
+
+    #[test]
+    fn test_prepare_state_unlimited() {
+        let state = make_state(1, Some(5));
+        let prepared = prepare_state(state, None);
+
+        assert!(prepared.active);
+        assert_eq!(prepared.max_iterations, None);
+    }
+
+    #[test]
+    fn test_is_max_iterations_reached_under() {
+        let state = make_state(3, Some(10));
+        assert!(!is_max_iterations_reached(&state));
+    }
+
+    #[test]
+    fn test_is_max_iterations_reached_at() {
+        let state = make_state(10, Some(10));
+        assert!(!is_max_iterations_reached(&state));
+    }
+
+    #[test]
+    fn test_is_max_iterations_reached_over() {
+        let state = make_state(11, Some(10));
+        assert!(is_max_iterations_reached(&state));
     }
 
     #[test]
@@ -1707,6 +4013,37 @@ mod tests {
         assert_eq!(provider, Provider::Cursor);
     }
 
+    #[test]
+    fn test_fallback_provider_switch_decision() {
+        let threshold = FALLBACK_AFTER_CONSECUTIVE_ERRORS;
+        let claude = Some(Provider::Claude);
+        let switch = |rate_limit, errors, fallback, switched| {
+            fallback_provider_to_switch_to(rate_limit, errors, Provider::Cursor, fallback, switched)
+        };
+
+        // Switches once the threshold is reached on a rate limit
+        assert_eq!(switch(true, threshold, claude, false), claude);
+        assert_eq!(switch(true, threshold + 5, claude, false), claude);
+
+        // Below threshold, not a rate limit, or already switched: stay put
+        assert_eq!(switch(true, threshold - 1, claude, false), None);
+        assert_eq!(switch(false, threshold, claude, false), None);
+        assert_eq!(switch(true, threshold, claude, true), None);
+
+        // No fallback configured, or fallback is the current provider
+        assert_eq!(switch(true, threshold, None, false), None);
+        assert_eq!(switch(true, threshold, Some(Provider::Cursor), false), None);
+    }
+
+    #[test]
+    fn test_check_provider_sandbox() {
+        assert!(check_provider_sandbox(Provider::Cursor, true).is_ok());
+        assert!(check_provider_sandbox(Provider::Claude, true).is_ok());
+        assert!(check_provider_sandbox(Provider::Ollama, false).is_ok());
+        let err = check_provider_sandbox(Provider::Ollama, true).unwrap_err();
+        assert!(err.to_string().contains("--no-sandbox"));
+    }
+
     #[test]
     fn test_resolve_provider_invalid() {
         let config = Config::default();
@@ -1756,7 +4093,7 @@ mod tests {
     async fn test_validate_code_simple_command() {
         // Simple command without quotes should work
         let cwd = std::env::current_dir().unwrap();
-        let result = validate_code(&cwd, "true").await;
+        let result = validate_code(&cwd, "true", None).await;
         assert!(result.is_ok());
     }
 
@@ -1765,7 +4102,7 @@ mod tests {
         // Quoted arguments should be parsed correctly
         // sh -c "echo hello" should be parsed as ["sh", "-c", "echo hello"]
         let cwd = std::env::current_dir().unwrap();
-        let result = validate_code(&cwd, "sh -c \"exit 0\"").await;
+        let result = validate_code(&cwd, "sh -c \"exit 0\"", None).await;
         assert!(result.is_ok());
     }
 
@@ -1774,14 +4111,14 @@ mod tests {
         // Complex quoted arguments with && should work
         // This was broken with split_whitespace()
         let cwd = std::env::current_dir().unwrap();
-        let result = validate_code(&cwd, "sh -c \"true && true\"").await;
+        let result = validate_code(&cwd, "sh -c \"true && true\"", None).await;
         assert!(result.is_ok());
     }
 
     #[tokio::test]
     async fn test_validate_code_empty_command() {
         let cwd = std::env::current_dir().unwrap();
-        let result = validate_code(&cwd, "").await;
+        let result = validate_code(&cwd, "", None).await;
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("cannot be empty"));
     }
@@ -1790,11 +4127,724 @@ mod tests {
     async fn test_validate_code_unmatched_quote() {
         // Unmatched quote should fail parsing
         let cwd = std::env::current_dir().unwrap();
-        let result = validate_code(&cwd, "sh -c \"unclosed").await;
+        let result = validate_code(&cwd, "sh -c \"unclosed", None).await;
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("parse"));
     }
 
+    #[tokio::test]
+    async fn test_validate_code_timeout() {
+        let cwd = std::env::current_dir().unwrap();
+        let started = std::time::Instant::now();
+        let result =
+            validate_code(&cwd, "sleep 5", Some(std::time::Duration::from_millis(200))).await;
+
+        let err = result.unwrap_err();
+        assert!(
+            err.contains("Validation timed out after 200 ms"),
+            "got: {err}"
+        );
+        assert!(started.elapsed() < std::time::Duration::from_secs(4));
+    }
+
+    #[test]
+    fn test_format_time_limit() {
+        let secs = std::time::Duration::from_secs;
+        assert_eq!(format_time_limit(secs(30)), "30 seconds");
+        assert_eq!(format_time_limit(secs(60)), "1 minute");
+        assert_eq!(format_time_limit(secs(1800)), "30 minutes");
+        assert_eq!(format_time_limit(secs(90)), "90 seconds");
+        assert_eq!(
+            format_time_limit(std::time::Duration::from_millis(200)),
+            "200 ms"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate_code_within_timeout() {
+        let cwd = std::env::current_dir().unwrap();
+        let result = validate_code(&cwd, "true", Some(std::time::Duration::from_secs(30))).await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_run_hook_success() {
+        let cwd = std::env::current_dir().unwrap();
+        assert!(run_hook(&cwd, "sh -c \"exit 0\"").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_run_hook_failure_includes_output() {
+        let cwd = std::env::current_dir().unwrap();
+        let err = run_hook(&cwd, "sh -c \"echo formatter broke >&2; exit 1\"")
+            .await
+            .unwrap_err();
+        assert!(err.contains("Hook failed"));
+        assert!(err.contains("formatter broke"));
+    }
+
+    #[tokio::test]
+    async fn test_run_hook_empty_command() {
+        let cwd = std::env::current_dir().unwrap();
+        let err = run_hook(&cwd, "").await.unwrap_err();
+        assert!(err.contains("cannot be empty"));
+    }
+
+    /// Sandbox whose reused containers listed in `dead` report unhealthy.
+    #[derive(Default)]
+    struct DeadContainerSandbox {
+        dead: Vec<String>,
+        created: std::sync::Mutex<u32>,
+        removed: std::sync::Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Sandbox for DeadContainerSandbox {
+        async fn cleanup_orphaned(&self) -> Result<u32> {
+            Ok(0)
+        }
+
+        async fn create_persistent(&self, _project_dir: &Path) -> Result<String> {
+            let mut created = self.created.lock().unwrap();
+            *created += 1;
+            Ok(format!("ralph-new{created}"))
+        }
+
+        async fn remove_persistent(&self, id: &str) -> Result<()> {
+            self.removed.lock().unwrap().push(id.to_string());
+            Ok(())
+        }
+
+        async fn run(
+            &self,
+            _project_dir: &Path,
+            _prompt: &str,
+            reuse_id: Option<&str>,
+            _iteration: u32,
+            _session: Option<&str>,
+        ) -> Result<String> {
+            match reuse_id {
+                Some(id) if self.dead.iter().any(|d| d == id) => {
+                    Err(SandboxError::container_unhealthy(format!("Container {id} is dead")).into())
+                }
+                Some(id) => Ok(format!("ran in {id}")),
+                None => Ok("ran in fresh container".to_string()),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_in_sandbox_recreates_dead_container() {
+        let sandbox = DeadContainerSandbox {
+            dead: vec!["ralph-old".to_string()],
+            ..Default::default()
+        };
+        let mut container = Some("ralph-old".to_string());
+        let mut recreations = 0;
+
+        let output = run_in_sandbox(
+            &sandbox,
+            Path::new("."),
+            "prompt",
+            &mut container,
+            &mut recreations,
+            1,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(output, "ran in ralph-new1");
+        assert_eq!(container.as_deref(), Some("ralph-new1"));
+        assert_eq!(recreations, 1);
+        assert_eq!(*sandbox.removed.lock().unwrap(), vec!["ralph-old"]);
+    }
+
+    #[tokio::test]
+    async fn test_run_in_sandbox_caps_recreations() {
+        let sandbox = DeadContainerSandbox {
+            dead: vec!["ralph-old".to_string(), "ralph-new1".to_string()],
+            ..Default::default()
+        };
+        let mut container = Some("ralph-old".to_string());
+        let mut recreations = MAX_CONTAINER_RECREATIONS - 1;
+
+        // The replacement is dead too: the iteration fails, keeping the new name
+        let err = run_in_sandbox(
+            &sandbox,
+            Path::new("."),
+            "prompt",
+            &mut container,
+            &mut recreations,
+            1,
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert!(is_container_unhealthy(&err));
+        assert_eq!(container.as_deref(), Some("ralph-new1"));
+        assert_eq!(recreations, MAX_CONTAINER_RECREATIONS);
+
+        // Cap reached: no further recreation
+        let err = run_in_sandbox(
+            &sandbox,
+            Path::new("."),
+            "prompt",
+            &mut container,
+            &mut recreations,
+            2,
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert!(is_container_unhealthy(&err));
+        assert_eq!(container.as_deref(), Some("ralph-new1"));
+        assert_eq!(*sandbox.created.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_is_container_unhealthy_only_for_unhealthy_errors() {
+        let unhealthy = anyhow::Error::from(SandboxError::container_unhealthy("dead"))
+            .context("Agent iteration failed");
+        assert!(is_container_unhealthy(&unhealthy));
+        assert!(!is_container_unhealthy(
+            &SandboxError::non_zero_exit(1).into()
+        ));
+        assert!(!is_container_unhealthy(&anyhow::anyhow!(
+            "Container is dead"
+        )));
+    }
+
+    fn breaker_monitoring(cooldown_minutes: u64) -> MonitoringConfig {
+        MonitoringConfig {
+            max_consecutive_errors: 3,
+            circuit_breaker_cooldown_minutes: cooldown_minutes,
+            ..MonitoringConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_circuit_breaker_without_cooldown_trips() {
+        let monitoring = breaker_monitoring(0);
+        let mut state = make_state(1, None);
+
+        state.consecutive_errors = 2;
+        assert_eq!(
+            circuit_breaker_action(&state, &monitoring),
+            BreakerAction::Continue
+        );
+        state.consecutive_errors = 3;
+        assert_eq!(
+            circuit_breaker_action(&state, &monitoring),
+            BreakerAction::Trip
+        );
+    }
+
+    #[test]
+    fn test_circuit_breaker_cools_down_once_then_trips() {
+        let monitoring = breaker_monitoring(5);
+        let mut state = make_state(1, None);
+
+        // First trip: cool down and reset the streak
+        state.consecutive_errors = 3;
+        assert_eq!(
+            circuit_breaker_action(&state, &monitoring),
+            BreakerAction::CoolDown(std::time::Duration::from_mins(5))
+        );
+        begin_cooldown(&mut state);
+        assert_eq!(state.consecutive_errors, 0);
+        assert!(state.circuit_breaker_cooled_down);
+        assert_eq!(
+            circuit_breaker_action(&state, &monitoring),
+            BreakerAction::Continue
+        );
+
+        // Second trip: abort
+        state.consecutive_errors = 3;
+        assert_eq!(
+            circuit_breaker_action(&state, &monitoring),
+            BreakerAction::Trip
+        );
+    }
+
+    #[test]
+    fn test_circuit_breaker_disabled_never_trips() {
+        let monitoring = MonitoringConfig {
+            max_consecutive_errors: 0,
+            circuit_breaker_cooldown_minutes: 5,
+            ..MonitoringConfig::default()
+        };
+        let state = RalphState {
+            consecutive_errors: 100,
+            ..make_state(1, None)
+        };
+        assert_eq!(
+            circuit_breaker_action(&state, &monitoring),
+            BreakerAction::Continue
+        );
+    }
+
+    #[test]
+    fn test_record_agent_output_keeps_session_across_iterations() {
+        let agent = ClaudeProvider::new(crate::config::ClaudeConfig {
+            output_format: "json".to_string(),
+            resume_session: true,
+            ..Default::default()
+        });
+        let mut state = make_state(1, None);
+
+        record_agent_output(
+            &mut state,
+            &agent,
+            r#"{"type":"result","session_id":"sess-1","usage":{"input_tokens":7,"output_tokens":3}}"#,
+        );
+        assert_eq!(state.session_id.as_deref(), Some("sess-1"));
+        assert_eq!(state.total_input_tokens, 7);
+
+        // Output without a session id keeps the previous one
+        record_agent_output(&mut state, &agent, "not json");
+        assert_eq!(state.session_id.as_deref(), Some("sess-1"));
+    }
+
+    #[test]
+    fn test_max_errors_override_takes_precedence() {
+        let state = RalphState {
+            consecutive_errors: 2,
+            ..make_state(1, None)
+        };
+
+        // Config alone: 2 errors is under the limit of 3
+        let mut monitoring = breaker_monitoring(0);
+        apply_max_errors_override(&mut monitoring, None);
+        assert_eq!(monitoring.max_consecutive_errors, 3);
+        assert_eq!(
+            circuit_breaker_action(&state, &monitoring),
+            BreakerAction::Continue
+        );
+
+        // A lower override trips sooner
+        apply_max_errors_override(&mut monitoring, Some(2));
+        assert_eq!(
+            circuit_breaker_action(&state, &monitoring),
+            BreakerAction::Trip
+        );
+
+        // Zero disables the breaker even though the config enables it
+        let mut monitoring = breaker_monitoring(0);
+        apply_max_errors_override(&mut monitoring, Some(0));
+        let state = RalphState {
+            consecutive_errors: 100,
+            ..state
+        };
+        assert_eq!(
+            circuit_breaker_action(&state, &monitoring),
+            BreakerAction::Continue
+        );
+    }
+
+    #[test]
+    fn test_validation_override_combinations() {
+        let config = ValidationConfig {
+            command: "cargo check".to_string(),
+            final_command: Some("cargo test".to_string()),
+            ..Default::default()
+        };
+
+        // No flags: config unchanged
+        let mut validation = config.clone();
+        apply_validation_override(&mut validation, false, None);
+        assert!(validation.enabled);
+        assert_eq!(validation.command, "cargo check");
+        assert_eq!(validation.final_command.as_deref(), Some("cargo test"));
+
+        // --validation-cmd replaces the command
+        let mut validation = config.clone();
+        apply_validation_override(&mut validation, false, Some("cargo test".to_string()));
+        assert!(validation.enabled);
+        assert_eq!(validation.command, "cargo test");
+
+        // --no-validation disables validation, including the final gate
+        let mut validation = config.clone();
+        apply_validation_override(&mut validation, true, None);
+        assert!(!validation.enabled);
+        assert!(validation.final_command.is_none());
+
+        // --no-validation wins over --validation-cmd
+        let mut validation = config.clone();
+        apply_validation_override(&mut validation, true, Some("cargo test".to_string()));
+        assert!(!validation.enabled);
+    }
+
+    #[tokio::test]
+    async fn test_check_idle_by_commit_count() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut completion = CompletionConfig::default();
+
+        // Counting off: no count, even with a start commit
+        assert_eq!(
+            idle_commit_count(dir.path(), &completion, Some("abc123")).await,
+            None
+        );
+
+        // Counting on, HEAD hash changes but no count is available: idle
+        completion.idle_by_commit_count = true;
+        let mut detector = CompletionDetector::new(1);
+        detector.record_commit(Some("abc123".to_string()));
+        assert!(
+            check_idle(
+                &mut detector,
+                dir.path(),
+                &completion,
+                None,
+                Some("def456"),
+                false
+            )
+            .await
+        );
+
+        // Hash mode sees the same change as progress
+        completion.idle_by_commit_count = false;
+        let mut detector = CompletionDetector::new(1);
+        detector.record_commit(Some("abc123".to_string()));
+        assert!(
+            !check_idle(
+                &mut detector,
+                dir.path(),
+                &completion,
+                None,
+                Some("def456"),
+                false
+            )
+            .await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_completion_strategies() {
+        let dir = tempfile::tempdir().unwrap();
+        let detector = |strategy| CompletionDetector::new(1).with_strategy(strategy);
+
+        // Default: idle finishes before the success command is consulted
+        let default = detector(CompletionStrategy::default());
+        assert_eq!(
+            check_completion(&default, 1, dir.path(), Some("false"), true, "").await,
+            CompletionCheck::Detected
+        );
+        assert_eq!(
+            check_completion(&default, 1, dir.path(), Some("true"), false, "").await,
+            CompletionCheck::SuccessCommand
+        );
+        assert_eq!(
+            check_completion(&default, 1, dir.path(), None, false, "").await,
+            CompletionCheck::Pending
+        );
+
+        // Idle only: a passing success command is ignored
+        let idle = detector(CompletionStrategy::Idle);
+        assert_eq!(
+            check_completion(&idle, 1, dir.path(), Some("true"), false, "").await,
+            CompletionCheck::Pending
+        );
+
+        // All: idle alone waits for the command
+        let all = detector(CompletionStrategy::All(vec![
+            CompletionStrategy::Idle,
+            CompletionStrategy::Command,
+        ]));
+        assert_eq!(
+            check_completion(&all, 1, dir.path(), Some("false"), true, "").await,
+            CompletionCheck::Pending
+        );
+        assert_eq!(
+            check_completion(&all, 1, dir.path(), Some("true"), true, "").await,
+            CompletionCheck::SuccessCommand
+        );
+    }
+
+    #[test]
+    fn test_validation_error_excerpt_head_by_default() {
+        let error = "Validation failed (cargo check):\n1\n2\n3\n4\n5\n6";
+        assert_eq!(
+            validation_error_excerpt(error, None),
+            "Validation failed (cargo check):\n1\n2\n3\n4"
+        );
+        assert_eq!(validation_error_excerpt("one line", None), "one line");
+    }
+
+    #[test]
+    fn test_validation_error_excerpt_tail() {
+        let error = "Validation failed (cargo check):\n1\n2\n3\nerror[E0308]: mismatched types";
+        assert_eq!(
+            validation_error_excerpt(error, Some(2)),
+            "3\nerror[E0308]: mismatched types"
+        );
+        // More lines than the error has: everything
+        assert_eq!(validation_error_excerpt(error, Some(50)), error);
+        // 0 shows the full output
+        assert_eq!(validation_error_excerpt(error, Some(0)), error);
+        assert_eq!(validation_error_excerpt("", Some(3)), "");
+    }
+
+    #[test]
+    fn test_apply_simulate_overrides() {
+        let mut config = Config::default();
+        config.completion.success_command = Some("cargo test".to_string());
+        config.agent.fallback_provider = Some("cursor".to_string());
+        config.validation.final_command = Some("cargo test".to_string());
+
+        apply_simulate_overrides(&mut config);
+        assert!(!config.sandbox.enabled);
+        assert!(!config.validation.enabled);
+        assert!(config.validation.final_command.is_none());
+        assert!(config.completion.success_command.is_none());
+        assert!(!config.git.auto_commit);
+        assert!(!config.git.auto_push);
+        assert!(config.agent.fallback_provider.is_none());
+    }
+
+    #[test]
+    fn test_resolve_agent_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("packages/app")).unwrap();
+        let agent = |working_dir: Option<&str>| AgentConfig {
+            working_dir: working_dir.map(String::from),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            resolve_agent_dir(dir.path(), &agent(None)).unwrap(),
+            dir.path()
+        );
+        assert_eq!(
+            resolve_agent_dir(dir.path(), &agent(Some("packages/app"))).unwrap(),
+            dir.path().join("packages/app")
+        );
+
+        let err = resolve_agent_dir(dir.path(), &agent(Some("packages/missing"))).unwrap_err();
+        assert!(err.to_string().contains("not found"));
+        assert!(resolve_agent_dir(dir.path(), &agent(Some("../elsewhere"))).is_err());
+        assert!(resolve_agent_dir(dir.path(), &agent(Some("/tmp"))).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_agent_dir_rejects_symlink_escape() {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("link")).unwrap();
+        let agent = AgentConfig {
+            working_dir: Some("link".to_string()),
+            ..Default::default()
+        };
+
+        let err = resolve_agent_dir(dir.path(), &agent).unwrap_err();
+        assert!(err.to_string().contains("outside the project"));
+    }
+
+    #[test]
+    fn test_validation_cmd_enables_disabled_validation() {
+        let mut validation = ValidationConfig {
+            enabled: false,
+            ..Default::default()
+        };
+        apply_validation_override(&mut validation, false, Some("make check".to_string()));
+        assert!(validation.enabled);
+        assert_eq!(validation.command, "make check");
+    }
+
+    #[test]
+    fn test_circuit_breaker_details_carry_reason_and_count() {
+        let state = RalphState {
+            consecutive_errors: 3,
+            last_error: Some(
+                "Validation error:line 1\nline 2\nline 3\nline 4\nline 5\nline 6\nline 7\n"
+                    .to_string(),
+            ),
+            ..make_state(6, None)
+        };
+
+        let details = circuit_breaker_details(&state);
+        assert_eq!(details.iteration, Some(5)); // The iteration that failed
+        let ctx = details.context.unwrap();
+        assert_eq!(ctx["reason"], "circuit_breaker");
+        assert_eq!(ctx["consecutive_errors"], 3);
+        let last_error = ctx["last_error"].as_str().unwrap();
+        assert!(last_error.starts_with("Validation error:line 1"));
+        assert!(last_error.ends_with("line 5"));
+    }
+
+    #[tokio::test]
+    async fn test_check_circuit_breaker_persists_cooldown() {
+        let dir = tempfile::tempdir().unwrap();
+        let monitoring = breaker_monitoring(1);
+        let mut state = RalphState {
+            consecutive_errors: 3,
+            ..make_state(1, None)
+        };
+        let notifier = Notifier::new(NotificationConfig::default());
+        // Signalled up front so the cool-down sleep returns immediately
+        let (tx, mut shutdown) = tokio::sync::watch::channel(true);
+
+        let reason = check_circuit_breaker(
+            &mut state,
+            dir.path(),
+            &monitoring,
+            "errors",
+            &notifier,
+            &mut shutdown,
+        )
+        .await
+        .unwrap();
+        assert!(reason.is_none());
+        let saved = RalphState::load(dir.path()).unwrap().unwrap();
+        assert!(saved.circuit_breaker_cooled_down);
+        assert_eq!(saved.consecutive_errors, 0);
+
+        state.consecutive_errors = 3;
+        let reason = check_circuit_breaker(
+            &mut state,
+            dir.path(),
+            &monitoring,
+            "errors",
+            &notifier,
+            &mut shutdown,
+        )
+        .await
+        .unwrap();
+        assert!(
+            matches!(reason, Some(TerminationReason::Error(msg)) if msg.starts_with(CIRCUIT_BREAKER_MESSAGE))
+        );
+        drop(tx);
+    }
+
+    #[tokio::test]
+    async fn test_check_branch_circuit_breaker_cools_down_then_fails_branch() {
+        let dir = tempfile::tempdir().unwrap();
+        let monitoring = breaker_monitoring(1);
+        let mut state = RalphState {
+            consecutive_errors: 3,
+            ..make_state(1, None)
+        };
+        // Cancelled up front so the cool-down sleep returns immediately
+        let (tx, mut cancelled) = tokio::sync::watch::channel(true);
+
+        check_branch_circuit_breaker(
+            &mut state,
+            dir.path(),
+            &monitoring,
+            "errors",
+            &mut cancelled,
+        )
+        .await
+        .unwrap();
+        let saved = RalphState::load(dir.path()).unwrap().unwrap();
+        assert!(saved.circuit_breaker_cooled_down);
+        assert_eq!(saved.consecutive_errors, 0);
+
+        state.consecutive_errors = 3;
+        let err = check_branch_circuit_breaker(
+            &mut state,
+            dir.path(),
+            &monitoring,
+            "errors",
+            &mut cancelled,
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            err.to_string().starts_with(CIRCUIT_BREAKER_MESSAGE),
+            "{err}"
+        );
+        assert!(!RalphState::load(dir.path()).unwrap().unwrap().active);
+        drop(tx);
+    }
+
+    #[test]
+    fn test_recoverable_error_classify() {
+        let classify = |error: anyhow::Error| RecoverableError::classify(&error);
+        assert_eq!(
+            classify(SandboxError::timeout(std::time::Duration::from_secs(90)).into()),
+            Some(RecoverableError::Timeout)
+        );
+        assert_eq!(
+            classify(anyhow::anyhow!("Agent execution timed out after 5 minutes")),
+            Some(RecoverableError::Timeout)
+        );
+        assert_eq!(
+            classify(anyhow::anyhow!("HTTP 429: Too Many Requests")),
+            Some(RecoverableError::RateLimit)
+        );
+        assert_eq!(
+            classify(anyhow::anyhow!("Daily quota exceeded")),
+            Some(RecoverableError::RateLimit)
+        );
+        assert_eq!(
+            classify(SandboxError::non_zero_exit(1).into()),
+            Some(RecoverableError::Crash)
+        );
+        assert_eq!(
+            classify(anyhow::anyhow!("Failed to spawn cursor-agent")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_record_failed_iteration_advances_and_saves() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = RalphState::load_or_create(dir.path(), Mode::Build).unwrap();
+        let iteration = state.iteration;
+
+        record_failed_iteration(
+            &mut state,
+            dir.path(),
+            ErrorKind::Hook,
+            "Pre-iteration hook error:boom".to_string(),
+        )
+        .unwrap();
+
+        let saved = RalphState::load(dir.path()).unwrap().unwrap();
+        assert_eq!(saved.iteration, iteration + 1);
+        assert_eq!(saved.consecutive_errors, 1);
+        assert!(saved.last_iteration_at.is_some());
+        assert_eq!(
+            saved.last_error.as_deref(),
+            Some("Pre-iteration hook error:boom")
+        );
+    }
+
+    #[test]
+    fn test_backoff_seconds_default_schedule() {
+        let backoff = BackoffConfig::default();
+        let schedule: Vec<u64> = (0..7).map(|n| backoff_seconds(n, &backoff)).collect();
+        assert_eq!(schedule, vec![30, 60, 120, 240, 480, 600, 600]);
+        assert_eq!(backoff_seconds(u32::MAX, &backoff), 600);
+    }
+
+    #[test]
+    fn test_backoff_seconds_custom() {
+        let backoff = BackoffConfig {
+            base_seconds: 5,
+            multiplier: 1.5,
+            max_seconds: 20,
+        };
+        let schedule: Vec<u64> = (0..5).map(|n| backoff_seconds(n, &backoff)).collect();
+        assert_eq!(schedule, vec![5, 7, 11, 16, 20]);
+
+        // A multiplier of 1 keeps a constant wait
+        let constant = BackoffConfig {
+            multiplier: 1.0,
+            ..BackoffConfig::default()
+        };
+        assert_eq!(backoff_seconds(10, &constant), 30);
+
+        // max_seconds below base caps every wait
+        let capped = BackoffConfig {
+            max_seconds: 10,
+            ..BackoffConfig::default()
+        };
+        assert_eq!(backoff_seconds(0, &capped), 10);
+    }
+
     #[test]
     fn test_resolve_timeout_uses_provider_specific() {
         // Provider timeout should override global
@@ -1821,35 +4871,336 @@ timeout_minutes = 45
         assert_eq!(resolve_timeout(&config, Provider::Claude), 45);
     }
 
-    #[test]
-    fn test_resolve_timeout_different_providers() {
-        // Different timeouts for different providers
-        let toml = r"
-[agent.cursor]
-timeout_minutes = 30
+    #[test]
+    fn test_resolve_timeout_different_providers() {
+        // Different timeouts for different providers
+        let toml = r"
+[agent.cursor]
+timeout_minutes = 30
+
+[agent.claude]
+timeout_minutes = 180
+
+[sandbox.resources]
+timeout_minutes = 60
+";
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(resolve_timeout(&config, Provider::Cursor), 30);
+        assert_eq!(resolve_timeout(&config, Provider::Claude), 180);
+    }
+
+    #[test]
+    fn test_resolve_timeout_fallback_chain() {
+        let toml = r"
+[agent]
+timeout_minutes = 90
+
+[agent.claude]
+timeout_minutes = 180
+
+[sandbox.resources]
+timeout_minutes = 60
+";
+        let config: Config = toml::from_str(toml).unwrap();
+        // Provider-specific beats the agent default
+        assert_eq!(resolve_timeout(&config, Provider::Claude), 180);
+        // Agent default beats the sandbox timeout
+        assert_eq!(resolve_timeout(&config, Provider::Cursor), 90);
+        assert_eq!(resolve_timeout(&config, Provider::Ollama), 90);
+
+        // Without the agent default, the sandbox timeout applies
+        let mut config = config;
+        config.agent.timeout_minutes = None;
+        assert_eq!(resolve_timeout(&config, Provider::Claude), 180);
+        assert_eq!(resolve_timeout(&config, Provider::Cursor), 60);
+    }
+
+    #[test]
+    fn test_resolve_timeout_default_config() {
+        // Default config should use sandbox.resources.timeout_minutes (60)
+        let config = Config::default();
+        assert_eq!(resolve_timeout(&config, Provider::Cursor), 60);
+        assert_eq!(resolve_timeout(&config, Provider::Claude), 60);
+    }
+
+    // -------------------------------------------------------------------------
+    // Branch Build Tests
+    // -------------------------------------------------------------------------
+
+    #[tokio::test]
+    async fn test_run_bounded_limits_in_flight_builds() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_seen = Arc::new(AtomicUsize::new(0));
+
+        let results = run_bounded(
+            (0..5).collect(),
+            2,
+            BRANCH_CANCEL_GRACE,
+            |_| false,
+            |i: u32, _| {
+                let in_flight = Arc::clone(&in_flight);
+                let max_seen = Arc::clone(&max_seen);
+                async move {
+                    let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_seen.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                    i
+                }
+            },
+        )
+        .await;
+
+        let mut sorted = results.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, vec![0, 1, 2, 3, 4]);
+        assert_eq!(max_seen.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_failed_dependency() {
+        let branch =
+            parse_implementation_plan("## Branch: c\nGoal: C\nBase: master\nDepends: a, b\n")
+                .branches
+                .remove(0);
+
+        let results = vec![BranchResult::success("a", 2, None)];
+        assert_eq!(failed_dependency(&branch, &results), None);
+
+        let results = vec![
+            BranchResult::success("a", 2, None),
+            BranchResult::failure("b", 1, "Build failed".to_string()),
+            BranchResult::failure("unrelated", 1, "Build failed".to_string()),
+        ];
+        assert_eq!(failed_dependency(&branch, &results), Some("b"));
+    }
+
+    #[test]
+    fn test_branch_overrides_precedence() {
+        let plan = parse_implementation_plan(
+            "---\nprovider: cursor\nmax_iterations: 40\n---\n\
+             ## Branch: own\nGoal: A\nBase: master\nProvider: claude\nMaxIterations: 5\n\
+             ## Branch: plain\nGoal: B\nBase: master\n",
+        );
+        let mut branches = plan.branches;
+        for branch in &mut branches {
+            plan.defaults.apply(branch);
+        }
+        let (own, bare) = (&branches[0], &branches[1]);
+
+        // CLI beats everything
+        assert_eq!(
+            branch_overrides(own, IterationCap::Max(9), Some("ollama")),
+            (Some(9), Some("ollama"))
+        );
+        assert_eq!(
+            branch_overrides(own, IterationCap::Unlimited, None),
+            (None, Some("claude"))
+        );
+        // Branch metadata beats the plan defaults
+        assert_eq!(
+            branch_overrides(own, IterationCap::Default, None),
+            (Some(5), Some("claude"))
+        );
+        // Plan defaults apply when nothing else is set
+        assert_eq!(
+            branch_overrides(bare, IterationCap::Default, None),
+            (Some(40), Some("cursor"))
+        );
+        assert_eq!(
+            branch_overrides(bare, IterationCap::Max(2), None),
+            (Some(2), Some("cursor"))
+        );
+    }
+
+    #[test]
+    fn test_branch_overrides_without_flags_uses_plan_caps() {
+        // `ralph loop` with neither --max-iterations nor --unlimited
+        let cap = IterationCap::from_flags(None, false);
+        let plan = parse_implementation_plan(
+            "---\nmax_iterations: 40\n---\n\
+             ## Branch: own\nGoal: A\nBase: master\nMaxIterations: 5\n\
+             ## Branch: plain\nGoal: B\nBase: master\n",
+        );
+        let mut branches = plan.branches;
+        for branch in &mut branches {
+            plan.defaults.apply(branch);
+        }
+        assert_eq!(branch_overrides(&branches[0], cap, None).0, Some(5));
+        assert_eq!(branch_overrides(&branches[1], cap, None).0, Some(40));
+
+        // Without any plan cap the build-mode default applies
+        let bare = parse_implementation_plan("## Branch: bare\nGoal: C\nBase: master\n");
+        assert_eq!(branch_overrides(&bare.branches[0], cap, None).0, Some(20));
+    }
+
+    #[test]
+    fn test_iteration_cap_from_flags() {
+        assert_eq!(IterationCap::from_flags(None, false), IterationCap::Default);
+        assert_eq!(
+            IterationCap::from_flags(Some(3), false),
+            IterationCap::Max(3)
+        );
+        assert_eq!(
+            IterationCap::from_flags(Some(3), true),
+            IterationCap::Unlimited
+        );
+        assert_eq!(
+            IterationCap::from_flags(None, false).resolve(LoopMode::Plan, None),
+            Some(10)
+        );
+        assert_eq!(
+            IterationCap::from_flags(None, false).resolve(LoopMode::Build, None),
+            Some(20)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_in_order_fail_fast_skips_later_items() {
+        let started = std::sync::Mutex::new(Vec::new());
+        let build = |i: u32| {
+            started.lock().unwrap().push(i);
+            async move {
+                if i == 0 {
+                    Err(i)
+                } else {
+                    Ok(i)
+                }
+            }
+        };
+
+        let results = run_in_order(vec![0, 1, 2], Result::is_err, build).await;
+        assert_eq!(results, vec![Err(0)]);
+        assert_eq!(*started.lock().unwrap(), vec![0]);
+
+        // Without fail-fast every item runs
+        started.lock().unwrap().clear();
+        let results = run_in_order(vec![0, 1, 2], |_| false, build).await;
+        assert_eq!(results, vec![Err(0), Ok(1), Ok(2)]);
+        assert_eq!(*started.lock().unwrap(), vec![0, 1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_run_bounded_fail_fast_cancels_in_flight() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        // Running builds see the signal and stop on their own; queued ones never start
+        let started = Arc::new(AtomicUsize::new(0));
+        let results = run_bounded(
+            vec![0, 1, 2, 3],
+            2,
+            BRANCH_CANCEL_GRACE,
+            Result::is_err,
+            |i: u32, mut cancelled| {
+                let started = Arc::clone(&started);
+                async move {
+                    started.fetch_add(1, Ordering::SeqCst);
+                    if i == 0 {
+                        return Err(i);
+                    }
+                    signalled(&mut cancelled).await;
+                    // Cleanup runs before the result is returned
+                    Ok(i * 10)
+                }
+            },
+        )
+        .await;
+
+        assert_eq!(results, vec![Err(0), Ok(10)]);
+        assert_eq!(started.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_run_branch_loop_stops_when_cancelled() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("PROMPT_build.md"), "Build it").unwrap();
+        let (cancel, cancelled) = tokio::sync::watch::channel(true);
+
+        let err = run_branch_loop(
+            dir.path(),
+            &Config::default(),
+            Some(5),
+            true,
+            None,
+            cancelled,
+        )
+        .await
+        .unwrap_err();
+        drop(cancel);
+
+        assert_eq!(err.to_string(), FAIL_FAST_CANCELLED);
+        assert!(!RalphState::load(dir.path()).unwrap().unwrap().active);
+    }
 
-[agent.claude]
-timeout_minutes = 180
+    #[tokio::test]
+    async fn test_run_bounded_fail_fast_aborts_after_grace() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        // A build that ignores the signal is aborted once the grace period ends
+        let completed = Arc::new(AtomicUsize::new(0));
+        let started = std::time::Instant::now();
+        let results = run_bounded(
+            vec![0, 1, 2, 3],
+            2,
+            std::time::Duration::from_millis(50),
+            Result::is_err,
+            |i: u32, _| {
+                let completed = Arc::clone(&completed);
+                async move {
+                    if i == 0 {
+                        return Err(i);
+                    }
+                    tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+                    completed.fetch_add(1, Ordering::SeqCst);
+                    Ok(i)
+                }
+            },
+        )
+        .await;
 
-[sandbox.resources]
-timeout_minutes = 60
-";
-        let config: Config = toml::from_str(toml).unwrap();
-        assert_eq!(resolve_timeout(&config, Provider::Cursor), 30);
-        assert_eq!(resolve_timeout(&config, Provider::Claude), 180);
+        assert_eq!(results, vec![Err(0)]);
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        assert_eq!(completed.load(Ordering::SeqCst), 0);
     }
 
     #[test]
-    fn test_resolve_timeout_default_config() {
-        // Default config should use sandbox.resources.timeout_minutes (60)
-        let config = Config::default();
-        assert_eq!(resolve_timeout(&config, Provider::Cursor), 60);
-        assert_eq!(resolve_timeout(&config, Provider::Claude), 60);
+    fn test_cancel_branch_marks_worktree_inactive() {
+        let dir = tempfile::tempdir().unwrap();
+        let wt_path = worktree_path(dir.path(), "feature-a");
+        std::fs::create_dir_all(&wt_path).unwrap();
+        let state = RalphState {
+            active: true,
+            ..RalphState::default()
+        };
+        state.save(&wt_path).unwrap();
+
+        let result = cancel_branch(dir.path(), "feature-a");
+
+        assert!(!result.success);
+        assert_eq!(result.error.as_deref(), Some(FAIL_FAST_CANCELLED));
+        assert!(!RalphState::load(&wt_path).unwrap().unwrap().active);
+
+        // A branch whose worktree has no state is still reported
+        assert!(!cancel_branch(dir.path(), "missing").success);
     }
 
-    // -------------------------------------------------------------------------
-    // Branch Build Tests
-    // -------------------------------------------------------------------------
+    #[tokio::test]
+    async fn test_run_bounded_zero_limit_still_runs() {
+        let results = run_bounded(
+            vec![1, 2, 3],
+            0,
+            BRANCH_CANCEL_GRACE,
+            |_| false,
+            |i: u32, _| async move { i * 2 },
+        )
+        .await;
+        assert_eq!(results.len(), 3);
+    }
 
     #[test]
     fn test_is_branch_incomplete_with_unchecked_tasks() {
@@ -1947,6 +5298,36 @@ Base: master
         assert!(result.pr_url.is_none());
     }
 
+    #[test]
+    fn test_branch_results_serialize_all_fields() {
+        let results = vec![
+            BranchResult::success("branch-a", 5, Some("http://pr/1".to_string())),
+            BranchResult::failure("branch-b", 2, "Build failed".to_string()),
+        ];
+        let value: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&results).unwrap()).unwrap();
+
+        assert_eq!(
+            value,
+            serde_json::json!([
+                {
+                    "branch": "branch-a",
+                    "success": true,
+                    "iterations": 5,
+                    "error": null,
+                    "pr_url": "http://pr/1",
+                },
+                {
+                    "branch": "branch-b",
+                    "success": false,
+                    "iterations": 2,
+                    "error": "Build failed",
+                    "pr_url": null,
+                },
+            ])
+        );
+    }
+
     #[test]
     fn test_format_branch_summary_all_success() {
         let results = vec![
@@ -1975,6 +5356,45 @@ Base: master
         assert!(summary.contains("Build failed"));
     }
 
+    #[test]
+    fn test_branch_outcome() {
+        let all_success = branch_outcome(&[
+            BranchResult::success("branch-a", 5, None),
+            BranchResult::success("branch-b", 3, None),
+        ]);
+        assert_eq!(all_success.final_iteration, 8);
+        assert!(all_success.ensure_success().is_ok());
+
+        let mixed = branch_outcome(&[
+            BranchResult::success("branch-a", 5, None),
+            BranchResult::failure("branch-b", 3, "Build failed".to_string()),
+        ]);
+        assert_eq!(mixed.error_count, 1);
+        assert_eq!(
+            mixed.ensure_success().unwrap_err().to_string(),
+            "1 branch(es) failed"
+        );
+    }
+
+    #[test]
+    fn test_termination_reason_labels() {
+        assert_eq!(
+            TerminationReason::MaxIterations.label(),
+            "max_iterations_reached"
+        );
+        assert_eq!(TerminationReason::CompletionDetected.label(), "agent_idle");
+        assert_eq!(TerminationReason::Signalled.label(), "cancelled_by_signal");
+        assert_eq!(
+            TerminationReason::Error(format!("{CIRCUIT_BREAKER_MESSAGE}: 5 consecutive errors"))
+                .label(),
+            "circuit_breaker"
+        );
+        assert_eq!(
+            TerminationReason::Error("boom".to_string()).label(),
+            "error"
+        );
+    }
+
     // -------------------------------------------------------------------------
     // E2E Loop Tests
     // -------------------------------------------------------------------------
@@ -1997,42 +5417,300 @@ Base: master
             (dir, project_dir)
         }
 
-        /// Create a minimal config for testing.
-        fn test_config() -> Config {
-            let mut config = Config::default();
-            config.validation.enabled = false; // Disable validation for basic tests
-            config.sandbox.enabled = false;
-            config.git.auto_push = false;
-            config.monitoring.show_progress = false;
-            config
+        /// Create a minimal config for testing.
+        fn test_config() -> Config {
+            let mut config = Config::default();
+            config.validation.enabled = false; // Disable validation for basic tests
+            config.sandbox.enabled = false;
+            config.git.auto_push = false;
+            config.monitoring.show_progress = false;
+            config.backoff.base_seconds = 0; // Retry rate limits right away
+            config
+        }
+
+        /// Create initial state for testing.
+        fn test_state(max_iterations: Option<u32>) -> RalphState {
+            RalphState {
+                active: false,
+                mode: Mode::Build,
+                iteration: 1,
+                max_iterations,
+                started_at: Utc::now(),
+                last_iteration_at: None,
+                error_count: 0,
+                consecutive_errors: 0,
+                circuit_breaker_cooled_down: false,
+                last_error: None,
+                last_commit: None,
+                idle_iterations: 0,
+                commit_count: None,
+                total_input_tokens: 0,
+                total_output_tokens: 0,
+                initial_commit: None,
+                pid: None,
+                session_id: None,
+                error_history: Vec::new(),
+                settings: LoopSettings::default(),
+            }
+        }
+
+        #[tokio::test]
+        async fn test_e2e_loop_max_iterations() {
+            // Test: Loop stops after max iterations (before idle detection triggers)
+            let (_dir, project_dir) = setup_test_project("Test prompt");
+            let prompt_file = project_dir.join("PROMPT_build.md");
+
+            let agent = MockAgentProvider::always_succeed("Agent output");
+
+            // Use high idle_threshold so max_iterations triggers first
+            let mut config = test_config();
+            config.completion.idle_threshold = 10;
+
+            let deps = LoopDependencies {
+                agent: Box::new(agent.clone()),
+                sandbox: None,
+                config,
+                project_dir: project_dir.clone(),
+                prompt_file,
+            };
+
+            let state = test_state(Some(3)); // Max 3 iterations
+
+            let result = run_loop_core(deps, state).await.unwrap();
+
+            assert_eq!(result.termination_reason, TerminationReason::MaxIterations);
+            assert_eq!(result.final_iteration, 4); // Stopped after iteration > max (4 > 3)
+            assert_eq!(result.error_count, 0);
+            assert_eq!(agent.invocation_count(), 3); // Ran exactly 3 times
+            assert!(result.ensure_success().is_ok());
+        }
+
+        #[tokio::test]
+        async fn test_e2e_loop_promise_completes() {
+            // Test: A configured promise in agent output stops the loop before idle
+            let (_dir, project_dir) = setup_test_project("Test prompt");
+            let prompt_file = project_dir.join("PROMPT_build.md");
+
+            let agent = MockAgentProvider::new(vec![
+                MockResponse::Success("Working".to_string()),
+                MockResponse::Success("Finished <promise>DONE</promise>".to_string()),
+                MockResponse::Success("Should not run".to_string()),
+            ]);
+
+            let mut config = test_config();
+            config.completion.idle_threshold = 10;
+            config.completion.promise = Some("DONE".to_string());
+
+            let deps = LoopDependencies {
+                agent: Box::new(agent.clone()),
+                sandbox: None,
+                config,
+                project_dir: project_dir.clone(),
+                prompt_file,
+            };
+
+            let result = run_loop_core(deps, test_state(Some(10))).await.unwrap();
+
+            assert_eq!(
+                result.termination_reason,
+                TerminationReason::CompletionDetected
+            );
+            assert_eq!(agent.invocation_count(), 2);
+        }
+
+        #[tokio::test]
+        async fn test_e2e_loop_time_budget_exceeded() {
+            // Test: Loop stops before invoking the agent once max_minutes has elapsed
+            let (_dir, project_dir) = setup_test_project("Test prompt");
+            let prompt_file = project_dir.join("PROMPT_build.md");
+
+            let agent = MockAgentProvider::always_succeed("Agent output");
+
+            let mut config = test_config();
+            config.budget.max_minutes = Some(60);
+
+            let deps = LoopDependencies {
+                agent: Box::new(agent.clone()),
+                sandbox: None,
+                config,
+                project_dir: project_dir.clone(),
+                prompt_file,
+            };
+
+            let mut state = test_state(Some(10));
+            state.started_at = Utc::now() - chrono::Duration::minutes(61);
+
+            let result = run_loop_core(deps, state).await.unwrap();
+
+            assert_eq!(result.termination_reason, TerminationReason::BudgetExceeded);
+            assert_eq!(result.final_iteration, 1);
+            assert_eq!(agent.invocation_count(), 0);
+
+            let saved = RalphState::load(&project_dir).unwrap().unwrap();
+            assert!(!saved.active);
+        }
+
+        #[tokio::test]
+        async fn test_e2e_loop_within_time_budget() {
+            // Test: A generous time budget does not interfere with normal termination
+            let (_dir, project_dir) = setup_test_project("Test prompt");
+            let prompt_file = project_dir.join("PROMPT_build.md");
+
+            let agent = MockAgentProvider::always_succeed("Agent output");
+
+            let mut config = test_config();
+            config.completion.idle_threshold = 10;
+            config.budget.max_minutes = Some(60);
+            config.budget.max_iterations_per_hour = Some(100);
+
+            let deps = LoopDependencies {
+                agent: Box::new(agent.clone()),
+                sandbox: None,
+                config,
+                project_dir: project_dir.clone(),
+                prompt_file,
+            };
+
+            let result = run_loop_core(deps, test_state(Some(2))).await.unwrap();
+
+            assert_eq!(result.termination_reason, TerminationReason::MaxIterations);
+            assert_eq!(agent.invocation_count(), 2);
+        }
+
+        #[tokio::test]
+        async fn test_e2e_loop_idle_detection() {
+            // Test: Loop stops when agent is idle (no commits) for idle_threshold iterations
+            // Default idle_threshold is 2, so after 2 idle iterations, loop should complete
+            let (_dir, project_dir) = setup_test_project("Test prompt");
+            let prompt_file = project_dir.join("PROMPT_build.md");
+
+            // Agent produces output but no commits (test has no git repo)
+            let agent = MockAgentProvider::always_succeed("Working...");
+
+            let deps = LoopDependencies {
+                agent: Box::new(agent.clone()),
+                sandbox: None,
+                config: test_config(),
+                project_dir: project_dir.clone(),
+                prompt_file,
+            };
+
+            let state = test_state(Some(10)); // High max to ensure idle detection triggers first
+
+            let result = run_loop_core(deps, state).await.unwrap();
+
+            assert_eq!(
+                result.termination_reason,
+                TerminationReason::CompletionDetected
+            );
+            // With idle_threshold=2, completes after 2 idle iterations
+            assert_eq!(result.final_iteration, 2);
+            assert_eq!(agent.invocation_count(), 2);
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn test_e2e_loop_iteration_delay() {
+            // Test: one pause between the two idle iterations, none after the last
+            // (on tokio's paused clock, which jumps ahead while the loop sleeps)
+            let (_dir, project_dir) = setup_test_project("Test prompt");
+            let mut config = test_config();
+            config.agent.iteration_delay_seconds = 1;
+            let deps = LoopDependencies {
+                agent: Box::new(MockAgentProvider::always_succeed("Working...")),
+                sandbox: None,
+                config,
+                project_dir: project_dir.clone(),
+                prompt_file: project_dir.join("PROMPT_build.md"),
+            };
+
+            let start = tokio::time::Instant::now();
+            let result = run_loop_core(deps, test_state(Some(10))).await.unwrap();
+            let elapsed = start.elapsed();
+
+            assert_eq!(result.final_iteration, 2);
+            assert_eq!(elapsed, std::time::Duration::from_secs(1));
+        }
+
+        fn final_gate_deps(project_dir: &Path, final_command: &str) -> LoopDependencies {
+            let mut config = test_config();
+            config.validation.final_command = Some(final_command.to_string());
+            LoopDependencies {
+                agent: Box::new(MockAgentProvider::always_succeed("Working...")),
+                sandbox: None,
+                config,
+                project_dir: project_dir.to_path_buf(),
+                prompt_file: project_dir.join("PROMPT_build.md"),
+            }
+        }
+
+        #[tokio::test]
+        async fn test_e2e_loop_final_validation_passes() {
+            // Test: a passing final gate keeps the idle completion
+            if crate::agent::is_nix_sandbox() {
+                return;
+            }
+            let (_dir, project_dir) = setup_test_project("Test prompt");
+            let deps = final_gate_deps(&project_dir, "sh -c 'touch .final-ran'");
+
+            let result = run_loop_core(deps, test_state(Some(10))).await.unwrap();
+
+            assert_eq!(
+                result.termination_reason,
+                TerminationReason::CompletionDetected
+            );
+            assert!(result.ensure_success().is_ok());
+            assert!(project_dir.join(".final-ran").exists());
+        }
+
+        #[tokio::test]
+        async fn test_e2e_loop_final_validation_fails() {
+            // Test: a failing final gate turns the idle completion into a failure
+            if crate::agent::is_nix_sandbox() {
+                return;
+            }
+            let (_dir, project_dir) = setup_test_project("Test prompt");
+            let deps = final_gate_deps(&project_dir, "sh -c 'echo tests are red >&2; exit 1'");
+
+            let result = run_loop_core(deps, test_state(Some(10))).await.unwrap();
+
+            assert_eq!(result.termination_reason.label(), "final_validation_failed");
+            let err = result.ensure_success().unwrap_err().to_string();
+            assert!(err.starts_with(FINAL_VALIDATION_MESSAGE), "{err}");
+            assert!(err.contains("tests are red"), "{err}");
+
+            // Recorded like a validation failure, for the prompt on resume
+            let state = RalphState::load(&project_dir).unwrap().unwrap();
+            assert!(state.last_error.unwrap().starts_with("Validation error:"));
+            assert!(!state.active);
         }
 
-        /// Create initial state for testing.
-        fn test_state(max_iterations: Option<u32>) -> RalphState {
-            RalphState {
-                active: false,
-                mode: Mode::Build,
-                iteration: 1,
-                max_iterations,
-                started_at: Utc::now(),
-                last_iteration_at: None,
-                error_count: 0,
-                consecutive_errors: 0,
-                last_error: None,
-                last_commit: None,
-                idle_iterations: 0,
+        #[tokio::test]
+        async fn test_e2e_loop_final_validation_skipped_without_completion() {
+            // Test: the gate only runs when the loop completed
+            if crate::agent::is_nix_sandbox() {
+                return;
             }
+            let (_dir, project_dir) = setup_test_project("Test prompt");
+            let mut deps = final_gate_deps(&project_dir, "sh -c 'touch .final-ran; exit 1'");
+            deps.config.completion.idle_threshold = 10;
+
+            let result = run_loop_core(deps, test_state(Some(2))).await.unwrap();
+
+            assert_eq!(result.termination_reason, TerminationReason::MaxIterations);
+            assert!(!project_dir.join(".final-ran").exists());
         }
 
         #[tokio::test]
-        async fn test_e2e_loop_max_iterations() {
-            // Test: Loop stops after max iterations (before idle detection triggers)
+        async fn test_e2e_loop_retries_empty_output() {
+            // Test: Empty output is a recoverable error that does not count as idle
             let (_dir, project_dir) = setup_test_project("Test prompt");
             let prompt_file = project_dir.join("PROMPT_build.md");
 
-            let agent = MockAgentProvider::always_succeed("Agent output");
+            let agent = MockAgentProvider::new(vec![
+                MockResponse::Success("  \n".to_string()),
+                MockResponse::Success("Working".to_string()),
+            ]);
 
-            // Use high idle_threshold so max_iterations triggers first
             let mut config = test_config();
             config.completion.idle_threshold = 10;
 
@@ -2044,45 +5722,77 @@ Base: master
                 prompt_file,
             };
 
-            let state = test_state(Some(3)); // Max 3 iterations
-
-            let result = run_loop_core(deps, state).await.unwrap();
+            let result = run_loop_core(deps, test_state(Some(2))).await.unwrap();
 
             assert_eq!(result.termination_reason, TerminationReason::MaxIterations);
-            assert_eq!(result.final_iteration, 4); // Stopped after iteration > max (4 > 3)
-            assert_eq!(result.error_count, 0);
-            assert_eq!(agent.invocation_count(), 3); // Ran exactly 3 times
+            assert_eq!(agent.invocation_count(), 2);
+            assert_eq!(result.error_count, 1);
+
+            let saved = RalphState::load(&project_dir).unwrap().unwrap();
+            assert_eq!(saved.idle_iterations, 1);
+            assert_eq!(saved.consecutive_errors, 0);
+            assert_eq!(saved.error_history.len(), 1);
+            assert_eq!(saved.error_history[0].kind, ErrorKind::Agent);
+            assert_eq!(saved.error_history[0].message, EMPTY_OUTPUT_ERROR);
         }
 
         #[tokio::test]
-        async fn test_e2e_loop_idle_detection() {
-            // Test: Loop stops when agent is idle (no commits) for idle_threshold iterations
-            // Default idle_threshold is 2, so after 2 idle iterations, loop should complete
+        async fn test_e2e_loop_empty_output_retries_exhausted() {
+            // Test: Once retries are used up, empty output counts toward idle again
             let (_dir, project_dir) = setup_test_project("Test prompt");
             let prompt_file = project_dir.join("PROMPT_build.md");
 
-            // Agent produces output but no commits (test has no git repo)
-            let agent = MockAgentProvider::always_succeed("Working...");
+            let agent = MockAgentProvider::always_succeed("");
+
+            let mut config = test_config();
+            config.agent.max_empty_retries = 1;
 
             let deps = LoopDependencies {
                 agent: Box::new(agent.clone()),
                 sandbox: None,
-                config: test_config(),
+                config,
                 project_dir: project_dir.clone(),
                 prompt_file,
             };
 
-            let state = test_state(Some(10)); // High max to ensure idle detection triggers first
+            let result = run_loop_core(deps, test_state(Some(10))).await.unwrap();
 
-            let result = run_loop_core(deps, state).await.unwrap();
+            assert_eq!(
+                result.termination_reason,
+                TerminationReason::CompletionDetected
+            );
+            // One retry, then two idle iterations
+            assert_eq!(agent.invocation_count(), 3);
+            assert_eq!(result.error_count, 1);
+        }
+
+        #[tokio::test]
+        async fn test_e2e_loop_empty_output_retries_disabled() {
+            // Test: max_empty_retries = 0 treats empty output as a normal iteration
+            let (_dir, project_dir) = setup_test_project("Test prompt");
+            let prompt_file = project_dir.join("PROMPT_build.md");
+
+            let agent = MockAgentProvider::always_succeed("");
+
+            let mut config = test_config();
+            config.agent.max_empty_retries = 0;
+
+            let deps = LoopDependencies {
+                agent: Box::new(agent.clone()),
+                sandbox: None,
+                config,
+                project_dir: project_dir.clone(),
+                prompt_file,
+            };
+
+            let result = run_loop_core(deps, test_state(Some(10))).await.unwrap();
 
             assert_eq!(
                 result.termination_reason,
                 TerminationReason::CompletionDetected
             );
-            // With idle_threshold=2, completes after 2 idle iterations
-            assert_eq!(result.final_iteration, 2);
             assert_eq!(agent.invocation_count(), 2);
+            assert_eq!(result.error_count, 0);
         }
 
         #[tokio::test]
@@ -2118,6 +5828,68 @@ Base: master
             assert_eq!(agent.invocation_count(), 3);
         }
 
+        #[tokio::test]
+        async fn test_e2e_loop_crash_forgets_persisted_session() {
+            // Test: A crashed agent fails its iteration and drops its session,
+            // then the loop carries on
+            let (_dir, project_dir) = setup_test_project("Test prompt");
+            let prompt_file = project_dir.join("PROMPT_build.md");
+
+            let agent = MockAgentProvider::new(vec![
+                MockResponse::Crash(1),
+                MockResponse::Success("Working...".to_string()),
+                MockResponse::Success("Still working...".to_string()),
+            ]);
+            let deps = LoopDependencies {
+                agent: Box::new(agent.clone()),
+                sandbox: None,
+                config: test_config(),
+                project_dir: project_dir.clone(),
+                prompt_file,
+            };
+            let state = RalphState {
+                session_id: Some("sess-1".to_string()),
+                ..test_state(Some(10))
+            };
+
+            let result = run_loop_core(deps, state).await.unwrap();
+            assert_eq!(
+                result.termination_reason,
+                TerminationReason::CompletionDetected
+            );
+            assert_eq!(result.error_count, 1);
+            assert_eq!(agent.invocation_count(), 3);
+
+            let saved = RalphState::load(&project_dir).unwrap().unwrap();
+            assert_eq!(saved.session_id, None);
+            let crash = &saved.error_history[0];
+            assert!(
+                crash.message.contains("Agent exited with code 1"),
+                "{crash:?}"
+            );
+        }
+
+        #[tokio::test]
+        async fn test_e2e_loop_repeated_crashes_trip_circuit_breaker() {
+            // Test: Crashes count toward the circuit breaker instead of ending the loop
+            let (_dir, project_dir) = setup_test_project("Test prompt");
+            let prompt_file = project_dir.join("PROMPT_build.md");
+
+            let mut config = test_config();
+            config.monitoring.max_consecutive_errors = 2;
+            let deps = LoopDependencies {
+                agent: Box::new(MockAgentProvider::new(vec![MockResponse::Crash(2)])),
+                sandbox: None,
+                config,
+                project_dir: project_dir.clone(),
+                prompt_file,
+            };
+
+            let result = run_loop_core(deps, test_state(Some(10))).await.unwrap();
+            assert_eq!(result.termination_reason.label(), "circuit_breaker");
+            assert_eq!(result.error_count, 2);
+        }
+
         #[tokio::test]
         async fn test_e2e_loop_validation_error_recovery() {
             // Test: Validation errors are appended to prompt for next iteration
@@ -2240,6 +6012,9 @@ Base: master
                 TerminationReason::Error(_)
             ));
             assert_eq!(result.error_count, 3); // Exactly 3 errors before circuit breaker
+            assert_eq!(result.final_iteration, 4);
+            assert_eq!(result.termination_reason.label(), "circuit_breaker");
+            assert!(result.ensure_success().is_err());
         }
 
         #[tokio::test]
@@ -2340,9 +6115,18 @@ Base: master
                 last_iteration_at: None,
                 error_count: 0,
                 consecutive_errors: 0,
+                circuit_breaker_cooled_down: false,
                 last_error: None,
                 last_commit: None,
                 idle_iterations: 0,
+                commit_count: None,
+                total_input_tokens: 0,
+                total_output_tokens: 0,
+                initial_commit: None,
+                pid: None,
+                session_id: None,
+                error_history: Vec::new(),
+                settings: LoopSettings::default(),
             };
 
             let result = run_loop_core(deps, state).await.unwrap();
@@ -2390,9 +6174,18 @@ Base: master
                 last_iteration_at: None,
                 error_count: 0,
                 consecutive_errors: 0,
+                circuit_breaker_cooled_down: false,
                 last_error: None,
                 last_commit: None,
                 idle_iterations: 0,
+                commit_count: None,
+                total_input_tokens: 0,
+                total_output_tokens: 0,
+                initial_commit: None,
+                pid: None,
+                session_id: None,
+                error_history: Vec::new(),
+                settings: LoopSettings::default(),
             };
 
             let result1 = run_loop_core(deps1, state1).await.unwrap();
@@ -2420,9 +6213,18 @@ Base: master
                 last_iteration_at: None,
                 error_count: loaded_state.error_count,
                 consecutive_errors: 0, // Reset on restart
+                circuit_breaker_cooled_down: false,
                 last_error: None,
                 last_commit: loaded_state.last_commit.clone(),
                 idle_iterations: loaded_state.idle_iterations,
+                commit_count: None,
+                total_input_tokens: 0,
+                total_output_tokens: 0,
+                initial_commit: None,
+                pid: None,
+                session_id: None,
+                error_history: Vec::new(),
+                settings: LoopSettings::default(),
             };
 
             // Second run: continues from saved state
@@ -2482,5 +6284,190 @@ Base: master
             assert_eq!(result.error_count, 3);
             assert_eq!(agent.invocation_count(), 3);
         }
+
+        #[tokio::test]
+        async fn test_e2e_loop_hooks_run_each_iteration() {
+            // Test: pre and post hooks run in the project dir around each iteration
+            let (_dir, project_dir) = setup_test_project("Test prompt");
+            let prompt_file = project_dir.join("PROMPT_build.md");
+
+            let agent = MockAgentProvider::always_succeed("Output");
+
+            let mut config = test_config();
+            config.completion.idle_threshold = 100;
+            config.hooks.pre_iteration = Some("touch pre_hook_ran".to_string());
+            config.hooks.post_iteration = Some("touch post_hook_ran".to_string());
+
+            let deps = LoopDependencies {
+                agent: Box::new(agent.clone()),
+                sandbox: None,
+                config,
+                project_dir: project_dir.clone(),
+                prompt_file,
+            };
+
+            let result = run_loop_core(deps, test_state(Some(2))).await.unwrap();
+
+            assert_eq!(result.termination_reason, TerminationReason::MaxIterations);
+            assert_eq!(result.error_count, 0);
+            assert_eq!(agent.invocation_count(), 2);
+            assert!(project_dir.join("pre_hook_ran").exists());
+            assert!(project_dir.join("post_hook_ran").exists());
+        }
+
+        #[tokio::test]
+        async fn test_e2e_loop_pre_hook_failure_skips_iteration() {
+            // Test: a failing pre-iteration hook is recoverable and skips the agent
+            let (_dir, project_dir) = setup_test_project("Test prompt");
+            let prompt_file = project_dir.join("PROMPT_build.md");
+
+            let agent = MockAgentProvider::always_succeed("Output");
+
+            let mut config = test_config();
+            config.completion.idle_threshold = 100;
+            config.hooks.pre_iteration = Some("false".to_string());
+
+            let deps = LoopDependencies {
+                agent: Box::new(agent.clone()),
+                sandbox: None,
+                config,
+                project_dir: project_dir.clone(),
+                prompt_file,
+            };
+
+            let result = run_loop_core(deps, test_state(Some(3))).await.unwrap();
+
+            assert_eq!(result.termination_reason, TerminationReason::MaxIterations);
+            assert_eq!(result.error_count, 3);
+            assert_eq!(agent.invocation_count(), 0);
+
+            let saved = RalphState::load(&project_dir).unwrap().unwrap();
+            assert!(saved
+                .last_error
+                .unwrap()
+                .starts_with("Pre-iteration hook error:"));
+        }
+
+        #[tokio::test]
+        async fn test_e2e_loop_pre_hook_failure_triggers_circuit_breaker() {
+            // Test: repeated pre-hook failures count toward the circuit breaker
+            let (_dir, project_dir) = setup_test_project("Test prompt");
+            let prompt_file = project_dir.join("PROMPT_build.md");
+
+            let agent = MockAgentProvider::always_succeed("Output");
+
+            let mut config = test_config();
+            config.monitoring.max_consecutive_errors = 2;
+            config.hooks.pre_iteration = Some("false".to_string());
+
+            let deps = LoopDependencies {
+                agent: Box::new(agent.clone()),
+                sandbox: None,
+                config,
+                project_dir: project_dir.clone(),
+                prompt_file,
+            };
+
+            let result = run_loop_core(deps, test_state(Some(10))).await.unwrap();
+
+            assert!(matches!(
+                &result.termination_reason,
+                TerminationReason::Error(message)
+                    if message.starts_with("Circuit breaker triggered: 2 consecutive errors")
+            ));
+            assert_eq!(result.error_count, 2);
+            assert_eq!(agent.invocation_count(), 0);
+        }
+
+        #[tokio::test]
+        async fn test_e2e_loop_post_hook_failure_only_warns() {
+            // Test: a failing post-iteration hook does not count as an error
+            let (_dir, project_dir) = setup_test_project("Test prompt");
+            let prompt_file = project_dir.join("PROMPT_build.md");
+
+            let agent = MockAgentProvider::always_succeed("Output");
+
+            let mut config = test_config();
+            config.completion.idle_threshold = 100;
+            config.hooks.post_iteration = Some("false".to_string());
+
+            let deps = LoopDependencies {
+                agent: Box::new(agent.clone()),
+                sandbox: None,
+                config,
+                project_dir: project_dir.clone(),
+                prompt_file,
+            };
+
+            let result = run_loop_core(deps, test_state(Some(3))).await.unwrap();
+
+            assert_eq!(result.termination_reason, TerminationReason::MaxIterations);
+            assert_eq!(result.error_count, 0);
+            assert_eq!(agent.invocation_count(), 3);
+        }
+
+        #[tokio::test]
+        async fn test_e2e_loop_success_command_completes() {
+            // Test: success command that passes on the third iteration stops the loop
+            if crate::agent::is_nix_sandbox() {
+                return;
+            }
+
+            let (_dir, project_dir) = setup_test_project("Test prompt");
+            let prompt_file = project_dir.join("PROMPT_build.md");
+
+            let agent = MockAgentProvider::always_succeed("Output");
+
+            let mut config = test_config();
+            config.completion.idle_threshold = 100; // Only the success command can finish
+            config.completion.success_command = Some(
+                "sh -c 'n=$(cat .checks 2>/dev/null || echo 0); n=$((n+1)); \
+                 echo $n > .checks; [ $n -ge 3 ]'"
+                    .to_string(),
+            );
+
+            let deps = LoopDependencies {
+                agent: Box::new(agent.clone()),
+                sandbox: None,
+                config,
+                project_dir: project_dir.clone(),
+                prompt_file,
+            };
+
+            let result = run_loop_core(deps, test_state(Some(10))).await.unwrap();
+
+            assert_eq!(result.termination_reason, TerminationReason::SuccessCommand);
+            assert_eq!(result.final_iteration, 3);
+            assert_eq!(agent.invocation_count(), 3);
+        }
+
+        #[tokio::test]
+        async fn test_e2e_loop_success_command_failing_keeps_idle_detection() {
+            // Test: a never-passing success command leaves idle detection in charge
+            let (_dir, project_dir) = setup_test_project("Test prompt");
+            let prompt_file = project_dir.join("PROMPT_build.md");
+
+            let agent = MockAgentProvider::always_succeed("Output");
+
+            let mut config = test_config();
+            config.completion.success_command = Some("false".to_string());
+
+            let deps = LoopDependencies {
+                agent: Box::new(agent.clone()),
+                sandbox: None,
+                config,
+                project_dir: project_dir.clone(),
+                prompt_file,
+            };
+
+            let result = run_loop_core(deps, test_state(Some(10))).await.unwrap();
+
+            // Default idle_threshold of 2 triggers completion
+            assert_eq!(
+                result.termination_reason,
+                TerminationReason::CompletionDetected
+            );
+            assert_eq!(agent.invocation_count(), 2);
+        }
     }
 }
@@ -0,0 +1,130 @@
+//! SIGINT/SIGTERM handling for the Ralph loop.
+//!
+//! A background task flips a `watch` flag on the first signal. The loop
+//! checks the flag between iterations and selects on it around agent runs
+//! and sleeps, so an in-flight iteration is interrupted and state is saved
+//! as inactive before exiting.
+
+use anyhow::Result;
+use std::path::Path;
+use tokio::sync::watch;
+
+use crate::state::RalphState;
+
+/// Receiver side of the shutdown flag.
+pub(crate) type ShutdownSignal = watch::Receiver<bool>;
+
+/// Spawns a listener that sets the returned flag on SIGINT or SIGTERM.
+pub(crate) fn spawn_signal_listener() -> ShutdownSignal {
+    let (tx, rx) = watch::channel(false);
+    tokio::spawn(async move {
+        wait_for_signal().await;
+        let _ = tx.send(true);
+    });
+    rx
+}
+
+/// Returns true if a shutdown signal has been received.
+pub(crate) fn is_signalled(signal: &ShutdownSignal) -> bool {
+    *signal.borrow()
+}
+
+/// Resolves once a shutdown signal has been received.
+///
+/// Never resolves if the listener could not be installed.
+pub(crate) async fn signalled(signal: &mut ShutdownSignal) {
+    if signal.wait_for(|&flag| flag).await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Sleeps for `duration`, returning early if a shutdown signal arrives.
+pub(crate) async fn sleep_or_signal(duration: std::time::Duration, signal: &mut ShutdownSignal) {
+    tokio::select! {
+        () = tokio::time::sleep(duration) => {}
+        () = signalled(signal) => {}
+    }
+}
+
+/// Marks the loop inactive after a signal so `ralph status` stays truthful.
+pub(crate) fn stop_on_signal(state: &mut RalphState, project_dir: &Path) -> Result<()> {
+    state.active = false;
+    state.save(project_dir)
+}
+
+// -----------------------------------------------------------------------------
+// Helper functions
+// -----------------------------------------------------------------------------
+
+/// Waits for Ctrl+C, or SIGTERM on Unix.
+async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = sigterm.recv() => {}
+                }
+            }
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_signalled_resolves_after_flag_set() {
+        let (tx, mut rx) = watch::channel(false);
+        assert!(!is_signalled(&rx));
+
+        tx.send(true).unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(1), signalled(&mut rx))
+            .await
+            .unwrap();
+        assert!(is_signalled(&rx));
+    }
+
+    #[tokio::test]
+    async fn test_sleep_or_signal_returns_early() {
+        let (tx, mut rx) = watch::channel(false);
+        tx.send(true).unwrap();
+
+        let started = std::time::Instant::now();
+        sleep_or_signal(std::time::Duration::from_mins(1), &mut rx).await;
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_stop_on_signal_sets_state_inactive() {
+        let dir = TempDir::new().unwrap();
+        let mut state = RalphState {
+            active: true,
+            ..Default::default()
+        };
+        state.save(dir.path()).unwrap();
+
+        let (tx, rx) = watch::channel(false);
+        tx.send(true).unwrap();
+        if is_signalled(&rx) {
+            stop_on_signal(&mut state, dir.path()).unwrap();
+        }
+
+        assert!(!state.active);
+        let saved = RalphState::load(dir.path()).unwrap().unwrap();
+        assert!(!saved.active);
+    }
+}
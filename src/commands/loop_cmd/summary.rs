@@ -0,0 +1,141 @@
+//! Markdown run summary (`ralph loop --output <file>`).
+//!
+//! Written when the loop ends from the same figures as the metrics export,
+//! plus token usage and the recent error history, so a run can be attached
+//! to a PR. Rendering is pure.
+
+use anyhow::{Context, Result};
+use std::fmt::Write;
+use std::path::Path;
+
+use super::format::format_duration;
+use crate::metrics::LoopSummary;
+use crate::state::RalphState;
+
+/// Writes the markdown summary of a finished loop to `path`, creating
+/// parent directories and replacing any previous file.
+pub(crate) fn write_summary(path: &Path, summary: &LoopSummary, state: &RalphState) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    std::fs::write(path, render_summary(summary, state))
+        .with_context(|| format!("Failed to write run summary {}", path.display()))
+}
+
+// -----------------------------------------------------------------------------
+// Helper functions
+// -----------------------------------------------------------------------------
+
+/// Renders the summary: a table of run figures, then the recorded errors.
+fn render_summary(summary: &LoopSummary, state: &RalphState) -> String {
+    let elapsed = chrono::Duration::seconds(i64::try_from(summary.elapsed_seconds).unwrap_or(0));
+
+    let mut out = String::new();
+    writeln!(&mut out, "# Ralph Run Summary\n").unwrap();
+    writeln!(&mut out, "| | |\n|---|---|").unwrap();
+    writeln!(&mut out, "| Mode | {} |", summary.mode).unwrap();
+    writeln!(&mut out, "| Iterations | {} |", summary.iterations).unwrap();
+    writeln!(
+        &mut out,
+        "| Termination reason | `{}` |",
+        summary.termination_reason
+    )
+    .unwrap();
+    writeln!(&mut out, "| Elapsed | {} |", format_duration(&elapsed)).unwrap();
+    writeln!(&mut out, "| Commits since start | {} |", summary.commits).unwrap();
+    writeln!(&mut out, "| Errors | {} |", summary.error_count).unwrap();
+    if state.total_input_tokens > 0 || state.total_output_tokens > 0 {
+        writeln!(
+            &mut out,
+            "| Tokens | {} input, {} output |",
+            state.total_input_tokens, state.total_output_tokens
+        )
+        .unwrap();
+    }
+
+    writeln!(&mut out, "\n## Errors\n").unwrap();
+    if state.error_history.is_empty() {
+        writeln!(&mut out, "None.").unwrap();
+    }
+    for entry in &state.error_history {
+        let message = entry.message.lines().next().unwrap_or_default().trim();
+        writeln!(
+            &mut out,
+            "- Iteration {} ({}): {message}",
+            entry.iteration,
+            format!("{:?}", entry.kind).to_lowercase()
+        )
+        .unwrap();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::ErrorKind;
+
+    fn summary() -> LoopSummary {
+        LoopSummary {
+            mode: "build".to_string(),
+            iterations: 12,
+            error_count: 2,
+            elapsed_seconds: 3725,
+            commits: 9,
+            termination_reason: "agent_idle".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_render_summary_sections() {
+        let mut state = RalphState {
+            total_input_tokens: 1200,
+            total_output_tokens: 340,
+            iteration: 3,
+            ..RalphState::default()
+        };
+        state.record_error(ErrorKind::Agent, "Agent timeout: timed out".to_string());
+        state.iteration = 7;
+        state.record_error(
+            ErrorKind::Validation,
+            "Validation error:cargo check failed\nerror[E0308]".to_string(),
+        );
+
+        let out = render_summary(&summary(), &state);
+
+        assert!(out.starts_with("# Ralph Run Summary\n"));
+        assert!(out.contains("| Mode | build |\n"));
+        assert!(out.contains("| Iterations | 12 |\n"));
+        assert!(out.contains("| Termination reason | `agent_idle` |\n"));
+        assert!(out.contains("| Elapsed | 1h 2m 5s |\n"));
+        assert!(out.contains("| Commits since start | 9 |\n"));
+        assert!(out.contains("| Errors | 2 |\n"));
+        assert!(out.contains("| Tokens | 1200 input, 340 output |\n"));
+        assert!(out.contains("\n## Errors\n\n"));
+        assert!(out.contains("- Iteration 3 (agent): Agent timeout: timed out\n"));
+        assert!(out.contains("- Iteration 7 (validation): Validation error:cargo check failed\n"));
+        assert!(!out.contains("E0308"));
+    }
+
+    #[test]
+    fn test_render_summary_without_tokens_or_errors() {
+        let state = RalphState::default();
+        let out = render_summary(&summary(), &state);
+
+        assert!(!out.contains("| Tokens |"));
+        assert!(out.ends_with("## Errors\n\nNone.\n"));
+    }
+
+    #[test]
+    fn test_write_summary_creates_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("reports").join("run.md");
+        let state = RalphState::default();
+
+        write_summary(&path, &summary(), &state).unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content, render_summary(&summary(), &state));
+    }
+}
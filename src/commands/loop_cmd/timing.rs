@@ -0,0 +1,86 @@
+//! Per-phase timing of loop iterations.
+//!
+//! The agent run, validation, and git operations each run inside a `phase`
+//! tracing span that records its elapsed milliseconds. The totals for the
+//! iteration are logged on `iteration_complete` and shown in the progress
+//! summary.
+
+use std::future::Future;
+use std::time::Instant;
+use tracing::{field, info_span, Instrument};
+
+/// Elapsed milliseconds per phase of one iteration; `None` if the phase did
+/// not run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[allow(clippy::struct_field_names)] // Names match the logged `iteration_complete` fields
+pub(crate) struct PhaseTimings {
+    /// Agent invocation, in the sandbox or on the host.
+    pub agent_ms: Option<u64>,
+    /// `validation.command`.
+    pub validation_ms: Option<u64>,
+    /// Auto-commit and push.
+    pub git_ms: Option<u64>,
+}
+
+impl PhaseTimings {
+    /// Adds `ms` to the git total; git runs in more than one step.
+    pub(crate) fn add_git(&mut self, ms: u64) {
+        self.git_ms = Some(self.git_ms.unwrap_or(0) + ms);
+    }
+}
+
+/// Runs `future` inside a `phase` span for `iteration`, returning its
+/// output and elapsed milliseconds. The span's `elapsed_ms` field is set
+/// when the phase finishes.
+pub(crate) async fn timed<F: Future>(
+    phase: &'static str,
+    iteration: u32,
+    future: F,
+) -> (F::Output, u64) {
+    let span = info_span!("phase", phase, iteration, elapsed_ms = field::Empty);
+    let start = Instant::now();
+    let output = future.instrument(span.clone()).await;
+    let elapsed_ms = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX);
+    span.record("elapsed_ms", elapsed_ms);
+    (output, elapsed_ms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_timed_measures_phase() {
+        let (output, ms) = timed("agent", 1, async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            "done"
+        })
+        .await;
+
+        assert_eq!(output, "done");
+        assert!(ms >= 20, "measured {ms} ms");
+    }
+
+    #[tokio::test]
+    async fn test_timings_populated_per_phase() {
+        let mut timings = PhaseTimings::default();
+        assert_eq!(timings.agent_ms, None);
+
+        let ((), agent_ms) = timed("agent", 2, tokio::time::sleep(Duration::from_millis(10))).await;
+        timings.agent_ms = Some(agent_ms);
+        let ((), validation_ms) = timed(
+            "validation",
+            2,
+            tokio::time::sleep(Duration::from_millis(5)),
+        )
+        .await;
+        timings.validation_ms = Some(validation_ms);
+        timings.add_git(3);
+        timings.add_git(4);
+
+        assert!(timings.agent_ms.unwrap() >= 10);
+        assert!(timings.validation_ms.unwrap() >= 5);
+        assert_eq!(timings.git_ms, Some(7));
+    }
+}
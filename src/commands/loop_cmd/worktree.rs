@@ -4,6 +4,7 @@
 //! in the implementation plan.
 
 use anyhow::{bail, Context, Result};
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 use tokio::process::Command;
 
@@ -22,25 +23,71 @@ pub struct BranchSection {
     pub goal: String,
     /// Base branch to branch from (from `Base: <branch>`).
     pub base: String,
+    /// Agent provider for this branch (from `Provider: <name>`).
+    pub provider: Option<String>,
+    /// Iteration cap for this branch (from `MaxIterations: <n>`).
+    pub max_iterations: Option<u32>,
+    /// Branches that must finish first (from `Depends: <a>, <b>`).
+    pub depends_on: Vec<String>,
 }
 
-/// Parse `IMPLEMENTATION_PLAN.md` and extract all branch sections.
+/// Plan-wide defaults from the frontmatter block at the top of
+/// `IMPLEMENTATION_PLAN.md`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlanDefaults {
+    /// Agent provider for branches without `Provider:` (from `provider: <name>`).
+    pub provider: Option<String>,
+    /// Iteration cap for branches without `MaxIterations:` (from
+    /// `max_iterations: <n>`).
+    pub max_iterations: Option<u32>,
+}
+
+impl PlanDefaults {
+    /// Fills in the branch's provider and iteration cap where its section
+    /// sets none.
+    pub fn apply(&self, branch: &mut BranchSection) {
+        if branch.provider.is_none() {
+            branch.provider.clone_from(&self.provider);
+        }
+        branch.max_iterations = branch.max_iterations.or(self.max_iterations);
+    }
+}
+
+/// A parsed `IMPLEMENTATION_PLAN.md`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImplementationPlan {
+    /// Defaults from the frontmatter block, if any.
+    pub defaults: PlanDefaults,
+    /// Branch sections, in plan order.
+    pub branches: Vec<BranchSection>,
+}
+
+/// Parse `IMPLEMENTATION_PLAN.md` into its defaults and branch sections.
 ///
 /// Expected format:
 /// ```markdown
+/// ---                       (optional frontmatter)
+/// provider: <name>
+/// max_iterations: <n>
+/// ---
+///
 /// ## Branch: <name>
 /// Goal: <description>
 /// Base: <branch>
+/// Provider: <name>          (optional)
+/// MaxIterations: <n>        (optional)
+/// Depends: <branch>, ...    (optional)
 ///
 /// - [ ] Task 1
 /// - [ ] Task 2
 /// ```
-#[allow(dead_code)] // Used by parallel-build (not yet implemented)
-pub fn parse_implementation_plan(content: &str) -> Vec<BranchSection> {
+///
+/// The frontmatter must be the first thing in the file. Sections without
+/// `Goal:` or `Base:` are skipped. An iteration cap that is not a number is
+/// ignored, as are unknown frontmatter keys.
+pub fn parse_implementation_plan(content: &str) -> ImplementationPlan {
     let mut sections = Vec::new();
-    let mut current_name: Option<String> = None;
-    let mut current_goal: Option<String> = None;
-    let mut current_base: Option<String> = None;
+    let mut current: Option<PendingSection> = None;
 
     for line in content.lines() {
         let trimmed = line.trim();
@@ -48,33 +95,163 @@ pub fn parse_implementation_plan(content: &str) -> Vec<BranchSection> {
         // Check for branch header
         if let Some(name) = trimmed.strip_prefix("## Branch:") {
             // Save previous section if complete
-            if let (Some(name), Some(goal), Some(base)) = (
-                current_name.take(),
-                current_goal.take(),
-                current_base.take(),
-            ) {
-                sections.push(BranchSection { name, goal, base });
-            }
-            current_name = Some(name.trim().to_string());
-            current_goal = None;
-            current_base = None;
-        } else if let Some(goal) = trimmed.strip_prefix("Goal:") {
-            current_goal = Some(goal.trim().to_string());
+            sections.extend(current.take().and_then(PendingSection::finish));
+            current = Some(PendingSection::new(name.trim()));
+            continue;
+        }
+        let Some(section) = current.as_mut() else {
+            continue;
+        };
+
+        if let Some(goal) = trimmed.strip_prefix("Goal:") {
+            section.goal = Some(goal.trim().to_string());
         } else if let Some(base) = trimmed.strip_prefix("Base:") {
-            current_base = Some(base.trim().to_string());
+            section.base = Some(base.trim().to_string());
+        } else if let Some(provider) = trimmed.strip_prefix("Provider:") {
+            let provider = provider.trim();
+            section.provider = (!provider.is_empty()).then(|| provider.to_string());
+        } else if let Some(max) = trimmed.strip_prefix("MaxIterations:") {
+            section.max_iterations = max.trim().parse().ok();
+        } else if let Some(depends) = trimmed.strip_prefix("Depends:") {
+            section.depends_on = depends
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(String::from)
+                .collect();
         }
     }
 
     // Don't forget the last section
-    if let (Some(name), Some(goal), Some(base)) = (
-        current_name.take(),
-        current_goal.take(),
-        current_base.take(),
-    ) {
-        sections.push(BranchSection { name, goal, base });
+    sections.extend(current.and_then(PendingSection::finish));
+
+    ImplementationPlan {
+        defaults: parse_frontmatter(content),
+        branches: sections,
+    }
+}
+
+/// Parses the `---`-fenced block opening the plan. The block ends at the
+/// closing `---` or, if that is missing, at the first `## Branch:`.
+fn parse_frontmatter(content: &str) -> PlanDefaults {
+    let mut defaults = PlanDefaults::default();
+    let mut lines = content.lines().map(str::trim).skip_while(|l| l.is_empty());
+    if lines.next() != Some("---") {
+        return defaults;
     }
 
-    sections
+    for line in lines {
+        if line == "---" || line.starts_with("## Branch:") {
+            break;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "provider" => defaults.provider = (!value.is_empty()).then(|| value.to_string()),
+            "max_iterations" => defaults.max_iterations = value.parse().ok(),
+            _ => {}
+        }
+    }
+    defaults
+}
+
+/// Orders branches into build stages by their `Depends:` lines.
+///
+/// Every branch in a stage depends only on branches in earlier stages, so
+/// stages run one after another while the branches within a stage can run
+/// concurrently. Stages keep plan order. Dependencies on branches outside
+/// `branches` (already complete, or not in the plan) are treated as met.
+///
+/// Fails with the offending cycle if the dependencies cannot be ordered.
+pub fn schedule(branches: &[BranchSection]) -> Result<Vec<Vec<String>>> {
+    let building: HashSet<&str> = branches.iter().map(|b| b.name.as_str()).collect();
+    let mut done: HashSet<&str> = HashSet::new();
+    let mut pending: Vec<&BranchSection> = branches.iter().collect();
+    let mut stages = Vec::new();
+
+    while !pending.is_empty() {
+        let (ready, waiting): (Vec<_>, Vec<_>) = pending.into_iter().partition(|branch| {
+            branch
+                .depends_on
+                .iter()
+                .all(|dep| done.contains(dep.as_str()) || !building.contains(dep.as_str()))
+        });
+        if ready.is_empty() {
+            bail!(
+                "Dependency cycle in IMPLEMENTATION_PLAN.md: {}",
+                find_cycle(&waiting).join(" -> ")
+            );
+        }
+        done.extend(ready.iter().map(|b| b.name.as_str()));
+        stages.push(ready.iter().map(|b| b.name.clone()).collect());
+        pending = waiting;
+    }
+
+    Ok(stages)
+}
+
+/// A branch section being parsed, before `Goal:` and `Base:` are known.
+struct PendingSection {
+    name: String,
+    goal: Option<String>,
+    base: Option<String>,
+    provider: Option<String>,
+    max_iterations: Option<u32>,
+    depends_on: Vec<String>,
+}
+
+impl PendingSection {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            goal: None,
+            base: None,
+            provider: None,
+            max_iterations: None,
+            depends_on: Vec::new(),
+        }
+    }
+
+    /// Returns the section if both `Goal:` and `Base:` were found.
+    fn finish(self) -> Option<BranchSection> {
+        Some(BranchSection {
+            name: self.name,
+            goal: self.goal?,
+            base: self.base?,
+            provider: self.provider,
+            max_iterations: self.max_iterations,
+            depends_on: self.depends_on,
+        })
+    }
+}
+
+/// Follows dependencies among `stuck` branches until one repeats.
+///
+/// Every stuck branch waits on another stuck branch, so the walk always
+/// closes a loop. Returns the loop with its first branch repeated at the end.
+fn find_cycle(stuck: &[&BranchSection]) -> Vec<String> {
+    let by_name: HashMap<&str, &BranchSection> =
+        stuck.iter().map(|b| (b.name.as_str(), *b)).collect();
+    let mut path: Vec<&str> = Vec::new();
+    let mut current = stuck.first().map(|b| b.name.as_str());
+
+    while let Some(name) = current {
+        if let Some(start) = path.iter().position(|&seen| seen == name) {
+            let mut cycle: Vec<String> = path[start..].iter().map(ToString::to_string).collect();
+            cycle.push(name.to_string());
+            return cycle;
+        }
+        path.push(name);
+        current = by_name[name]
+            .depends_on
+            .iter()
+            .map(String::as_str)
+            .find(|dep| by_name.contains_key(dep));
+    }
+
+    path.iter().map(ToString::to_string).collect()
 }
 
 /// Enable worktree configuration in git.
@@ -116,34 +293,51 @@ pub async fn create_worktree(project_dir: &Path, branch: &str) -> Result<()> {
 
 /// Configure worktree identity using git config --worktree.
 ///
-/// Sets `user.name`, `user.email`, and optionally `signing_key` and `ssh_key`.
+/// Applies the settings from [`identity_settings`].
 #[allow(dead_code)] // Used by parallel-build (not yet implemented)
 pub async fn configure_worktree_identity(
     project_dir: &Path,
     branch: &str,
     config: &WorktreeConfig,
+    sign_commits: bool,
+    signing_key: Option<&str>,
 ) -> Result<()> {
     let worktree_path = project_dir.join(WORKTREE_DIR).join(branch);
 
-    // Set user.name
-    run_git_config(&worktree_path, "user.name", &config.name).await?;
-
-    // Set user.email
-    run_git_config(&worktree_path, "user.email", &config.email).await?;
+    for (key, value) in identity_settings(config, sign_commits, signing_key) {
+        run_git_config(&worktree_path, key, &value).await?;
+    }
+    Ok(())
+}
 
-    // Set signing key if configured
-    if let Some(ref key) = config.signing_key {
-        run_git_config(&worktree_path, "user.signingkey", key).await?;
-        run_git_config(&worktree_path, "commit.gpgsign", "true").await?;
+/// The `git config` settings for a worktree identity.
+///
+/// Sets `user.name` and `user.email`, plus `core.sshCommand` for `ssh_key`.
+/// Commits are signed when the worktree has its own `signing_key` or
+/// `sign_commits` is on; the worktree key wins over `signing_key`.
+fn identity_settings(
+    config: &WorktreeConfig,
+    sign_commits: bool,
+    signing_key: Option<&str>,
+) -> Vec<(&'static str, String)> {
+    let mut settings = vec![
+        ("user.name", config.name.clone()),
+        ("user.email", config.email.clone()),
+    ];
+
+    let key = config.signing_key.as_deref().or(signing_key);
+    if let Some(key) = key {
+        settings.push(("user.signingkey", key.to_string()));
+    }
+    if sign_commits || config.signing_key.is_some() {
+        settings.push(("commit.gpgsign", "true".to_string()));
     }
 
-    // Set SSH command if configured
     if let Some(ref ssh_key) = config.ssh_key {
         let ssh_command = format!("ssh -i {ssh_key} -o IdentitiesOnly=yes");
-        run_git_config(&worktree_path, "core.sshCommand", &ssh_command).await?;
+        settings.push(("core.sshCommand", ssh_command));
     }
-
-    Ok(())
+    settings
 }
 
 /// Run a git config command in the worktree.
@@ -182,33 +376,41 @@ pub async fn remove_worktree(project_dir: &Path, branch: &str) -> Result<()> {
     Ok(())
 }
 
-/// Remove all worktrees in the .worktrees directory.
-pub async fn remove_all_worktrees(project_dir: &Path) -> Result<Vec<String>> {
+/// List the branches that have a worktree in the .worktrees directory.
+pub fn list_worktrees(project_dir: &Path) -> Result<Vec<String>> {
     let worktrees_dir = project_dir.join(WORKTREE_DIR);
 
     if !worktrees_dir.exists() {
         return Ok(Vec::new());
     }
 
-    let mut removed = Vec::new();
+    let mut branches = Vec::new();
     let entries = std::fs::read_dir(&worktrees_dir)
         .with_context(|| format!("Failed to read {WORKTREE_DIR}"))?;
 
     for entry in entries {
         let entry = entry?;
         if entry.file_type()?.is_dir() {
-            let branch = entry.file_name().to_string_lossy().to_string();
-            remove_worktree(project_dir, &branch).await?;
-            removed.push(branch);
+            branches.push(entry.file_name().to_string_lossy().to_string());
         }
     }
+    branches.sort();
 
-    // Clean up the .worktrees directory if empty
+    Ok(branches)
+}
+
+/// Remove the worktrees for `branches`, then the .worktrees directory if empty.
+pub async fn remove_worktrees(project_dir: &Path, branches: &[String]) -> Result<()> {
+    for branch in branches {
+        remove_worktree(project_dir, branch).await?;
+    }
+
+    let worktrees_dir = project_dir.join(WORKTREE_DIR);
     if worktrees_dir.exists() {
         let _ = std::fs::remove_dir(&worktrees_dir);
     }
 
-    Ok(removed)
+    Ok(())
 }
 
 /// Copy `IMPLEMENTATION_PLAN.md` to a worktree.
@@ -229,7 +431,6 @@ pub fn copy_plan_to_worktree(project_dir: &Path, branch: &str) -> Result<()> {
 }
 
 /// Get the path to a worktree.
-#[allow(dead_code)] // Used by parallel-build (not yet implemented)
 pub fn worktree_path(project_dir: &Path, branch: &str) -> std::path::PathBuf {
     project_dir.join(WORKTREE_DIR).join(branch)
 }
@@ -250,7 +451,7 @@ Base: master
 - [ ] Task 1
 - [ ] Task 2
 ";
-        let sections = parse_implementation_plan(content);
+        let sections = parse_implementation_plan(content).branches;
         assert_eq!(sections.len(), 1);
         assert_eq!(sections[0].name, "fix-bug");
         assert_eq!(sections[0].goal, "Fix the critical bug");
@@ -272,7 +473,7 @@ Base: develop
 
 - [ ] Task 2
 ";
-        let sections = parse_implementation_plan(content);
+        let sections = parse_implementation_plan(content).branches;
         assert_eq!(sections.len(), 2);
 
         assert_eq!(sections[0].name, "feature-a");
@@ -287,7 +488,7 @@ Base: develop
     #[test]
     fn test_parse_implementation_plan_empty() {
         let content = "# Just some text\nNo branches here";
-        let sections = parse_implementation_plan(content);
+        let sections = parse_implementation_plan(content).branches;
         assert!(sections.is_empty());
     }
 
@@ -300,10 +501,262 @@ Goal: Missing base
 
 - [ ] Task 1
 ";
-        let sections = parse_implementation_plan(content);
+        let sections = parse_implementation_plan(content).branches;
         assert!(sections.is_empty());
     }
 
+    #[test]
+    fn test_parse_implementation_plan_metadata() {
+        let content = r"
+## Branch: feature-b
+Goal: Add feature B
+Base: master
+Provider: cursor
+MaxIterations: 30
+Depends: feature-a, fix-ci
+
+- [ ] Task 1
+
+## Branch: feature-a
+Goal: Add feature A
+Base: master
+";
+        let sections = parse_implementation_plan(content).branches;
+        assert_eq!(sections.len(), 2);
+
+        assert_eq!(sections[0].provider.as_deref(), Some("cursor"));
+        assert_eq!(sections[0].max_iterations, Some(30));
+        assert_eq!(sections[0].depends_on, vec!["feature-a", "fix-ci"]);
+
+        // Metadata does not leak into the next section
+        assert!(sections[1].provider.is_none());
+        assert!(sections[1].max_iterations.is_none());
+        assert!(sections[1].depends_on.is_empty());
+    }
+
+    #[test]
+    fn test_parse_implementation_plan_invalid_metadata_ignored() {
+        let content = r"
+Provider: claude
+
+## Branch: feature-a
+Goal: Add feature A
+Base: master
+Provider:
+MaxIterations: lots
+Depends: ,
+";
+        let sections = parse_implementation_plan(content).branches;
+        assert_eq!(sections.len(), 1);
+        assert!(sections[0].provider.is_none());
+        assert!(sections[0].max_iterations.is_none());
+        assert!(sections[0].depends_on.is_empty());
+    }
+
+    #[test]
+    fn test_parse_implementation_plan_frontmatter() {
+        let content = r"---
+provider: cursor
+max_iterations: 40
+unknown: ignored
+---
+
+# Implementation Plan
+
+## Branch: feature-a
+Goal: Add feature A
+Base: master
+";
+        let plan = parse_implementation_plan(content);
+        assert_eq!(
+            plan.defaults,
+            PlanDefaults {
+                provider: Some("cursor".to_string()),
+                max_iterations: Some(40),
+            }
+        );
+        assert_eq!(plan.branches.len(), 1);
+        assert!(plan.branches[0].provider.is_none());
+    }
+
+    #[test]
+    fn test_parse_frontmatter_must_open_the_plan() {
+        // Not at the top: a horizontal rule, not frontmatter
+        let plan = parse_implementation_plan("# Plan\n---\nprovider: cursor\n---\n");
+        assert_eq!(plan.defaults, PlanDefaults::default());
+
+        // Unterminated block stops at the first branch
+        let plan = parse_implementation_plan(
+            "\n---\nmax_iterations: lots\nprovider:\n## Branch: a\nGoal: A\nBase: master\nprovider: x\n",
+        );
+        assert_eq!(plan.defaults, PlanDefaults::default());
+        assert_eq!(plan.branches.len(), 1);
+    }
+
+    #[test]
+    fn test_plan_defaults_fill_unset_metadata() {
+        let defaults = PlanDefaults {
+            provider: Some("cursor".to_string()),
+            max_iterations: Some(40),
+        };
+        let mut plain = branch("plain", &[]);
+        defaults.apply(&mut plain);
+        assert_eq!(plain.provider.as_deref(), Some("cursor"));
+        assert_eq!(plain.max_iterations, Some(40));
+
+        let mut own = BranchSection {
+            provider: Some("claude".to_string()),
+            max_iterations: Some(5),
+            ..branch("own", &[])
+        };
+        defaults.apply(&mut own);
+        assert_eq!(own.provider.as_deref(), Some("claude"));
+        assert_eq!(own.max_iterations, Some(5));
+    }
+
+    fn branch(name: &str, depends_on: &[&str]) -> BranchSection {
+        BranchSection {
+            name: name.to_string(),
+            goal: format!("Build {name}"),
+            base: "master".to_string(),
+            provider: None,
+            max_iterations: None,
+            depends_on: depends_on.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    #[test]
+    fn test_schedule_linear_chain() {
+        let stages =
+            schedule(&[branch("c", &["b"]), branch("b", &["a"]), branch("a", &[])]).unwrap();
+        assert_eq!(stages, vec![vec!["a"], vec!["b"], vec!["c"]]);
+    }
+
+    #[test]
+    fn test_schedule_diamond() {
+        let stages = schedule(&[
+            branch("top", &[]),
+            branch("left", &["top"]),
+            branch("right", &["top"]),
+            branch("bottom", &["left", "right"]),
+        ])
+        .unwrap();
+        assert_eq!(
+            stages,
+            vec![vec!["top"], vec!["left", "right"], vec!["bottom"]]
+        );
+    }
+
+    #[test]
+    fn test_schedule_independent_branches_share_a_stage() {
+        let stages = schedule(&[
+            branch("a", &[]),
+            branch("b", &[]),
+            // Dependencies outside the build are already met
+            branch("c", &["done-already"]),
+        ])
+        .unwrap();
+        assert_eq!(stages, vec![vec!["a", "b", "c"]]);
+
+        assert!(schedule(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_schedule_cycle_fails_with_cycle() {
+        let err = schedule(&[
+            branch("a", &[]),
+            branch("x", &["a", "y"]),
+            branch("y", &["z"]),
+            branch("z", &["x"]),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Dependency cycle in IMPLEMENTATION_PLAN.md: x -> y -> z -> x"
+        );
+
+        let err = schedule(&[branch("self", &["self"])]).unwrap_err();
+        assert!(err.to_string().ends_with("self -> self"));
+    }
+
+    #[test]
+    fn test_list_worktrees() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(list_worktrees(dir.path()).unwrap().is_empty());
+
+        let worktrees = dir.path().join(WORKTREE_DIR);
+        std::fs::create_dir_all(worktrees.join("feature-b")).unwrap();
+        std::fs::create_dir_all(worktrees.join("feature-a")).unwrap();
+        std::fs::write(worktrees.join("stray.txt"), "").unwrap();
+
+        assert_eq!(
+            list_worktrees(dir.path()).unwrap(),
+            vec!["feature-a".to_string(), "feature-b".to_string()]
+        );
+    }
+
+    fn bot(signing_key: Option<&str>) -> WorktreeConfig {
+        WorktreeConfig {
+            name: "ralph-bot".to_string(),
+            email: "bot@example.com".to_string(),
+            signing_key: signing_key.map(str::to_string),
+            ssh_key: None,
+        }
+    }
+
+    #[test]
+    fn test_identity_settings_unsigned() {
+        assert_eq!(
+            identity_settings(&bot(None), false, None),
+            vec![
+                ("user.name", "ralph-bot".to_string()),
+                ("user.email", "bot@example.com".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_identity_settings_signing() {
+        fn signed(
+            config: &WorktreeConfig,
+            sign: bool,
+            key: Option<&str>,
+        ) -> Vec<(&'static str, String)> {
+            identity_settings(config, sign, key)
+                .into_iter()
+                .filter(|(k, _)| *k == "user.signingkey" || *k == "commit.gpgsign")
+                .collect()
+        }
+        let gpgsign = ("commit.gpgsign", "true".to_string());
+
+        // A worktree key signs on its own, and wins over git.signing_key
+        assert_eq!(
+            signed(&bot(Some("WT")), false, Some("GIT")),
+            vec![("user.signingkey", "WT".to_string()), gpgsign.clone()]
+        );
+        // sign_commits uses git.signing_key, or git's default key
+        assert_eq!(
+            signed(&bot(None), true, Some("GIT")),
+            vec![("user.signingkey", "GIT".to_string()), gpgsign.clone()]
+        );
+        assert_eq!(signed(&bot(None), true, None), vec![gpgsign]);
+    }
+
+    #[test]
+    fn test_identity_settings_ssh_key() {
+        let config = WorktreeConfig {
+            ssh_key: Some("~/.ssh/bot".to_string()),
+            ..bot(None)
+        };
+        assert_eq!(
+            identity_settings(&config, false, None).last().unwrap(),
+            &(
+                "core.sshCommand",
+                "ssh -i ~/.ssh/bot -o IdentitiesOnly=yes".to_string()
+            )
+        );
+    }
+
     #[test]
     fn test_worktree_path() {
         let project = Path::new("/project");
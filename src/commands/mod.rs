@@ -5,8 +5,15 @@
 
 pub mod cancel;
 pub mod clean;
+pub mod config;
+pub mod diff;
+pub mod doctor;
 pub mod image;
 pub mod init;
+pub mod logs;
 pub mod loop_cmd;
+pub mod plan;
+pub mod resume;
 pub mod revert;
+pub mod shell;
 pub mod status;
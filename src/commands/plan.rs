@@ -0,0 +1,294 @@
+//! Inspect `IMPLEMENTATION_PLAN.md`.
+//!
+//! `list` shows each `## Branch:` section with whether its tasks are done,
+//! using the same rule as branch build mode: a branch is incomplete while it
+//! has any unchecked `- [ ]` task. Status computation and formatting are pure.
+
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use colored::Colorize;
+use serde::Serialize;
+use std::fmt::Write;
+
+use super::loop_cmd::is_branch_incomplete;
+use super::loop_cmd::worktree::parse_implementation_plan;
+
+/// Plan file read from the project directory.
+const PLAN_FILE: &str = "IMPLEMENTATION_PLAN.md";
+
+/// Plan inspection actions.
+#[derive(Subcommand, Debug)]
+pub enum PlanAction {
+    /// Show each branch in `IMPLEMENTATION_PLAN.md` and whether it is complete
+    List {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Runs a plan action.
+pub(crate) fn run(action: &PlanAction) -> Result<()> {
+    let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    let plan_path = cwd.join(PLAN_FILE);
+    let content = std::fs::read_to_string(&plan_path).with_context(|| {
+        format!(
+            "Failed to read {}. Run 'ralph loop plan' to create it.",
+            plan_path.display()
+        )
+    })?;
+
+    match action {
+        PlanAction::List { json } => {
+            let branches = branch_statuses(&content);
+            if *json {
+                println!("{}", format_branches_json(&branches));
+            } else {
+                print!("{}", format_branches(&branches));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// -----------------------------------------------------------------------------
+// Internal types
+// -----------------------------------------------------------------------------
+
+/// Whether a branch still has unchecked tasks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum BranchStatus {
+    /// Every task is checked.
+    Complete,
+    /// At least one `- [ ]` task remains.
+    Incomplete,
+}
+
+impl BranchStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Complete => "complete",
+            Self::Incomplete => "incomplete",
+        }
+    }
+}
+
+/// One branch row of `ralph plan list`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct BranchRow {
+    name: String,
+    goal: String,
+    base: String,
+    status: BranchStatus,
+}
+
+/// Machine-readable output for `ralph plan list --json`.
+#[derive(Serialize)]
+struct PlanListJson<'a> {
+    branches: &'a [BranchRow],
+    complete: usize,
+    incomplete: usize,
+}
+
+// -----------------------------------------------------------------------------
+// Helper functions
+// -----------------------------------------------------------------------------
+
+/// Parses the plan and computes each branch's status, in plan order.
+fn branch_statuses(content: &str) -> Vec<BranchRow> {
+    parse_implementation_plan(content)
+        .branches
+        .into_iter()
+        .map(|branch| {
+            let status = if is_branch_incomplete(content, &branch.name) {
+                BranchStatus::Incomplete
+            } else {
+                BranchStatus::Complete
+            };
+            BranchRow {
+                name: branch.name,
+                goal: branch.goal,
+                base: branch.base,
+                status,
+            }
+        })
+        .collect()
+}
+
+/// Number of `(complete, incomplete)` branches.
+fn count_statuses(branches: &[BranchRow]) -> (usize, usize) {
+    let incomplete = branches
+        .iter()
+        .filter(|b| b.status == BranchStatus::Incomplete)
+        .count();
+    (branches.len() - incomplete, incomplete)
+}
+
+/// Formats the branch table with a count summary.
+fn format_branches(branches: &[BranchRow]) -> String {
+    if branches.is_empty() {
+        return format!("No branches found in {PLAN_FILE}\n");
+    }
+
+    let width = |header: &str, field: fn(&BranchRow) -> &str| {
+        branches
+            .iter()
+            .map(|b| field(b).chars().count())
+            .chain([header.len()])
+            .max()
+            .unwrap_or(0)
+    };
+    let name_w = width("BRANCH", |b| &b.name);
+    let goal_w = width("GOAL", |b| &b.goal);
+    let base_w = width("BASE", |b| &b.base);
+
+    let mut out = String::new();
+    writeln!(
+        &mut out,
+        "{}",
+        format!(
+            "{:name_w$}  {:goal_w$}  {:base_w$}  STATUS",
+            "BRANCH", "GOAL", "BASE"
+        )
+        .bold()
+    )
+    .unwrap();
+    for branch in branches {
+        let status = match branch.status {
+            BranchStatus::Complete => branch.status.as_str().green(),
+            BranchStatus::Incomplete => branch.status.as_str().yellow(),
+        };
+        writeln!(
+            &mut out,
+            "{:name_w$}  {:goal_w$}  {:base_w$}  {status}",
+            branch.name, branch.goal, branch.base
+        )
+        .unwrap();
+    }
+
+    let (complete, incomplete) = count_statuses(branches);
+    writeln!(
+        &mut out,
+        "\n{} branch(es): {complete} complete, {incomplete} incomplete",
+        branches.len()
+    )
+    .unwrap();
+    out
+}
+
+/// Formats the branches and counts as pretty-printed JSON.
+fn format_branches_json(branches: &[BranchRow]) -> String {
+    let (complete, incomplete) = count_statuses(branches);
+    let list = PlanListJson {
+        branches,
+        complete,
+        incomplete,
+    };
+    serde_json::to_string_pretty(&list).expect("plan list is always serializable")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_PLAN: &str = "\
+# Implementation Plan
+
+## Branch: auth
+Goal: Add login
+Base: main
+
+- [x] Add user model
+- [x] Add session handling
+
+## Branch: api
+Goal: REST endpoints
+Base: auth
+Depends: auth
+
+- [x] List endpoint
+- [ ] Create endpoint
+
+## Branch: docs
+Goal: Write docs
+Base: main
+
+- [ ] README
+";
+
+    fn statuses(content: &str) -> Vec<(String, BranchStatus)> {
+        branch_statuses(content)
+            .into_iter()
+            .map(|b| (b.name, b.status))
+            .collect()
+    }
+
+    #[test]
+    fn test_branch_statuses_sample_plan() {
+        assert_eq!(
+            statuses(SAMPLE_PLAN),
+            vec![
+                ("auth".to_string(), BranchStatus::Complete),
+                ("api".to_string(), BranchStatus::Incomplete),
+                ("docs".to_string(), BranchStatus::Incomplete),
+            ]
+        );
+
+        let rows = branch_statuses(SAMPLE_PLAN);
+        assert_eq!(rows[1].goal, "REST endpoints");
+        assert_eq!(rows[1].base, "auth");
+        assert_eq!(count_statuses(&rows), (1, 2));
+    }
+
+    #[test]
+    fn test_branch_without_tasks_is_complete() {
+        let plan = "## Branch: empty\nGoal: Nothing yet\nBase: main\n";
+        assert_eq!(
+            statuses(plan),
+            vec![("empty".to_string(), BranchStatus::Complete)]
+        );
+    }
+
+    #[test]
+    fn test_format_branches_table() {
+        let out = format_branches(&branch_statuses(SAMPLE_PLAN));
+        let lines: Vec<&str> = out.lines().collect();
+
+        assert!(lines[0].contains("BRANCH  GOAL            BASE  STATUS"));
+        assert!(lines[1].starts_with("auth    Add login       main  "));
+        assert!(lines[1].contains("complete"));
+        assert!(lines[2].starts_with("api     REST endpoints  auth  "));
+        assert!(lines[2].contains("incomplete"));
+        assert!(lines[3].starts_with("docs    Write docs      main  "));
+        assert!(out.ends_with("3 branch(es): 1 complete, 2 incomplete\n"));
+    }
+
+    #[test]
+    fn test_format_branches_empty_plan() {
+        assert_eq!(
+            format_branches(&branch_statuses("# Plan\n\n- [ ] Loose task\n")),
+            "No branches found in IMPLEMENTATION_PLAN.md\n"
+        );
+    }
+
+    #[test]
+    fn test_format_branches_json() {
+        let json = format_branches_json(&branch_statuses(SAMPLE_PLAN));
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value["complete"], 1);
+        assert_eq!(value["incomplete"], 2);
+        assert_eq!(
+            value["branches"][0],
+            serde_json::json!({
+                "name": "auth",
+                "goal": "Add login",
+                "base": "main",
+                "status": "complete",
+            })
+        );
+        assert_eq!(value["branches"][1]["status"], "incomplete");
+    }
+}
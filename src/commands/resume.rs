@@ -0,0 +1,237 @@
+//! Resume a stopped Ralph loop.
+//!
+//! Reactivates the persisted state and re-enters the loop without resetting
+//! progress, with the options the loop was started with. Core logic is pure:
+//! takes state, returns updated state.
+//! IO happens only at the top level.
+
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::commands::loop_cmd::{
+    self, EventFormat, IterationCap, LoopMode, LoopOptions, LoopOutcome,
+};
+use crate::state::RalphState;
+
+// -----------------------------------------------------------------------------
+// Public API
+// -----------------------------------------------------------------------------
+
+/// Runs the resume command, continuing the loop recorded in `.ralph/state.toml`.
+///
+/// `extend` sets the cap to `extend` more iterations past the last completed one.
+/// The state is only saved once the loop holds the loop lock.
+pub(crate) async fn run(
+    extend: Option<u32>,
+    no_sandbox: bool,
+    provider_override: Option<String>,
+    events: EventFormat,
+    config_file: Option<&Path>,
+) -> Result<LoopOutcome> {
+    let cwd = std::env::current_dir().context("Failed to get current directory")?;
+
+    let Some(state) = RalphState::load(&cwd)? else {
+        bail!(
+            "No saved Ralph loop state found.\n\
+             Run 'ralph loop' to start a new loop."
+        );
+    };
+
+    let state = resume_state(state, extend)?;
+    if events == EventFormat::Human {
+        print!("{}", format_resume(&state));
+    }
+
+    let options = resume_options(state, no_sandbox, provider_override);
+    loop_cmd::run(options, events, config_file).await
+}
+
+// -----------------------------------------------------------------------------
+// Helper functions
+// -----------------------------------------------------------------------------
+
+/// Pure resume logic: reactivates the loop and optionally extends its cap.
+///
+/// Progress (iteration, error count, idle detection) is preserved. Consecutive
+/// errors are reset so a loop stopped by the circuit breaker gets a fresh run.
+fn resume_state(mut state: RalphState, extend: Option<u32>) -> Result<RalphState> {
+    let completed = state.iteration.saturating_sub(1);
+
+    if let Some(n) = extend {
+        state.max_iterations = Some(completed.saturating_add(n));
+    } else if state.max_iterations.is_some_and(|max| completed >= max) {
+        bail!(
+            "Loop already reached its iteration limit ({completed}).\n\
+             Use 'ralph resume --max-iterations <N>' to run N more iterations."
+        );
+    }
+
+    state.active = true;
+    state.consecutive_errors = 0;
+    Ok(state)
+}
+
+/// Builds the loop options from the options saved in `state`. `--no-sandbox`
+/// and `--provider` on the resume command take precedence over the saved ones.
+fn resume_options(
+    state: RalphState,
+    no_sandbox: bool,
+    provider_override: Option<String>,
+) -> LoopOptions {
+    let mut settings = state.settings.clone();
+    settings.no_sandbox |= no_sandbox;
+    if provider_override.is_some() {
+        settings.provider = provider_override;
+    }
+
+    LoopOptions {
+        mode: state.mode.into(),
+        max_iterations: state
+            .max_iterations
+            .map_or(IterationCap::Unlimited, IterationCap::Max),
+        settings,
+        dry_run: false,
+        interactive: false,
+        simulate: false,
+        output: None,
+        keep_container: false,
+        resume: Some(state),
+    }
+}
+
+/// Formats the resume announcement.
+fn format_resume(state: &RalphState) -> String {
+    let mode = LoopMode::from(state.mode);
+    let max = state
+        .max_iterations
+        .map_or_else(|| "unlimited".to_string(), |n| n.to_string());
+
+    let mut out = String::new();
+    writeln!(
+        &mut out,
+        "\n{} Resuming {:?} loop at iteration {} (max: {}).",
+        "↻".green(),
+        mode,
+        state.iteration.to_string().cyan(),
+        max.cyan()
+    )
+    .unwrap();
+    out
+}
+
+// -----------------------------------------------------------------------------
+// Tests
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{LoopSettings, Mode};
+    use std::path::PathBuf;
+
+    fn make_state(iteration: u32, max_iterations: Option<u32>) -> RalphState {
+        RalphState {
+            active: false,
+            mode: Mode::Build,
+            iteration,
+            max_iterations,
+            error_count: 4,
+            consecutive_errors: 5,
+            circuit_breaker_cooled_down: false,
+            last_error: Some("Agent execution timed out".to_string()),
+            last_commit: Some("abc123".to_string()),
+            idle_iterations: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_resume_reactivates_and_preserves_progress() {
+        let state = resume_state(make_state(7, Some(20)), None).unwrap();
+
+        assert!(state.active);
+        assert_eq!(state.iteration, 7);
+        assert_eq!(state.max_iterations, Some(20));
+        assert_eq!(state.error_count, 4);
+        assert_eq!(state.last_commit, Some("abc123".to_string()));
+        assert_eq!(state.idle_iterations, 1);
+    }
+
+    #[test]
+    fn test_resume_resets_consecutive_errors() {
+        let state = resume_state(make_state(7, Some(20)), None).unwrap();
+        assert_eq!(state.consecutive_errors, 0);
+    }
+
+    #[test]
+    fn test_resume_extends_relative_to_current_iteration() {
+        // Stopped after completing 20 iterations (next would be 21)
+        let state = resume_state(make_state(21, Some(20)), Some(5)).unwrap();
+        assert_eq!(state.max_iterations, Some(25));
+    }
+
+    #[test]
+    fn test_resume_at_limit_without_extension_fails() {
+        let err = resume_state(make_state(21, Some(20)), None).unwrap_err();
+        assert!(err.to_string().contains("--max-iterations"));
+    }
+
+    #[test]
+    fn test_resume_unlimited() {
+        let state = resume_state(make_state(50, None), None).unwrap();
+        assert!(state.active);
+        assert_eq!(state.max_iterations, None);
+    }
+
+    #[test]
+    fn test_resume_extension_saturates() {
+        let state = resume_state(make_state(u32::MAX, Some(20)), Some(5)).unwrap();
+        assert_eq!(state.max_iterations, Some(u32::MAX));
+    }
+
+    #[test]
+    fn test_resume_options_reapply_saved_settings() {
+        let mut state = make_state(7, Some(20));
+        state.settings = LoopSettings {
+            prompt: Some("PROMPT_custom.md".to_string()),
+            prompt_append: vec![PathBuf::from("notes.md")],
+            no_validation: true,
+            workdir: Some("crates/app".to_string()),
+            max_errors: Some(3),
+            tail_errors: Some(40),
+            provider: Some("cursor".to_string()),
+            fail_fast: true,
+            ..LoopSettings::default()
+        };
+
+        let options = resume_options(state.clone(), false, None);
+
+        assert_eq!(options.settings, state.settings);
+        assert_eq!(options.mode, LoopMode::Build);
+        assert_eq!(options.max_iterations, IterationCap::Max(20));
+        assert!(options.resume.is_some_and(|resumed| resumed.iteration == 7));
+    }
+
+    #[test]
+    fn test_resume_options_flags_override_saved_settings() {
+        let mut state = make_state(7, None);
+        state.settings.provider = Some("cursor".to_string());
+
+        let options = resume_options(state, true, Some("claude".to_string()));
+
+        assert!(options.settings.no_sandbox);
+        assert_eq!(options.settings.provider, Some("claude".to_string()));
+        assert_eq!(options.max_iterations, IterationCap::Unlimited);
+    }
+
+    #[test]
+    fn test_format_resume() {
+        let output = format_resume(&make_state(7, Some(20)));
+        assert!(output.contains("Resuming"));
+        assert!(output.contains("Build"));
+        assert!(output.contains('7'));
+        assert!(output.contains("20"));
+    }
+}
@@ -1,34 +1,63 @@
 //! Revert Ralph commits.
 //!
-//! Core validation is pure. Git operations are injected.
+//! Only commits carrying the `Ralph-Iteration` trailer are reverted; manual
+//! commits in between are left intact. Each selected commit is undone with
+//! `git revert`, newest first, and commits already reverted are skipped.
+//!
+//! Core selection is pure. Git operations are injected.
 
 use anyhow::{bail, Context, Result};
 use colored::Colorize;
 use std::path::PathBuf;
 use tracing::info;
 
+use crate::commands::loop_cmd::ITERATION_TRAILER;
+use crate::state::RalphState;
+
 // -----------------------------------------------------------------------------
 // Public API
 // -----------------------------------------------------------------------------
 
-/// Runs the revert command, resetting the specified number of commits.
-pub(crate) async fn run(count: u32) -> Result<()> {
-    validate_count(count).map_err(|e| anyhow::anyhow!("{e}"))?;
+/// Runs the revert command.
+///
+/// Reverts the last `count` Ralph commits, or every Ralph commit since the
+/// loop started when `all_ralph` is set.
+pub(crate) async fn run(count: u32, all_ralph: bool) -> Result<()> {
+    let limit = if all_ralph {
+        None
+    } else {
+        validate_count(count).map_err(|e| anyhow::anyhow!("{e}"))?;
+        Some(count)
+    };
 
     let cwd = std::env::current_dir().context("Failed to get current directory")?;
 
-    println!("{}", format_revert_start(count));
+    let range = if all_ralph {
+        let initial_commit = RalphState::load(&cwd)?
+            .and_then(|state| state.initial_commit)
+            .ok_or_else(|| anyhow::anyhow!("{}", RevertError::NoLoopStart))?;
+        format!("{initial_commit}..HEAD")
+    } else {
+        "HEAD".to_string()
+    };
 
     // Get commits to revert
-    let commits = git_log(&cwd, count).await?;
-
-    print!("{}", format_commits_to_revert(&commits));
-
-    // Perform reset
-    git_reset(&cwd, count).await?;
+    let log = git_log(&cwd, &range).await?;
+    let commits =
+        select_ralph_commits(parse_commits(&log), limit).map_err(|e| anyhow::anyhow!("{e}"))?;
+    let reverted = u32::try_from(commits.len()).unwrap_or(u32::MAX);
+
+    println!("{}", format_revert_start(reverted));
+    let lines: Vec<String> = commits.iter().map(LogCommit::summary).collect();
+    print!("{}", format_commits_to_revert(&lines));
+
+    // Newest first, so later changes are undone before the ones they build on
+    for commit in &commits {
+        git_revert(&cwd, &commit.hash).await?;
+    }
 
-    info!("Reverted {} commits", count);
-    print!("{}", format_revert_success(count));
+    info!("Reverted {} commits", reverted);
+    print!("{}", format_revert_success(reverted));
 
     Ok(())
 }
@@ -42,12 +71,49 @@ pub(crate) async fn run(count: u32) -> Result<()> {
 enum RevertError {
     #[error("count must be greater than 0")]
     InvalidCount,
+    #[error("no Ralph commits found (commits are tagged with a {ITERATION_TRAILER} trailer)")]
+    NoRalphCommits,
+    #[error("only {found} Ralph commit(s) found, cannot revert {requested}")]
+    NotEnoughCommits { found: usize, requested: u32 },
+    #[error("no loop start commit recorded in .ralph/state.toml; use --last N instead")]
+    NoLoopStart,
+}
+
+/// A commit parsed from `git log`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LogCommit {
+    /// Full commit hash.
+    hash: String,
+    /// Commit subject line.
+    subject: String,
+    /// Value of the `Ralph-Iteration` trailer, if present.
+    iteration: Option<u32>,
+    /// Hash of the commit this one reverts, if it is a `git revert` commit.
+    reverts: Option<String>,
+}
+
+impl LogCommit {
+    /// One-line summary shown before reverting.
+    fn summary(&self) -> String {
+        let short = &self.hash[..self.hash.len().min(SHORT_HASH_LEN)];
+        match self.iteration {
+            Some(n) => format!("{short} {} (iteration {n})", self.subject),
+            None => format!("{short} {}", self.subject),
+        }
+    }
 }
 
 // -----------------------------------------------------------------------------
 // Helper functions
 // -----------------------------------------------------------------------------
 
+/// `git log` format: hash, subject and full message separated by unit
+/// separators, one record per commit terminated by a record separator.
+const LOG_FORMAT: &str = "--format=%H%x1f%s%x1f%B%x1e";
+
+/// Hash prefix length shown in summaries.
+const SHORT_HASH_LEN: usize = 7;
+
 /// Validates that revert count is greater than zero.
 fn validate_count(count: u32) -> Result<(), RevertError> {
     if count == 0 {
@@ -57,15 +123,80 @@ fn validate_count(count: u32) -> Result<(), RevertError> {
     }
 }
 
-/// Parses git log output into commit summary lines.
-fn parse_commits(log_output: &str) -> Vec<String> {
+/// Parses `git log` output in [`LOG_FORMAT`] into commits, newest first.
+fn parse_commits(log_output: &str) -> Vec<LogCommit> {
     log_output
-        .lines()
-        .filter(|line| !line.is_empty())
-        .map(std::string::ToString::to_string)
+        .split('\x1e')
+        .map(|record| record.trim_start_matches('\n'))
+        .filter(|record| !record.is_empty())
+        .filter_map(|record| {
+            let mut fields = record.splitn(3, '\x1f');
+            let hash = fields.next()?.trim();
+            let subject = fields.next()?.trim();
+            let message = fields.next().unwrap_or_default();
+            Some(LogCommit {
+                hash: hash.to_string(),
+                subject: subject.to_string(),
+                iteration: parse_iteration_trailer(message),
+                reverts: parse_reverted_hash(message),
+            })
+        })
         .collect()
 }
 
+/// Extracts the iteration from a `Ralph-Iteration: <n>` trailer line.
+///
+/// The last matching line wins, mirroring how git reads trailers.
+fn parse_iteration_trailer(message: &str) -> Option<u32> {
+    message.lines().rev().find_map(|line| {
+        line.trim()
+            .strip_prefix(ITERATION_TRAILER)?
+            .strip_prefix(':')?
+            .trim()
+            .parse()
+            .ok()
+    })
+}
+
+/// Extracts the target of a `git revert` commit ("This reverts commit <hash>.").
+fn parse_reverted_hash(message: &str) -> Option<String> {
+    message.lines().find_map(|line| {
+        let hash = line
+            .trim()
+            .strip_prefix("This reverts commit ")?
+            .trim_end_matches('.');
+        Some(hash.to_string())
+    })
+}
+
+/// Keeps only Ralph commits that were not already reverted, newest first,
+/// up to `limit` of them.
+fn select_ralph_commits(
+    commits: Vec<LogCommit>,
+    limit: Option<u32>,
+) -> Result<Vec<LogCommit>, RevertError> {
+    let reverted: std::collections::HashSet<String> =
+        commits.iter().filter_map(|c| c.reverts.clone()).collect();
+    let ralph = commits
+        .into_iter()
+        .filter(|c| c.iteration.is_some() && !reverted.contains(&c.hash));
+    let selected: Vec<LogCommit> = match limit {
+        Some(n) => ralph.take(n as usize).collect(),
+        None => ralph.collect(),
+    };
+
+    match limit {
+        _ if selected.is_empty() => Err(RevertError::NoRalphCommits),
+        Some(requested) if selected.len() < requested as usize => {
+            Err(RevertError::NotEnoughCommits {
+                found: selected.len(),
+                requested,
+            })
+        }
+        _ => Ok(selected),
+    }
+}
+
 /// Formats the revert start message.
 fn format_revert_start(count: u32) -> String {
     format!(
@@ -110,10 +241,10 @@ fn format_revert_success(count: u32) -> String {
 // Git operations
 // -----------------------------------------------------------------------------
 
-async fn git_log(cwd: &PathBuf, count: u32) -> Result<Vec<String>> {
+async fn git_log(cwd: &PathBuf, range: &str) -> Result<String> {
     let output = tokio::process::Command::new("git")
         .current_dir(cwd)
-        .args(["log", "--oneline", "-n", &count.to_string()])
+        .args(["log", LOG_FORMAT, range])
         .output()
         .await
         .context("Failed to get git log")?;
@@ -122,21 +253,23 @@ async fn git_log(cwd: &PathBuf, count: u32) -> Result<Vec<String>> {
         bail!("Failed to get git log");
     }
 
-    Ok(parse_commits(&String::from_utf8_lossy(&output.stdout)))
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
 }
 
-async fn git_reset(cwd: &PathBuf, count: u32) -> Result<()> {
-    let reset_ref = format!("HEAD~{count}");
+async fn git_revert(cwd: &PathBuf, hash: &str) -> Result<()> {
     let output = tokio::process::Command::new("git")
         .current_dir(cwd)
-        .args(["reset", "--hard", &reset_ref])
+        .args(["revert", "--no-edit", hash])
         .output()
         .await
-        .context("Failed to run git reset")?;
+        .context("Failed to run git revert")?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        bail!("Git reset failed: {stderr}");
+        bail!(
+            "Git revert of {hash} failed: {stderr}\
+             Resolve the conflict and run 'git revert --continue', or 'git revert --abort'."
+        );
     }
 
     Ok(())
@@ -162,21 +295,54 @@ mod tests {
         assert!(validate_count(100).is_ok());
     }
 
+    /// Builds one `git log` record in [`LOG_FORMAT`].
+    fn record(hash: &str, subject: &str, body: &str) -> String {
+        format!("{hash}\x1f{subject}\x1f{subject}\n\n{body}\n\x1e\n")
+    }
+
+    fn commit(hash: &str, iteration: Option<u32>) -> LogCommit {
+        LogCommit {
+            hash: hash.to_string(),
+            subject: format!("subject {hash}"),
+            iteration,
+            reverts: None,
+        }
+    }
+
     #[test]
     fn test_parse_commits_single() {
-        let log = "abc1234 Fix bug";
-        let commits = parse_commits(log);
-        assert_eq!(commits, vec!["abc1234 Fix bug"]);
+        let log = record("abc1234", "Fix bug", "Ralph-Iteration: 3");
+        let commits = parse_commits(&log);
+        assert_eq!(
+            commits,
+            vec![LogCommit {
+                hash: "abc1234".to_string(),
+                subject: "Fix bug".to_string(),
+                iteration: Some(3),
+                reverts: None,
+            }]
+        );
     }
 
     #[test]
     fn test_parse_commits_multiple() {
-        let log = "abc1234 Fix bug\ndef5678 Add feature\nghi9012 Update docs";
-        let commits = parse_commits(log);
+        let log = [
+            record("abc1234", "Fix bug", "Ralph-Iteration: 4"),
+            record(
+                "def5678",
+                "Manual tweak",
+                "Signed-off-by: Me <me@example.com>",
+            ),
+            record("ghi9012", "Update docs", "Details.\n\nRalph-Iteration: 2"),
+        ]
+        .concat();
+        let commits = parse_commits(&log);
         assert_eq!(commits.len(), 3);
-        assert_eq!(commits[0], "abc1234 Fix bug");
-        assert_eq!(commits[1], "def5678 Add feature");
-        assert_eq!(commits[2], "ghi9012 Update docs");
+        assert_eq!(commits[0].hash, "abc1234");
+        assert_eq!(commits[0].iteration, Some(4));
+        assert_eq!(commits[1].subject, "Manual tweak");
+        assert_eq!(commits[1].iteration, None);
+        assert_eq!(commits[2].iteration, Some(2));
     }
 
     #[test]
@@ -187,11 +353,104 @@ mod tests {
 
     #[test]
     fn test_parse_commits_with_empty_lines() {
-        let log = "abc1234 Fix bug\n\ndef5678 Add feature\n";
-        let commits = parse_commits(log);
+        let log = format!(
+            "{}\n\n{}",
+            record("abc1234", "Fix bug", ""),
+            record("def5678", "Add feature", "")
+        );
+        let commits = parse_commits(&log);
         assert_eq!(commits.len(), 2);
     }
 
+    #[test]
+    fn test_parse_iteration_trailer() {
+        assert_eq!(
+            parse_iteration_trailer("subject\n\nRalph-Iteration: 12\n"),
+            Some(12)
+        );
+        assert_eq!(
+            parse_iteration_trailer("subject\n\nRalph-Iteration:7"),
+            Some(7)
+        );
+        assert_eq!(parse_iteration_trailer("subject"), None);
+        assert_eq!(parse_iteration_trailer("Ralph-Iteration: soon"), None);
+        assert_eq!(parse_iteration_trailer("Ralph-Iterations: 1"), None);
+    }
+
+    #[test]
+    fn test_select_ralph_commits_skips_manual_commits() {
+        let commits = vec![
+            commit("m1", None),
+            commit("r3", Some(3)),
+            commit("m2", None),
+            commit("r2", Some(2)),
+            commit("r1", Some(1)),
+        ];
+        let selected = select_ralph_commits(commits, Some(2)).unwrap();
+        let hashes: Vec<&str> = selected.iter().map(|c| c.hash.as_str()).collect();
+        assert_eq!(hashes, vec!["r3", "r2"]);
+    }
+
+    #[test]
+    fn test_parse_reverted_hash() {
+        let log = record(
+            "fed4321",
+            "Revert \"Fix bug\"",
+            "This reverts commit abc1234def5678.",
+        );
+        let commits = parse_commits(&log);
+        assert_eq!(commits[0].reverts.as_deref(), Some("abc1234def5678"));
+        assert_eq!(commits[0].iteration, None);
+    }
+
+    #[test]
+    fn test_select_ralph_commits_skips_already_reverted() {
+        let revert = LogCommit {
+            reverts: Some("r2".to_string()),
+            ..commit("v2", None)
+        };
+        let commits = vec![revert, commit("r2", Some(2)), commit("r1", Some(1))];
+        let selected = select_ralph_commits(commits, Some(1)).unwrap();
+        assert_eq!(selected[0].hash, "r1");
+    }
+
+    #[test]
+    fn test_select_ralph_commits_all() {
+        let commits = vec![
+            commit("r2", Some(2)),
+            commit("m1", None),
+            commit("r1", Some(1)),
+        ];
+        let selected = select_ralph_commits(commits, None).unwrap();
+        assert_eq!(selected.len(), 2);
+    }
+
+    #[test]
+    fn test_select_ralph_commits_errors() {
+        assert_eq!(
+            select_ralph_commits(vec![commit("m1", None)], None),
+            Err(RevertError::NoRalphCommits)
+        );
+        assert_eq!(
+            select_ralph_commits(vec![commit("r1", Some(1)), commit("m1", None)], Some(3)),
+            Err(RevertError::NotEnoughCommits {
+                found: 1,
+                requested: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_log_commit_summary() {
+        assert_eq!(
+            commit("r1", Some(1)).summary(),
+            "r1 subject r1 (iteration 1)"
+        );
+        assert_eq!(commit("m1", None).summary(), "m1 subject m1");
+        let full = commit("abc1234def5678", None);
+        assert_eq!(full.summary(), "abc1234 subject abc1234def5678");
+    }
+
     #[test]
     fn test_revert_error_display() {
         assert_eq!(
@@ -0,0 +1,64 @@
+//! Open an interactive shell in the project's sandbox container.
+//!
+//! Picks the most recently created running Ralph container for this
+//! project, such as one left by `ralph loop --keep-container`, and runs
+//! `docker exec -it <name> bash` (or `podman`) with the terminal attached.
+
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+
+use crate::config::{Config, ContainerRuntime};
+use crate::sandbox::DockerSandbox;
+
+// -----------------------------------------------------------------------------
+// Public API
+// -----------------------------------------------------------------------------
+
+/// Runs the shell command, using the runtime from `config_file`
+/// (`--config`) or `ralph.toml`.
+pub(crate) async fn run(config_file: Option<&Path>) -> Result<()> {
+    let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    let config = Config::load_from(&cwd, config_file).context("Failed to load ralph.toml")?;
+    let runtime = config.sandbox.runtime;
+
+    let Some(name) = DockerSandbox::latest_project_container(runtime, &cwd).await? else {
+        bail!(
+            "No running Ralph container for this project.\n\
+             Keep one with `ralph loop --keep-container` and `sandbox.reuse_container = true`"
+        );
+    };
+
+    let command = shell_command(runtime, &name);
+    let status = std::process::Command::new(&command[0])
+        .args(&command[1..])
+        .status()
+        .with_context(|| format!("Failed to run {}", command[0]))?;
+    if !status.success() {
+        bail!("Shell in {name} exited with {status}");
+    }
+    Ok(())
+}
+
+/// The command that opens a shell in container `name`, program first.
+pub(crate) fn shell_command(runtime: ContainerRuntime, name: &str) -> Vec<String> {
+    [runtime.command(), "exec", "-it", name, "bash"]
+        .map(String::from)
+        .to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_command_per_runtime() {
+        assert_eq!(
+            shell_command(ContainerRuntime::Docker, "ralph-1a2b3c4d").join(" "),
+            "docker exec -it ralph-1a2b3c4d bash"
+        );
+        assert_eq!(
+            shell_command(ContainerRuntime::Podman, "ralph-1a2b3c4d")[0],
+            "podman"
+        );
+    }
+}
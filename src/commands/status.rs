@@ -2,33 +2,132 @@
 //!
 //! Separates display formatting from state loading for testability.
 //! Formatting is pure. IO happens only at the top level.
+//! `--json` emits the raw state plus derived timing for scripting.
+//! `--watch` re-renders the status on an interval until the loop stops.
+//! `--commits` and `--since` control the recent-commits list.
+//! The idle count is shown against `completion.idle_threshold` from
+//! ralph.toml.
 
 use anyhow::{Context, Result};
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 use colored::Colorize;
+use serde::Serialize;
 use std::fmt::Write;
 use std::path::Path;
 
-use crate::state::RalphState;
+use crate::config::Config;
+use crate::detection::format_idle_progress;
+use crate::state::{ErrorEntry, RalphState};
 
 // -----------------------------------------------------------------------------
 // Public API
 // -----------------------------------------------------------------------------
 
+/// Default `--watch` refresh interval in seconds (used when no value is given).
+pub(crate) const DEFAULT_WATCH_INTERVAL: &str = "2";
+
+/// Default number of recent commits shown (`--commits`).
+pub(crate) const DEFAULT_RECENT_COMMITS: &str = "5";
+
+/// ANSI sequence that clears the screen and moves the cursor home.
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
 /// Runs the status command, displaying current loop state.
-pub(crate) fn run() -> Result<()> {
+/// With `json`, prints a machine-readable JSON document instead.
+/// With `watch`, refreshes every `watch` seconds until the loop stops.
+/// Lists up to `commits` recent commits, only those after `since` if given.
+/// The idle threshold comes from `config_file` (`--config`) or `ralph.toml`.
+pub(crate) async fn run(
+    json: bool,
+    watch: Option<u64>,
+    commits: u32,
+    since: Option<&str>,
+    config_file: Option<&Path>,
+) -> Result<()> {
     let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    let log_args = recent_commits_args(commits, since);
+    let config = Config::load_from(&cwd, config_file).context("Failed to load ralph.toml")?;
+
+    if let Some(secs) = watch {
+        return watch_status(
+            &cwd,
+            std::time::Duration::from_secs(secs),
+            &log_args,
+            &config,
+        )
+        .await;
+    }
 
     let state = RalphState::load(&cwd)?;
+    if json {
+        let recent_commits = state
+            .as_ref()
+            .map(|_| get_recent_commits(&cwd, &log_args).unwrap_or_default())
+            .unwrap_or_default();
+        println!(
+            "{}",
+            format_status_json(state.as_ref(), &recent_commits, Utc::now())
+        );
+        return Ok(());
+    }
+
     let status = state.as_ref().map(|s| {
-        let recent_commits = get_recent_commits(&cwd).unwrap_or_default();
-        StatusDisplay::from_state(s, &recent_commits)
+        let recent_commits = get_recent_commits(&cwd, &log_args).unwrap_or_default();
+        StatusDisplay::from_state(s, &recent_commits, &config)
     });
     print!("{}", format_status_colored(status.as_ref()));
 
     Ok(())
 }
 
+/// Parses the `--watch` interval: a positive number of seconds.
+pub(crate) fn parse_watch_interval(value: &str) -> Result<u64, String> {
+    match value.trim().parse::<u64>() {
+        Ok(0) => Err("watch interval must be at least 1 second".to_string()),
+        Ok(secs) => Ok(secs),
+        Err(_) => Err(format!(
+            "invalid watch interval '{value}': expected seconds"
+        )),
+    }
+}
+
+/// Clears the screen and re-renders the status every `interval`.
+///
+/// Stops once the loop is no longer active. If the state file disappears,
+/// the last rendered status stays on screen.
+async fn watch_status(
+    cwd: &Path,
+    interval: std::time::Duration,
+    log_args: &[String],
+    config: &Config,
+) -> Result<()> {
+    let mut rendered_once = false;
+    loop {
+        let Some(state) = RalphState::load(cwd)? else {
+            if !rendered_once {
+                print!("{}", format_status_colored(None));
+            }
+            println!("\n{} Loop state removed. Stopped watching.", "ℹ".blue());
+            return Ok(());
+        };
+
+        let recent_commits = get_recent_commits(cwd, log_args).unwrap_or_default();
+        let status = StatusDisplay::from_state(&state, &recent_commits, config);
+        print!("{CLEAR_SCREEN}{}", format_status_colored(Some(&status)));
+        rendered_once = true;
+
+        if !state.active {
+            println!(
+                "\n{} Loop is no longer active. Stopped watching.",
+                "ℹ".blue()
+            );
+            return Ok(());
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
 // -----------------------------------------------------------------------------
 // Internal types
 // -----------------------------------------------------------------------------
@@ -47,43 +146,17 @@ struct StatusDisplay {
     estimated_remaining: Option<String>,
     error_count: u32,
     last_error: Option<String>,
+    recent_errors: Vec<String>,
+    input_tokens: u64,
+    output_tokens: u64,
     recent_commits: Vec<String>,
+    /// Idle count against the threshold, e.g. `1/2`.
+    idle: String,
 }
 
 impl StatusDisplay {
-    fn from_state(state: &RalphState, recent_commits: &[String]) -> Self {
-        let now = Utc::now();
-        let elapsed = now.signed_duration_since(state.started_at);
-        let elapsed_time = format_duration(&elapsed);
-
-        let avg_iteration_duration = if state.iteration > 1 {
-            let total_duration = elapsed;
-            let avg_seconds = total_duration.num_seconds() / i64::from(state.iteration);
-            Some(format_duration(&Duration::seconds(avg_seconds)))
-        } else {
-            None
-        };
-
-        let estimated_remaining = if let (Some(max_iter), Some(_avg_dur)) =
-            (state.max_iterations, &avg_iteration_duration)
-        {
-            if state.iteration < max_iter {
-                let remaining_iterations = max_iter - state.iteration;
-                // Parse average duration to estimate remaining time
-                // For simplicity, use elapsed time per iteration
-                if state.iteration > 1 {
-                    let avg_seconds = elapsed.num_seconds() / i64::from(state.iteration);
-                    let remaining_seconds = avg_seconds * i64::from(remaining_iterations);
-                    Some(format_duration(&Duration::seconds(remaining_seconds)))
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        } else {
-            None
-        };
+    fn from_state(state: &RalphState, recent_commits: &[String], config: &Config) -> Self {
+        let timing = IterationTiming::from_state(state, Utc::now());
 
         Self {
             active: state.active,
@@ -94,20 +167,106 @@ impl StatusDisplay {
             last_iteration_at: state
                 .last_iteration_at
                 .map(|dt| dt.format("%Y-%m-%d %H:%M:%S UTC").to_string()),
-            elapsed_time,
-            avg_iteration_duration,
-            estimated_remaining,
+            elapsed_time: format_duration(&timing.elapsed),
+            avg_iteration_duration: timing.avg_iteration.as_ref().map(format_duration),
+            estimated_remaining: timing.estimated_remaining.as_ref().map(format_duration),
             error_count: state.error_count,
             last_error: state.last_error.clone(),
+            recent_errors: state
+                .error_history
+                .iter()
+                .rev()
+                .take(RECENT_ERRORS)
+                .map(format_error_entry)
+                .collect(),
+            input_tokens: state.total_input_tokens,
+            output_tokens: state.total_output_tokens,
             recent_commits: recent_commits.to_vec(),
+            idle: format_idle_progress(
+                state.idle_iterations,
+                config.completion.idle_threshold_for(state.mode),
+            ),
         }
     }
 }
 
+/// Elapsed and projected durations derived from loop state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct IterationTiming {
+    elapsed: Duration,
+    avg_iteration: Option<Duration>,
+    estimated_remaining: Option<Duration>,
+}
+
+impl IterationTiming {
+    fn from_state(state: &RalphState, now: DateTime<Utc>) -> Self {
+        let elapsed = now.signed_duration_since(state.started_at);
+
+        // Averages need at least one completed iteration to be meaningful
+        let avg_iteration = (state.iteration > 1)
+            .then(|| Duration::seconds(elapsed.num_seconds() / i64::from(state.iteration)));
+
+        let estimated_remaining = match (state.max_iterations, avg_iteration) {
+            (Some(max_iter), Some(avg)) if state.iteration < max_iter => {
+                let remaining_iterations = max_iter - state.iteration;
+                Some(Duration::seconds(
+                    avg.num_seconds() * i64::from(remaining_iterations),
+                ))
+            }
+            _ => None,
+        };
+
+        Self {
+            elapsed,
+            avg_iteration,
+            estimated_remaining,
+        }
+    }
+}
+
+/// Machine-readable status for `ralph status --json`.
+///
+/// Flattens the raw persisted state and adds derived timing in whole seconds.
+#[derive(Debug, Serialize)]
+struct StatusJson<'a> {
+    exists: bool,
+    #[serde(flatten)]
+    state: &'a RalphState,
+    elapsed_seconds: i64,
+    avg_iteration_seconds: Option<i64>,
+    estimated_remaining_seconds: Option<i64>,
+    recent_commits: &'a [String],
+}
+
 // -----------------------------------------------------------------------------
 // Helper functions
 // -----------------------------------------------------------------------------
 
+/// Formats status as a JSON document.
+/// When no loop exists, emits `{"active": false, "exists": false}`.
+fn format_status_json(
+    state: Option<&RalphState>,
+    recent_commits: &[String],
+    now: DateTime<Utc>,
+) -> String {
+    let value = match state {
+        Some(state) => {
+            let timing = IterationTiming::from_state(state, now);
+            let status = StatusJson {
+                exists: true,
+                state,
+                elapsed_seconds: timing.elapsed.num_seconds(),
+                avg_iteration_seconds: timing.avg_iteration.map(|d| d.num_seconds()),
+                estimated_remaining_seconds: timing.estimated_remaining.map(|d| d.num_seconds()),
+                recent_commits,
+            };
+            serde_json::to_value(&status).expect("status is always serializable")
+        }
+        None => serde_json::json!({ "active": false, "exists": false }),
+    };
+    serde_json::to_string_pretty(&value).expect("JSON value is always serializable")
+}
+
 /// Formats status for terminal output (plain text, testable).
 #[cfg(test)]
 fn format_status(status: Option<&StatusDisplay>) -> String {
@@ -139,6 +298,7 @@ fn format_status(status: Option<&StatusDisplay>) -> String {
         if let Some(ref remaining) = s.estimated_remaining {
             writeln!(&mut out, "  Est. left:  {remaining}").unwrap();
         }
+        writeln!(&mut out, "  Idle:       {}", s.idle).unwrap();
         if s.error_count > 0 {
             writeln!(&mut out, "  Errors:    {}", s.error_count).unwrap();
             if let Some(ref last_error) = s.last_error {
@@ -150,9 +310,23 @@ fn format_status(status: Option<&StatusDisplay>) -> String {
                 writeln!(&mut out, "  Last error: {display_error}").unwrap();
             }
         }
+        if !s.recent_errors.is_empty() {
+            writeln!(&mut out, "\n  Recent errors:").unwrap();
+            for error in &s.recent_errors {
+                writeln!(&mut out, "    {error}").unwrap();
+            }
+        }
+        if s.input_tokens > 0 || s.output_tokens > 0 {
+            writeln!(
+                &mut out,
+                "  Tokens:     {} in / {} out",
+                s.input_tokens, s.output_tokens
+            )
+            .unwrap();
+        }
         if !s.recent_commits.is_empty() {
             writeln!(&mut out, "\n  Recent commits:").unwrap();
-            for commit in s.recent_commits.iter().take(5) {
+            for commit in &s.recent_commits {
                 writeln!(&mut out, "    {commit}").unwrap();
             }
         }
@@ -202,6 +376,8 @@ fn format_status_colored(status: Option<&StatusDisplay>) -> String {
             writeln!(&mut out, "  Est. left:  {}", remaining.cyan()).unwrap();
         }
 
+        writeln!(&mut out, "  Idle:       {}", s.idle.cyan()).unwrap();
+
         if s.error_count > 0 {
             writeln!(
                 &mut out,
@@ -219,9 +395,26 @@ fn format_status_colored(status: Option<&StatusDisplay>) -> String {
             }
         }
 
+        if !s.recent_errors.is_empty() {
+            writeln!(&mut out, "\n  Recent errors:").unwrap();
+            for error in &s.recent_errors {
+                writeln!(&mut out, "    {}", error.yellow()).unwrap();
+            }
+        }
+
+        if s.input_tokens > 0 || s.output_tokens > 0 {
+            writeln!(
+                &mut out,
+                "  Tokens:     {} in / {} out",
+                s.input_tokens.to_string().cyan(),
+                s.output_tokens.to_string().cyan()
+            )
+            .unwrap();
+        }
+
         if !s.recent_commits.is_empty() {
             writeln!(&mut out, "\n  Recent commits:").unwrap();
-            for commit in s.recent_commits.iter().take(5) {
+            for commit in &s.recent_commits {
                 writeln!(&mut out, "    {}", commit.dimmed()).unwrap();
             }
         }
@@ -238,6 +431,32 @@ fn format_status_colored(status: Option<&StatusDisplay>) -> String {
 // Helper functions
 // -----------------------------------------------------------------------------
 
+/// Error history entries shown by `ralph status`, newest first.
+const RECENT_ERRORS: usize = 3;
+
+/// Characters of an error message shown per history line.
+const RECENT_ERROR_CHARS: usize = 70;
+
+/// Formats an error history entry as one line: iteration, time, and message.
+///
+/// Whitespace in the message, including newlines, collapses to single spaces.
+fn format_error_entry(entry: &ErrorEntry) -> String {
+    let message = entry
+        .message
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let message = match message.char_indices().nth(RECENT_ERROR_CHARS) {
+        Some((end, _)) => format!("{}...", &message[..end]),
+        None => message,
+    };
+    format!(
+        "#{} {} {message}",
+        entry.iteration,
+        entry.timestamp.format("%H:%M:%S")
+    )
+}
+
 /// Formats a duration into a human-readable string.
 fn format_duration(duration: &Duration) -> String {
     let total_seconds = duration.num_seconds();
@@ -254,13 +473,32 @@ fn format_duration(duration: &Duration) -> String {
     }
 }
 
+/// Builds `git log` arguments for the recent-commits list.
+///
+/// `since` is passed to `git log --since` unchanged, so any git-parseable
+/// time works ("2 hours ago", "2024-01-15T10:00:00Z"). Git simply returns
+/// fewer lines when the repo has fewer matching commits.
+fn recent_commits_args(count: u32, since: Option<&str>) -> Vec<String> {
+    let mut args = vec![
+        "log".to_string(),
+        "--oneline".to_string(),
+        "-n".to_string(),
+        count.to_string(),
+    ];
+    if let Some(since) = since {
+        args.push("--since".to_string());
+        args.push(since.to_string());
+    }
+    args
+}
+
 /// Gets recent commit messages from git log.
-fn get_recent_commits(cwd: &Path) -> Result<Vec<String>> {
+fn get_recent_commits(cwd: &Path, log_args: &[String]) -> Result<Vec<String>> {
     use std::process::Command;
 
     let output = Command::new("git")
         .current_dir(cwd)
-        .args(["log", "--oneline", "-n", "5"])
+        .args(log_args)
         .output()
         .context("Failed to get git log")?;
 
@@ -288,7 +526,7 @@ fn parse_commits(log_output: &str) -> Vec<String> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::state::Mode;
+    use crate::state::{ErrorKind, LoopSettings, Mode};
     use chrono::Utc;
 
     #[test]
@@ -302,18 +540,63 @@ mod tests {
             last_iteration_at: None,
             error_count: 0,
             consecutive_errors: 0,
+            circuit_breaker_cooled_down: false,
             last_error: None,
             last_commit: None,
             idle_iterations: 0,
+            commit_count: None,
+            total_input_tokens: 0,
+            total_output_tokens: 0,
+            initial_commit: None,
+            pid: None,
+            session_id: None,
+            error_history: Vec::new(),
+            settings: LoopSettings::default(),
         };
 
-        let status = StatusDisplay::from_state(&state, &[]);
+        let status = StatusDisplay::from_state(&state, &[], &Config::default());
         assert!(status.active);
         assert_eq!(status.mode, "Build");
         assert_eq!(status.iteration, 5);
         assert_eq!(status.max_iterations, Some(10));
     }
 
+    #[test]
+    fn test_status_display_idle_against_threshold() {
+        let mut config = Config::default();
+        config.completion.idle_threshold = 3;
+        config.completion.plan.idle_threshold = Some(1);
+        let idle = |idle_iterations, mode| {
+            let state = RalphState {
+                mode,
+                idle_iterations,
+                ..Default::default()
+            };
+            StatusDisplay::from_state(&state, &[], &config).idle
+        };
+
+        assert_eq!(idle(0, Mode::Build), "0/3");
+        assert_eq!(idle(1, Mode::Build), "1/3");
+        assert_eq!(
+            idle(2, Mode::Build),
+            "2/3 (next idle iteration completes the loop)"
+        );
+        assert_eq!(idle(3, Mode::Build), "3/3");
+        // Plan mode reads [completion.plan]
+        assert_eq!(
+            idle(0, Mode::Plan),
+            "0/1 (next idle iteration completes the loop)"
+        );
+
+        let state = RalphState {
+            idle_iterations: 2,
+            ..Default::default()
+        };
+        let status = StatusDisplay::from_state(&state, &[], &config);
+        assert!(format_status(Some(&status))
+            .contains("  Idle:       2/3 (next idle iteration completes the loop)\n"));
+    }
+
     #[test]
     fn test_format_status_with_state() {
         let status = StatusDisplay {
@@ -328,7 +611,11 @@ mod tests {
             estimated_remaining: Some("85m".to_string()),
             error_count: 0,
             last_error: None,
+            recent_errors: Vec::new(),
+            input_tokens: 0,
+            output_tokens: 0,
             recent_commits: Vec::new(),
+            idle: "0/2".to_string(),
         };
 
         let output = format_status(Some(&status));
@@ -339,6 +626,55 @@ mod tests {
         assert!(output.contains("12:05:00"));
     }
 
+    #[test]
+    fn test_format_status_shows_tokens() {
+        let state = RalphState {
+            total_input_tokens: 4200,
+            total_output_tokens: 310,
+            ..Default::default()
+        };
+        let status = StatusDisplay::from_state(&state, &[], &Config::default());
+
+        let output = format_status(Some(&status));
+        assert!(output.contains("Tokens:     4200 in / 310 out"));
+
+        let json = format_status_json(Some(&state), &[], Utc::now());
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["total_input_tokens"], 4200);
+        assert_eq!(value["total_output_tokens"], 310);
+    }
+
+    #[test]
+    fn test_format_status_shows_recent_errors_newest_first() {
+        let mut state = RalphState::default();
+        for iteration in 1..=5 {
+            state.iteration = iteration;
+            state.record_error(ErrorKind::Agent, format!("Agent timeout: run {iteration}"));
+        }
+        state.iteration = 6;
+        state.record_error(
+            ErrorKind::Validation,
+            format!("Validation error:\n  error[E0382]:\n{}", "x".repeat(200)),
+        );
+        let status = StatusDisplay::from_state(&state, &[], &Config::default());
+
+        assert_eq!(status.recent_errors.len(), RECENT_ERRORS);
+        assert!(status.recent_errors[0].starts_with("#6 "));
+        assert!(status.recent_errors[0].contains("Validation error: error[E0382]: xxx"));
+        assert!(status.recent_errors[0].ends_with("..."));
+        assert!(status.recent_errors[1].starts_with("#5 "));
+        assert!(status.recent_errors[1].ends_with("Agent timeout: run 5"));
+
+        let output = format_status(Some(&status));
+        assert!(output.contains("Recent errors:"));
+        assert!(!output.contains("run 3"));
+
+        let json = format_status_json(Some(&state), &[], Utc::now());
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["error_history"].as_array().unwrap().len(), 6);
+        assert_eq!(value["error_history"][5]["kind"], "validation");
+    }
+
     #[test]
     fn test_format_status_none() {
         let output = format_status(None);
@@ -359,7 +695,11 @@ mod tests {
             estimated_remaining: None,
             error_count: 0,
             last_error: None,
+            recent_errors: Vec::new(),
+            input_tokens: 0,
+            output_tokens: 0,
             recent_commits: Vec::new(),
+            idle: "0/2".to_string(),
         };
 
         let output = format_status(Some(&status));
@@ -381,7 +721,11 @@ mod tests {
             estimated_remaining: None,
             error_count: 0,
             last_error: None,
+            recent_errors: Vec::new(),
+            input_tokens: 0,
+            output_tokens: 0,
             recent_commits: Vec::new(),
+            idle: "0/2".to_string(),
         };
 
         let output = format_status_colored(Some(&status));
@@ -389,6 +733,134 @@ mod tests {
         assert!(output.contains("━"));
     }
 
+    #[test]
+    fn test_iteration_timing() {
+        let now = Utc::now();
+        let state = RalphState {
+            iteration: 5,
+            max_iterations: Some(10),
+            started_at: now - Duration::minutes(50),
+            ..Default::default()
+        };
+
+        let timing = IterationTiming::from_state(&state, now);
+        assert_eq!(timing.elapsed.num_seconds(), 3000);
+        assert_eq!(timing.avg_iteration, Some(Duration::seconds(600)));
+        assert_eq!(timing.estimated_remaining, Some(Duration::seconds(3000)));
+    }
+
+    #[test]
+    fn test_iteration_timing_first_iteration() {
+        let now = Utc::now();
+        let state = RalphState {
+            iteration: 1,
+            max_iterations: Some(10),
+            started_at: now - Duration::minutes(5),
+            ..Default::default()
+        };
+
+        let timing = IterationTiming::from_state(&state, now);
+        assert!(timing.avg_iteration.is_none());
+        assert!(timing.estimated_remaining.is_none());
+    }
+
+    #[test]
+    fn test_format_status_json_no_loop() {
+        let output = format_status_json(None, &[], Utc::now());
+        let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({ "active": false, "exists": false })
+        );
+    }
+
+    #[test]
+    fn test_format_status_json_with_state() {
+        let now = Utc::now();
+        let state = RalphState {
+            active: true,
+            mode: Mode::Build,
+            iteration: 3,
+            max_iterations: Some(5),
+            started_at: now - Duration::minutes(30),
+            error_count: 2,
+            last_error: Some("Agent execution timed out".to_string()),
+            ..Default::default()
+        };
+        let commits = vec!["abc1234 Fix bug".to_string()];
+
+        let output = format_status_json(Some(&state), &commits, now);
+        let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(value["exists"], true);
+        assert_eq!(value["active"], true);
+        assert_eq!(value["mode"], "build");
+        assert_eq!(value["iteration"], 3);
+        assert_eq!(value["max_iterations"], 5);
+        assert_eq!(value["error_count"], 2);
+        assert_eq!(value["last_error"], "Agent execution timed out");
+        assert_eq!(value["elapsed_seconds"], 1800);
+        assert_eq!(value["avg_iteration_seconds"], 600);
+        assert_eq!(value["estimated_remaining_seconds"], 1200);
+        assert_eq!(value["recent_commits"][0], "abc1234 Fix bug");
+    }
+
+    #[test]
+    fn test_format_status_json_unlimited() {
+        let state = RalphState {
+            max_iterations: None,
+            ..Default::default()
+        };
+
+        let output = format_status_json(Some(&state), &[], Utc::now());
+        let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert!(value["max_iterations"].is_null());
+        assert!(value["avg_iteration_seconds"].is_null());
+        assert!(value["estimated_remaining_seconds"].is_null());
+    }
+
+    #[test]
+    fn test_parse_watch_interval() {
+        assert_eq!(parse_watch_interval("5"), Ok(5));
+        assert_eq!(parse_watch_interval(" 10 "), Ok(10));
+        assert!(parse_watch_interval("0").is_err());
+        assert!(parse_watch_interval("-1").is_err());
+        assert!(parse_watch_interval("fast").is_err());
+    }
+
+    #[test]
+    fn test_default_watch_interval() {
+        assert_eq!(parse_watch_interval(DEFAULT_WATCH_INTERVAL), Ok(2));
+    }
+
+    #[tokio::test]
+    async fn test_watch_status_stops_when_inactive() {
+        let dir = tempfile::tempdir().unwrap();
+        RalphState::default().save(dir.path()).unwrap();
+
+        // Inactive loop: renders once and returns without sleeping
+        watch_status(
+            dir.path(),
+            std::time::Duration::from_hours(1),
+            &recent_commits_args(5, None),
+            &Config::default(),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_watch_status_stops_without_state() {
+        let dir = tempfile::tempdir().unwrap();
+        watch_status(
+            dir.path(),
+            std::time::Duration::from_hours(1),
+            &recent_commits_args(5, None),
+            &Config::default(),
+        )
+        .await
+        .unwrap();
+    }
+
     #[test]
     fn test_format_duration_seconds() {
         let duration = Duration::seconds(45);
@@ -407,6 +879,22 @@ mod tests {
         assert_eq!(format_duration(&duration), "1h 1m 5s");
     }
 
+    #[test]
+    fn test_recent_commits_args_default() {
+        assert_eq!(
+            recent_commits_args(5, None),
+            vec!["log", "--oneline", "-n", "5"]
+        );
+    }
+
+    #[test]
+    fn test_recent_commits_args_with_since() {
+        assert_eq!(
+            recent_commits_args(20, Some("2 hours ago")),
+            vec!["log", "--oneline", "-n", "20", "--since", "2 hours ago"]
+        );
+    }
+
     #[test]
     fn test_parse_commits_single() {
         let log = "abc1234 Fix bug";
@@ -440,12 +928,21 @@ mod tests {
             last_iteration_at: Some(Utc::now() - Duration::minutes(2)),
             error_count: 0,
             consecutive_errors: 0,
+            circuit_breaker_cooled_down: false,
             last_error: None,
             last_commit: None,
             idle_iterations: 0,
+            commit_count: None,
+            total_input_tokens: 0,
+            total_output_tokens: 0,
+            initial_commit: None,
+            pid: None,
+            session_id: None,
+            error_history: Vec::new(),
+            settings: LoopSettings::default(),
         };
 
-        let status = StatusDisplay::from_state(&state, &[]);
+        let status = StatusDisplay::from_state(&state, &[], &Config::default());
         assert!(status.active);
         assert_eq!(status.iteration, 5);
         assert!(status.elapsed_time.contains('m') || status.elapsed_time.contains('h'));
@@ -464,12 +961,21 @@ mod tests {
             last_iteration_at: Some(Utc::now() - Duration::minutes(5)),
             error_count: 3,
             consecutive_errors: 2,
+            circuit_breaker_cooled_down: false,
             last_error: Some("Git push failed: connection timeout".to_string()),
             last_commit: None,
             idle_iterations: 0,
+            commit_count: None,
+            total_input_tokens: 0,
+            total_output_tokens: 0,
+            initial_commit: None,
+            pid: None,
+            session_id: None,
+            error_history: Vec::new(),
+            settings: LoopSettings::default(),
         };
 
-        let status = StatusDisplay::from_state(&state, &[]);
+        let status = StatusDisplay::from_state(&state, &[], &Config::default());
         assert_eq!(status.error_count, 3);
         assert_eq!(
             status.last_error,
@@ -491,7 +997,11 @@ mod tests {
             estimated_remaining: None,
             error_count: 2,
             last_error: Some("Agent execution timed out".to_string()),
+            recent_errors: Vec::new(),
+            input_tokens: 0,
+            output_tokens: 0,
             recent_commits: Vec::new(),
+            idle: "0/2".to_string(),
         };
 
         let output = format_status(Some(&status));
@@ -515,7 +1025,11 @@ mod tests {
             estimated_remaining: None,
             error_count: 1,
             last_error: Some("Test error message".to_string()),
+            recent_errors: Vec::new(),
+            input_tokens: 0,
+            output_tokens: 0,
             recent_commits: Vec::new(),
+            idle: "0/2".to_string(),
         };
 
         let output = format_status_colored(Some(&status));
@@ -539,7 +1053,11 @@ mod tests {
             estimated_remaining: None,
             error_count: 0,
             last_error: None,
+            recent_errors: Vec::new(),
+            input_tokens: 0,
+            output_tokens: 0,
             recent_commits: Vec::new(),
+            idle: "0/2".to_string(),
         };
 
         let output = format_status(Some(&status));
@@ -2,13 +2,21 @@
 //!
 //! Handles loading and parsing of project configuration including agent settings,
 //! sandbox configuration, git options, and completion detection.
+//!
+//! `${VAR}` references in the file are expanded from the environment before
+//! parsing. `${VAR:-default}` falls back to `default` and `${VAR:?message}`
+//! fails with `message` when `VAR` is unset or empty. A plain `${VAR}` that
+//! is unset is left as-is.
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
 
-use crate::agent::Provider;
+use crate::agent::{template, Provider};
+use crate::metrics::MetricsFormat;
+use crate::state::Mode;
 
 const CONFIG_FILE: &str = "ralph.toml";
 
@@ -33,12 +41,24 @@ pub(crate) struct Config {
     /// Code validation settings.
     #[serde(default)]
     pub validation: ValidationConfig,
+    /// Per-iteration shell hooks.
+    #[serde(default)]
+    pub hooks: HooksConfig,
+    /// Time and rate limits for the loop.
+    #[serde(default)]
+    pub budget: BudgetConfig,
+    /// Rate-limit retry backoff.
+    #[serde(default)]
+    pub backoff: BackoffConfig,
+    /// Prompt file processing.
+    #[serde(default)]
+    pub prompt: PromptConfig,
 }
 
 /// Agent configuration - selects and configures the AI agent CLI.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct AgentConfig {
-    /// Which agent provider to use: "cursor" or "claude"
+    /// Which agent provider to use: "cursor", "claude", or "ollama"
     #[serde(default = "default_provider")]
     pub provider: String,
 
@@ -49,6 +69,40 @@ pub(crate) struct AgentConfig {
     /// Claude-specific configuration
     #[serde(default)]
     pub claude: ClaudeConfig,
+
+    /// Ollama-specific configuration
+    #[serde(default)]
+    pub ollama: OllamaConfig,
+
+    /// Provider to switch to when the main provider keeps hitting rate limits.
+    /// The switch happens at most once per run. Default: none.
+    #[serde(default)]
+    pub fallback_provider: Option<String>,
+
+    /// Timeout in minutes for agent runs outside the sandbox, for every
+    /// provider without its own `timeout_minutes`. Falls back to
+    /// `sandbox.resources.timeout_minutes` when unset.
+    #[serde(default)]
+    pub timeout_minutes: Option<u32>,
+
+    /// Times in a row an iteration is retried when the agent returns empty
+    /// output and leaves the tree unchanged (usually a CLI hiccup). Each
+    /// retry counts as a recoverable error instead of an idle iteration.
+    /// 0 disables. Default: 3
+    #[serde(default = "default_max_empty_retries")]
+    pub max_empty_retries: u32,
+
+    /// Directory the agent runs in, relative to the project root
+    /// (e.g. "packages/app" in a monorepo). Git, validation, and hooks still
+    /// run at the root, which stays the sandbox mount. Default: the root.
+    #[serde(default)]
+    pub working_dir: Option<String>,
+
+    /// Seconds to pause after each successful iteration before the next
+    /// one, e.g. for a rate-limited API. Skipped after the last iteration.
+    /// 0 disables. Default: 0
+    #[serde(default)]
+    pub iteration_delay_seconds: u64,
 }
 
 impl Default for AgentConfig {
@@ -57,6 +111,12 @@ impl Default for AgentConfig {
             provider: default_provider(),
             cursor: CursorConfig::default(),
             claude: ClaudeConfig::default(),
+            ollama: OllamaConfig::default(),
+            fallback_provider: None,
+            timeout_minutes: None,
+            max_empty_retries: default_max_empty_retries(),
+            working_dir: None,
+            iteration_delay_seconds: 0,
         }
     }
 }
@@ -67,12 +127,54 @@ impl AgentConfig {
         self.provider.parse()
     }
 
+    /// Parse the fallback provider string, if one is configured
+    pub fn get_fallback_provider(&self) -> Result<Option<Provider>> {
+        self.fallback_provider
+            .as_deref()
+            .map(str::parse)
+            .transpose()
+    }
+
+    /// Normalize `working_dir` to a path relative to the project root.
+    ///
+    /// `.` components are dropped, so `None`, `"."`, and `""` all mean the
+    /// root (an empty path). Absolute paths and `..` are rejected so the
+    /// agent cannot leave the project.
+    pub fn relative_working_dir(&self) -> Result<PathBuf> {
+        let Some(dir) = self.working_dir.as_deref() else {
+            return Ok(PathBuf::new());
+        };
+        let mut relative = PathBuf::new();
+        for component in Path::new(dir).components() {
+            match component {
+                Component::Normal(part) => relative.push(part),
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    bail!("'{dir}' must not contain '..'; use a path inside the project")
+                }
+                Component::RootDir | Component::Prefix(_) => {
+                    bail!("'{dir}' must be relative to the project root")
+                }
+            }
+        }
+        Ok(relative)
+    }
+
+    /// Problems with the Cursor and Claude settings (see
+    /// [`CursorConfig::problems`] and [`ClaudeConfig::problems`]).
+    pub(crate) fn problems(&self) -> Vec<String> {
+        let mut problems = self.cursor.problems();
+        problems.extend(self.claude.problems());
+        problems
+    }
+
     /// Get the timeout for a specific provider.
     /// Returns `None` if no provider-specific timeout is configured.
     pub fn get_provider_timeout(&self, provider: Provider) -> Option<u32> {
         match provider {
             Provider::Cursor => self.cursor.timeout_minutes,
             Provider::Claude => self.claude.timeout_minutes,
+            Provider::Ollama => self.ollama.timeout_minutes,
         }
     }
 }
@@ -95,6 +197,11 @@ pub(crate) struct CursorConfig {
     #[serde(default)]
     pub model: Option<String>,
 
+    /// Models to rotate through, one per iteration. Overrides `model` when
+    /// non-empty.
+    #[serde(default)]
+    pub models: Vec<String>,
+
     /// Output format for non-interactive mode
     #[serde(default = "default_output_format")]
     pub output_format: String,
@@ -109,9 +216,40 @@ pub(crate) struct CursorConfig {
     pub sandbox: String,
 
     /// Timeout in minutes for cursor agent execution.
-    /// Overrides `sandbox.resources.timeout_minutes` when set.
+    /// Overrides `agent.timeout_minutes` and `sandbox.resources.timeout_minutes` when set.
     #[serde(default)]
     pub timeout_minutes: Option<u32>,
+
+    /// Program and arguments to run instead of the built-in flag layout,
+    /// with `{path}`, `{prompt}`, `{prompt_file}`, `{model}`, and
+    /// `{output_format}` placeholders. Empty uses the built-in layout.
+    #[serde(default)]
+    pub command_template: Vec<String>,
+}
+
+impl CursorConfig {
+    /// Model for `iteration`: from `models` when set, else `model`.
+    pub(crate) fn model_for_iteration(&self, iteration: u32) -> Option<&str> {
+        rotate_model(&self.models, self.model.as_deref(), iteration)
+    }
+
+    /// Checks `output_format` and `sandbox` against the values `cursor-agent`
+    /// accepts, so a typo fails at load instead of garbling agent output,
+    /// and `command_template` for unknown placeholders. Returns every problem.
+    pub(crate) fn problems(&self) -> Vec<String> {
+        [
+            check_one_of(
+                "agent.cursor.output_format",
+                &self.output_format,
+                CURSOR_OUTPUT_FORMATS,
+            ),
+            check_one_of("agent.cursor.sandbox", &self.sandbox, CURSOR_SANDBOX_MODES),
+            check_command_template("agent.cursor.command_template", &self.command_template),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
 }
 
 impl Default for CursorConfig {
@@ -119,13 +257,64 @@ impl Default for CursorConfig {
         Self {
             path: default_cursor_path(),
             model: None,
+            models: Vec::new(),
             output_format: default_output_format(),
             sandbox: default_cursor_sandbox(),
             timeout_minutes: None,
+            command_template: Vec::new(),
         }
     }
 }
 
+/// Picks `models[(iteration - 1) % models.len()]`, so the first listed model
+/// runs on iteration 1, falling back to `model` when `models` is empty.
+fn rotate_model<'a>(
+    models: &'a [String],
+    model: Option<&'a str>,
+    iteration: u32,
+) -> Option<&'a str> {
+    if models.is_empty() {
+        return model;
+    }
+    let index = usize::try_from(iteration.saturating_sub(1)).unwrap_or(usize::MAX) % models.len();
+    Some(models[index].as_str())
+}
+
+/// `--output-format` values `cursor-agent` accepts.
+const CURSOR_OUTPUT_FORMATS: &[&str] = &["text", "json", "stream-json"];
+
+/// `--sandbox` values `cursor-agent` accepts.
+const CURSOR_SANDBOX_MODES: &[&str] = &["enabled", "disabled"];
+
+/// `--output-format` values the Claude CLI accepts.
+const CLAUDE_OUTPUT_FORMATS: &[&str] = &["text", "json", "stream-json"];
+
+/// Fails with a message naming `key` if `template` uses a placeholder
+/// rendering does not know, such as a misspelled `{modle}`.
+fn check_command_template(key: &str, template: &[String]) -> Option<String> {
+    template::unknown_placeholder(template).map(|token| {
+        format!(
+            "{key}: unknown placeholder {token} (expected one of {})",
+            template::PLACEHOLDERS.join(", ")
+        )
+    })
+}
+
+/// Returns a problem naming `key` and the accepted values unless `value`
+/// is one of `allowed`.
+fn check_one_of(key: &str, value: &str, allowed: &[&str]) -> Option<String> {
+    (!allowed.contains(&value)).then(|| {
+        format!(
+            "{key}: '{value}' is not one of {}",
+            allowed
+                .iter()
+                .map(|v| format!("\"{v}\""))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    })
+}
+
 fn default_cursor_sandbox() -> String {
     "disabled".to_string()
 }
@@ -153,6 +342,11 @@ pub(crate) struct ClaudeConfig {
     #[serde(default = "default_claude_model")]
     pub model: Option<String>,
 
+    /// Models to rotate through, one per iteration. Overrides `model` when
+    /// non-empty.
+    #[serde(default)]
+    pub models: Vec<String>,
+
     /// Skip permission prompts (required for autonomous operation)
     #[serde(default = "default_true")]
     pub skip_permissions: bool,
@@ -166,10 +360,45 @@ pub(crate) struct ClaudeConfig {
     pub verbose: bool,
 
     /// Timeout in minutes for claude agent execution.
-    /// Overrides `sandbox.resources.timeout_minutes` when set.
+    /// Overrides `agent.timeout_minutes` and `sandbox.resources.timeout_minutes` when set.
     /// Claude Opus often needs longer timeouts than other providers.
     #[serde(default)]
     pub timeout_minutes: Option<u32>,
+
+    /// Resume the previous iteration's session (`--resume <id>`) instead of
+    /// starting fresh. Needs a JSON `output_format` to capture the session id.
+    #[serde(default)]
+    pub resume_session: bool,
+
+    /// Program and arguments to run instead of the built-in flag layout,
+    /// with `{path}`, `{prompt}`, `{prompt_file}`, `{model}`, and
+    /// `{output_format}` placeholders. The prompt goes to stdin unless
+    /// `{prompt}` or `{prompt_file}` is used. Empty uses the built-in layout.
+    #[serde(default)]
+    pub command_template: Vec<String>,
+}
+
+impl ClaudeConfig {
+    /// Model for `iteration`: from `models` when set, else `model`.
+    pub(crate) fn model_for_iteration(&self, iteration: u32) -> Option<&str> {
+        rotate_model(&self.models, self.model.as_deref(), iteration)
+    }
+
+    /// Checks `output_format` against the values the Claude CLI accepts,
+    /// and `command_template` for unknown placeholders. Returns every problem.
+    pub(crate) fn problems(&self) -> Vec<String> {
+        [
+            check_one_of(
+                "agent.claude.output_format",
+                &self.output_format,
+                CLAUDE_OUTPUT_FORMATS,
+            ),
+            check_command_template("agent.claude.command_template", &self.command_template),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
 }
 
 impl Default for ClaudeConfig {
@@ -177,10 +406,13 @@ impl Default for ClaudeConfig {
         Self {
             path: default_claude_path(),
             model: default_claude_model(),
+            models: Vec::new(),
             skip_permissions: true,
             output_format: default_claude_output_format(),
             verbose: false,
             timeout_minutes: None,
+            resume_session: false,
+            command_template: Vec::new(),
         }
     }
 }
@@ -198,13 +430,55 @@ fn default_claude_output_format() -> String {
     "text".to_string()
 }
 
+/// Ollama HTTP API configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct OllamaConfig {
+    /// Base URL of the Ollama server
+    /// - Default: `"http://localhost:11434"`
+    #[serde(default = "default_ollama_host")]
+    pub host: String,
+
+    /// Model to use (must already be pulled with `ollama pull`)
+    /// - Default: "llama3.1"
+    #[serde(default = "default_ollama_model")]
+    pub model: String,
+
+    /// Timeout in minutes for Ollama generation.
+    /// Overrides `agent.timeout_minutes` and `sandbox.resources.timeout_minutes` when set.
+    #[serde(default)]
+    pub timeout_minutes: Option<u32>,
+}
+
+impl Default for OllamaConfig {
+    fn default() -> Self {
+        Self {
+            host: default_ollama_host(),
+            model: default_ollama_model(),
+            timeout_minutes: None,
+        }
+    }
+}
+
+fn default_ollama_host() -> String {
+    "http://localhost:11434".to_string()
+}
+
+fn default_ollama_model() -> String {
+    "llama3.1".to_string()
+}
+
 /// Docker sandbox configuration for isolated execution.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(clippy::struct_excessive_bools)] // Independent on/off settings, not a state machine
 pub(crate) struct SandboxConfig {
     /// Enable/disable Docker sandboxing.
     #[serde(default = "default_true")]
     pub enabled: bool,
 
+    /// Container runtime that serves the Docker-compatible API.
+    #[serde(default)]
+    pub runtime: ContainerRuntime,
+
     /// Docker image to use
     #[serde(default = "default_image")]
     pub image: String,
@@ -221,6 +495,11 @@ pub(crate) struct SandboxConfig {
     #[serde(default = "default_true")]
     pub use_local_image: bool,
 
+    /// Dockerfile used by `ralph image build`, relative to the project root.
+    /// Written from the built-in default if missing.
+    #[serde(default = "default_dockerfile")]
+    pub dockerfile: String,
+
     /// Additional volume mounts
     #[serde(default)]
     pub mounts: Vec<Mount>,
@@ -231,6 +510,22 @@ pub(crate) struct SandboxConfig {
     #[serde(default = "default_credential_mounts")]
     pub credential_mounts: Vec<Mount>,
 
+    /// Credential mounts to skip, by host path as written (e.g. "~/.npmrc")
+    /// or container path. Removes single defaults without re-listing the rest.
+    #[serde(default)]
+    pub disabled_credential_mounts: Vec<String>,
+
+    /// Shell command run once per container before the agent (e.g. "npm ci").
+    /// Persistent containers run it once at creation. A non-zero exit fails
+    /// the iteration.
+    #[serde(default)]
+    pub setup_command: Option<String>,
+
+    /// Container paths backed by named cache volumes (e.g., "/root/.cargo").
+    /// Volumes persist across iterations and runs; `ralph clean --caches` removes them.
+    #[serde(default)]
+    pub cache_volumes: Vec<String>,
+
     /// Network configuration
     #[serde(default)]
     pub network: NetworkConfig,
@@ -238,19 +533,79 @@ pub(crate) struct SandboxConfig {
     /// Resource limits
     #[serde(default)]
     pub resources: ResourceConfig,
+
+    /// Environment variables injected into the container
+    #[serde(default)]
+    pub env: SandboxEnvConfig,
+
+    /// Container hardening options
+    #[serde(default)]
+    pub security: SecurityConfig,
+
+    /// User the container runs as: `"uid"`, `"uid:gid"`, a user name, or
+    /// `"host"` for the current host uid/gid. Default: unset (image user,
+    /// usually root), which leaves agent-created files owned by root.
+    #[serde(default)]
+    pub run_as_user: Option<String>,
+
+    /// Mount `/workspace` read-only so the agent cannot change the project.
+    /// Scratch files go to a writable tmpfs at `/scratch` that is discarded
+    /// with the container. Useful for review and experiment runs.
+    #[serde(default = "default_false")]
+    pub workspace_readonly: bool,
+
+    /// Append everything the agent prints in the container (stdout and
+    /// stderr) to this file, relative to the project root or absolute, under
+    /// a timestamped header per iteration. For audit trails. Default: unset
+    #[serde(default)]
+    pub container_log_file: Option<String>,
 }
 
 impl Default for SandboxConfig {
     fn default() -> Self {
         Self {
             enabled: true,
+            runtime: ContainerRuntime::default(),
             image: default_image(),
             reuse_container: false,
             use_local_image: true,
+            dockerfile: default_dockerfile(),
             mounts: Vec::new(),
             credential_mounts: default_credential_mounts(),
+            disabled_credential_mounts: Vec::new(),
+            setup_command: None,
+            cache_volumes: Vec::new(),
             network: NetworkConfig::default(),
             resources: ResourceConfig::default(),
+            env: SandboxEnvConfig::default(),
+            security: SecurityConfig::default(),
+            run_as_user: None,
+            workspace_readonly: false,
+            container_log_file: None,
+        }
+    }
+}
+
+/// Container runtime used by the sandbox.
+///
+/// Both runtimes are driven through the Docker Engine API; Podman is reached
+/// via its Docker-compatible socket.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ContainerRuntime {
+    /// Docker daemon via the local default socket (default).
+    #[default]
+    Docker,
+    /// Podman via its Docker-compatible API socket.
+    Podman,
+}
+
+impl ContainerRuntime {
+    /// The runtime's command-line tool, for commands the user runs.
+    pub(crate) fn command(self) -> &'static str {
+        match self {
+            Self::Docker => "docker",
+            Self::Podman => "podman",
         }
     }
 }
@@ -281,6 +636,16 @@ fn default_credential_mounts() -> Vec<Mount> {
             container: "/root/.gitconfig".to_string(),
             readonly: true,
         },
+        Mount {
+            host: "~/.git-credentials".to_string(),
+            container: "/root/.git-credentials".to_string(),
+            readonly: true,
+        },
+        Mount {
+            host: "~/.config/gh/hosts.yml".to_string(),
+            container: "/root/.config/gh/hosts.yml".to_string(),
+            readonly: true,
+        },
         Mount {
             host: "~/.npmrc".to_string(),
             container: "/root/.npmrc".to_string(),
@@ -323,9 +688,43 @@ pub(crate) struct NetworkConfig {
     #[serde(default)]
     pub allowed: Vec<String>,
 
+    /// CIDR blocks allowed when policy is `Allowlist` (e.g., "10.0.0.0/8").
+    #[serde(default)]
+    pub allowed_cidrs: Vec<String>,
+
+    /// TCP ports allowed when policy is `Allowlist`.
+    /// Empty means all ports to allowed destinations.
+    #[serde(default)]
+    pub allowed_ports: Vec<u16>,
+
     /// Custom DNS servers.
     #[serde(default = "default_dns")]
     pub dns: Vec<String>,
+
+    /// Static `/etc/hosts` entries as `hostname:ip`.
+    /// Under `Allowlist`, their addresses are also allowed.
+    #[serde(default)]
+    pub extra_hosts: Vec<String>,
+
+    /// Proxy for HTTP requests, set as `HTTP_PROXY` in the container
+    /// (e.g., `http://proxy.corp:3128`). Under `Allowlist`, the proxy is allowed.
+    #[serde(default)]
+    pub http_proxy: Option<String>,
+
+    /// Proxy for HTTPS requests, set as `HTTPS_PROXY` in the container.
+    /// Under `Allowlist`, the proxy is allowed.
+    #[serde(default)]
+    pub https_proxy: Option<String>,
+
+    /// Hosts that bypass the proxy, set as `NO_PROXY` in the container
+    /// (e.g., "localhost,.internal").
+    #[serde(default)]
+    pub no_proxy: Option<String>,
+
+    /// Fill any of the three proxy settings left unset from the host's
+    /// `HTTP_PROXY`/`HTTPS_PROXY`/`NO_PROXY` (or lowercase) variables.
+    #[serde(default)]
+    pub inherit_proxy: bool,
 }
 
 impl Default for NetworkConfig {
@@ -333,11 +732,49 @@ impl Default for NetworkConfig {
         Self {
             policy: NetworkPolicy::AllowAll,
             allowed: Vec::new(),
+            allowed_cidrs: Vec::new(),
+            allowed_ports: Vec::new(),
             dns: default_dns(),
+            extra_hosts: Vec::new(),
+            http_proxy: None,
+            https_proxy: None,
+            no_proxy: None,
+            inherit_proxy: false,
         }
     }
 }
 
+/// Environment variables for sandbox containers.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct SandboxEnvConfig {
+    /// Fixed variables set in the container (`KEY = "VALUE"`).
+    #[serde(default)]
+    pub vars: BTreeMap<String, String>,
+
+    /// Names of host variables forwarded at container creation.
+    /// Variables missing on the host are skipped.
+    #[serde(default)]
+    pub from_host: Vec<String>,
+}
+
+/// Hardening options for sandbox containers. All are off by default.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct SecurityConfig {
+    /// Mount the container's root filesystem read-only.
+    /// `/tmp` and `/run` get writable tmpfs mounts; `/workspace` stays writable.
+    #[serde(default)]
+    pub readonly_rootfs: bool,
+
+    /// Path to a seccomp profile (JSON) applied to the container.
+    /// Unset uses the runtime's default profile.
+    #[serde(default)]
+    pub seccomp_profile: Option<String>,
+
+    /// Prevent processes from gaining privileges (e.g., via setuid binaries).
+    #[serde(default)]
+    pub no_new_privileges: bool,
+}
+
 /// Resource limits for sandbox containers.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct ResourceConfig {
@@ -352,6 +789,11 @@ pub(crate) struct ResourceConfig {
     /// Timeout in minutes before killing the container.
     #[serde(default = "default_timeout")]
     pub timeout_minutes: u32,
+
+    /// NVIDIA GPUs to pass through: "all", a device count (e.g., "2"),
+    /// or specific devices (e.g., "device=0,1"). Unset means no GPUs.
+    #[serde(default)]
+    pub gpus: Option<String>,
 }
 
 impl Default for ResourceConfig {
@@ -360,6 +802,7 @@ impl Default for ResourceConfig {
             memory: default_memory(),
             cpus: default_cpus(),
             timeout_minutes: default_timeout(),
+            gpus: None,
         }
     }
 }
@@ -388,6 +831,7 @@ pub(crate) struct WorktreeConfig {
 
 /// Git integration configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(clippy::struct_excessive_bools)] // Independent on/off settings, not a state machine
 pub(crate) struct GitConfig {
     /// Automatically push after each iteration.
     #[serde(default = "default_true")]
@@ -402,12 +846,56 @@ pub(crate) struct GitConfig {
     pub pr_base: String,
 
     /// Branches that should not be modified directly.
+    /// The loop refuses to start on one of them.
     #[serde(default = "default_protected_branches")]
     pub protected_branches: Vec<String>,
 
+    /// When the loop starts on a protected branch, create and switch to a
+    /// `ralph/<timestamp>` branch instead of refusing to run.
+    #[serde(default)]
+    pub auto_branch: bool,
+
     /// Worktree identity configuration for bot commits.
     #[serde(default)]
     pub worktree: Option<WorktreeConfig>,
+
+    /// Commit uncommitted changes after a successful iteration.
+    /// Keeps idle detection from mistaking uncommitted work for an idle agent.
+    #[serde(default)]
+    pub auto_commit: bool,
+
+    /// Message for auto-commits. Supports `{iteration}` and `{mode}` placeholders.
+    #[serde(default = "default_commit_message_template")]
+    pub commit_message_template: String,
+
+    /// Sign the commits Ralph creates (auto-commits and worktree commits).
+    /// Uses git's own signing setup, so `gpg.format = "ssh"` works too.
+    #[serde(default)]
+    pub sign_commits: bool,
+
+    /// Key to sign with: a GPG key ID, or an SSH key path with
+    /// `gpg.format = "ssh"`. Default: git's `user.signingkey`.
+    #[serde(default)]
+    pub signing_key: Option<String>,
+
+    /// Maximum number of branches built at once in parallel build mode.
+    /// Default: number of CPUs.
+    #[serde(default = "default_max_parallel_branches")]
+    pub max_parallel_branches: usize,
+
+    /// Retries for a failed `git push` before it counts as an error.
+    /// Rejections such as non-fast-forward are not retried. 0 disables retries.
+    #[serde(default = "default_push_retries")]
+    pub push_retries: u32,
+
+    /// Title of branch-build PRs. Supports `{branch}`, `{goal}`, and
+    /// `{iterations}` placeholders.
+    #[serde(default = "default_pr_title_template")]
+    pub pr_title_template: String,
+
+    /// Body of branch-build PRs. Same placeholders as `pr_title_template`.
+    #[serde(default = "default_pr_body_template")]
+    pub pr_body_template: String,
 }
 
 impl Default for GitConfig {
@@ -417,7 +905,16 @@ impl Default for GitConfig {
             auto_pr: true,
             pr_base: default_pr_base(),
             protected_branches: default_protected_branches(),
+            auto_branch: false,
             worktree: None,
+            auto_commit: false,
+            commit_message_template: default_commit_message_template(),
+            sign_commits: false,
+            signing_key: None,
+            max_parallel_branches: default_max_parallel_branches(),
+            push_retries: default_push_retries(),
+            pr_title_template: default_pr_title_template(),
+            pr_body_template: default_pr_body_template(),
         }
     }
 }
@@ -426,6 +923,26 @@ fn default_pr_base() -> String {
     "master".to_string()
 }
 
+fn default_commit_message_template() -> String {
+    "ralph: {mode} iteration {iteration}".to_string()
+}
+
+fn default_pr_title_template() -> String {
+    "{branch}: {goal}".to_string()
+}
+
+fn default_pr_body_template() -> String {
+    "## Summary\n\n{goal}\n\n## Branch\n\n`{branch}`\n\n---\n\n🤖 Generated by Ralph".to_string()
+}
+
+fn default_max_parallel_branches() -> usize {
+    std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+}
+
+fn default_push_retries() -> u32 {
+    3
+}
+
 /// Completion detection configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct CompletionConfig {
@@ -433,20 +950,145 @@ pub(crate) struct CompletionConfig {
     /// An iteration is "idle" if validation passes but no new commits are created.
     #[serde(default = "default_idle_threshold")]
     pub idle_threshold: u32,
+
+    /// Iteration before which idle detection cannot complete the loop, for
+    /// agents that think for several iterations between commits. The promise
+    /// and success command still apply. 0 disables the grace period.
+    #[serde(default)]
+    pub min_iterations_before_idle: u32,
+
+    /// Optional command that signals completion when it exits 0.
+    /// Checked after each successful iteration in addition to idle detection.
+    /// Examples: `"./check_done.sh"`, `"sh -c 'grep -q DONE STATUS.md'"`
+    #[serde(default)]
+    pub success_command: Option<String>,
+
+    /// Optional promise string that signals completion when the agent prints it.
+    /// Matched as `promise_format` with `{promise}` replaced, e.g. `<promise>DONE</promise>`.
+    #[serde(default)]
+    pub promise: Option<String>,
+
+    /// Marker the agent must print to fulfil the promise.
+    /// Default: `"<promise>{promise}</promise>"`
+    #[serde(default = "default_promise_format")]
+    pub promise_format: String,
+
+    /// Detect idleness by the number of commits since the loop started
+    /// (`git rev-list --count`) instead of the HEAD hash, so a rebase that
+    /// keeps the commit count does not count as progress.
+    #[serde(default)]
+    pub idle_by_commit_count: bool,
+
+    /// Which checks must pass for the loop to finish.
+    /// Default: `{ any = ["idle", "promise", "command"] }`
+    #[serde(default)]
+    pub strategy: CompletionStrategy,
+
+    /// Plan-mode overrides (`[completion.plan]`).
+    #[serde(default)]
+    pub plan: ModeCompletionConfig,
+
+    /// Build-mode overrides (`[completion.build]`).
+    #[serde(default)]
+    pub build: ModeCompletionConfig,
 }
 
 impl Default for CompletionConfig {
     fn default() -> Self {
         Self {
             idle_threshold: default_idle_threshold(),
+            min_iterations_before_idle: 0,
+            success_command: None,
+            promise: None,
+            promise_format: default_promise_format(),
+            idle_by_commit_count: false,
+            strategy: CompletionStrategy::default(),
+            plan: ModeCompletionConfig::default(),
+            build: ModeCompletionConfig::default(),
+        }
+    }
+}
+
+impl CompletionConfig {
+    /// Idle threshold for `mode`: the mode-specific value if set, else the
+    /// top-level `idle_threshold`.
+    pub fn idle_threshold_for(&self, mode: Mode) -> u32 {
+        let overrides = match mode {
+            Mode::Plan => &self.plan,
+            Mode::Build => &self.build,
+        };
+        overrides.idle_threshold.unwrap_or(self.idle_threshold)
+    }
+}
+
+/// How the loop decides the task is done (`completion.strategy`).
+///
+/// A string names one check, e.g. `"idle"`; `{ any = [...] }` and
+/// `{ all = [...] }` combine checks and may nest.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum CompletionStrategy {
+    /// The agent was idle for `idle_threshold` iterations.
+    Idle,
+    /// The agent printed the `promise` marker.
+    Promise,
+    /// `success_command` exited 0.
+    Command,
+    /// At least one listed strategy is satisfied.
+    Any(Vec<CompletionStrategy>),
+    /// Every listed strategy is satisfied in the same iteration.
+    All(Vec<CompletionStrategy>),
+}
+
+impl Default for CompletionStrategy {
+    /// Idle detection, plus the promise and success command when configured.
+    fn default() -> Self {
+        Self::Any(vec![Self::Idle, Self::Promise, Self::Command])
+    }
+}
+
+impl CompletionStrategy {
+    /// Whether any part of the strategy consults `success_command`.
+    pub fn uses_command(&self) -> bool {
+        match self {
+            Self::Command => true,
+            Self::Idle | Self::Promise => false,
+            Self::Any(strategies) | Self::All(strategies) => {
+                strategies.iter().any(Self::uses_command)
+            }
+        }
+    }
+
+    /// Whether the strategy can ever be satisfied, given whether `promise`
+    /// and `success_command` are set. Empty `any`/`all` lists never are.
+    pub fn can_complete(&self, has_promise: bool, has_command: bool) -> bool {
+        let can = |s: &Self| s.can_complete(has_promise, has_command);
+        match self {
+            Self::Idle => true,
+            Self::Promise => has_promise,
+            Self::Command => has_command,
+            Self::Any(strategies) => strategies.iter().any(can),
+            Self::All(strategies) => !strategies.is_empty() && strategies.iter().all(can),
         }
     }
 }
 
+/// Per-mode completion overrides.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct ModeCompletionConfig {
+    /// Idle threshold for this mode; falls back to `completion.idle_threshold`.
+    #[serde(default)]
+    pub idle_threshold: Option<u32>,
+}
+
 fn default_idle_threshold() -> u32 {
     2
 }
 
+fn default_promise_format() -> String {
+    "<promise>{promise}</promise>".to_string()
+}
+
 /// Log rotation policy.
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
@@ -461,6 +1103,7 @@ pub(crate) enum LogRotation {
 }
 /// Monitoring and logging configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(clippy::struct_excessive_bools)] // Independent on/off settings, not a state machine
 pub(crate) struct MonitoringConfig {
     /// Path to log file (relative to project root or absolute).
     #[serde(default = "default_log_file")]
@@ -479,12 +1122,42 @@ pub(crate) struct MonitoringConfig {
     #[serde(default = "default_true")]
     pub show_progress: bool,
 
+    /// Add the persistent sandbox container's memory and CPU usage to the
+    /// progress display. Needs `sandbox.reuse_container`. Default: false.
+    #[serde(default)]
+    pub show_resource_usage: bool,
+
     /// Maximum consecutive errors before stopping the loop (circuit breaker).
     /// Set to 0 to disable the limit and continue indefinitely.
     /// Default: 5
     #[serde(default = "default_max_consecutive_errors")]
     pub max_consecutive_errors: u32,
 
+    /// Minutes to pause when the circuit breaker first trips, after which
+    /// the error streak is reset and the loop continues. A second trip stops
+    /// the loop. Default: 0 (no cool-down; the first trip stops the loop).
+    #[serde(default)]
+    pub circuit_breaker_cooldown_minutes: u64,
+
+    /// Echo agent output to the terminal live while it runs.
+    /// Default: false (output is shown only through logs).
+    #[serde(default)]
+    pub stream_output: bool,
+
+    /// Save each iteration's prompt and raw agent output to
+    /// `.ralph/iterations/<n>.prompt` and `<n>.log`. Default: false.
+    #[serde(default)]
+    pub save_iteration_output: bool,
+
+    /// Path to write a run summary to when the loop ends (relative to
+    /// project root or absolute). Default: unset (no metrics file).
+    #[serde(default)]
+    pub metrics_file: Option<String>,
+
+    /// Metrics file format: "json" or "prometheus" (textfile collector).
+    #[serde(default)]
+    pub metrics_format: MetricsFormat,
+
     /// Notification configuration.
     #[serde(default)]
     pub notifications: NotificationConfig,
@@ -497,30 +1170,45 @@ impl Default for MonitoringConfig {
             log_format: default_log_format(),
             log_rotation: LogRotation::default(),
             show_progress: true,
+            show_resource_usage: false,
             max_consecutive_errors: default_max_consecutive_errors(),
+            circuit_breaker_cooldown_minutes: 0,
+            stream_output: false,
+            save_iteration_output: false,
+            metrics_file: None,
+            metrics_format: MetricsFormat::default(),
             notifications: NotificationConfig::default(),
         }
     }
 }
 
-/// Notification configuration for loop completion and errors.
+/// Notification configuration for loop completion, errors, and iteration heartbeats.
 ///
-/// Both `on_complete` and `on_error` support the same notification types:
-/// - `"webhook:<url>"` - POST to webhook URL
+/// `on_complete`, `on_error`, and `on_iteration` support the same notification types:
+/// - `"webhook:<url>"` - POST generic JSON to webhook URL
+/// - `"slack:<url>"` - POST a Slack Block Kit message to an incoming webhook
+/// - `"discord:<url>"` - POST a Discord embed to a channel webhook
 /// - `"desktop"` - Desktop notification (notify-send/osascript)
 /// - `"sound"` - Sound alert (system sound or bell)
 /// - `"none"` or omit - No notification
 ///
 /// For backward compatibility, bare URLs (without `webhook:` prefix) are treated as webhooks.
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[allow(clippy::struct_field_names)] // `on_*` names match the config keys
 pub(crate) struct NotificationConfig {
-    /// Notification method on completion: "webhook:<url>", "desktop", "sound", or "none".
+    /// Notification method on completion: "webhook:<url>", "slack:<url>", "discord:<url>",
+    /// "desktop", "sound", or "none".
     #[serde(default)]
     pub on_complete: Option<String>,
 
-    /// Notification method on error: "webhook:<url>", "desktop", "sound", or "none".
+    /// Notification method on error: "webhook:<url>", "slack:<url>", "discord:<url>",
+    /// "desktop", "sound", or "none".
     #[serde(default)]
     pub on_error: Option<String>,
+
+    /// Heartbeat after each successful iteration. Opt-in: unset sends nothing.
+    #[serde(default)]
+    pub on_iteration: Option<String>,
 }
 
 /// Code validation configuration.
@@ -540,6 +1228,45 @@ pub(crate) struct ValidationConfig {
     ///   - "./validate.sh"
     #[serde(default = "default_validation_command")]
     pub command: String,
+
+    /// Reset the tree to the iteration's start commit when validation fails.
+    /// Never resets past the commit the loop started from.
+    #[serde(default)]
+    pub rollback_on_failure: bool,
+
+    /// Maximum characters of a validation error appended to the next prompt.
+    /// Longer errors keep their head and tail around a truncation marker; the
+    /// full error is still stored in state. Set to 0 to disable truncation.
+    /// Default: 4000
+    #[serde(default = "default_max_error_chars")]
+    pub max_error_chars: usize,
+
+    /// Minutes the validation command may run before it is killed and the
+    /// iteration counts as a validation failure. Set to 0 to disable.
+    /// Default: 30
+    #[serde(default = "default_validation_timeout_minutes")]
+    pub timeout_minutes: u32,
+
+    /// Trailing lines of a failed validation's output shown in the terminal
+    /// warning and notifications, where compiler errors usually are. 0 shows
+    /// the full output; unset shows the first 5 lines. Does not change what
+    /// is appended to the prompt.
+    #[serde(default)]
+    pub error_tail_lines: Option<usize>,
+
+    /// Command run once after the loop completes (idle detection, promise,
+    /// or success command), even when per-iteration validation is off. A
+    /// failure reports the loop as failed. Uses `timeout_minutes`.
+    #[serde(default)]
+    pub final_command: Option<String>,
+}
+
+impl ValidationConfig {
+    /// Validation command timeout, or `None` when disabled.
+    pub(crate) fn timeout(&self) -> Option<std::time::Duration> {
+        (self.timeout_minutes > 0)
+            .then(|| std::time::Duration::from_secs(u64::from(self.timeout_minutes) * 60))
+    }
 }
 
 impl Default for ValidationConfig {
@@ -547,36 +1274,140 @@ impl Default for ValidationConfig {
         Self {
             enabled: true,
             command: default_validation_command(),
+            rollback_on_failure: false,
+            max_error_chars: default_max_error_chars(),
+            timeout_minutes: default_validation_timeout_minutes(),
+            error_tail_lines: None,
+            final_command: None,
         }
     }
 }
 
-// Default value functions
-fn default_true() -> bool {
-    true
-}
+/// Per-iteration shell hooks.
+///
+/// Commands are parsed with shell-style quoting and run in the project directory.
+/// Use `sh -c "..."` for pipes or chained commands.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub(crate) struct HooksConfig {
+    /// Command to run before each iteration (before the prompt is read).
+    /// A failure aborts the iteration as a recoverable error.
+    #[serde(default)]
+    pub pre_iteration: Option<String>,
 
-fn default_false() -> bool {
-    false
+    /// Command to run after each successful iteration (after git push).
+    /// A failure only logs a warning.
+    #[serde(default)]
+    pub post_iteration: Option<String>,
 }
 
-fn default_image() -> String {
-    "ralph:latest".to_string()
-}
+/// Time and rate limits for long-running loops.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub(crate) struct BudgetConfig {
+    /// Stop the loop once it has run this many minutes (None = unlimited).
+    #[serde(default)]
+    pub max_minutes: Option<u32>,
 
-fn default_dns() -> Vec<String> {
-    vec!["8.8.8.8".to_string(), "1.1.1.1".to_string()]
+    /// Maximum iterations started within any rolling hour (None = unlimited).
+    /// The loop sleeps until a slot frees up.
+    #[serde(default)]
+    pub max_iterations_per_hour: Option<u32>,
 }
 
-fn default_memory() -> String {
-    "8g".to_string()
+/// Exponential backoff for agent rate-limit errors.
+///
+/// The wait before retrying is `min(base_seconds * multiplier^n, max_seconds)`,
+/// where `n` is the number of consecutive errors so far.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct BackoffConfig {
+    /// Wait after the first rate limit, in seconds.
+    /// Default: 30
+    #[serde(default = "default_backoff_base_seconds")]
+    pub base_seconds: u64,
+
+    /// Growth factor per consecutive error.
+    /// Default: 2.0
+    #[serde(default = "default_backoff_multiplier")]
+    pub multiplier: f64,
+
+    /// Upper bound on any single wait, in seconds.
+    /// Default: 600
+    #[serde(default = "default_backoff_max_seconds")]
+    pub max_seconds: u64,
 }
 
-fn default_cpus() -> String {
-    "4".to_string()
+impl Default for BackoffConfig {
+    fn default() -> Self {
+        Self {
+            base_seconds: default_backoff_base_seconds(),
+            multiplier: default_backoff_multiplier(),
+            max_seconds: default_backoff_max_seconds(),
+        }
+    }
 }
 
-fn default_timeout() -> u32 {
+/// Prompt file processing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct PromptConfig {
+    /// Replace `{iteration}`, `{mode}`, `{max_iterations}`, and `{last_error}`
+    /// in the prompt with the loop's current values. Off by default so
+    /// prompts with literal braces are sent unchanged.
+    #[serde(default)]
+    pub substitute: bool,
+
+    /// Block appended to the prompt after a failed validation. `{error}` is
+    /// replaced with the (truncated) validation output.
+    /// Default: a "VALIDATION ERROR FROM PREVIOUS ITERATION" heading with the
+    /// error in a code fence
+    #[serde(default = "default_validation_error_template")]
+    pub validation_error_template: String,
+
+    /// Ask the agent to end its own commit messages with the
+    /// `Ralph-Iteration` trailer so `ralph revert` can find them. Auto-commits
+    /// carry the trailer either way.
+    #[serde(default)]
+    pub commit_trailer: bool,
+}
+
+impl Default for PromptConfig {
+    fn default() -> Self {
+        Self {
+            substitute: false,
+            validation_error_template: default_validation_error_template(),
+            commit_trailer: false,
+        }
+    }
+}
+
+// Default value functions
+fn default_true() -> bool {
+    true
+}
+
+fn default_false() -> bool {
+    false
+}
+
+fn default_image() -> String {
+    "ralph:latest".to_string()
+}
+
+fn default_dockerfile() -> String {
+    ".ralph/Dockerfile".to_string()
+}
+
+fn default_dns() -> Vec<String> {
+    vec!["8.8.8.8".to_string(), "1.1.1.1".to_string()]
+}
+
+fn default_memory() -> String {
+    "8g".to_string()
+}
+
+fn default_cpus() -> String {
+    "4".to_string()
+}
+
+fn default_timeout() -> u32 {
     60
 }
 
@@ -584,6 +1415,26 @@ fn default_validation_command() -> String {
     "nix flake check --quiet".to_string()
 }
 
+fn default_validation_error_template() -> String {
+    "## ⚠️ VALIDATION ERROR FROM PREVIOUS ITERATION\n\
+     The following validation error occurred. Please fix it:\n\n\
+     ```\n{error}\n```\n\
+     \nFix the issues above and ensure validation passes before proceeding.\n"
+        .to_string()
+}
+
+fn default_max_empty_retries() -> u32 {
+    3
+}
+
+fn default_max_error_chars() -> usize {
+    4000
+}
+
+fn default_validation_timeout_minutes() -> u32 {
+    30
+}
+
 fn default_protected_branches() -> Vec<String> {
     vec![
         "main".to_string(),
@@ -604,6 +1455,139 @@ fn default_max_consecutive_errors() -> u32 {
     5
 }
 
+fn default_backoff_base_seconds() -> u64 {
+    30
+}
+
+fn default_backoff_multiplier() -> f64 {
+    2.0
+}
+
+fn default_backoff_max_seconds() -> u64 {
+    600
+}
+
+/// Settings that hold commands. Their `${VAR}` references are left for the
+/// command itself (e.g. `sh -c "..."`) rather than expanded at load time.
+const UNEXPANDED_KEYS: &[&str] = &[
+    "completion.success_command",
+    "hooks.post_iteration",
+    "hooks.pre_iteration",
+    "sandbox.setup_command",
+    "validation.command",
+    "validation.final_command",
+];
+
+/// Expands environment references in each string value of `value`, where
+/// `path` is the dotted key of `value` (empty for the document itself), and
+/// adds the key of each changed value to `expanded`.
+///
+/// Keys, comments, non-string values, and the commands in
+/// [`UNEXPANDED_KEYS`] are left alone, and expanded text is never parsed as
+/// TOML, so values may contain quotes, backslashes, or newlines.
+fn expand_env_values(
+    value: &mut toml::Value,
+    path: &str,
+    lookup: &impl Fn(&str) -> Option<String>,
+    expanded: &mut Vec<String>,
+) -> Result<()> {
+    match value {
+        toml::Value::String(text) if !UNEXPANDED_KEYS.contains(&path) => {
+            let result =
+                interpolate_env(text, lookup).with_context(|| format!("Invalid {path}"))?;
+            if result != *text {
+                *text = result;
+                if !expanded.iter().any(|key| key == path) {
+                    expanded.push(path.to_string());
+                }
+            }
+        }
+        toml::Value::Array(items) => {
+            for item in items {
+                expand_env_values(item, path, lookup, expanded)?;
+            }
+        }
+        toml::Value::Table(table) => {
+            for (key, item) in table {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                expand_env_values(item, &path, lookup, expanded)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Expands `${VAR}`, `${VAR:-default}`, and `${VAR:?message}` in `content`.
+///
+/// `lookup` resolves variable names (the environment, in production). Empty
+/// values count as unset for the `:-` and `:?` forms. `$${` is an escape for
+/// a literal `${`. Anything that is not a well-formed reference is copied
+/// through unchanged.
+fn interpolate_env(content: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let mut out = String::with_capacity(content.len());
+    let mut rest = content;
+
+    while let Some(start) = rest.find("${") {
+        if start > 0 && rest.as_bytes()[start - 1] == b'$' {
+            out.push_str(&rest[..start - 1]);
+            out.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find('}') else {
+            rest = &rest[start..];
+            break;
+        };
+        let reference = &after[..end];
+        let original = &rest[start..=start + 2 + end];
+        rest = &after[end + 1..];
+
+        let (name, fallback) = match reference.find(":-").or_else(|| reference.find(":?")) {
+            Some(i) => (&reference[..i], Some(&reference[i..])),
+            None => (reference, None),
+        };
+        if !is_env_var_name(name) {
+            out.push_str(original);
+            continue;
+        }
+
+        let value = lookup(name);
+        match fallback {
+            None => out.push_str(value.as_deref().unwrap_or(original)),
+            Some(op) => match value.filter(|v| !v.is_empty()) {
+                Some(v) => out.push_str(&v),
+                None if op.starts_with(":-") => out.push_str(&op[2..]),
+                None => {
+                    let message = &op[2..];
+                    if message.is_empty() {
+                        bail!("Environment variable {name} is not set");
+                    }
+                    bail!("Environment variable {name} is not set: {message}");
+                }
+            },
+        }
+    }
+
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Returns true if `name` is a valid environment variable name.
+fn is_env_var_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
 impl Config {
     /// Load configuration from file, using defaults if not found
     pub fn load(project_dir: &Path) -> Result<Self> {
@@ -613,14 +1597,80 @@ impl Config {
             return Ok(Self::default());
         }
 
-        let content = fs::read_to_string(&config_path)
-            .with_context(|| format!("Failed to read config file: {}", config_path.display()))?;
+        Self::load_file(&config_path)
+    }
+
+    /// Load configuration from `config_file` when given (`--config`), else
+    /// from `ralph.toml` in `project_dir`
+    pub fn load_from(project_dir: &Path, config_file: Option<&Path>) -> Result<Self> {
+        match config_file {
+            Some(path) => Self::load_file(path),
+            None => Self::load(project_dir),
+        }
+    }
 
-        let config: Self = toml::from_str(&content)
-            .with_context(|| format!("Failed to parse config file: {}", config_path.display()))?;
+    /// Load configuration from an explicit file, which must exist
+    pub fn load_file(config_path: &Path) -> Result<Self> {
+        let (config, _) = Self::read_file(config_path)?;
+        let problems = config.agent.problems();
+        if !problems.is_empty() {
+            return Err(anyhow::anyhow!(problems.join("\n")))
+                .with_context(|| format!("Invalid config file: {}", config_path.display()));
+        }
 
         Ok(config)
     }
+
+    /// Like [`Config::load_from`], but leaves the agent checks to the caller
+    /// ([`AgentConfig::problems`]) so `ralph config validate` can report them
+    /// with everything else. Also returns the keys whose values were expanded
+    /// from environment references.
+    pub(crate) fn load_unchecked(
+        project_dir: &Path,
+        config_file: Option<&Path>,
+    ) -> Result<(Self, Vec<String>)> {
+        match config_file {
+            Some(path) => Self::read_file(path),
+            None if project_dir.join(CONFIG_FILE).exists() => {
+                Self::read_file(&project_dir.join(CONFIG_FILE))
+            }
+            None => Ok((Self::default(), Vec::new())),
+        }
+    }
+
+    /// Reads and parses `config_path`, returning the config and its
+    /// environment-expanded keys.
+    fn read_file(config_path: &Path) -> Result<(Self, Vec<String>)> {
+        let content = fs::read_to_string(config_path)
+            .with_context(|| format!("Failed to read config file: {}", config_path.display()))?;
+        Self::parse_with_keys(&content, |name| std::env::var(name).ok())
+            .with_context(|| format!("Failed to parse config file: {}", config_path.display()))
+    }
+
+    /// Parses `content`, expanding environment references in its string
+    /// values (see [`expand_env_values`]) with `lookup`.
+    #[cfg(test)]
+    fn parse(content: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        Self::parse_with_keys(content, lookup).map(|(config, _)| config)
+    }
+
+    /// Parses `content` like `parse`, also returning the expanded keys.
+    fn parse_with_keys(
+        content: &str,
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> Result<(Self, Vec<String>)> {
+        let mut value: toml::Value = toml::from_str(content)?;
+        let mut expanded = Vec::new();
+        expand_env_values(&mut value, "", &lookup, &mut expanded)?;
+
+        // Parse the text when nothing was expanded, so errors keep their line
+        let config = if expanded.is_empty() {
+            toml::from_str(content)?
+        } else {
+            value.try_into()?
+        };
+        Ok((config, expanded))
+    }
 }
 
 #[cfg(test)]
@@ -634,7 +1684,9 @@ mod tests {
         assert!(!config.sandbox.reuse_container); // Default is false
         assert!(config.git.auto_push);
         assert!(config.git.protected_branches.contains(&"main".to_string()));
+        assert!(!config.git.auto_branch);
         assert_eq!(config.agent.provider, "cursor");
+        assert_eq!(config.agent.max_empty_retries, 3);
         assert_eq!(config.monitoring.log_file, ".ralph/loop.log");
         assert_eq!(config.monitoring.log_format, "json");
         assert!(config.monitoring.show_progress);
@@ -669,6 +1721,21 @@ auto_push = false
             config.sandbox.network.policy,
             NetworkPolicy::Allowlist
         ));
+        assert!(config.sandbox.network.allowed_cidrs.is_empty());
+        assert!(config.sandbox.network.allowed_ports.is_empty());
+    }
+
+    #[test]
+    fn test_parse_network_cidrs_and_ports() {
+        let toml = r#"
+[sandbox.network]
+policy = "allowlist"
+allowed_cidrs = ["10.0.0.0/8"]
+allowed_ports = [443, 22]
+"#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.sandbox.network.allowed_cidrs, vec!["10.0.0.0/8"]);
+        assert_eq!(config.sandbox.network.allowed_ports, vec![443, 22]);
     }
 
     #[test]
@@ -685,6 +1752,25 @@ show_progress = false
         assert!(!config.monitoring.show_progress);
     }
 
+    #[test]
+    fn test_parse_metrics_config() {
+        let config = Config::default();
+        assert!(config.monitoring.metrics_file.is_none());
+        assert_eq!(config.monitoring.metrics_format, MetricsFormat::Json);
+
+        let toml = r#"
+[monitoring]
+metrics_file = "/var/lib/node_exporter/ralph.prom"
+metrics_format = "prometheus"
+"#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(
+            config.monitoring.metrics_file.as_deref(),
+            Some("/var/lib/node_exporter/ralph.prom")
+        );
+        assert_eq!(config.monitoring.metrics_format, MetricsFormat::Prometheus);
+    }
+
     #[test]
     fn test_cursor_config() {
         let toml = r#"
@@ -731,6 +1817,42 @@ reuse_container = false
         assert!(!config.sandbox.reuse_container);
     }
 
+    #[test]
+    fn test_sandbox_runtime_default() {
+        let config = Config::default();
+        assert_eq!(config.sandbox.runtime, ContainerRuntime::Docker);
+    }
+
+    #[test]
+    fn test_sandbox_runtime_podman() {
+        let toml = r#"
+[sandbox]
+runtime = "podman"
+"#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.sandbox.runtime, ContainerRuntime::Podman);
+    }
+
+    #[test]
+    fn test_sandbox_env_default() {
+        let config = Config::default();
+        assert!(config.sandbox.env.vars.is_empty());
+        assert!(config.sandbox.env.from_host.is_empty());
+    }
+
+    #[test]
+    fn test_sandbox_env_custom() {
+        let toml = r#"
+[sandbox.env]
+vars = { RUST_LOG = "debug", PROJECT = "ralph" }
+from_host = ["ANTHROPIC_API_KEY"]
+"#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.sandbox.env.vars.len(), 2);
+        assert_eq!(config.sandbox.env.vars["RUST_LOG"], "debug");
+        assert_eq!(config.sandbox.env.from_host, vec!["ANTHROPIC_API_KEY"]);
+    }
+
     #[test]
     fn test_sandbox_use_local_image_default() {
         let config = Config::default();
@@ -748,6 +1870,15 @@ use_local_image = false
         assert!(!config.sandbox.use_local_image);
     }
 
+    #[test]
+    fn test_sandbox_dockerfile() {
+        assert_eq!(Config::default().sandbox.dockerfile, ".ralph/Dockerfile");
+
+        let config: Config =
+            toml::from_str("[sandbox]\ndockerfile = \"docker/Dockerfile\"\n").unwrap();
+        assert_eq!(config.sandbox.dockerfile, "docker/Dockerfile");
+    }
+
     #[test]
     fn test_sandbox_use_local_image_enabled() {
         let toml = r"
@@ -835,6 +1966,20 @@ max_consecutive_errors = 0
         assert_eq!(config.monitoring.max_consecutive_errors, 0);
     }
 
+    #[test]
+    fn test_circuit_breaker_cooldown() {
+        assert_eq!(
+            Config::default()
+                .monitoring
+                .circuit_breaker_cooldown_minutes,
+            0
+        );
+
+        let config: Config =
+            toml::from_str("[monitoring]\ncircuit_breaker_cooldown_minutes = 15\n").unwrap();
+        assert_eq!(config.monitoring.circuit_breaker_cooldown_minutes, 15);
+    }
+
     #[test]
     fn test_log_rotation_default() {
         let config = Config::default();
@@ -884,6 +2029,8 @@ log_rotation = "never"
             .collect();
         assert!(hosts.contains(&"~/.ssh"));
         assert!(hosts.contains(&"~/.gitconfig"));
+        assert!(hosts.contains(&"~/.git-credentials"));
+        assert!(hosts.contains(&"~/.config/gh/hosts.yml"));
         assert!(hosts.contains(&"~/.npmrc"));
         assert!(hosts.contains(&"~/.cargo/credentials.toml"));
     }
@@ -914,6 +2061,80 @@ credential_mounts = []
         assert!(config.sandbox.credential_mounts.is_empty());
     }
 
+    #[test]
+    fn test_setup_command() {
+        assert!(Config::default().sandbox.setup_command.is_none());
+
+        let toml = r#"
+[sandbox]
+setup_command = "npm ci"
+"#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.sandbox.setup_command.as_deref(), Some("npm ci"));
+    }
+
+    #[test]
+    fn test_disabled_credential_mounts() {
+        assert!(Config::default()
+            .sandbox
+            .disabled_credential_mounts
+            .is_empty());
+
+        let toml = r#"
+[sandbox]
+disabled_credential_mounts = ["~/.npmrc", "/root/.pypirc"]
+"#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(
+            config.sandbox.disabled_credential_mounts,
+            vec!["~/.npmrc", "/root/.pypirc"]
+        );
+        // The defaults are kept; filtering happens when binds are assembled
+        assert_eq!(
+            config.sandbox.credential_mounts,
+            default_credential_mounts()
+        );
+    }
+
+    #[test]
+    fn test_rotate_model_index() {
+        let models = vec![
+            "gpt-5".to_string(),
+            "sonnet".to_string(),
+            "opus".to_string(),
+        ];
+        assert_eq!(rotate_model(&models, None, 1), Some("gpt-5"));
+        assert_eq!(rotate_model(&models, None, 2), Some("sonnet"));
+        assert_eq!(rotate_model(&models, None, 3), Some("opus"));
+        assert_eq!(rotate_model(&models, None, 4), Some("gpt-5"));
+        assert_eq!(rotate_model(&models, Some("default"), 8), Some("sonnet"));
+        // Iteration 0 never runs, but stays in range
+        assert_eq!(rotate_model(&models, None, 0), Some("gpt-5"));
+        assert_eq!(rotate_model(&models, None, u32::MAX), Some("opus"));
+    }
+
+    #[test]
+    fn test_rotate_model_falls_back_to_model() {
+        assert_eq!(rotate_model(&[], Some("opus"), 5), Some("opus"));
+        assert_eq!(rotate_model(&[], None, 5), None);
+    }
+
+    #[test]
+    fn test_agent_models_from_toml() {
+        let toml = r#"
+[agent.cursor]
+models = ["gpt-5", "sonnet"]
+
+[agent.claude]
+model = "sonnet"
+"#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.agent.cursor.model_for_iteration(1), Some("gpt-5"));
+        assert_eq!(config.agent.cursor.model_for_iteration(2), Some("sonnet"));
+        assert!(config.agent.claude.models.is_empty());
+        assert_eq!(config.agent.claude.model_for_iteration(1), Some("sonnet"));
+    }
+
     #[test]
     fn test_cursor_timeout_default() {
         let config = Config::default();
@@ -979,6 +2200,707 @@ timeout_minutes = 180
         let config = Config::default();
         assert_eq!(config.agent.get_provider_timeout(Provider::Cursor), None);
         assert_eq!(config.agent.get_provider_timeout(Provider::Claude), None);
+        assert_eq!(config.agent.get_provider_timeout(Provider::Ollama), None);
+    }
+
+    #[test]
+    fn test_relative_working_dir() {
+        let working_dir = |dir: Option<&str>| AgentConfig {
+            working_dir: dir.map(String::from),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            working_dir(None).relative_working_dir().unwrap(),
+            PathBuf::new()
+        );
+        assert_eq!(
+            working_dir(Some(".")).relative_working_dir().unwrap(),
+            PathBuf::new()
+        );
+        assert_eq!(
+            working_dir(Some("./packages/app/"))
+                .relative_working_dir()
+                .unwrap(),
+            Path::new("packages/app")
+        );
+
+        let toml = r#"
+[agent]
+working_dir = "packages/app"
+"#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(
+            config.agent.relative_working_dir().unwrap(),
+            Path::new("packages/app")
+        );
+    }
+
+    #[test]
+    fn test_relative_working_dir_rejects_escapes() {
+        for dir in ["/etc", "../other", "packages/../../other", "packages/.."] {
+            let config = AgentConfig {
+                working_dir: Some(dir.to_string()),
+                ..Default::default()
+            };
+            assert!(config.relative_working_dir().is_err(), "{dir} accepted");
+        }
+    }
+
+    #[test]
+    fn test_ollama_config_default() {
+        let config = Config::default();
+        assert_eq!(config.agent.ollama.host, "http://localhost:11434");
+        assert_eq!(config.agent.ollama.model, "llama3.1");
+        assert_eq!(config.agent.ollama.timeout_minutes, None);
+    }
+
+    #[test]
+    fn test_ollama_config_custom() {
+        let toml = r#"
+[agent]
+provider = "ollama"
+
+[agent.ollama]
+host = "http://gpu-box:11434"
+model = "qwen2.5-coder:32b"
+timeout_minutes = 30
+"#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.agent.get_provider().unwrap(), Provider::Ollama);
+        assert_eq!(config.agent.ollama.host, "http://gpu-box:11434");
+        assert_eq!(config.agent.ollama.model, "qwen2.5-coder:32b");
+        assert_eq!(
+            config.agent.get_provider_timeout(Provider::Ollama),
+            Some(30)
+        );
+    }
+
+    #[test]
+    fn test_success_command_default() {
+        let config = Config::default();
+        assert!(config.completion.success_command.is_none());
+    }
+
+    #[test]
+    fn test_success_command_custom() {
+        let toml = r#"
+[completion]
+success_command = "./check_done.sh"
+"#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(
+            config.completion.success_command,
+            Some("./check_done.sh".to_string())
+        );
+        assert_eq!(config.completion.idle_threshold, 2);
+    }
+
+    #[test]
+    fn test_min_iterations_before_idle() {
+        assert_eq!(Config::default().completion.min_iterations_before_idle, 0);
+
+        let toml = r"
+[completion]
+min_iterations_before_idle = 6
+";
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.completion.min_iterations_before_idle, 6);
+    }
+
+    #[test]
+    fn test_completion_strategy_parsing() {
+        let config = Config::default();
+        assert_eq!(
+            config.completion.strategy,
+            CompletionStrategy::Any(vec![
+                CompletionStrategy::Idle,
+                CompletionStrategy::Promise,
+                CompletionStrategy::Command,
+            ])
+        );
+
+        let toml = r#"
+[completion]
+strategy = "promise"
+"#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.completion.strategy, CompletionStrategy::Promise);
+
+        let toml = r#"
+[completion]
+strategy = { all = ["command", { any = ["idle", "promise"] }] }
+"#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(
+            config.completion.strategy,
+            CompletionStrategy::All(vec![
+                CompletionStrategy::Command,
+                CompletionStrategy::Any(vec![
+                    CompletionStrategy::Idle,
+                    CompletionStrategy::Promise,
+                ]),
+            ])
+        );
+
+        let toml = r#"
+[completion]
+strategy = "never"
+"#;
+        assert!(toml::from_str::<Config>(toml).is_err());
+    }
+
+    #[test]
+    fn test_completion_strategy_uses_command() {
+        assert!(CompletionStrategy::default().uses_command());
+        assert!(CompletionStrategy::Command.uses_command());
+        assert!(!CompletionStrategy::Idle.uses_command());
+        assert!(CompletionStrategy::All(vec![CompletionStrategy::Any(vec![
+            CompletionStrategy::Command
+        ])])
+        .uses_command());
+        assert!(!CompletionStrategy::Any(vec![CompletionStrategy::Promise]).uses_command());
+    }
+
+    #[test]
+    fn test_completion_strategy_can_complete() {
+        use CompletionStrategy::{All, Any, Command, Idle, Promise};
+
+        assert!(CompletionStrategy::default().can_complete(false, false));
+        assert!(Idle.can_complete(false, false));
+        assert!(!Promise.can_complete(false, true));
+        assert!(Promise.can_complete(true, false));
+        assert!(!Command.can_complete(true, false));
+        assert!(Command.can_complete(false, true));
+
+        // `any` needs one reachable check, `all` needs every one
+        assert!(Any(vec![Promise, Idle]).can_complete(false, false));
+        assert!(!All(vec![Promise, Idle]).can_complete(false, false));
+        assert!(All(vec![Promise, Idle]).can_complete(true, false));
+        assert!(!Any(vec![]).can_complete(true, true));
+        assert!(!All(vec![]).can_complete(true, true));
+    }
+
+    #[test]
+    fn test_idle_threshold_for_mode_default() {
+        let config = Config::default();
+        assert_eq!(config.completion.idle_threshold_for(Mode::Plan), 2);
+        assert_eq!(config.completion.idle_threshold_for(Mode::Build), 2);
+    }
+
+    #[test]
+    fn test_idle_threshold_for_mode_global() {
+        let toml = r"
+[completion]
+idle_threshold = 4
+";
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.completion.idle_threshold_for(Mode::Plan), 4);
+        assert_eq!(config.completion.idle_threshold_for(Mode::Build), 4);
+    }
+
+    #[test]
+    fn test_idle_threshold_for_mode_specific_overrides_global() {
+        let toml = r"
+[completion]
+idle_threshold = 4
+
+[completion.plan]
+idle_threshold = 1
+";
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.completion.idle_threshold_for(Mode::Plan), 1);
+        assert_eq!(config.completion.idle_threshold_for(Mode::Build), 4);
+
+        // Mode-specific without a global falls back to the default for the other mode
+        let toml = r"
+[completion.build]
+idle_threshold = 6
+";
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.completion.idle_threshold_for(Mode::Plan), 2);
+        assert_eq!(config.completion.idle_threshold_for(Mode::Build), 6);
+    }
+
+    fn test_env(name: &str) -> Option<String> {
+        match name {
+            "HOME" => Some("/home/ralph".to_string()),
+            "RALPH_IMAGE" => Some("ralph:custom".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_interpolate_env_simple_substitution() {
+        let toml = r#"image = "${RALPH_IMAGE}"
+path = "${HOME}/.cache:${HOME}/.config""#;
+        let expanded = interpolate_env(toml, test_env).unwrap();
+        assert_eq!(
+            expanded,
+            r#"image = "ralph:custom"
+path = "/home/ralph/.cache:/home/ralph/.config""#
+        );
+    }
+
+    #[test]
+    fn test_interpolate_env_defaults() {
+        assert_eq!(
+            interpolate_env("${MISSING:-ralph:latest}", test_env).unwrap(),
+            "ralph:latest"
+        );
+        assert_eq!(
+            interpolate_env("${EMPTY:-fallback}", test_env).unwrap(),
+            "fallback"
+        );
+        assert_eq!(
+            interpolate_env("${RALPH_IMAGE:-ignored}", test_env).unwrap(),
+            "ralph:custom"
+        );
+        assert_eq!(interpolate_env("${MISSING:-}", test_env).unwrap(), "");
+    }
+
+    #[test]
+    fn test_interpolate_env_missing_variable() {
+        // Plain references to unset variables are left as-is
+        assert_eq!(
+            interpolate_env("dir = \"${MISSING}/x\"", test_env).unwrap(),
+            "dir = \"${MISSING}/x\""
+        );
+
+        // Required references fail with the given message
+        let err = interpolate_env("${MISSING:?set it in .envrc}", test_env).unwrap_err();
+        assert!(err.to_string().contains("MISSING"));
+        assert!(err.to_string().contains("set it in .envrc"));
+        assert!(interpolate_env("${EMPTY:?}", test_env).is_err());
+    }
+
+    #[test]
+    fn test_interpolate_env_passes_through_non_references() {
+        for text in [
+            "$HOME",
+            "${}",
+            "${1ABC}",
+            "x ${HOME",
+            "plain text",
+            "${A B}",
+        ] {
+            assert_eq!(interpolate_env(text, test_env).unwrap(), text);
+        }
+    }
+
+    #[test]
+    fn test_interpolate_env_escape() {
+        assert_eq!(
+            interpolate_env("$${HOME} is ${HOME}", test_env).unwrap(),
+            "${HOME} is /home/ralph"
+        );
+        assert_eq!(
+            interpolate_env("$${MISSING:?not checked}", test_env).unwrap(),
+            "${MISSING:?not checked}"
+        );
+    }
+
+    #[test]
+    fn test_parse_expands_only_string_values() {
+        let lookup = |name: &str| match name {
+            "QUOTED" => Some("say \"hi\" \\ bye".to_string()),
+            "LINES" => Some("first\nsecond = true".to_string()),
+            _ => test_env(name),
+        };
+        let config = Config::parse(
+            "# ${MISSING:?comments are not expanded}\n\
+             [sandbox]\nimage = \"${QUOTED}\"\nsetup_command = \"echo ${LINES}\"\n\
+             [git]\ncommit_message_template = \"${LINES}\"\n",
+            lookup,
+        )
+        .unwrap();
+        assert_eq!(config.sandbox.image, "say \"hi\" \\ bye");
+        assert_eq!(config.git.commit_message_template, "first\nsecond = true");
+        // Command settings reach the shell unexpanded
+        assert_eq!(
+            config.sandbox.setup_command.as_deref(),
+            Some("echo ${LINES}")
+        );
+    }
+
+    #[test]
+    fn test_parse_keeps_shell_references_in_commands() {
+        let config = Config::parse(
+            "[validation]\ncommand = \"sh -c 'cd ${HOME} && ${CHECK:?set CHECK}'\"\n\
+             [hooks]\npre_iteration = \"sh -c 'echo ${HOME}'\"\n\
+             [[sandbox.mounts]]\nhost = \"${HOME}/.cargo\"\ncontainer = \"/root/.cargo\"\n",
+            test_env,
+        )
+        .unwrap();
+        assert_eq!(
+            config.validation.command,
+            "sh -c 'cd ${HOME} && ${CHECK:?set CHECK}'"
+        );
+        assert_eq!(
+            config.hooks.pre_iteration.as_deref(),
+            Some("sh -c 'echo ${HOME}'")
+        );
+        assert_eq!(config.sandbox.mounts[0].host, "/home/ralph/.cargo");
+    }
+
+    #[test]
+    fn test_parse_reports_missing_variable_key() {
+        let err = Config::parse(
+            "[sandbox]\nimage = \"${MISSING:?set the image}\"\n",
+            test_env,
+        )
+        .unwrap_err();
+        let message = format!("{err:#}");
+        assert!(message.contains("sandbox.image"), "got: {message}");
+        assert!(message.contains("set the image"), "got: {message}");
+    }
+
+    #[test]
+    fn test_parse_keeps_template_unchanged() {
+        let config = Config::parse(crate::templates::RALPH_TOML, test_env).unwrap();
+        let plain: Config = toml::from_str(crate::templates::RALPH_TOML).unwrap();
+        assert_eq!(config.sandbox.image, plain.sandbox.image);
+        assert_eq!(config.validation.command, plain.validation.command);
+    }
+
+    #[test]
+    fn test_load_expands_env_references() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(CONFIG_FILE),
+            "[sandbox]\nimage = \"${RALPH_TEST_UNSET_VAR:-ralph:from-default}\"\n",
+        )
+        .unwrap();
+
+        let config = Config::load(dir.path()).unwrap();
+        assert_eq!(config.sandbox.image, "ralph:from-default");
+    }
+
+    #[test]
+    fn test_load_from_explicit_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let custom = dir.path().join("configs").join("ci.toml");
+        std::fs::create_dir_all(custom.parent().unwrap()).unwrap();
+        std::fs::write(&custom, "[sandbox]\nimage = \"ralph:ci\"\n").unwrap();
+        std::fs::write(
+            dir.path().join(CONFIG_FILE),
+            "[sandbox]\nimage = \"ralph:default\"\n",
+        )
+        .unwrap();
+
+        let config = Config::load_from(dir.path(), Some(&custom)).unwrap();
+        assert_eq!(config.sandbox.image, "ralph:ci");
+
+        let config = Config::load_from(dir.path(), None).unwrap();
+        assert_eq!(config.sandbox.image, "ralph:default");
+    }
+
+    #[test]
+    fn test_load_from_missing_explicit_path_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing.toml");
+
+        let err = Config::load_from(dir.path(), Some(&missing)).unwrap_err();
+        assert!(err.to_string().contains("Failed to read config file"));
+
+        // Without --config a missing ralph.toml still means defaults
+        assert!(Config::load_from(dir.path(), None).is_ok());
+    }
+
+    #[test]
+    fn test_agent_output_format_accepted() {
+        assert!(CursorConfig::default().problems().is_empty());
+        assert!(ClaudeConfig::default().problems().is_empty());
+
+        for format in ["text", "json", "stream-json"] {
+            let cursor = CursorConfig {
+                output_format: format.to_string(),
+                ..CursorConfig::default()
+            };
+            assert!(cursor.problems().is_empty(), "{format}");
+            let claude = ClaudeConfig {
+                output_format: format.to_string(),
+                ..ClaudeConfig::default()
+            };
+            assert!(claude.problems().is_empty(), "{format}");
+        }
+        let cursor = CursorConfig {
+            sandbox: "enabled".to_string(),
+            ..CursorConfig::default()
+        };
+        assert!(cursor.problems().is_empty());
+    }
+
+    #[test]
+    fn test_agent_output_format_rejected() {
+        let cursor = CursorConfig {
+            output_format: "jsn".to_string(),
+            ..CursorConfig::default()
+        };
+        assert_eq!(
+            cursor.problems(),
+            ["agent.cursor.output_format: 'jsn' is not one of \"text\", \"json\", \"stream-json\""]
+        );
+
+        let cursor = CursorConfig {
+            sandbox: "off".to_string(),
+            ..CursorConfig::default()
+        };
+        assert!(cursor.problems()[0].starts_with("agent.cursor.sandbox: 'off'"));
+
+        // Every problem is reported, not just the first
+        let cursor = CursorConfig {
+            output_format: "jsn".to_string(),
+            sandbox: "off".to_string(),
+            ..CursorConfig::default()
+        };
+        assert_eq!(cursor.problems().len(), 2);
+
+        // Values are case-sensitive, as the CLIs expect
+        let claude = ClaudeConfig {
+            output_format: "JSON".to_string(),
+            ..ClaudeConfig::default()
+        };
+        assert!(claude.problems()[0].starts_with("agent.claude.output_format: 'JSON'"));
+    }
+
+    #[test]
+    fn test_command_template() {
+        let toml = r#"
+[agent.claude]
+command_template = ["{path}", "-p", "--model", "{model}"]
+"#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.agent.claude.command_template.len(), 4);
+        assert!(config.agent.claude.problems().is_empty());
+        assert!(config.agent.cursor.command_template.is_empty());
+
+        let cursor = CursorConfig {
+            command_template: vec!["{path}".to_string(), "{promt}".to_string()],
+            ..CursorConfig::default()
+        };
+        assert!(cursor.problems()[0]
+            .starts_with("agent.cursor.command_template: unknown placeholder {promt}"));
+    }
+
+    #[test]
+    fn test_load_rejects_invalid_output_format() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(CONFIG_FILE),
+            "[agent.claude]\noutput_format = \"streamjson\"\n",
+        )
+        .unwrap();
+
+        let err = Config::load(dir.path()).unwrap_err();
+        assert!(format!("{err:#}").contains("agent.claude.output_format: 'streamjson'"));
+    }
+
+    #[test]
+    fn test_sandbox_cache_volumes() {
+        assert!(Config::default().sandbox.cache_volumes.is_empty());
+
+        let toml = r#"
+[sandbox]
+cache_volumes = ["/root/.cargo", "/root/.npm"]
+"#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(
+            config.sandbox.cache_volumes,
+            vec!["/root/.cargo".to_string(), "/root/.npm".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_sandbox_security_default() {
+        let security = Config::default().sandbox.security;
+        assert!(!security.readonly_rootfs);
+        assert!(security.seccomp_profile.is_none());
+        assert!(!security.no_new_privileges);
+    }
+
+    #[test]
+    fn test_sandbox_security_custom() {
+        let toml = r#"
+[sandbox.security]
+readonly_rootfs = true
+seccomp_profile = "~/.config/ralph/seccomp.json"
+no_new_privileges = true
+"#;
+        let config: Config = toml::from_str(toml).unwrap();
+        let security = config.sandbox.security;
+        assert!(security.readonly_rootfs);
+        assert_eq!(
+            security.seccomp_profile.as_deref(),
+            Some("~/.config/ralph/seccomp.json")
+        );
+        assert!(security.no_new_privileges);
+    }
+
+    #[test]
+    fn test_resources_gpus() {
+        assert!(Config::default().sandbox.resources.gpus.is_none());
+
+        let toml = r#"
+[sandbox.resources]
+gpus = "all"
+"#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.sandbox.resources.gpus.as_deref(), Some("all"));
+        assert_eq!(config.sandbox.resources.memory, "8g");
+    }
+
+    #[test]
+    fn test_rollback_on_failure_default() {
+        let config = Config::default();
+        assert!(!config.validation.rollback_on_failure);
+    }
+
+    #[test]
+    fn test_rollback_on_failure_enabled() {
+        let toml = r"
+[validation]
+rollback_on_failure = true
+";
+        let config: Config = toml::from_str(toml).unwrap();
+        assert!(config.validation.rollback_on_failure);
+        assert!(config.validation.enabled);
+    }
+
+    #[test]
+    fn test_max_error_chars() {
+        assert_eq!(Config::default().validation.max_error_chars, 4000);
+
+        let toml = r"
+[validation]
+max_error_chars = 0
+";
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.validation.max_error_chars, 0);
+    }
+
+    #[test]
+    fn test_error_tail_lines() {
+        assert_eq!(Config::default().validation.error_tail_lines, None);
+
+        let toml = r"
+[validation]
+error_tail_lines = 20
+";
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.validation.error_tail_lines, Some(20));
+    }
+
+    #[test]
+    fn test_final_command() {
+        assert_eq!(Config::default().validation.final_command, None);
+
+        let toml = r#"
+[validation]
+enabled = false
+final_command = "cargo test --workspace"
+"#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(
+            config.validation.final_command.as_deref(),
+            Some("cargo test --workspace")
+        );
+    }
+
+    #[test]
+    fn test_validation_timeout() {
+        let config = Config::default();
+        assert_eq!(config.validation.timeout_minutes, 30);
+        assert_eq!(
+            config.validation.timeout(),
+            Some(std::time::Duration::from_mins(30))
+        );
+
+        let toml = r"
+[validation]
+timeout_minutes = 0
+";
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.validation.timeout(), None);
+    }
+
+    #[test]
+    fn test_hooks_config_default() {
+        let config = Config::default();
+        assert!(config.hooks.pre_iteration.is_none());
+        assert!(config.hooks.post_iteration.is_none());
+    }
+
+    #[test]
+    fn test_hooks_config_custom() {
+        let toml = r#"
+[hooks]
+pre_iteration = "cargo fmt"
+post_iteration = "sh -c './notify.sh done'"
+"#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.hooks.pre_iteration, Some("cargo fmt".to_string()));
+        assert_eq!(
+            config.hooks.post_iteration,
+            Some("sh -c './notify.sh done'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_budget_config_default() {
+        let config = Config::default();
+        assert!(config.budget.max_minutes.is_none());
+        assert!(config.budget.max_iterations_per_hour.is_none());
+    }
+
+    #[test]
+    fn test_budget_config_custom() {
+        let toml = r"
+[budget]
+max_minutes = 480
+max_iterations_per_hour = 6
+";
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.budget.max_minutes, Some(480));
+        assert_eq!(config.budget.max_iterations_per_hour, Some(6));
+    }
+
+    #[test]
+    fn test_backoff_config_default_and_custom() {
+        let config = Config::default();
+        assert_eq!(config.backoff.base_seconds, 30);
+        assert!((config.backoff.multiplier - 2.0).abs() < f64::EPSILON);
+        assert_eq!(config.backoff.max_seconds, 600);
+
+        let toml = r"
+[backoff]
+base_seconds = 5
+multiplier = 1.5
+";
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.backoff.base_seconds, 5);
+        assert!((config.backoff.multiplier - 1.5).abs() < f64::EPSILON);
+        assert_eq!(config.backoff.max_seconds, 600);
+    }
+
+    #[test]
+    fn test_prompt_config_default_and_custom() {
+        assert!(!Config::default().prompt.substitute);
+        assert!(!Config::default().prompt.commit_trailer);
+
+        let config: Config =
+            toml::from_str("[prompt]\nsubstitute = true\ncommit_trailer = true\n").unwrap();
+        assert!(config.prompt.substitute);
+        assert!(config.prompt.commit_trailer);
+        assert!(config
+            .prompt
+            .validation_error_template
+            .contains("```\n{error}\n```"));
+
+        let config: Config =
+            toml::from_str("[prompt]\nvalidation_error_template = \"Fix: {error}\"\n").unwrap();
+        assert_eq!(config.prompt.validation_error_template, "Fix: {error}");
     }
 
     #[test]
@@ -990,6 +2912,65 @@ timeout_minutes = 180
         assert!(config.git.worktree.is_none());
     }
 
+    #[test]
+    fn test_git_auto_commit_default() {
+        let config = Config::default();
+        assert!(!config.git.auto_commit);
+        assert_eq!(
+            config.git.commit_message_template,
+            "ralph: {mode} iteration {iteration}"
+        );
+    }
+
+    #[test]
+    fn test_git_auto_commit_custom() {
+        let toml = r#"
+[git]
+auto_commit = true
+commit_message_template = "chore(ralph): {iteration}"
+"#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert!(config.git.auto_commit);
+        assert_eq!(
+            config.git.commit_message_template,
+            "chore(ralph): {iteration}"
+        );
+    }
+
+    #[test]
+    fn test_git_pr_templates() {
+        let config = Config::default();
+        assert_eq!(config.git.pr_title_template, "{branch}: {goal}");
+        assert!(config
+            .git
+            .pr_body_template
+            .starts_with("## Summary\n\n{goal}"));
+
+        let toml = r#"
+[git]
+pr_title_template = "[ralph] {goal}"
+pr_body_template = """
+{goal}
+
+Built in {iterations} iterations.
+"""
+"#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.git.pr_title_template, "[ralph] {goal}");
+        assert_eq!(
+            config.git.pr_body_template,
+            "{goal}\n\nBuilt in {iterations} iterations.\n"
+        );
+    }
+
+    #[test]
+    fn test_git_push_retries() {
+        assert_eq!(Config::default().git.push_retries, 3);
+
+        let config: Config = toml::from_str("[git]\npush_retries = 0\n").unwrap();
+        assert_eq!(config.git.push_retries, 0);
+    }
+
     #[test]
     fn test_git_auto_pr_custom() {
         let toml = r#"
@@ -1043,4 +3024,23 @@ auto_push = true
         let config: Config = toml::from_str(toml).unwrap();
         assert!(config.git.worktree.is_none());
     }
+
+    #[test]
+    fn test_git_sign_commits() {
+        let git = Config::default().git;
+        assert!(!git.sign_commits);
+        assert!(git.signing_key.is_none());
+
+        let toml = r#"
+[git]
+sign_commits = true
+signing_key = "~/.ssh/ralph-signing.pub"
+"#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert!(config.git.sign_commits);
+        assert_eq!(
+            config.git.signing_key.as_deref(),
+            Some("~/.ssh/ralph-signing.pub")
+        );
+    }
 }
@@ -1,24 +1,53 @@
 //! Completion detection for Ralph loops.
 //!
 //! Detects when a loop should complete based on agent activity:
-//! validation passes and the agent stops making changes (no new commits).
+//! validation passes and the agent stops making changes (no new commits
+//! and, when the worktree is checked, no uncommitted changes).
+//! Optionally, a user-supplied success command exiting 0 also signals completion,
+//! as does a configured promise marker appearing in the agent's output.
+//! Idle detection compares the HEAD hash by default, or the number of commits
+//! since the loop started, which a rebase leaves unchanged.
+//! `completion.strategy` decides how these checks combine.
 
 use std::path::Path;
-use tracing::debug;
+use tracing::{debug, warn};
+
+use crate::config::CompletionStrategy;
+
+/// Results of the individual completion checks for one iteration.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct CompletionSignals {
+    /// The idle count reached the threshold.
+    pub idle: bool,
+    /// The agent output contained the promise marker.
+    pub promise: bool,
+    /// The success command exited 0.
+    pub command: bool,
+}
 
 /// Detects when a Ralph loop should complete based on agent idleness.
 ///
-/// The agent is considered "done" when:
-/// - Validation passes (no errors)
+/// The agent is considered "done" when validation passes and the configured
+/// strategy is satisfied. By default that is either:
 /// - No new commits are created for `idle_threshold` consecutive iterations
+/// - The agent output contains the promise marker, if one is configured
+/// - The success command exits 0, if one is configured
 #[derive(Debug)]
 pub(crate) struct CompletionDetector {
     /// Last known commit hash.
     last_commit: Option<String>,
+    /// Last known number of commits since the loop started (count mode).
+    last_commit_count: Option<u32>,
     /// Consecutive iterations with no changes (and validation passing).
     idle_count: u32,
     /// Number of idle iterations before considering complete.
     idle_threshold: u32,
+    /// Rendered promise marker (e.g. `<promise>DONE</promise>`).
+    promise_marker: Option<String>,
+    /// How the individual checks combine.
+    strategy: CompletionStrategy,
+    /// First iteration at which idleness may complete the loop.
+    min_iterations_before_idle: u32,
 }
 
 impl CompletionDetector {
@@ -27,8 +56,12 @@ impl CompletionDetector {
     pub fn new(idle_threshold: u32) -> Self {
         Self {
             last_commit: None,
+            last_commit_count: None,
             idle_count: 0,
             idle_threshold,
+            promise_marker: None,
+            strategy: CompletionStrategy::default(),
+            min_iterations_before_idle: 0,
         }
     }
 
@@ -38,16 +71,60 @@ impl CompletionDetector {
     pub fn from_state(idle_threshold: u32, last_commit: Option<String>, idle_count: u32) -> Self {
         Self {
             last_commit,
+            last_commit_count: None,
             idle_count,
             idle_threshold,
+            promise_marker: None,
+            strategy: CompletionStrategy::default(),
+            min_iterations_before_idle: 0,
         }
     }
 
+    /// Also complete when the agent output contains `promise_format` with
+    /// `{promise}` replaced by `promise`. `None` disables the matcher.
+    pub fn with_promise(mut self, promise: Option<&str>, promise_format: &str) -> Self {
+        self.promise_marker = promise.map(|p| promise_format.replace("{promise}", p));
+        self
+    }
+
+    /// Decide completion with `strategy` instead of the default.
+    pub fn with_strategy(mut self, strategy: CompletionStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Ignore idleness until iteration `min`, for agents that think for
+    /// several iterations before committing. Other checks still apply.
+    pub fn with_min_iterations_before_idle(mut self, min: u32) -> Self {
+        self.min_iterations_before_idle = min;
+        self
+    }
+
+    /// Restore the persisted commit count for
+    /// [`check_completion_by_count`](Self::check_completion_by_count).
+    pub fn with_commit_count(mut self, commit_count: Option<u32>) -> Self {
+        self.last_commit_count = commit_count;
+        self
+    }
+
     /// Get the last known commit hash (for state persistence).
     pub fn last_commit(&self) -> Option<&str> {
         self.last_commit.as_deref()
     }
 
+    /// Get the last known commit count (for state persistence).
+    pub fn commit_count(&self) -> Option<u32> {
+        self.last_commit_count
+    }
+
+    /// Record the current commit count at the start of an iteration.
+    pub fn record_commit_count(&mut self, commit_count: Option<u32>) {
+        if self.last_commit_count.is_none() {
+            // First iteration - just record, don't compare
+            self.last_commit_count = commit_count;
+        }
+    }
+
     /// Record the current commit hash at the start of an iteration.
     pub fn record_commit(&mut self, commit_hash: Option<String>) {
         if self.last_commit.is_none() {
@@ -58,9 +135,57 @@ impl CompletionDetector {
 
     /// Check if the loop should complete.
     ///
-    /// Call this after validation passes. Compares current commit to last known.
-    /// Returns true if agent has been idle for `idle_threshold` iterations.
-    pub fn check_completion(&mut self, current_commit: Option<&str>) -> bool {
+    /// Call this after validation passes. Compares current commit to last known
+    /// and scans `output` (the latest agent output) for the promise marker.
+    /// Returns true if the strategy is satisfied without the success command.
+    #[cfg(test)]
+    pub fn check_completion(
+        &mut self,
+        iteration: u32,
+        current_commit: Option<&str>,
+        output: &str,
+    ) -> bool {
+        let idle = self.check_completion_with_worktree(current_commit, false, true);
+        self.is_complete(
+            iteration,
+            CompletionSignals {
+                idle,
+                promise: self.promise_in(output),
+                command: false,
+            },
+        )
+    }
+
+    /// Returns true if the strategy is satisfied by the checks of `iteration`.
+    ///
+    /// The idle signal does not count while `iteration` is below
+    /// `min_iterations_before_idle`; the idle count still accumulates.
+    pub fn is_complete(&self, iteration: u32, signals: CompletionSignals) -> bool {
+        let idle = signals.idle && iteration >= self.min_iterations_before_idle;
+        if signals.idle && !idle {
+            debug!(
+                "Idle at iteration {iteration}, ignored before iteration {}",
+                self.min_iterations_before_idle
+            );
+        }
+        strategy_satisfied(&self.strategy, CompletionSignals { idle, ..signals })
+    }
+
+    /// Whether the strategy consults the success command, so it is worth running.
+    pub fn uses_command(&self) -> bool {
+        self.strategy.uses_command()
+    }
+
+    /// Like [`check_completion`](Self::check_completion), without the promise
+    /// check, but only counts the iteration as idle when the worktree is clean
+    /// (`!is_dirty`) and tests pass. Uncommitted work or failing tests reset
+    /// the idle count.
+    pub fn check_completion_with_worktree(
+        &mut self,
+        current_commit: Option<&str>,
+        is_dirty: bool,
+        tests_pass: bool,
+    ) -> bool {
         let changed = match (&self.last_commit, current_commit) {
             (Some(last), Some(current)) => last != current,
             (None, Some(_)) => true,         // First commit
@@ -72,8 +197,47 @@ impl CompletionDetector {
                 "Commit changed: {:?} -> {:?}, resetting idle count",
                 self.last_commit, current_commit
             );
-            self.idle_count = 0;
             self.last_commit = current_commit.map(String::from);
+        }
+        self.update_idle(changed, is_dirty, tests_pass)
+    }
+
+    /// Like [`check_completion_with_worktree`](Self::check_completion_with_worktree),
+    /// but compares `current_count`, the number of commits since the loop
+    /// started, instead of the HEAD hash. A rebase that rewrites hashes but
+    /// keeps the count is idle; any change in the count is progress.
+    pub fn check_completion_by_count(
+        &mut self,
+        current_count: Option<u32>,
+        is_dirty: bool,
+        tests_pass: bool,
+    ) -> bool {
+        let changed = match (self.last_commit_count, current_count) {
+            (Some(last), Some(current)) => last != current,
+            (None, Some(_)) => true,         // First count
+            (Some(_) | None, None) => false, // No commit info, assume no change
+        };
+
+        if changed {
+            debug!(
+                "Commit count changed: {:?} -> {:?}, resetting idle count",
+                self.last_commit_count, current_count
+            );
+            self.last_commit_count = current_count;
+        }
+        self.update_idle(changed, is_dirty, tests_pass)
+    }
+
+    /// Updates the idle count after an iteration and returns true once it
+    /// reaches the threshold.
+    fn update_idle(&mut self, changed: bool, is_dirty: bool, tests_pass: bool) -> bool {
+        if changed {
+            self.idle_count = 0;
+        } else if is_dirty || !tests_pass {
+            debug!(
+                "No new commit but dirty={is_dirty} tests_pass={tests_pass}, resetting idle count"
+            );
+            self.idle_count = 0;
         } else {
             self.idle_count += 1;
             debug!(
@@ -85,12 +249,50 @@ impl CompletionDetector {
         self.idle_count >= self.idle_threshold
     }
 
+    /// Returns true if `output` contains the configured promise marker.
+    pub fn promise_in(&self, output: &str) -> bool {
+        let promised = self
+            .promise_marker
+            .as_deref()
+            .is_some_and(|marker| output.contains(marker));
+        if promised {
+            debug!("Promise marker found in agent output");
+        }
+        promised
+    }
+
     /// Get current idle count (for display/logging).
     pub fn idle_count(&self) -> u32 {
         self.idle_count
     }
 }
 
+/// Formats the idle count against the threshold for `ralph status` and the
+/// progress display, e.g. `1/2`, noting when one more idle iteration
+/// completes the loop.
+pub(crate) fn format_idle_progress(idle_count: u32, idle_threshold: u32) -> String {
+    if idle_count.saturating_add(1) == idle_threshold {
+        format!("{idle_count}/{idle_threshold} (next idle iteration completes the loop)")
+    } else {
+        format!("{idle_count}/{idle_threshold}")
+    }
+}
+
+/// Evaluates `strategy` against one iteration's check results.
+fn strategy_satisfied(strategy: &CompletionStrategy, signals: CompletionSignals) -> bool {
+    match strategy {
+        CompletionStrategy::Idle => signals.idle,
+        CompletionStrategy::Promise => signals.promise,
+        CompletionStrategy::Command => signals.command,
+        CompletionStrategy::Any(strategies) => {
+            strategies.iter().any(|s| strategy_satisfied(s, signals))
+        }
+        CompletionStrategy::All(strategies) => {
+            strategies.iter().all(|s| strategy_satisfied(s, signals))
+        }
+    }
+}
+
 /// Get current git HEAD commit hash.
 pub(crate) async fn get_commit_hash(project_dir: &Path) -> Option<String> {
     let output = tokio::process::Command::new("git")
@@ -112,12 +314,105 @@ pub(crate) async fn get_commit_hash(project_dir: &Path) -> Option<String> {
     }
 }
 
+/// Number of commits on HEAD since `start` (`git rev-list --count start..HEAD`).
+///
+/// Returns `None` on git failures, e.g. when `start` no longer exists.
+pub(crate) async fn commit_count_since(project_dir: &Path, start: &str) -> Option<u32> {
+    let output = tokio::process::Command::new("git")
+        .current_dir(project_dir)
+        .args(["rev-list", "--count", &format!("{start}..HEAD")])
+        .output()
+        .await
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
+
+/// Returns true if the working tree has uncommitted changes outside `.ralph/`.
+///
+/// Loop state under `.ralph/` changes every iteration and is ignored. Git
+/// failures (e.g. not a repository) count as clean.
+pub(crate) async fn is_worktree_dirty(project_dir: &Path) -> bool {
+    let output = tokio::process::Command::new("git")
+        .current_dir(project_dir)
+        .args(["status", "--porcelain", "--", ".", ":(exclude).ralph"])
+        .output()
+        .await;
+
+    match output {
+        Ok(output) if output.status.success() => !output.stdout.iter().all(u8::is_ascii_whitespace),
+        _ => false,
+    }
+}
+
+/// Run the optional `completion.success_command` in the project directory.
+///
+/// Returns true only if a command is configured and exits with status 0.
+/// Parse or spawn failures are logged and treated as "not done".
+pub(crate) async fn success_command_passed(project_dir: &Path, command: Option<&str>) -> bool {
+    let Some(command) = command else {
+        return false;
+    };
+
+    let parts = match shell_words::split(command) {
+        Ok(parts) => parts,
+        Err(e) => {
+            warn!("Failed to parse success command '{}': {}", command, e);
+            return false;
+        }
+    };
+    let Some((program, args)) = parts.split_first() else {
+        warn!("Success command is empty, ignoring");
+        return false;
+    };
+
+    match tokio::process::Command::new(program)
+        .current_dir(project_dir)
+        .args(args)
+        .output()
+        .await
+    {
+        Ok(output) => {
+            debug!(
+                "Success command '{}' exited with {:?}",
+                command,
+                output.status.code()
+            );
+            output.status.success()
+        }
+        Err(e) => {
+            warn!("Failed to run success command '{}': {}", command, e);
+            false
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     const DEFAULT_THRESHOLD: u32 = 2;
 
+    #[test]
+    fn test_format_idle_progress() {
+        assert_eq!(format_idle_progress(0, 3), "0/3");
+        assert_eq!(format_idle_progress(1, 3), "1/3");
+        assert_eq!(
+            format_idle_progress(2, 3),
+            "2/3 (next idle iteration completes the loop)"
+        );
+        assert_eq!(
+            format_idle_progress(0, 1),
+            "0/1 (next idle iteration completes the loop)"
+        );
+        // At or past the threshold the loop is already done
+        assert_eq!(format_idle_progress(3, 3), "3/3");
+        assert_eq!(format_idle_progress(0, 0), "0/0");
+    }
+
     #[test]
     fn test_first_iteration_records_commit() {
         let mut detector = CompletionDetector::new(DEFAULT_THRESHOLD);
@@ -133,7 +428,7 @@ mod tests {
         detector.record_commit(Some("abc123".to_string()));
 
         // First check - different commit
-        assert!(!detector.check_completion(Some("def456")));
+        assert!(!detector.check_completion(1, Some("def456"), ""));
         assert_eq!(detector.idle_count, 0);
         assert_eq!(detector.last_commit, Some("def456".to_string()));
     }
@@ -144,14 +439,47 @@ mod tests {
         detector.record_commit(Some("abc123".to_string()));
 
         // Same commit
-        assert!(!detector.check_completion(Some("abc123")));
+        assert!(!detector.check_completion(1, Some("abc123"), ""));
         assert_eq!(detector.idle_count, 1);
 
         // Still same commit
-        assert!(detector.check_completion(Some("abc123")));
+        assert!(detector.check_completion(2, Some("abc123"), ""));
+        assert_eq!(detector.idle_count, 2);
+    }
+
+    #[test]
+    fn test_dirty_tree_resets_idle() {
+        let mut detector = CompletionDetector::new(DEFAULT_THRESHOLD);
+        detector.record_commit(Some("abc123".to_string()));
+
+        assert!(!detector.check_completion_with_worktree(Some("abc123"), false, true));
+        assert_eq!(detector.idle_count, 1);
+
+        // No commit, but uncommitted work left behind: not idle
+        assert!(!detector.check_completion_with_worktree(Some("abc123"), true, true));
+        assert_eq!(detector.idle_count, 0);
+    }
+
+    #[test]
+    fn test_clean_tree_no_commit_increments_idle() {
+        let mut detector = CompletionDetector::new(DEFAULT_THRESHOLD);
+        detector.record_commit(Some("abc123".to_string()));
+
+        assert!(!detector.check_completion_with_worktree(Some("abc123"), false, true));
+        assert_eq!(detector.idle_count, 1);
+        assert!(detector.check_completion_with_worktree(Some("abc123"), false, true));
         assert_eq!(detector.idle_count, 2);
     }
 
+    #[test]
+    fn test_failing_tests_reset_idle() {
+        let mut detector = CompletionDetector::new(1);
+        detector.record_commit(Some("abc123".to_string()));
+
+        assert!(!detector.check_completion_with_worktree(Some("abc123"), false, false));
+        assert_eq!(detector.idle_count, 0);
+    }
+
     #[test]
     fn test_idle_threshold_triggers_completion() {
         let threshold = 3;
@@ -159,7 +487,7 @@ mod tests {
         detector.record_commit(Some("abc123".to_string()));
 
         for i in 0..threshold {
-            let complete = detector.check_completion(Some("abc123"));
+            let complete = detector.check_completion(i + 1, Some("abc123"), "");
             if i + 1 >= threshold {
                 assert!(complete, "Should complete after {} idles", i + 1);
             } else {
@@ -174,11 +502,11 @@ mod tests {
         detector.record_commit(Some("abc123".to_string()));
 
         // Build up idle count
-        detector.check_completion(Some("abc123"));
+        detector.check_completion(1, Some("abc123"), "");
         assert_eq!(detector.idle_count, 1);
 
         // New commit resets
-        detector.check_completion(Some("def456"));
+        detector.check_completion(2, Some("def456"), "");
         assert_eq!(detector.idle_count, 0);
     }
 
@@ -187,13 +515,171 @@ mod tests {
         let mut detector = CompletionDetector::new(DEFAULT_THRESHOLD);
         detector.record_commit(None);
 
-        assert!(!detector.check_completion(None));
+        assert!(!detector.check_completion(1, None, ""));
         assert_eq!(detector.idle_count, 1);
 
-        assert!(detector.check_completion(None));
+        assert!(detector.check_completion(2, None, ""));
         assert_eq!(detector.idle_count, 2);
     }
 
+    #[test]
+    fn test_promise_marker_completes_immediately() {
+        let mut detector = CompletionDetector::new(DEFAULT_THRESHOLD)
+            .with_promise(Some("DONE"), "<promise>{promise}</promise>");
+        detector.record_commit(Some("abc123".to_string()));
+
+        // New commit (not idle), but the agent promised completion
+        assert!(detector.check_completion(
+            1,
+            Some("def456"),
+            "All done.\n<promise>DONE</promise>\n"
+        ));
+        assert_eq!(detector.idle_count, 0);
+    }
+
+    #[test]
+    fn test_promise_requires_exact_marker() {
+        let mut detector = CompletionDetector::new(DEFAULT_THRESHOLD)
+            .with_promise(Some("DONE"), "<promise>{promise}</promise>");
+        detector.record_commit(Some("abc123".to_string()));
+
+        assert!(!detector.check_completion(1, Some("def456"), "DONE"));
+        assert!(!detector.check_completion(2, Some("ghi789"), "<promise>NOT DONE</promise>"));
+    }
+
+    #[test]
+    fn test_promise_falls_back_to_idle() {
+        let mut detector = CompletionDetector::new(DEFAULT_THRESHOLD)
+            .with_promise(Some("DONE"), "<promise>{promise}</promise>");
+        detector.record_commit(Some("abc123".to_string()));
+
+        assert!(!detector.check_completion(1, Some("abc123"), "still working"));
+        assert!(detector.check_completion(2, Some("abc123"), "still working"));
+    }
+
+    #[test]
+    fn test_promise_disabled_ignores_output() {
+        let mut detector = CompletionDetector::new(DEFAULT_THRESHOLD)
+            .with_promise(None, "<promise>{promise}</promise>");
+        detector.record_commit(Some("abc123".to_string()));
+
+        assert!(!detector.check_completion(1, Some("def456"), "<promise>DONE</promise>"));
+        assert!(!detector.check_completion(2, Some("ghi789"), "<promise>DONE</promise>"));
+    }
+
+    fn signals(idle: bool, promise: bool, command: bool) -> CompletionSignals {
+        CompletionSignals {
+            idle,
+            promise,
+            command,
+        }
+    }
+
+    #[test]
+    fn test_single_check_strategies() {
+        let cases = [
+            (CompletionStrategy::Idle, signals(true, false, false)),
+            (CompletionStrategy::Promise, signals(false, true, false)),
+            (CompletionStrategy::Command, signals(false, false, true)),
+        ];
+        for (strategy, only) in cases {
+            assert!(strategy_satisfied(&strategy, only), "{strategy:?}");
+            // The other two checks passing is not enough
+            let others = signals(!only.idle, !only.promise, !only.command);
+            assert!(!strategy_satisfied(&strategy, others), "{strategy:?}");
+        }
+    }
+
+    #[test]
+    fn test_any_strategy_needs_one_check() {
+        let any =
+            CompletionStrategy::Any(vec![CompletionStrategy::Idle, CompletionStrategy::Promise]);
+        assert!(strategy_satisfied(&any, signals(true, false, false)));
+        assert!(strategy_satisfied(&any, signals(false, true, false)));
+        assert!(!strategy_satisfied(&any, signals(false, false, true)));
+        assert!(!strategy_satisfied(
+            &CompletionStrategy::Any(vec![]),
+            signals(true, true, true)
+        ));
+    }
+
+    #[test]
+    fn test_all_strategy_needs_every_check() {
+        let all =
+            CompletionStrategy::All(vec![CompletionStrategy::Idle, CompletionStrategy::Command]);
+        assert!(strategy_satisfied(&all, signals(true, false, true)));
+        assert!(!strategy_satisfied(&all, signals(true, true, false)));
+        assert!(!strategy_satisfied(&all, signals(false, true, true)));
+    }
+
+    #[test]
+    fn test_nested_strategy() {
+        // Tests pass, and either the agent promised or went idle
+        let nested = CompletionStrategy::All(vec![
+            CompletionStrategy::Command,
+            CompletionStrategy::Any(vec![CompletionStrategy::Idle, CompletionStrategy::Promise]),
+        ]);
+        assert!(strategy_satisfied(&nested, signals(false, true, true)));
+        assert!(strategy_satisfied(&nested, signals(true, false, true)));
+        assert!(!strategy_satisfied(&nested, signals(true, true, false)));
+        assert!(!strategy_satisfied(&nested, signals(false, false, true)));
+    }
+
+    #[test]
+    fn test_idle_strategy_ignores_promise() {
+        let mut detector = CompletionDetector::new(DEFAULT_THRESHOLD)
+            .with_promise(Some("DONE"), "<promise>{promise}</promise>")
+            .with_strategy(CompletionStrategy::Idle);
+        detector.record_commit(Some("abc123".to_string()));
+
+        assert!(!detector.check_completion(1, Some("def456"), "<promise>DONE</promise>"));
+        assert!(!detector.uses_command());
+    }
+
+    #[test]
+    fn test_min_iterations_before_idle_blocks_early_completion() {
+        let mut detector = CompletionDetector::new(DEFAULT_THRESHOLD)
+            .with_min_iterations_before_idle(5)
+            .with_strategy(CompletionStrategy::Idle);
+        detector.record_commit(Some("abc123".to_string()));
+
+        // Idle every iteration, well past the threshold, but too early
+        for iteration in 1..5 {
+            assert!(
+                !detector.check_completion(iteration, Some("abc123"), ""),
+                "completed at iteration {iteration}"
+            );
+        }
+        assert_eq!(detector.idle_count, 4);
+
+        // From the minimum on, the accumulated idle count completes the loop
+        assert!(detector.check_completion(5, Some("abc123"), ""));
+    }
+
+    #[test]
+    fn test_min_iterations_before_idle_needs_idle_after_minimum() {
+        let mut detector =
+            CompletionDetector::new(DEFAULT_THRESHOLD).with_min_iterations_before_idle(3);
+        detector.record_commit(Some("abc123".to_string()));
+
+        assert!(!detector.check_completion(1, Some("abc123"), ""));
+        assert!(!detector.check_completion(2, Some("abc123"), ""));
+        // A commit at the minimum resets the count, so idleness starts over
+        assert!(!detector.check_completion(3, Some("def456"), ""));
+        assert!(!detector.check_completion(4, Some("def456"), ""));
+        assert!(detector.check_completion(5, Some("def456"), ""));
+    }
+
+    #[test]
+    fn test_min_iterations_before_idle_keeps_promise() {
+        let mut detector = CompletionDetector::new(DEFAULT_THRESHOLD)
+            .with_promise(Some("DONE"), "<promise>{promise}</promise>")
+            .with_min_iterations_before_idle(10);
+        detector.record_commit(Some("abc123".to_string()));
+
+        assert!(detector.check_completion(1, Some("def456"), "<promise>DONE</promise>"));
+    }
+
     #[test]
     fn test_from_state_restores_idle_count() {
         // Simulate a restart: detector was at idle_count=1, last_commit="abc123"
@@ -212,10 +698,70 @@ mod tests {
             CompletionDetector::from_state(DEFAULT_THRESHOLD, Some("abc123".to_string()), 1);
 
         // Same commit -> should complete (idle_count becomes 2, threshold is 2)
-        assert!(detector.check_completion(Some("abc123")));
+        assert!(detector.check_completion(2, Some("abc123"), ""));
+        assert_eq!(detector.idle_count, 2);
+    }
+
+    #[test]
+    fn test_count_increase_is_progress() {
+        let mut detector = CompletionDetector::new(DEFAULT_THRESHOLD);
+        detector.record_commit_count(Some(3));
+
+        assert!(!detector.check_completion_by_count(Some(4), false, true));
+        assert_eq!(detector.idle_count, 0);
+        assert_eq!(detector.commit_count(), Some(4));
+
+        // A squash that lowers the count also counts as a change
+        assert!(!detector.check_completion_by_count(Some(2), false, true));
+        assert_eq!(detector.idle_count, 0);
+    }
+
+    #[test]
+    fn test_stable_count_is_idle() {
+        let mut detector = CompletionDetector::new(DEFAULT_THRESHOLD);
+        detector.record_commit(Some("abc123".to_string()));
+        detector.record_commit_count(Some(3));
+
+        // A rebase rewrote HEAD but kept three commits: still idle
+        assert!(!detector.check_completion_by_count(Some(3), false, true));
+        assert_eq!(detector.idle_count, 1);
+        assert!(detector.check_completion_by_count(Some(3), false, true));
         assert_eq!(detector.idle_count, 2);
     }
 
+    #[test]
+    fn test_stable_count_dirty_tree_resets_idle() {
+        let mut detector = CompletionDetector::new(DEFAULT_THRESHOLD);
+        detector.record_commit_count(Some(3));
+
+        assert!(!detector.check_completion_by_count(Some(3), false, true));
+        assert!(!detector.check_completion_by_count(Some(3), true, true));
+        assert_eq!(detector.idle_count, 0);
+    }
+
+    #[test]
+    fn test_record_commit_count_keeps_first() {
+        let mut detector = CompletionDetector::new(DEFAULT_THRESHOLD);
+        assert!(detector.commit_count().is_none());
+
+        detector.record_commit_count(Some(1));
+        detector.record_commit_count(Some(5));
+        assert_eq!(detector.commit_count(), Some(1));
+    }
+
+    #[test]
+    fn test_from_state_restores_commit_count() {
+        let mut detector =
+            CompletionDetector::from_state(DEFAULT_THRESHOLD, Some("abc123".to_string()), 1)
+                .with_commit_count(Some(7));
+        assert_eq!(detector.commit_count(), Some(7));
+
+        // The restored count is the baseline, not replaced at iteration start
+        detector.record_commit_count(Some(9));
+        assert_eq!(detector.commit_count(), Some(7));
+        assert!(detector.check_completion_by_count(Some(7), false, true));
+    }
+
     #[test]
     fn test_last_commit_getter() {
         let mut detector = CompletionDetector::new(DEFAULT_THRESHOLD);
@@ -224,4 +770,86 @@ mod tests {
         detector.record_commit(Some("abc123".to_string()));
         assert_eq!(detector.last_commit(), Some("abc123"));
     }
+
+    #[tokio::test]
+    async fn test_is_worktree_dirty() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path();
+        // Not a repository: treated as clean
+        assert!(!is_worktree_dirty(path).await);
+
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .current_dir(path)
+                .args(args)
+                .output()
+                .is_ok_and(|o| o.status.success())
+        };
+        if !git(&["init", "-q"]) {
+            return; // Git not available
+        }
+        assert!(!is_worktree_dirty(path).await);
+
+        // Loop state is ignored
+        std::fs::create_dir(path.join(".ralph")).unwrap();
+        std::fs::write(path.join(".ralph/state.toml"), "active = true").unwrap();
+        assert!(!is_worktree_dirty(path).await);
+
+        std::fs::write(path.join("work.rs"), "fn main() {}").unwrap();
+        assert!(is_worktree_dirty(path).await);
+    }
+
+    #[tokio::test]
+    async fn test_commit_count_since() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path();
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .current_dir(path)
+                .args([
+                    "-c",
+                    "user.name=t",
+                    "-c",
+                    "user.email=t@t",
+                    "-c",
+                    "commit.gpgsign=false",
+                ])
+                .args(args)
+                .output()
+                .is_ok_and(|o| o.status.success())
+        };
+        if !git(&["init", "-q"]) {
+            return; // Git not available
+        }
+        assert!(git(&["commit", "-q", "--allow-empty", "-m", "start"]));
+        let start = get_commit_hash(path).await.unwrap();
+        assert_eq!(commit_count_since(path, &start).await, Some(0));
+
+        assert!(git(&["commit", "-q", "--allow-empty", "-m", "one"]));
+        assert!(git(&["commit", "-q", "--allow-empty", "-m", "two"]));
+        assert_eq!(commit_count_since(path, &start).await, Some(2));
+
+        assert_eq!(commit_count_since(path, "0000000").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_success_command_not_configured() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(!success_command_passed(dir.path(), None).await);
+    }
+
+    #[tokio::test]
+    async fn test_success_command_exit_codes() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(success_command_passed(dir.path(), Some("true")).await);
+        assert!(!success_command_passed(dir.path(), Some("false")).await);
+    }
+
+    #[tokio::test]
+    async fn test_success_command_invalid() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(!success_command_passed(dir.path(), Some("")).await);
+        assert!(!success_command_passed(dir.path(), Some("sh -c \"unclosed")).await);
+        assert!(!success_command_passed(dir.path(), Some("/nonexistent/ralph-check")).await);
+    }
 }
@@ -0,0 +1,218 @@
+//! `--env-file` support: load `KEY=VALUE` lines into the process environment.
+//!
+//! The file is read before config load, so `RALPH_PROVIDER` and agent auth
+//! variables (e.g. `ANTHROPIC_API_KEY`) can live outside `ralph.toml`. The
+//! format follows common `.env` conventions:
+//!
+//! - Blank lines and lines starting with `#` are skipped
+//! - An optional `export ` prefix is ignored
+//! - Unquoted values are trimmed and end at ` #` (an inline comment)
+//! - `'single'` quotes keep the value literally
+//! - `"double"` quotes support `\n`, `\t`, `\"`, and `\\` escapes
+//!
+//! Values from the file replace variables already set in the environment.
+//! Parsing is pure; only [`load_env_file`] touches the environment.
+
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+
+/// Reads `path` and sets each variable it defines in the process environment.
+///
+/// Must run before any other threads exist, i.e. before the Tokio runtime
+/// starts, since setting variables races with concurrent reads.
+#[allow(deprecated_safe_2024)] // Called from `main` while still single-threaded
+pub(crate) fn load_env_file(path: &Path) -> Result<()> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read env file {}", path.display()))?;
+    let vars =
+        parse_env_file(&content).with_context(|| format!("Invalid env file {}", path.display()))?;
+    for (key, value) in vars {
+        std::env::set_var(key, value);
+    }
+    Ok(())
+}
+
+/// Parses `.env` content into `(key, value)` pairs in file order.
+///
+/// Fails on the first malformed line, naming its line number.
+pub(crate) fn parse_env_file(content: &str) -> Result<Vec<(String, String)>> {
+    let mut vars = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let var = parse_line(line).with_context(|| format!("line {}", index + 1))?;
+        vars.push(var);
+    }
+    Ok(vars)
+}
+
+// -----------------------------------------------------------------------------
+// Helper functions
+// -----------------------------------------------------------------------------
+
+/// Parses one non-blank, non-comment `[export ]KEY=VALUE` line.
+fn parse_line(line: &str) -> Result<(String, String)> {
+    let line = line.strip_prefix("export ").unwrap_or(line).trim_start();
+    let Some((key, value)) = line.split_once('=') else {
+        bail!("expected KEY=VALUE");
+    };
+    let key = key.trim();
+    if !is_valid_key(key) {
+        bail!("invalid variable name '{key}'");
+    }
+    Ok((key.to_string(), parse_value(value.trim())?))
+}
+
+/// Variable names: a letter or `_`, then letters, digits, or `_`.
+fn is_valid_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Parses the (trimmed) text after `=`.
+fn parse_value(raw: &str) -> Result<String> {
+    let (value, rest) = if let Some(quoted) = raw.strip_prefix('\'') {
+        let Some((value, rest)) = quoted.split_once('\'') else {
+            bail!("unterminated single quote");
+        };
+        (value.to_string(), rest)
+    } else if let Some(quoted) = raw.strip_prefix('"') {
+        parse_double_quoted(quoted)?
+    } else {
+        // Unquoted: an inline comment needs whitespace before the `#`
+        let end = raw
+            .char_indices()
+            .find(|&(i, c)| c == '#' && raw[..i].ends_with(char::is_whitespace))
+            .map_or(raw.len(), |(i, _)| i);
+        return Ok(raw[..end].trim_end().to_string());
+    };
+
+    let rest = rest.trim_start();
+    if !rest.is_empty() && !rest.starts_with('#') {
+        bail!("unexpected text after closing quote");
+    }
+    Ok(value)
+}
+
+/// Parses a double-quoted value after the opening quote, returning the
+/// unescaped value and the text after the closing quote.
+fn parse_double_quoted(quoted: &str) -> Result<(String, &str)> {
+    let mut value = String::new();
+    let mut chars = quoted.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((value, &quoted[i + 1..])),
+            '\\' => match chars.next().map(|(_, escaped)| escaped) {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some(escaped @ ('"' | '\\')) => value.push(escaped),
+                Some(other) => {
+                    value.push('\\');
+                    value.push(other);
+                }
+                None => break,
+            },
+            _ => value.push(c),
+        }
+    }
+    bail!("unterminated double quote")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> Vec<(String, String)> {
+        parse_env_file(content).unwrap()
+    }
+
+    fn pair(key: &str, value: &str) -> (String, String) {
+        (key.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn test_parse_plain_values() {
+        assert_eq!(
+            parse("RALPH_PROVIDER=claude\nEMPTY=\n  SPACED = a value  \n"),
+            vec![
+                pair("RALPH_PROVIDER", "claude"),
+                pair("EMPTY", ""),
+                pair("SPACED", "a value"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_skips_comments_and_blank_lines() {
+        let content = "# API keys\n\n   \n  # indented comment\nKEY=value # trailing comment\nURL=http://host/#anchor\n";
+        assert_eq!(
+            parse(content),
+            vec![pair("KEY", "value"), pair("URL", "http://host/#anchor")]
+        );
+    }
+
+    #[test]
+    fn test_parse_export_prefix() {
+        assert_eq!(
+            parse("export ANTHROPIC_API_KEY=sk-ant-123\nexport  SPACED=1\n"),
+            vec![pair("ANTHROPIC_API_KEY", "sk-ant-123"), pair("SPACED", "1")]
+        );
+    }
+
+    #[test]
+    fn test_parse_single_quotes_are_literal() {
+        assert_eq!(
+            parse(r"TOKEN='a b # not a comment \n' # comment"),
+            vec![pair("TOKEN", r"a b # not a comment \n")]
+        );
+    }
+
+    #[test]
+    fn test_parse_double_quotes_with_escapes() {
+        assert_eq!(
+            parse(r#"MSG="line1\nline2 \"quoted\" \\ \$HOME" # comment"#),
+            vec![pair("MSG", "line1\nline2 \"quoted\" \\ \\$HOME")]
+        );
+        assert_eq!(parse(r#"EMPTY="""#), vec![pair("EMPTY", "")]);
+    }
+
+    #[test]
+    fn test_parse_value_may_contain_equals() {
+        assert_eq!(parse("QUERY=a=b=c\n"), vec![pair("QUERY", "a=b=c")]);
+    }
+
+    #[test]
+    fn test_parse_errors_name_the_line() {
+        let err = parse_env_file("OK=1\nno equals sign\n").unwrap_err();
+        assert_eq!(format!("{err:#}"), "line 2: expected KEY=VALUE");
+
+        let cases = [
+            ("1BAD=x", "invalid variable name '1BAD'"),
+            ("BAD-KEY=x", "invalid variable name 'BAD-KEY'"),
+            ("=x", "invalid variable name ''"),
+            ("KEY='open", "unterminated single quote"),
+            ("KEY=\"open", "unterminated double quote"),
+            ("KEY=\"a\" b", "unexpected text after closing quote"),
+        ];
+        for (content, expected) in cases {
+            let err = parse_env_file(content).unwrap_err();
+            assert_eq!(
+                format!("{err:#}"),
+                format!("line 1: {expected}"),
+                "{content}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_load_env_file_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = load_env_file(&dir.path().join(".env")).unwrap_err();
+        assert!(err.to_string().contains("Failed to read env file"));
+    }
+}
@@ -16,11 +16,17 @@
 //! # Start a build loop with max iterations
 //! ralph loop build --max-iterations 10
 //!
+//! # Resume a stopped loop with 5 more iterations
+//! ralph resume --max-iterations 5
+//!
 //! # Check status
 //! ralph status
 //!
 //! # Cancel an active loop
 //! ralph cancel
+//!
+//! # Follow loop errors as they happen
+//! ralph logs --follow --event error
 //! ```
 
 use anyhow::{Context, Result};
@@ -37,19 +43,36 @@ mod agent;
 mod commands;
 mod config;
 mod detection;
+mod envfile;
+mod metrics;
 mod notifications;
 mod sandbox;
 mod state;
 mod templates;
 
+/// Console log layer. Logs go to stderr when stdout carries JSON events.
+fn console_layer<S>(events: commands::loop_cmd::EventFormat) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    match events {
+        commands::loop_cmd::EventFormat::Human => fmt::layer().boxed(),
+        commands::loop_cmd::EventFormat::Json => fmt::layer().with_writer(std::io::stderr).boxed(),
+    }
+}
+
 /// Set up logging with optional file appender based on config.
 fn setup_logging(
     filter: EnvFilter,
     cwd: &Path,
     monitoring: &config::MonitoringConfig,
+    events: commands::loop_cmd::EventFormat,
 ) -> Result<Option<WorkerGuard>> {
     if monitoring.log_file.is_empty() {
-        Registry::default().with(fmt::layer()).with(filter).init();
+        Registry::default()
+            .with(console_layer(events))
+            .with(filter)
+            .init();
         return Ok(None);
     }
 
@@ -96,7 +119,7 @@ fn setup_logging(
     };
 
     Registry::default()
-        .with(fmt::layer())
+        .with(console_layer(events))
         .with(file_layer)
         .with(filter)
         .init();
@@ -119,6 +142,18 @@ struct Cli {
     /// Enable verbose logging
     #[arg(short, long, global = true)]
     verbose: bool,
+
+    /// Output format for loop events on stdout: human or json (one object per line)
+    #[arg(long, global = true, value_enum, default_value = "human")]
+    events: commands::loop_cmd::EventFormat,
+
+    /// Load KEY=VALUE lines from this file into the environment before running
+    #[arg(long, global = true, value_name = "PATH")]
+    env_file: Option<PathBuf>,
+
+    /// Read configuration from this file instead of ./ralph.toml
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
@@ -128,6 +163,10 @@ enum Commands {
         /// Force overwrite existing files
         #[arg(short, long)]
         force: bool,
+
+        /// Project template: rust, node, python, or nix
+        #[arg(long, value_enum, default_value = "nix")]
+        template: commands::init::InitTemplate,
     },
 
     /// Start a Ralph loop
@@ -144,6 +183,27 @@ enum Commands {
         #[arg(long)]
         unlimited: bool,
 
+        /// Stop after N consecutive errors (overrides `monitoring.max_consecutive_errors`; 0 disables)
+        #[arg(long, value_name = "N")]
+        max_errors: Option<u32>,
+
+        /// Skip validation for this run (overrides `validation.enabled` and `--validation-cmd`)
+        #[arg(long)]
+        no_validation: bool,
+
+        /// Validation command for this run (overrides `validation.command`)
+        #[arg(long, value_name = "CMD")]
+        validation_cmd: Option<String>,
+
+        /// Show the last N lines of failed validation output, 0 for all
+        /// (overrides `validation.error_tail_lines`)
+        #[arg(long, value_name = "N")]
+        tail_errors: Option<usize>,
+
+        /// Run the agent in this project subdirectory (overrides `agent.working_dir`)
+        #[arg(long, value_name = "RELPATH")]
+        workdir: Option<String>,
+
         /// Disable Docker sandbox
         #[arg(long)]
         no_sandbox: bool,
@@ -152,26 +212,128 @@ enum Commands {
         #[arg(short, long)]
         prompt: Option<String>,
 
-        /// Override agent provider (cursor or claude)
+        /// File appended to the prompt every iteration (repeatable, re-read each time)
+        #[arg(long, value_name = "FILE")]
+        prompt_append: Vec<PathBuf>,
+
+        /// Override agent provider (cursor, claude, or ollama)
         #[arg(long)]
         provider: Option<String>,
 
         /// Build branches sequentially instead of in parallel (build mode only)
         #[arg(long)]
         sequential: bool,
+
+        /// In branch builds, cancel the remaining branches once one fails
+        #[arg(long)]
+        fail_fast: bool,
+
+        /// Print the prompt the next iteration would send, without running the agent
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Review each iteration's changes and choose to continue, stop, or revert
+        #[arg(long, conflicts_with = "dry_run")]
+        interactive: bool,
+
+        /// Run a canned mock agent without a sandbox, validation, or push
+        #[arg(long, conflicts_with_all = ["dry_run", "interactive"])]
+        simulate: bool,
+
+        /// Write a markdown summary of the run to this file when the loop ends
+        #[arg(long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Leave the persistent sandbox container running when the loop ends
+        /// (needs `sandbox.reuse_container`); open it with `ralph shell`
+        #[arg(long)]
+        keep_container: bool,
+    },
+
+    /// Resume a stopped Ralph loop without resetting progress
+    Resume {
+        /// Run this many more iterations past the last completed one
+        #[arg(short, long)]
+        max_iterations: Option<u32>,
+
+        /// Disable Docker sandbox
+        #[arg(long)]
+        no_sandbox: bool,
+
+        /// Override agent provider (cursor, claude, or ollama)
+        #[arg(long)]
+        provider: Option<String>,
     },
 
     /// Show current Ralph loop status
-    Status,
+    Status {
+        /// Output status as JSON (for scripting)
+        #[arg(long, conflicts_with = "watch")]
+        json: bool,
+
+        /// Refresh every N seconds until the loop stops (default: 2)
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            num_args = 0..=1,
+            default_missing_value = commands::status::DEFAULT_WATCH_INTERVAL,
+            value_parser = commands::status::parse_watch_interval
+        )]
+        watch: Option<u64>,
+
+        /// Number of recent commits to show
+        #[arg(
+            long,
+            value_name = "N",
+            default_value = commands::status::DEFAULT_RECENT_COMMITS,
+            value_parser = clap::value_parser!(u32).range(1..)
+        )]
+        commits: u32,
+
+        /// Only show commits after this time (any `git log --since` value)
+        #[arg(long, value_name = "WHEN")]
+        since: Option<String>,
+    },
 
     /// Cancel active Ralph loop
-    Cancel,
+    Cancel {
+        /// Also kill this project's running sandbox containers, stopping an
+        /// in-flight agent immediately
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Show the loop log
+    Logs {
+        /// Keep watching the log for new lines
+        #[arg(short, long)]
+        follow: bool,
+
+        /// Only show lines with this event (e.g. `iteration_start`, `error`, `loop_end`)
+        #[arg(short, long)]
+        event: Option<String>,
+    },
 
     /// Revert Ralph commits
     Revert {
-        /// Number of commits to revert
+        /// Number of Ralph commits to revert
         #[arg(long, default_value = "1")]
         last: u32,
+
+        /// Revert every Ralph commit since the loop started
+        #[arg(long, conflicts_with = "last")]
+        all_ralph: bool,
+    },
+
+    /// Show what the loop changed since it started
+    Diff {
+        /// Only list changed file names
+        #[arg(long, conflicts_with = "full")]
+        name_only: bool,
+
+        /// Show the full patch
+        #[arg(long)]
+        full: bool,
     },
 
     /// Remove Ralph state files
@@ -183,6 +345,36 @@ enum Commands {
         /// Remove all worktrees created by Ralph
         #[arg(long)]
         worktrees: bool,
+
+        /// Remove sandbox cache volumes
+        #[arg(long)]
+        caches: bool,
+
+        /// With --worktrees, also remove worktrees whose loop is still active
+        #[arg(long, requires = "worktrees")]
+        force: bool,
+
+        /// List what would be removed without deleting anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Check that Ralph's dependencies are installed and reachable
+    Doctor,
+
+    /// Open a shell in this project's most recent sandbox container
+    Shell,
+
+    /// Validate or show ralph.toml
+    Config {
+        #[command(subcommand)]
+        action: commands::config::ConfigAction,
+    },
+
+    /// Inspect `IMPLEMENTATION_PLAN.md` branches
+    Plan {
+        #[command(subcommand)]
+        action: commands::plan::PlanAction,
     },
 
     /// Manage Docker sandbox image
@@ -192,74 +384,166 @@ enum Commands {
     },
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
+fn main() -> Result<()> {
     let cli = Cli::parse();
 
+    // Secrets and RALPH_PROVIDER must be set before config load and agent
+    // runs, and before the runtime starts threads that could read the env
+    if let Some(ref path) = cli.env_file {
+        envfile::load_env_file(path)?;
+    }
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .context("Failed to start the async runtime")?
+        .block_on(run(cli))
+}
+
+/// Runs the parsed command.
+#[allow(clippy::too_many_lines)] // One match arm per subcommand
+async fn run(cli: Cli) -> Result<()> {
     // Initialize logging
     let filter = if cli.verbose {
         EnvFilter::new("ralph=debug")
     } else {
         EnvFilter::new("ralph=info")
     };
+    let config_file = cli.config.as_deref();
 
     match cli.command {
-        Commands::Init { force } => {
+        Commands::Init { force, template } => {
             Registry::default().with(fmt::layer()).with(filter).init();
-            commands::init::run(force)?;
+            commands::init::run(force, template)?;
         }
         Commands::Loop {
             mode,
             max_iterations,
             unlimited,
+            max_errors,
+            no_validation,
+            validation_cmd,
+            tail_errors,
+            workdir,
             no_sandbox,
             prompt,
+            prompt_append,
             provider,
             sequential,
+            fail_fast,
+            dry_run,
+            interactive,
+            simulate,
+            output,
+            keep_container,
         } => {
             // Load config to get log file settings
             let cwd = std::env::current_dir().context("Failed to get current directory")?;
-            let config = config::Config::load(&cwd).context("Failed to load ralph.toml")?;
+            let config = config::Config::load_from(&cwd, config_file)
+                .context("Failed to load ralph.toml")?;
 
             // Set up logging with file appender (guard must stay alive for duration)
-            let _file_guard = setup_logging(filter, &cwd, &config.monitoring)?;
-
-            // Determine default max_iterations based on mode if not specified
-            let effective_max = if unlimited {
-                None
-            } else {
-                max_iterations.or({
-                    Some(match mode {
-                        commands::loop_cmd::LoopMode::Plan => 10,
-                        commands::loop_cmd::LoopMode::Build => 20,
-                    })
-                })
-            };
+            let _file_guard = setup_logging(filter, &cwd, &config.monitoring, cli.events)?;
 
-            commands::loop_cmd::run(
+            let options = commands::loop_cmd::LoopOptions {
                 mode,
-                effective_max,
+                max_iterations: commands::loop_cmd::IterationCap::from_flags(
+                    max_iterations,
+                    unlimited,
+                ),
+                settings: state::LoopSettings {
+                    prompt,
+                    prompt_append,
+                    no_validation,
+                    validation_cmd,
+                    workdir,
+                    max_errors,
+                    tail_errors,
+                    no_sandbox,
+                    provider,
+                    sequential,
+                    fail_fast,
+                },
+                dry_run,
+                interactive,
+                simulate,
+                output,
+                keep_container,
+                resume: None,
+            };
+            commands::loop_cmd::run(options, cli.events, config_file)
+                .await?
+                .ensure_success()?;
+        }
+        Commands::Resume {
+            max_iterations,
+            no_sandbox,
+            provider,
+        } => {
+            let cwd = std::env::current_dir().context("Failed to get current directory")?;
+            let config = config::Config::load_from(&cwd, config_file)
+                .context("Failed to load ralph.toml")?;
+            let _file_guard = setup_logging(filter, &cwd, &config.monitoring, cli.events)?;
+
+            commands::resume::run(
+                max_iterations,
                 no_sandbox,
-                prompt,
                 provider,
-                sequential,
+                cli.events,
+                config_file,
             )
-            .await?;
+            .await?
+            .ensure_success()?;
+        }
+        Commands::Status {
+            json,
+            watch,
+            commits,
+            since,
+        } => {
+            commands::status::run(json, watch, commits, since.as_deref(), config_file).await?;
+        }
+        Commands::Cancel { force } => {
+            commands::cancel::run(force, config_file).await?;
+        }
+        Commands::Logs { follow, event } => {
+            commands::logs::run(follow, event.as_deref(), config_file)?;
+        }
+        Commands::Revert { last, all_ralph } => {
+            commands::revert::run(last, all_ralph).await?;
+        }
+        Commands::Diff { name_only, full } => {
+            commands::diff::run(commands::diff::DiffView::from_flags(name_only, full))?;
+        }
+        Commands::Clean {
+            all,
+            worktrees,
+            caches,
+            force,
+            dry_run,
+        } => {
+            let flags = commands::clean::CleanFlags {
+                all,
+                worktrees,
+                caches,
+                force,
+            };
+            commands::clean::run(flags, dry_run, config_file).await?;
         }
-        Commands::Status => {
-            commands::status::run()?;
+        Commands::Doctor => {
+            commands::doctor::run(config_file).await?;
         }
-        Commands::Cancel => {
-            commands::cancel::run()?;
+        Commands::Shell => {
+            commands::shell::run(config_file).await?;
         }
-        Commands::Revert { last } => {
-            commands::revert::run(last).await?;
+        Commands::Config { action } => {
+            commands::config::run(&action, config_file)?;
         }
-        Commands::Clean { all, worktrees } => {
-            commands::clean::run(all, worktrees).await?;
+        Commands::Plan { action } => {
+            commands::plan::run(&action)?;
         }
         Commands::Image { action } => {
-            commands::image::run(action).await?;
+            commands::image::run(action, config_file).await?;
         }
     }
 
@@ -0,0 +1,205 @@
+//! End-of-loop metrics export.
+//!
+//! When `[monitoring].metrics_file` is set, the loop writes a summary of the
+//! run when it ends, in one of two formats:
+//! - `json`: a single JSON object
+//! - `prometheus`: text exposition format for node exporter's textfile collector
+//!
+//! Serializers are pure. The file is written atomically so collectors never
+//! read a partial document.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::path::Path;
+
+/// Metrics file format.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum MetricsFormat {
+    /// Single JSON object (default).
+    #[default]
+    Json,
+    /// Prometheus text exposition format.
+    Prometheus,
+}
+
+/// Summary of a finished loop.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct LoopSummary {
+    /// Loop mode ("plan" or "build").
+    pub mode: String,
+    /// Iteration counter at loop end.
+    pub iterations: u32,
+    /// Total errors encountered.
+    pub error_count: u32,
+    /// Wall-clock seconds since the loop started.
+    pub elapsed_seconds: u64,
+    /// Commits made since the loop started.
+    pub commits: u32,
+    /// Why the loop ended (e.g. `agent_idle`, `max_iterations_reached`).
+    pub termination_reason: String,
+}
+
+/// Writes `summary` to `path` in `format`, replacing any previous file.
+pub(crate) fn write_metrics(
+    path: &Path,
+    format: MetricsFormat,
+    summary: &LoopSummary,
+) -> Result<()> {
+    let content = match format {
+        MetricsFormat::Json => render_json(summary)?,
+        MetricsFormat::Prometheus => render_prometheus(summary),
+    };
+
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, content)
+        .with_context(|| format!("Failed to write {}", Path::new(&tmp).display()))?;
+    std::fs::rename(&tmp, path)
+        .with_context(|| format!("Failed to write metrics file {}", path.display()))
+}
+
+// -----------------------------------------------------------------------------
+// Helper functions
+// -----------------------------------------------------------------------------
+
+/// Renders the summary as pretty-printed JSON.
+fn render_json(summary: &LoopSummary) -> Result<String> {
+    let mut json = serde_json::to_string_pretty(summary).context("Failed to serialize metrics")?;
+    json.push('\n');
+    Ok(json)
+}
+
+/// Renders the summary in Prometheus text exposition format.
+///
+/// Every sample carries a `mode` label; the termination reason is exposed as
+/// a `ralph_loop_info` sample so it can be joined in queries.
+fn render_prometheus(summary: &LoopSummary) -> String {
+    let mode = escape_label(&summary.mode);
+    let mut out = String::new();
+
+    let gauges = [
+        (
+            "ralph_iterations",
+            "Iteration counter at loop end.",
+            u64::from(summary.iterations),
+        ),
+        (
+            "ralph_errors",
+            "Errors encountered during the loop.",
+            u64::from(summary.error_count),
+        ),
+        (
+            "ralph_elapsed_seconds",
+            "Wall-clock seconds since the loop started.",
+            summary.elapsed_seconds,
+        ),
+        (
+            "ralph_commits",
+            "Commits made since the loop started.",
+            u64::from(summary.commits),
+        ),
+    ];
+    for (name, help, value) in gauges {
+        writeln!(&mut out, "# HELP {name} {help}").unwrap();
+        writeln!(&mut out, "# TYPE {name} gauge").unwrap();
+        writeln!(&mut out, "{name}{{mode=\"{mode}\"}} {value}").unwrap();
+    }
+
+    writeln!(&mut out, "# HELP ralph_loop_info Why the loop ended.").unwrap();
+    writeln!(&mut out, "# TYPE ralph_loop_info gauge").unwrap();
+    writeln!(
+        &mut out,
+        "ralph_loop_info{{mode=\"{mode}\",reason=\"{}\"}} 1",
+        escape_label(&summary.termination_reason)
+    )
+    .unwrap();
+
+    out
+}
+
+/// Escapes a Prometheus label value (backslash, quote, newline).
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn summary() -> LoopSummary {
+        LoopSummary {
+            mode: "build".to_string(),
+            iterations: 12,
+            error_count: 2,
+            elapsed_seconds: 3600,
+            commits: 9,
+            termination_reason: "agent_idle".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_render_json() {
+        let json = render_json(&summary()).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value["mode"], "build");
+        assert_eq!(value["iterations"], 12);
+        assert_eq!(value["error_count"], 2);
+        assert_eq!(value["elapsed_seconds"], 3600);
+        assert_eq!(value["commits"], 9);
+        assert_eq!(value["termination_reason"], "agent_idle");
+    }
+
+    #[test]
+    fn test_render_prometheus() {
+        let text = render_prometheus(&summary());
+
+        assert!(text.contains("# TYPE ralph_iterations gauge\n"));
+        assert!(text.contains("ralph_iterations{mode=\"build\"} 12\n"));
+        assert!(text.contains("ralph_errors{mode=\"build\"} 2\n"));
+        assert!(text.contains("ralph_elapsed_seconds{mode=\"build\"} 3600\n"));
+        assert!(text.contains("ralph_commits{mode=\"build\"} 9\n"));
+        assert!(text.contains("ralph_loop_info{mode=\"build\",reason=\"agent_idle\"} 1\n"));
+
+        // Every sample has HELP and TYPE lines
+        let samples = text.lines().filter(|l| !l.starts_with('#')).count();
+        assert_eq!(text.matches("# HELP ").count(), samples);
+        assert_eq!(text.matches("# TYPE ").count(), samples);
+    }
+
+    #[test]
+    fn test_prometheus_escapes_labels() {
+        let summary = LoopSummary {
+            termination_reason: "error: \"boom\"\nat \\tmp".to_string(),
+            ..summary()
+        };
+        let text = render_prometheus(&summary);
+        assert!(text.contains(r#"reason="error: \"boom\"\nat \\tmp""#));
+    }
+
+    #[test]
+    fn test_write_metrics_creates_parent_and_replaces() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("metrics/ralph.prom");
+
+        write_metrics(&path, MetricsFormat::Prometheus, &summary()).unwrap();
+        let first = std::fs::read_to_string(&path).unwrap();
+        assert!(first.contains("ralph_commits"));
+
+        write_metrics(&path, MetricsFormat::Json, &summary()).unwrap();
+        let second = std::fs::read_to_string(&path).unwrap();
+        assert!(second.starts_with('{'));
+        assert!(!dir.path().join("metrics/ralph.prom.tmp").exists());
+    }
+}
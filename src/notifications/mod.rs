@@ -1,7 +1,9 @@
 //! Notification system for Ralph loop events.
 //!
-//! Supports webhook POST, desktop notifications, and sound alerts
-//! for loop completion and error events.
+//! Supports webhook POST (generic, Slack, Discord), desktop notifications,
+//! and sound alerts for loop completion and error events.
+
+mod payload;
 
 use anyhow::Result;
 use chrono::Utc;
@@ -10,6 +12,7 @@ use std::process::Command;
 use tracing::{debug, warn};
 
 use crate::config::NotificationConfig;
+use payload::{build_payload, PayloadInput, WebhookFormat};
 
 /// Notification event type.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -18,17 +21,28 @@ pub(crate) enum NotificationEvent {
     Complete,
     /// Loop encountered an error.
     Error,
+    /// A single iteration finished successfully (opt-in heartbeat).
+    IterationComplete,
+    /// The circuit breaker stopped the loop after too many consecutive errors.
+    CircuitBreaker,
 }
 
 /// Sends notifications based on configuration.
 pub(crate) struct Notifier {
     config: NotificationConfig,
+    mode: Option<String>,
 }
 
 impl Notifier {
     /// Create a new notifier from configuration.
     pub fn new(config: NotificationConfig) -> Self {
-        Self { config }
+        Self { config, mode: None }
+    }
+
+    /// Set the loop mode included in Slack/Discord payloads.
+    pub fn with_mode(mut self, mode: impl Into<String>) -> Self {
+        self.mode = Some(mode.into());
+        self
     }
 
     /// Send notification for an event.
@@ -43,6 +57,12 @@ impl Notifier {
             NotificationEvent::Error => {
                 self.notify_error(details).await;
             }
+            NotificationEvent::IterationComplete => {
+                self.notify_iteration_complete(details).await;
+            }
+            NotificationEvent::CircuitBreaker => {
+                self.notify_circuit_breaker(details).await;
+            }
         }
     }
 
@@ -62,10 +82,38 @@ impl Notifier {
         }
     }
 
+    /// Send circuit breaker notification (to the `on_error` target).
+    async fn notify_circuit_breaker(&self, details: &NotificationDetails) {
+        if let Some(ref value) = self.config.on_error {
+            self.send_notification(
+                value,
+                "circuit_breaker",
+                "Ralph Circuit Breaker Tripped",
+                details,
+            )
+            .await;
+        }
+    }
+
+    /// Send iteration heartbeat notification (only if `on_iteration` is set).
+    async fn notify_iteration_complete(&self, details: &NotificationDetails) {
+        if let Some(ref value) = self.config.on_iteration {
+            self.send_notification(
+                value,
+                "iteration_complete",
+                "Ralph Iteration Complete",
+                details,
+            )
+            .await;
+        }
+    }
+
     /// Parse notification value and send appropriate notification.
     ///
     /// Supports:
-    /// - `"webhook:<url>"` - POST to webhook
+    /// - `"webhook:<url>"` - POST generic JSON to webhook
+    /// - `"slack:<url>"` - POST Slack `blocks` message
+    /// - `"discord:<url>"` - POST Discord `embeds` message
     /// - `"desktop"` - Desktop notification
     /// - `"sound"` - Sound alert
     /// - Bare URL (backward compat) - Treated as webhook
@@ -76,10 +124,17 @@ impl Notifier {
         title: &str,
         details: &NotificationDetails,
     ) {
-        if value.starts_with("webhook:") {
-            let url = value.strip_prefix("webhook:").unwrap_or("");
+        let input = PayloadInput {
+            event_type,
+            title,
+            mode: self.mode.as_deref(),
+            details,
+        };
+
+        if let Some((format, url)) = parse_webhook_target(value) {
             if !url.is_empty() {
-                if let Err(e) = self.send_webhook(url, event_type, details).await {
+                let body = build_payload(format, &input);
+                if let Err(e) = self.send_webhook(url, &body).await {
                     warn!("Failed to send {} webhook: {}", event_type, e);
                 }
             }
@@ -93,7 +148,8 @@ impl Notifier {
             // Explicitly disabled
         } else if value.starts_with("http://") || value.starts_with("https://") {
             // Backward compatibility: bare URL treated as webhook
-            if let Err(e) = self.send_webhook(value, event_type, details).await {
+            let body = build_payload(WebhookFormat::Generic, &input);
+            if let Err(e) = self.send_webhook(value, &body).await {
                 warn!("Failed to send {} webhook: {}", event_type, e);
             }
         }
@@ -103,20 +159,7 @@ impl Notifier {
     ///
     /// Retries up to 3 times with delays of 1s, 2s, 4s on transient failures.
     #[allow(tail_expr_drop_order)] // Drop order changes are harmless for HTTP responses
-    async fn send_webhook(
-        &self,
-        url: &str,
-        event_type: &str,
-        details: &NotificationDetails,
-    ) -> Result<()> {
-        let payload = json!({
-            "event": event_type,
-            "iteration": details.iteration,
-            "message": details.message,
-            "timestamp": details.timestamp,
-            "context": details.context,
-        });
-
+    async fn send_webhook(&self, url: &str, payload: &serde_json::Value) -> Result<()> {
         debug!("Sending webhook to {}: {:?}", url, payload);
 
         let client = reqwest::Client::new();
@@ -134,7 +177,7 @@ impl Notifier {
                 tokio::time::sleep(std::time::Duration::from_secs(delay_secs)).await;
             }
 
-            match client.post(url).json(&payload).send().await {
+            match client.post(url).json(payload).send().await {
                 Ok(response) => {
                     if response.status().is_success() {
                         debug!("Webhook sent successfully");
@@ -167,6 +210,17 @@ impl Notifier {
     }
 }
 
+/// Split a `<format>:<url>` webhook target into its payload format and URL.
+fn parse_webhook_target(value: &str) -> Option<(WebhookFormat, &str)> {
+    [
+        ("webhook:", WebhookFormat::Generic),
+        ("slack:", WebhookFormat::Slack),
+        ("discord:", WebhookFormat::Discord),
+    ]
+    .into_iter()
+    .find_map(|(prefix, format)| value.strip_prefix(prefix).map(|url| (format, url)))
+}
+
 /// Send desktop notification (cross-platform).
 fn send_desktop_notification(title: &str, body: &str) -> Result<()> {
     // Try notify-send (Linux) first
@@ -274,6 +328,51 @@ impl NotificationDetails {
         }
     }
 
+    /// Create details for an iteration heartbeat.
+    pub fn iteration_complete(iteration: u32, commit: Option<&str>, elapsed_seconds: i64) -> Self {
+        let message = match commit {
+            Some(hash) => format!(
+                "Iteration {iteration} complete (commit {})",
+                &hash[..hash.len().min(7)]
+            ),
+            None => format!("Iteration {iteration} complete"),
+        };
+        Self {
+            iteration: Some(iteration),
+            message,
+            timestamp: Utc::now().to_rfc3339(),
+            context: Some(json!({
+                "iteration": iteration,
+                "commit": commit,
+                "elapsed_seconds": elapsed_seconds,
+            })),
+        }
+    }
+
+    /// Create details for a circuit breaker trip.
+    pub fn circuit_breaker(
+        iteration: u32,
+        consecutive_errors: u32,
+        last_error: Option<&str>,
+    ) -> Self {
+        let message = match last_error {
+            Some(error) => format!(
+                "Circuit breaker tripped after {consecutive_errors} consecutive errors. Last error: {error}"
+            ),
+            None => format!("Circuit breaker tripped after {consecutive_errors} consecutive errors"),
+        };
+        Self {
+            iteration: Some(iteration),
+            message,
+            timestamp: Utc::now().to_rfc3339(),
+            context: Some(json!({
+                "reason": "circuit_breaker",
+                "consecutive_errors": consecutive_errors,
+                "last_error": last_error,
+            })),
+        }
+    }
+
     /// Create details for an error event.
     pub fn error(iteration: Option<u32>, error: &str, context: Option<serde_json::Value>) -> Self {
         Self {
@@ -298,6 +397,85 @@ mod tests {
         assert!(details.context.is_some());
     }
 
+    #[test]
+    fn test_notification_details_iteration_complete() {
+        let details = NotificationDetails::iteration_complete(3, Some("abc1234def5678"), 754);
+        assert_eq!(details.iteration, Some(3));
+        assert_eq!(details.message, "Iteration 3 complete (commit abc1234)");
+
+        let ctx = details.context.unwrap();
+        assert_eq!(ctx["iteration"], 3);
+        assert_eq!(ctx["commit"], "abc1234def5678");
+        assert_eq!(ctx["elapsed_seconds"], 754);
+    }
+
+    #[test]
+    fn test_notification_details_iteration_complete_without_commit() {
+        let details = NotificationDetails::iteration_complete(1, None, 10);
+        assert_eq!(details.message, "Iteration 1 complete");
+        assert!(details.context.unwrap()["commit"].is_null());
+    }
+
+    #[test]
+    fn test_iteration_complete_generic_payload() {
+        let details = NotificationDetails::iteration_complete(8, Some("deadbeef"), 3600);
+        let payload = build_payload(
+            WebhookFormat::Generic,
+            &PayloadInput {
+                event_type: "iteration_complete",
+                title: "Ralph Iteration Complete",
+                mode: Some("Build"),
+                details: &details,
+            },
+        );
+        assert_eq!(payload["event"], "iteration_complete");
+        assert_eq!(payload["iteration"], 8);
+        assert_eq!(payload["context"]["commit"], "deadbeef");
+        assert_eq!(payload["context"]["elapsed_seconds"], 3600);
+    }
+
+    #[test]
+    fn test_notification_details_circuit_breaker() {
+        let details = NotificationDetails::circuit_breaker(7, 3, Some("Validation error: boom"));
+        assert_eq!(details.iteration, Some(7));
+        assert_eq!(
+            details.message,
+            "Circuit breaker tripped after 3 consecutive errors. Last error: Validation error: boom"
+        );
+
+        let ctx = details.context.unwrap();
+        assert_eq!(ctx["reason"], "circuit_breaker");
+        assert_eq!(ctx["consecutive_errors"], 3);
+        assert_eq!(ctx["last_error"], "Validation error: boom");
+    }
+
+    #[test]
+    fn test_notification_details_circuit_breaker_without_last_error() {
+        let details = NotificationDetails::circuit_breaker(2, 5, None);
+        assert_eq!(
+            details.message,
+            "Circuit breaker tripped after 5 consecutive errors"
+        );
+        assert!(details.context.unwrap()["last_error"].is_null());
+    }
+
+    #[test]
+    fn test_circuit_breaker_generic_payload() {
+        let details = NotificationDetails::circuit_breaker(4, 3, Some("agent crashed"));
+        let payload = build_payload(
+            WebhookFormat::Generic,
+            &PayloadInput {
+                event_type: "circuit_breaker",
+                title: "Ralph Circuit Breaker Tripped",
+                mode: Some("Build"),
+                details: &details,
+            },
+        );
+        assert_eq!(payload["event"], "circuit_breaker");
+        assert_eq!(payload["context"]["reason"], "circuit_breaker");
+        assert_eq!(payload["context"]["consecutive_errors"], 3);
+    }
+
     #[test]
     fn test_notification_details_error() {
         let details = NotificationDetails::error(Some(3), "Test error", None);
@@ -313,6 +491,36 @@ mod tests {
         assert_eq!(details.context, Some(ctx));
     }
 
+    #[test]
+    fn test_parse_webhook_target() {
+        assert_eq!(
+            parse_webhook_target("webhook:https://example.com/hook"),
+            Some((WebhookFormat::Generic, "https://example.com/hook"))
+        );
+        assert_eq!(
+            parse_webhook_target("slack:https://hooks.slack.com/services/T/B/X"),
+            Some((
+                WebhookFormat::Slack,
+                "https://hooks.slack.com/services/T/B/X"
+            ))
+        );
+        assert_eq!(
+            parse_webhook_target("discord:https://discord.com/api/webhooks/1/abc"),
+            Some((
+                WebhookFormat::Discord,
+                "https://discord.com/api/webhooks/1/abc"
+            ))
+        );
+        assert_eq!(parse_webhook_target("desktop"), None);
+        assert_eq!(parse_webhook_target("https://example.com/hook"), None);
+    }
+
+    #[test]
+    fn test_notifier_with_mode() {
+        let notifier = Notifier::new(NotificationConfig::default()).with_mode("Build");
+        assert_eq!(notifier.mode.as_deref(), Some("Build"));
+    }
+
     #[test]
     fn test_notifier_creation() {
         let config = NotificationConfig::default();
@@ -326,6 +534,7 @@ mod tests {
         let config = NotificationConfig {
             on_complete: Some("https://example.com/webhook".to_string()),
             on_error: Some("webhook:https://example.com/error".to_string()),
+            on_iteration: None,
         };
         assert_eq!(
             config.on_complete,
@@ -342,6 +551,7 @@ mod tests {
         let config = NotificationConfig {
             on_complete: None,
             on_error: Some("desktop".to_string()),
+            on_iteration: None,
         };
         assert_eq!(config.on_error, Some("desktop".to_string()));
     }
@@ -351,6 +561,7 @@ mod tests {
         let config = NotificationConfig {
             on_complete: None,
             on_error: Some("sound".to_string()),
+            on_iteration: None,
         };
         assert_eq!(config.on_error, Some("sound".to_string()));
     }
@@ -407,6 +618,7 @@ mod tests {
         let config = NotificationConfig {
             on_complete: None,
             on_error: Some("webhook:".to_string()),
+            on_iteration: None,
         };
         let notifier = Notifier::new(config);
         let details = NotificationDetails::error(Some(1), "err", None);
@@ -414,12 +626,69 @@ mod tests {
         notifier.notify(NotificationEvent::Error, &details).await;
     }
 
+    #[tokio::test]
+    async fn test_notifier_notify_empty_slack_and_discord() {
+        // slack:/discord: prefix but empty URL
+        let config = NotificationConfig {
+            on_complete: Some("slack:".to_string()),
+            on_error: Some("discord:".to_string()),
+            on_iteration: None,
+        };
+        let notifier = Notifier::new(config).with_mode("Build");
+        notifier
+            .notify(
+                NotificationEvent::Complete,
+                &NotificationDetails::complete(1, 1, "done"),
+            )
+            .await;
+        notifier
+            .notify(
+                NotificationEvent::Error,
+                &NotificationDetails::error(Some(1), "err", None),
+            )
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_notifier_notify_iteration_complete_unconfigured() {
+        // No on_iteration configured - heartbeat is skipped even if other targets are set
+        let config = NotificationConfig {
+            on_complete: Some("webhook:http://127.0.0.1:9/unreachable".to_string()),
+            on_error: Some("webhook:http://127.0.0.1:9/unreachable".to_string()),
+            on_iteration: None,
+        };
+        let notifier = Notifier::new(config);
+        let details = NotificationDetails::iteration_complete(1, None, 5);
+
+        // Would retry with backoff (>1s) if it tried the unreachable webhook
+        let start = std::time::Instant::now();
+        notifier
+            .notify(NotificationEvent::IterationComplete, &details)
+            .await;
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_notifier_notify_iteration_complete_empty_webhook() {
+        let config = NotificationConfig {
+            on_complete: None,
+            on_error: None,
+            on_iteration: Some("webhook:".to_string()),
+        };
+        let notifier = Notifier::new(config);
+        let details = NotificationDetails::iteration_complete(2, Some("abc"), 60);
+        notifier
+            .notify(NotificationEvent::IterationComplete, &details)
+            .await;
+    }
+
     #[tokio::test]
     async fn test_notifier_notify_error_sound() {
         // Sound notification - fires and forgets
         let config = NotificationConfig {
             on_complete: None,
             on_error: Some("sound".to_string()),
+            on_iteration: None,
         };
         let notifier = Notifier::new(config);
         let details = NotificationDetails::error(Some(1), "err", None);
@@ -432,6 +701,7 @@ mod tests {
         let config = NotificationConfig {
             on_complete: None,
             on_error: Some("desktop".to_string()),
+            on_iteration: None,
         };
         let notifier = Notifier::new(config);
         let details = NotificationDetails::error(Some(1), "err", None);
@@ -444,6 +714,7 @@ mod tests {
         let config = NotificationConfig {
             on_complete: Some("desktop".to_string()),
             on_error: None,
+            on_iteration: None,
         };
         let notifier = Notifier::new(config);
         let details = NotificationDetails::complete(1, 1, "done");
@@ -456,6 +727,7 @@ mod tests {
         let config = NotificationConfig {
             on_complete: Some("sound".to_string()),
             on_error: None,
+            on_iteration: None,
         };
         let notifier = Notifier::new(config);
         let details = NotificationDetails::complete(1, 1, "done");
@@ -468,6 +740,7 @@ mod tests {
         let config = NotificationConfig {
             on_complete: Some("webhook:".to_string()),
             on_error: None,
+            on_iteration: None,
         };
         let notifier = Notifier::new(config);
         let details = NotificationDetails::complete(1, 1, "done");
@@ -481,6 +754,7 @@ mod tests {
         let config = NotificationConfig {
             on_complete: Some("none".to_string()),
             on_error: None,
+            on_iteration: None,
         };
         let notifier = Notifier::new(config);
         let details = NotificationDetails::complete(1, 1, "done");
@@ -0,0 +1,318 @@
+//! Webhook payload formatters.
+//!
+//! Each webhook target gets the JSON schema its receiver expects:
+//! - `webhook:<url>` - generic event JSON
+//! - `slack:<url>` - Slack incoming webhook with `blocks`
+//! - `discord:<url>` - Discord webhook with `embeds`
+
+use serde_json::{json, Value};
+
+use super::NotificationDetails;
+
+/// Slack section text limit is 3000 chars; leave room for formatting.
+const SLACK_MAX_TEXT: usize = 2900;
+
+/// Discord embed description limit is 4096 chars.
+const DISCORD_MAX_DESCRIPTION: usize = 4000;
+
+/// Discord embed colors (decimal RGB).
+const DISCORD_COLOR_SUCCESS: u32 = 0x002E_CC71;
+const DISCORD_COLOR_ERROR: u32 = 0x00E7_4C3C;
+
+/// JSON schema used for a webhook POST.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum WebhookFormat {
+    /// Ralph's own event JSON.
+    Generic,
+    /// Slack Block Kit message.
+    Slack,
+    /// Discord embed message.
+    Discord,
+}
+
+/// Everything a formatter needs to describe one event.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PayloadInput<'a> {
+    /// Event type (`complete`, `error`, `circuit_breaker`, `iteration_complete`).
+    pub event_type: &'a str,
+    /// Human-readable title.
+    pub title: &'a str,
+    /// Loop mode, if known.
+    pub mode: Option<&'a str>,
+    /// Event details.
+    pub details: &'a NotificationDetails,
+}
+
+/// Builds the webhook body for `format`.
+pub(crate) fn build_payload(format: WebhookFormat, input: &PayloadInput<'_>) -> Value {
+    match format {
+        WebhookFormat::Generic => generic_payload(input),
+        WebhookFormat::Slack => slack_payload(input),
+        WebhookFormat::Discord => discord_payload(input),
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Helper functions
+// -----------------------------------------------------------------------------
+
+/// Generic event JSON (unchanged schema for existing webhook consumers).
+fn generic_payload(input: &PayloadInput<'_>) -> Value {
+    let details = input.details;
+    json!({
+        "event": input.event_type,
+        "iteration": details.iteration,
+        "message": details.message,
+        "timestamp": details.timestamp,
+        "context": details.context,
+    })
+}
+
+/// Slack Block Kit message: header, summary, and iteration/mode fields.
+fn slack_payload(input: &PayloadInput<'_>) -> Value {
+    let details = input.details;
+    let summary = if is_error_event(input.event_type) {
+        format!(
+            "```{}```",
+            truncate(&details.message, SLACK_MAX_TEXT.saturating_sub(6))
+        )
+    } else {
+        truncate(&details.message, SLACK_MAX_TEXT)
+    };
+
+    let mut fields = Vec::new();
+    if let Some(iteration) = details.iteration {
+        fields.push(json!({"type": "mrkdwn", "text": format!("*Iteration:*\n{iteration}")}));
+    }
+    if let Some(mode) = input.mode {
+        fields.push(json!({"type": "mrkdwn", "text": format!("*Mode:*\n{mode}")}));
+    }
+
+    let mut blocks = vec![
+        json!({"type": "header", "text": {"type": "plain_text", "text": input.title}}),
+        json!({"type": "section", "text": {"type": "mrkdwn", "text": summary}}),
+    ];
+    if !fields.is_empty() {
+        blocks.push(json!({"type": "section", "fields": fields}));
+    }
+    blocks.push(json!({
+        "type": "context",
+        "elements": [{"type": "mrkdwn", "text": details.timestamp}],
+    }));
+
+    json!({
+        "text": format!("{}: {}", input.title, truncate(&details.message, SLACK_MAX_TEXT)),
+        "blocks": blocks,
+    })
+}
+
+/// Discord embed: title, colored by event, with iteration/mode fields.
+fn discord_payload(input: &PayloadInput<'_>) -> Value {
+    let details = input.details;
+    let (description, color) = if is_error_event(input.event_type) {
+        (
+            format!(
+                "```\n{}\n```",
+                truncate(&details.message, DISCORD_MAX_DESCRIPTION.saturating_sub(8))
+            ),
+            DISCORD_COLOR_ERROR,
+        )
+    } else {
+        (
+            truncate(&details.message, DISCORD_MAX_DESCRIPTION),
+            DISCORD_COLOR_SUCCESS,
+        )
+    };
+
+    let mut fields = Vec::new();
+    if let Some(iteration) = details.iteration {
+        fields.push(json!({"name": "Iteration", "value": iteration.to_string(), "inline": true}));
+    }
+    if let Some(mode) = input.mode {
+        fields.push(json!({"name": "Mode", "value": mode, "inline": true}));
+    }
+
+    json!({
+        "embeds": [{
+            "title": input.title,
+            "description": description,
+            "color": color,
+            "fields": fields,
+            "timestamp": details.timestamp,
+        }],
+    })
+}
+
+/// Whether the event is styled as a failure (code block, error color).
+fn is_error_event(event_type: &str) -> bool {
+    matches!(event_type, "error" | "circuit_breaker")
+}
+
+/// Truncates `text` to at most `max` chars, marking the cut with an ellipsis.
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let kept: String = text.chars().take(max.saturating_sub(1)).collect();
+    format!("{kept}…")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn complete_input(details: &NotificationDetails) -> PayloadInput<'_> {
+        PayloadInput {
+            event_type: "complete",
+            title: "Ralph Loop Complete",
+            mode: Some("Build"),
+            details,
+        }
+    }
+
+    fn error_input(details: &NotificationDetails) -> PayloadInput<'_> {
+        PayloadInput {
+            event_type: "error",
+            title: "Ralph Loop Error",
+            mode: Some("Plan"),
+            details,
+        }
+    }
+
+    #[test]
+    fn test_generic_payload_shape() {
+        let details = NotificationDetails::complete(5, 5, "max_iterations_reached");
+        let payload = build_payload(WebhookFormat::Generic, &complete_input(&details));
+
+        assert_eq!(payload["event"], "complete");
+        assert_eq!(payload["iteration"], 5);
+        assert_eq!(payload["message"], details.message.as_str());
+        assert_eq!(payload["timestamp"], details.timestamp.as_str());
+        assert_eq!(payload["context"]["reason"], "max_iterations_reached");
+    }
+
+    #[test]
+    fn test_slack_payload_shape() {
+        let details = NotificationDetails::complete(7, 7, "completion_detected");
+        let payload = build_payload(WebhookFormat::Slack, &complete_input(&details));
+
+        assert!(payload["text"]
+            .as_str()
+            .unwrap()
+            .starts_with("Ralph Loop Complete: "));
+
+        let blocks = payload["blocks"].as_array().unwrap();
+        assert_eq!(blocks[0]["type"], "header");
+        assert_eq!(blocks[0]["text"]["type"], "plain_text");
+        assert_eq!(blocks[0]["text"]["text"], "Ralph Loop Complete");
+        assert_eq!(blocks[1]["type"], "section");
+        assert_eq!(blocks[1]["text"]["type"], "mrkdwn");
+        assert_eq!(blocks[1]["text"]["text"], details.message.as_str());
+
+        let fields = blocks[2]["fields"].as_array().unwrap();
+        assert_eq!(fields[0]["text"], "*Iteration:*\n7");
+        assert_eq!(fields[1]["text"], "*Mode:*\nBuild");
+
+        assert_eq!(blocks[3]["type"], "context");
+    }
+
+    #[test]
+    fn test_slack_error_payload_wraps_summary_in_code_block() {
+        let details = NotificationDetails::error(Some(3), "cargo build failed", None);
+        let payload = build_payload(WebhookFormat::Slack, &error_input(&details));
+
+        let summary = payload["blocks"][1]["text"]["text"].as_str().unwrap();
+        assert_eq!(summary, "```cargo build failed```");
+    }
+
+    #[test]
+    fn test_slack_payload_omits_missing_fields() {
+        let details = NotificationDetails::error(None, "boom", None);
+        let input = PayloadInput {
+            mode: None,
+            ..error_input(&details)
+        };
+        let payload = build_payload(WebhookFormat::Slack, &input);
+
+        let blocks = payload["blocks"].as_array().unwrap();
+        assert_eq!(blocks.len(), 3);
+        assert!(blocks.iter().all(|b| b.get("fields").is_none()));
+    }
+
+    #[test]
+    fn test_discord_payload_shape() {
+        let details = NotificationDetails::complete(4, 4, "success_command");
+        let payload = build_payload(WebhookFormat::Discord, &complete_input(&details));
+
+        let embeds = payload["embeds"].as_array().unwrap();
+        assert_eq!(embeds.len(), 1);
+        let embed = &embeds[0];
+        assert_eq!(embed["title"], "Ralph Loop Complete");
+        assert_eq!(embed["description"], details.message.as_str());
+        assert_eq!(embed["color"], DISCORD_COLOR_SUCCESS);
+        assert_eq!(embed["timestamp"], details.timestamp.as_str());
+
+        let fields = embed["fields"].as_array().unwrap();
+        assert_eq!(
+            fields[0],
+            json!({"name": "Iteration", "value": "4", "inline": true})
+        );
+        assert_eq!(
+            fields[1],
+            json!({"name": "Mode", "value": "Build", "inline": true})
+        );
+    }
+
+    #[test]
+    fn test_discord_error_payload() {
+        let details = NotificationDetails::error(Some(2), "Agent execution timed out", None);
+        let payload = build_payload(WebhookFormat::Discord, &error_input(&details));
+
+        let embed = &payload["embeds"][0];
+        assert_eq!(embed["color"], DISCORD_COLOR_ERROR);
+        assert_eq!(embed["description"], "```\nAgent execution timed out\n```");
+        assert_eq!(embed["fields"][1]["value"], "Plan");
+    }
+
+    #[test]
+    fn test_circuit_breaker_payload_styled_as_error() {
+        let details = NotificationDetails::circuit_breaker(6, 3, Some("agent crashed"));
+        let input = PayloadInput {
+            event_type: "circuit_breaker",
+            title: "Ralph Circuit Breaker Tripped",
+            ..error_input(&details)
+        };
+
+        let discord = build_payload(WebhookFormat::Discord, &input);
+        assert_eq!(discord["embeds"][0]["color"], DISCORD_COLOR_ERROR);
+        assert_eq!(
+            discord["embeds"][0]["title"],
+            "Ralph Circuit Breaker Tripped"
+        );
+
+        let slack = build_payload(WebhookFormat::Slack, &input);
+        let summary = slack["blocks"][1]["text"]["text"].as_str().unwrap();
+        assert!(summary.starts_with("```Circuit breaker tripped after 3"));
+    }
+
+    #[test]
+    fn test_long_error_summary_is_truncated() {
+        let long = "x".repeat(10_000);
+        let details = NotificationDetails::error(Some(1), &long, None);
+
+        let slack = build_payload(WebhookFormat::Slack, &error_input(&details));
+        let text = slack["blocks"][1]["text"]["text"].as_str().unwrap();
+        assert!(text.chars().count() <= SLACK_MAX_TEXT);
+        assert!(text.contains('…'));
+
+        let discord = build_payload(WebhookFormat::Discord, &error_input(&details));
+        let description = discord["embeds"][0]["description"].as_str().unwrap();
+        assert!(description.chars().count() <= DISCORD_MAX_DESCRIPTION);
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("abcdef", 4), "abc…");
+    }
+}
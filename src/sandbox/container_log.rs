@@ -0,0 +1,127 @@
+//! Audit log of the agent's container output (`sandbox.container_log_file`).
+//!
+//! Every stdout and stderr chunk the agent exec produces is appended to the
+//! file under a timestamped header per iteration. This is separate from the
+//! output returned to the loop, which holds stdout only.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use tracing::warn;
+
+/// Appends raw container output to an audit log.
+pub(crate) struct ContainerLog<W: Write> {
+    writer: W,
+    /// Set after the first write failure so it is only reported once.
+    failed: bool,
+}
+
+impl ContainerLog<File> {
+    /// Opens `path` for appending, creating parent directories, and writes
+    /// the header for `iteration`.
+    pub(crate) fn open(path: &Path, iteration: u32) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).with_context(|| {
+                format!(
+                    "Failed to create container log directory {}",
+                    parent.display()
+                )
+            })?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open container log {}", path.display()))?;
+        Self::new(file, iteration, Utc::now())
+            .with_context(|| format!("Failed to write container log {}", path.display()))
+    }
+}
+
+impl<W: Write> ContainerLog<W> {
+    /// Wraps `writer` and writes the header for `iteration` started at `now`.
+    pub(crate) fn new(mut writer: W, iteration: u32, now: DateTime<Utc>) -> std::io::Result<Self> {
+        writer.write_all(format_header(iteration, now).as_bytes())?;
+        writer.flush()?;
+        Ok(Self {
+            writer,
+            failed: false,
+        })
+    }
+
+    /// Appends one output chunk.
+    ///
+    /// Write failures are logged once and otherwise ignored so a full disk
+    /// never fails the iteration.
+    pub(crate) fn write(&mut self, chunk: &[u8]) {
+        if self.failed {
+            return;
+        }
+        if let Err(e) = self
+            .writer
+            .write_all(chunk)
+            .and_then(|()| self.writer.flush())
+        {
+            warn!("Failed to write container log: {}", e);
+            self.failed = true;
+        }
+    }
+
+    /// Returns the wrapped writer.
+    #[cfg(test)]
+    pub(crate) fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Helper functions
+// -----------------------------------------------------------------------------
+
+/// Header line separating iterations in the log.
+fn format_header(iteration: u32, now: DateTime<Utc>) -> String {
+    format!(
+        "\n===== Iteration {iteration} ({}) =====\n",
+        now.to_rfc3339()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 5, 7, 8, 9).unwrap()
+    }
+
+    #[test]
+    fn test_header_then_chunks() {
+        let mut log = ContainerLog::new(Vec::new(), 3, at()).unwrap();
+        log.write(b"out\n");
+        log.write(b"err\n");
+
+        assert_eq!(
+            String::from_utf8(log.into_inner()).unwrap(),
+            "\n===== Iteration 3 (2024-03-05T07:08:09+00:00) =====\nout\nerr\n"
+        );
+    }
+
+    #[test]
+    fn test_open_creates_parents_and_appends() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit").join("container.log");
+
+        ContainerLog::open(&path, 1).unwrap().write(b"first\n");
+        ContainerLog::open(&path, 2).unwrap().write(b"second\n");
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let first = content.find("Iteration 1").unwrap();
+        let second = content.find("Iteration 2").unwrap();
+        assert!(first < content.find("first").unwrap());
+        assert!(second > content.find("first").unwrap());
+        assert!(content.ends_with("second\n"));
+    }
+}
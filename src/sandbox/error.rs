@@ -31,6 +31,10 @@ pub enum SandboxError {
     /// Container operation failed (create, start, exec, etc.).
     #[error("Container operation failed: {message}")]
     ContainerFailed { message: String },
+
+    /// The agent process exited with a non-zero code (-1 if killed by a signal).
+    #[error("Agent exited with code {0}")]
+    NonZeroExit(i64),
 }
 
 impl SandboxError {
@@ -76,11 +80,34 @@ impl SandboxError {
         }
     }
 
+    /// Creates a `NonZeroExit` error.
+    pub fn non_zero_exit(exit_code: i64) -> Self {
+        Self::NonZeroExit(exit_code)
+    }
+
     /// Returns true if this is a timeout error.
     pub fn is_timeout(&self) -> bool {
         matches!(self, Self::Timeout { .. })
     }
 
+    /// Returns true if the container must be recreated to be used again.
+    pub fn is_container_unhealthy(&self) -> bool {
+        matches!(self, Self::ContainerUnhealthy { .. })
+    }
+
+    /// Returns true if the agent exited with a non-zero code.
+    pub fn is_non_zero_exit(&self) -> bool {
+        matches!(self, Self::NonZeroExit(_))
+    }
+
+    /// Returns the agent's exit code for `NonZeroExit` errors.
+    pub fn exit_code(&self) -> Option<i64> {
+        match self {
+            Self::NonZeroExit(code) => Some(*code),
+            _ => None,
+        }
+    }
+
     /// Returns true if this is a Docker unavailability error.
     #[allow(dead_code)] // Public API for callers
     pub fn is_docker_unavailable(&self) -> bool {
@@ -118,7 +145,7 @@ mod tests {
 
     #[test]
     fn test_timeout_error() {
-        let err = SandboxError::timeout(Duration::from_secs(3600));
+        let err = SandboxError::timeout(Duration::from_hours(1));
         assert!(err.is_timeout());
         assert_eq!(
             err.to_string(),
@@ -129,6 +156,7 @@ mod tests {
     #[test]
     fn test_container_unhealthy_error() {
         let err = SandboxError::container_unhealthy("container is dead");
+        assert!(err.is_container_unhealthy());
         assert!(!err.is_timeout());
         assert_eq!(err.to_string(), "Container is unhealthy: container is dead");
     }
@@ -148,9 +176,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_non_zero_exit_error() {
+        let err = SandboxError::non_zero_exit(2);
+        assert!(err.is_non_zero_exit());
+        assert!(!err.is_timeout());
+        assert_eq!(err.exit_code(), Some(2));
+        assert_eq!(err.to_string(), "Agent exited with code 2");
+
+        assert_eq!(
+            SandboxError::timeout(Duration::from_mins(1)).exit_code(),
+            None
+        );
+    }
+
     #[test]
     fn test_error_variants_are_distinct() {
-        let timeout = SandboxError::timeout(Duration::from_secs(60));
+        let timeout = SandboxError::timeout(Duration::from_mins(1));
         let docker = SandboxError::docker_unavailable("test");
         let image = SandboxError::image_not_found("test");
 
@@ -3,17 +3,20 @@
 //! Provides a trait-based interface for sandboxed execution, with
 //! Docker as the primary implementation.
 
+mod container_log;
 mod docker;
 mod error;
 mod network;
 mod noop;
+mod ralphignore;
 
 use anyhow::Result;
 use async_trait::async_trait;
 use std::path::Path;
 
-pub(crate) use docker::DockerSandbox;
+pub(crate) use docker::{connect_docker, parse_memory_limit, DockerSandbox};
 pub(crate) use error::SandboxError;
+pub(crate) use network::{validate_cidr, validate_domain, validate_extra_host, validate_proxy_url};
 #[allow(unused_imports)] // Available for tests and future use
 pub(crate) use noop::NoopSandbox;
 
@@ -27,7 +30,7 @@ pub(crate) use noop::NoopSandbox;
 pub(crate) trait Sandbox: Send + Sync {
     /// Cleans up orphaned resources from previous runs.
     ///
-    /// For Docker, this removes containers with names matching `ralph-*`.
+    /// For Docker, this removes `ralph-*` containers labelled `ralph.container`.
     /// Returns the number of resources cleaned up.
     async fn cleanup_orphaned(&self) -> Result<u32>;
 
@@ -43,7 +46,15 @@ pub(crate) trait Sandbox: Send + Sync {
     /// Runs the agent with the given prompt.
     ///
     /// If `reuse_id` is provided, attempts to reuse an existing environment.
+    /// `iteration` picks the model when the provider rotates through several.
+    /// `session` continues an earlier agent conversation, if supported.
     /// Returns the agent's output.
-    async fn run(&self, project_dir: &Path, prompt: &str, reuse_id: Option<&str>)
-        -> Result<String>;
+    async fn run(
+        &self,
+        project_dir: &Path,
+        prompt: &str,
+        reuse_id: Option<&str>,
+        iteration: u32,
+        session: Option<&str>,
+    ) -> Result<String>;
 }
@@ -46,6 +46,8 @@ impl Sandbox for NoopSandbox {
         _project_dir: &Path,
         _prompt: &str,
         _reuse_id: Option<&str>,
+        _iteration: u32,
+        _session: Option<&str>,
     ) -> Result<String> {
         // Return empty output - caller should handle this case
         // by running the agent directly without sandboxing
@@ -86,7 +88,9 @@ mod tests {
     async fn test_noop_sandbox_run() {
         let sandbox = NoopSandbox::new();
         let temp_dir = tempdir().unwrap();
-        let result = sandbox.run(temp_dir.path(), "test prompt", None).await;
+        let result = sandbox
+            .run(temp_dir.path(), "test prompt", None, 1, None)
+            .await;
         assert!(result.is_ok());
         assert!(result.unwrap().is_empty());
     }
@@ -96,7 +100,13 @@ mod tests {
         let sandbox = NoopSandbox::new();
         let temp_dir = tempdir().unwrap();
         let result = sandbox
-            .run(temp_dir.path(), "test prompt", Some("container-id"))
+            .run(
+                temp_dir.path(),
+                "test prompt",
+                Some("container-id"),
+                1,
+                None,
+            )
             .await;
         assert!(result.is_ok());
         assert!(result.unwrap().is_empty());
@@ -0,0 +1,240 @@
+//! `.ralphignore` support for the workspace mount.
+//!
+//! The project is bind-mounted into the container as a whole. Paths matched
+//! by `.ralphignore` (gitignore syntax) are masked on top of that mount:
+//! directories with an empty read-only tmpfs, files with a read-only bind of
+//! `/dev/null`. Masked directories are not descended into.
+
+use anyhow::{Context, Result};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+/// Ignore file name, read from the project root.
+pub(crate) const RALPHIGNORE_FILE: &str = ".ralphignore";
+
+/// Directory holding loop state and the prompt file; never masked.
+const RALPH_DIR: &str = ".ralph";
+
+/// Mount options for the empty tmpfs masking an ignored directory.
+pub(crate) const MASK_TMPFS_OPTIONS: &str = "ro,nosuid,nodev,noexec";
+
+/// Extra mounts hiding ignored workspace paths.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct WorkspaceMasks {
+    /// Read-only binds over ignored files (`/dev/null:<path>:ro`).
+    pub binds: Vec<String>,
+    /// Container directories to cover with an empty tmpfs.
+    pub tmpfs: Vec<String>,
+}
+
+/// Reads `.ralphignore` from `project_dir` and builds the masks for a
+/// workspace mounted at `container_root`.
+///
+/// Returns no masks when the file does not exist.
+pub(crate) fn load_workspace_masks(
+    project_dir: &Path,
+    container_root: &str,
+) -> Result<WorkspaceMasks> {
+    let ignore_file = project_dir.join(RALPHIGNORE_FILE);
+    if !ignore_file.exists() {
+        return Ok(WorkspaceMasks::default());
+    }
+
+    let content = std::fs::read_to_string(&ignore_file)
+        .with_context(|| format!("Failed to read {}", ignore_file.display()))?;
+    let matcher = parse_patterns(project_dir, &content)?;
+    let ignored = find_ignored(project_dir, &matcher)?;
+    debug!("{} path(s) masked by {}", ignored.len(), RALPHIGNORE_FILE);
+
+    Ok(build_masks(&ignored, container_root))
+}
+
+// -----------------------------------------------------------------------------
+// Internal types
+// -----------------------------------------------------------------------------
+
+/// A project path matched by `.ralphignore`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct IgnoredPath {
+    /// Path relative to the project root.
+    relative: PathBuf,
+    /// Whether the path is a directory.
+    is_dir: bool,
+}
+
+// -----------------------------------------------------------------------------
+// Helper functions
+// -----------------------------------------------------------------------------
+
+/// Parses gitignore-syntax `content` with patterns anchored at `root`.
+fn parse_patterns(root: &Path, content: &str) -> Result<Gitignore> {
+    let mut builder = GitignoreBuilder::new(root);
+    for line in content.lines() {
+        builder
+            .add_line(None, line)
+            .with_context(|| format!("Invalid pattern in {RALPHIGNORE_FILE}: {line}"))?;
+    }
+    builder
+        .build()
+        .with_context(|| format!("Failed to parse {RALPHIGNORE_FILE}"))
+}
+
+/// Walks `root` and returns ignored paths in sorted order.
+///
+/// Ignored directories are returned without their contents. Symlinks are
+/// not followed, and `.git` and `.ralph` are never descended into or masked.
+fn find_ignored(root: &Path, matcher: &Gitignore) -> Result<Vec<IgnoredPath>> {
+    let mut ignored = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let mut entries = std::fs::read_dir(&dir)
+            .with_context(|| format!("Failed to read {}", dir.display()))?
+            .collect::<std::io::Result<Vec<_>>>()
+            .with_context(|| format!("Failed to read {}", dir.display()))?;
+        entries.sort_by_key(std::fs::DirEntry::file_name);
+
+        for entry in entries {
+            let path = entry.path();
+            let is_dir = entry.file_type()?.is_dir();
+            let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+
+            if relative == Path::new(RALPH_DIR) || relative == Path::new(".git") {
+                if matcher.matched(&path, is_dir).is_ignore() {
+                    warn!(
+                        "{} matches {}; it is needed by the loop and stays visible",
+                        RALPHIGNORE_FILE,
+                        relative.display()
+                    );
+                }
+                continue;
+            }
+
+            if matcher.matched(&path, is_dir).is_ignore() {
+                ignored.push(IgnoredPath { relative, is_dir });
+            } else if is_dir {
+                pending.push(path);
+            }
+        }
+    }
+
+    ignored.sort_by(|a, b| a.relative.cmp(&b.relative));
+    Ok(ignored)
+}
+
+/// Builds tmpfs masks for directories and `/dev/null` binds for files.
+///
+/// Paths that cannot be expressed in a bind spec (non-UTF-8, or containing
+/// `:` for files) are skipped with a warning.
+fn build_masks(ignored: &[IgnoredPath], container_root: &str) -> WorkspaceMasks {
+    let mut masks = WorkspaceMasks::default();
+    let root = container_root.trim_end_matches('/');
+
+    for path in ignored {
+        let Some(relative) = path.relative.to_str() else {
+            warn!("Cannot mask non-UTF-8 path {}", path.relative.display());
+            continue;
+        };
+        let target = format!("{root}/{}", relative.replace('\\', "/"));
+
+        if path.is_dir {
+            masks.tmpfs.push(target);
+        } else if target.contains(':') {
+            warn!(
+                "Cannot mask {}: ':' is not allowed in bind mounts",
+                relative
+            );
+        } else {
+            masks.binds.push(format!("/dev/null:{target}:ro"));
+        }
+    }
+
+    masks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn ignored(relative: &str, is_dir: bool) -> IgnoredPath {
+        IgnoredPath {
+            relative: PathBuf::from(relative),
+            is_dir,
+        }
+    }
+
+    #[test]
+    fn test_parse_patterns_gitignore_syntax() {
+        let root = Path::new("/project");
+        let matcher = parse_patterns(
+            root,
+            "# secrets\n.env\nsecrets/\n*.pem\n!public.pem\n/build\n\n",
+        )
+        .unwrap();
+
+        let is_ignored = |path: &str, is_dir| matcher.matched(root.join(path), is_dir).is_ignore();
+        assert!(is_ignored(".env", false));
+        assert!(is_ignored("config/.env", false));
+        assert!(is_ignored("secrets", true));
+        assert!(!is_ignored("secrets", false)); // Trailing slash: directories only
+        assert!(is_ignored("keys/server.pem", false));
+        assert!(!is_ignored("public.pem", false));
+        assert!(is_ignored("build", true));
+        assert!(!is_ignored("src/build", true)); // Leading slash anchors to root
+        assert!(!is_ignored("src/main.rs", false));
+    }
+
+    #[test]
+    fn test_build_masks() {
+        let masks = build_masks(
+            &[
+                ignored(".env", false),
+                ignored("secrets", true),
+                ignored("target", true),
+                ignored("a:b.txt", false),
+            ],
+            "/workspace",
+        );
+
+        assert_eq!(masks.binds, vec!["/dev/null:/workspace/.env:ro"]);
+        assert_eq!(masks.tmpfs, vec!["/workspace/secrets", "/workspace/target"]);
+    }
+
+    #[test]
+    fn test_load_workspace_masks_without_file() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join(".env"), "TOKEN=x").unwrap();
+
+        let masks = load_workspace_masks(dir.path(), "/workspace").unwrap();
+        assert_eq!(masks, WorkspaceMasks::default());
+    }
+
+    #[test]
+    fn test_load_workspace_masks_walks_project() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        for sub in ["target/debug", "src", "config", ".ralph"] {
+            std::fs::create_dir_all(root.join(sub)).unwrap();
+        }
+        std::fs::write(root.join("target/debug/app"), "").unwrap();
+        std::fs::write(root.join("src/main.rs"), "").unwrap();
+        std::fs::write(root.join("config/.env"), "").unwrap();
+        std::fs::write(root.join(".ralph/prompt.tmp"), "").unwrap();
+        std::fs::write(root.join(RALPHIGNORE_FILE), "target/\n.env\n.ralph/\n").unwrap();
+
+        let masks = load_workspace_masks(root, "/workspace").unwrap();
+        assert_eq!(masks.tmpfs, vec!["/workspace/target"]);
+        assert_eq!(masks.binds, vec!["/dev/null:/workspace/config/.env:ro"]);
+    }
+
+    #[test]
+    fn test_load_workspace_masks_invalid_pattern() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join(RALPHIGNORE_FILE), "src/{a,b\n").unwrap();
+
+        let err = load_workspace_masks(dir.path(), "/workspace").unwrap_err();
+        assert!(err.to_string().contains(RALPHIGNORE_FILE));
+    }
+}
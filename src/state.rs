@@ -2,15 +2,34 @@
 //!
 //! Persists loop state to `.ralph/state.toml` including iteration count,
 //! mode, and timing information.
+//!
+//! Reads and writes take an advisory lock on the state file so concurrent
+//! processes never see a half-written file. A running loop additionally
+//! holds [`LoopLock`] for its lifetime so a second loop in the same
+//! directory refuses to start.
 
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
+use fs2::FileExt;
 use serde::{Deserialize, Serialize};
-use std::fs;
-use std::path::Path;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::agent::TokenUsage;
 
 const STATE_FILE: &str = ".ralph/state.toml";
 
+/// Lock file held by a running loop.
+const LOOP_LOCK_FILE: &str = ".ralph/loop.lock";
+
+/// Entries kept in [`RalphState::error_history`]; older ones are dropped.
+pub(crate) const ERROR_HISTORY_LIMIT: usize = 20;
+
+/// Characters of an error message kept in a history entry.
+/// `last_error` still holds the full message.
+const ERROR_HISTORY_MESSAGE_CHARS: usize = 500;
+
 /// Loop execution mode.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -21,6 +40,65 @@ pub(crate) enum Mode {
     Build,
 }
 
+/// What failed when an error was recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ErrorKind {
+    /// The pre-iteration hook failed.
+    Hook,
+    /// The agent failed, timed out, or was rate limited.
+    Agent,
+    /// The validation command failed.
+    Validation,
+    /// Pushing the iteration's commits failed.
+    Push,
+}
+
+/// One error in [`RalphState::error_history`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct ErrorEntry {
+    /// When the error was recorded.
+    pub timestamp: DateTime<Utc>,
+    /// Iteration the error happened in.
+    pub iteration: u32,
+    /// What failed.
+    pub kind: ErrorKind,
+    /// Error message, truncated for long validation output.
+    pub message: String,
+}
+
+/// Command-line options a loop was started with.
+///
+/// Saved with the state so `ralph resume` continues with the same prompt,
+/// validation, and limits instead of the config defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+#[allow(clippy::struct_excessive_bools)] // One per CLI flag
+pub(crate) struct LoopSettings {
+    /// Prompt file given with `--prompt`.
+    pub prompt: Option<String>,
+    /// Files given with `--prompt-append`.
+    pub prompt_append: Vec<PathBuf>,
+    /// Whether validation was turned off with `--no-validation`.
+    pub no_validation: bool,
+    /// Validation command given with `--validation-cmd`.
+    pub validation_cmd: Option<String>,
+    /// Agent working directory given with `--workdir`.
+    pub workdir: Option<String>,
+    /// Circuit breaker limit given with `--max-errors`.
+    pub max_errors: Option<u32>,
+    /// Validation output lines given with `--tail-errors`.
+    pub tail_errors: Option<usize>,
+    /// Whether the sandbox was turned off with `--no-sandbox`.
+    pub no_sandbox: bool,
+    /// Agent provider given with `--provider`.
+    pub provider: Option<String>,
+    /// Whether branch builds ran one at a time (`--sequential`).
+    pub sequential: bool,
+    /// Whether branch builds stopped at the first failure (`--fail-fast`).
+    pub fail_fast: bool,
+}
+
 /// Persistent state for a Ralph loop.
 ///
 /// Stored in `.ralph/state.toml` and tracks the current iteration,
@@ -46,6 +124,9 @@ pub(crate) struct RalphState {
     /// Used for exponential backoff and circuit breaker logic.
     #[serde(default)]
     pub consecutive_errors: u32,
+    /// Whether the circuit breaker has already used its one cool-down.
+    #[serde(default)]
+    pub circuit_breaker_cooled_down: bool,
     /// Last error message encountered (if any).
     #[serde(default)]
     pub last_error: Option<String>,
@@ -57,6 +138,32 @@ pub(crate) struct RalphState {
     /// Persisted so idle detection continues correctly after restart.
     #[serde(default)]
     pub idle_iterations: u32,
+    /// Commits since `initial_commit` at the last check, for
+    /// `completion.idle_by_commit_count`. Persisted like `last_commit`.
+    #[serde(default)]
+    pub commit_count: Option<u32>,
+    /// Total input tokens reported by the agent across all iterations.
+    #[serde(default)]
+    pub total_input_tokens: u64,
+    /// Total output tokens reported by the agent across all iterations.
+    #[serde(default)]
+    pub total_output_tokens: u64,
+    /// Commit HEAD pointed at when the loop started.
+    /// Rollbacks never reset past it.
+    #[serde(default)]
+    pub initial_commit: Option<String>,
+    /// PID of the process running the loop.
+    #[serde(default)]
+    pub pid: Option<u32>,
+    /// Agent session to resume next iteration (`agent.claude.resume_session`).
+    #[serde(default)]
+    pub session_id: Option<String>,
+    /// Most recent errors, oldest first, capped at [`ERROR_HISTORY_LIMIT`].
+    #[serde(default)]
+    pub error_history: Vec<ErrorEntry>,
+    /// Options the loop was started with, reapplied by `ralph resume`.
+    #[serde(default)]
+    pub settings: LoopSettings,
 }
 
 impl Default for RalphState {
@@ -70,9 +177,18 @@ impl Default for RalphState {
             last_iteration_at: None,
             error_count: 0,
             consecutive_errors: 0,
+            circuit_breaker_cooled_down: false,
             last_error: None,
             last_commit: None,
             idle_iterations: 0,
+            commit_count: None,
+            total_input_tokens: 0,
+            total_output_tokens: 0,
+            initial_commit: None,
+            pid: None,
+            session_id: None,
+            error_history: Vec::new(),
+            settings: LoopSettings::default(),
         }
     }
 }
@@ -86,8 +202,15 @@ impl RalphState {
             return Ok(None);
         }
 
-        let content = fs::read_to_string(&state_path)
+        let mut file = File::open(&state_path)
             .with_context(|| format!("Failed to read state file: {}", state_path.display()))?;
+        FileExt::lock_shared(&file)
+            .with_context(|| format!("Failed to lock state file: {}", state_path.display()))?;
+        let mut content = String::new();
+        file.read_to_string(&mut content)
+            .with_context(|| format!("Failed to read state file: {}", state_path.display()))?;
+        FileExt::unlock(&file)
+            .with_context(|| format!("Failed to unlock state file: {}", state_path.display()))?;
 
         let state: Self = toml::from_str(&content)
             .with_context(|| format!("Failed to parse state file: {}", state_path.display()))?;
@@ -106,6 +229,37 @@ impl RalphState {
         }
     }
 
+    /// Add one iteration's token usage to the running totals
+    pub fn add_token_usage(&mut self, usage: TokenUsage) {
+        self.total_input_tokens = self.total_input_tokens.saturating_add(usage.input_tokens);
+        self.total_output_tokens = self.total_output_tokens.saturating_add(usage.output_tokens);
+    }
+
+    /// Count an iteration error, making it the last error and adding it to the history
+    pub fn record_error(&mut self, kind: ErrorKind, message: String) {
+        self.error_count += 1;
+        self.consecutive_errors += 1;
+        self.push_error_history(kind, &message);
+        self.last_error = Some(message);
+    }
+
+    /// Append an error to the history for the current iteration, dropping the oldest
+    /// entries beyond [`ERROR_HISTORY_LIMIT`]
+    pub fn push_error_history(&mut self, kind: ErrorKind, message: &str) {
+        let message = match message.char_indices().nth(ERROR_HISTORY_MESSAGE_CHARS) {
+            Some((end, _)) => format!("{}...", &message[..end]),
+            None => message.to_string(),
+        };
+        self.error_history.push(ErrorEntry {
+            timestamp: Utc::now(),
+            iteration: self.iteration,
+            kind,
+            message,
+        });
+        let excess = self.error_history.len().saturating_sub(ERROR_HISTORY_LIMIT);
+        self.error_history.drain(..excess);
+    }
+
     /// Save state to file
     pub fn save(&self, project_dir: &Path) -> Result<()> {
         let state_path = project_dir.join(STATE_FILE);
@@ -118,13 +272,57 @@ impl RalphState {
 
         let content = toml::to_string_pretty(self).context("Failed to serialize state")?;
 
-        fs::write(&state_path, content)
+        // Truncate only once the lock is held so readers never see an empty file
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&state_path)
+            .with_context(|| format!("Failed to write state file: {}", state_path.display()))?;
+        file.lock_exclusive()
+            .with_context(|| format!("Failed to lock state file: {}", state_path.display()))?;
+        file.set_len(0)
+            .and_then(|()| file.write_all(content.as_bytes()))
             .with_context(|| format!("Failed to write state file: {}", state_path.display()))?;
+        FileExt::unlock(&file)
+            .with_context(|| format!("Failed to unlock state file: {}", state_path.display()))?;
 
         Ok(())
     }
 }
 
+/// Exclusive lock marking a loop as running in a project directory.
+///
+/// Released when dropped, including when the process exits.
+#[derive(Debug)]
+pub(crate) struct LoopLock {
+    _file: File,
+}
+
+impl LoopLock {
+    /// Takes the loop lock, or returns `None` if another loop holds it.
+    pub fn try_acquire(project_dir: &Path) -> Result<Option<Self>> {
+        let lock_path = project_dir.join(LOOP_LOCK_FILE);
+        if let Some(parent) = lock_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&lock_path)
+            .with_context(|| format!("Failed to open lock file: {}", lock_path.display()))?;
+
+        match file.try_lock_exclusive() {
+            Ok(()) => Ok(Some(Self { _file: file })),
+            Err(e) if e.kind() == fs2::lock_contended_error().kind() => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to lock {}", lock_path.display())),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -140,9 +338,18 @@ mod tests {
             last_iteration_at: Some(Utc::now()),
             error_count: 0,
             consecutive_errors: 0,
+            circuit_breaker_cooled_down: false,
             last_error: None,
             last_commit: None,
             idle_iterations: 0,
+            commit_count: None,
+            total_input_tokens: 0,
+            total_output_tokens: 0,
+            initial_commit: None,
+            pid: None,
+            session_id: None,
+            error_history: Vec::new(),
+            settings: LoopSettings::default(),
         }
     }
 
@@ -221,6 +428,36 @@ mod tests {
         assert!(state.last_error.is_none());
         assert!(state.last_commit.is_none());
         assert_eq!(state.idle_iterations, 0);
+        assert_eq!(state.total_input_tokens, 0);
+        assert_eq!(state.total_output_tokens, 0);
+    }
+
+    #[test]
+    fn test_add_token_usage_accumulates() {
+        let mut state = RalphState::default();
+        state.add_token_usage(TokenUsage {
+            input_tokens: 100,
+            output_tokens: 20,
+        });
+        state.add_token_usage(TokenUsage {
+            input_tokens: 50,
+            output_tokens: 5,
+        });
+        assert_eq!(state.total_input_tokens, 150);
+        assert_eq!(state.total_output_tokens, 25);
+    }
+
+    #[test]
+    fn test_token_totals_persist() {
+        let dir = tempdir().unwrap();
+        let mut state = make_state(true, Mode::Build);
+        state.total_input_tokens = 1234;
+        state.total_output_tokens = 56;
+        state.save(dir.path()).unwrap();
+
+        let loaded = RalphState::load(dir.path()).unwrap().unwrap();
+        assert_eq!(loaded.total_input_tokens, 1234);
+        assert_eq!(loaded.total_output_tokens, 56);
     }
 
     #[test]
@@ -252,6 +489,44 @@ mod tests {
         assert!(dir.path().join(".ralph/state.toml").exists());
     }
 
+    #[test]
+    fn test_save_replaces_longer_content() {
+        let dir = tempdir().unwrap();
+        let mut state = make_state(true, Mode::Build);
+        state.last_error = Some("x".repeat(500));
+        state.save(dir.path()).unwrap();
+
+        state.last_error = None;
+        state.pid = Some(4242);
+        state.save(dir.path()).unwrap();
+
+        let loaded = RalphState::load(dir.path()).unwrap().unwrap();
+        assert_eq!(loaded.last_error, None);
+        assert_eq!(loaded.pid, Some(4242));
+    }
+
+    #[test]
+    fn test_loop_lock_contention() {
+        let dir = tempdir().unwrap();
+
+        let first = LoopLock::try_acquire(dir.path()).unwrap();
+        assert!(first.is_some());
+        assert!(LoopLock::try_acquire(dir.path()).unwrap().is_none());
+
+        drop(first);
+        assert!(LoopLock::try_acquire(dir.path()).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_state_io_while_loop_lock_held() {
+        let dir = tempdir().unwrap();
+        let _lock = LoopLock::try_acquire(dir.path()).unwrap().unwrap();
+
+        make_state(true, Mode::Plan).save(dir.path()).unwrap();
+        let loaded = RalphState::load(dir.path()).unwrap().unwrap();
+        assert_eq!(loaded.mode, Mode::Plan);
+    }
+
     #[test]
     fn test_state_with_errors() {
         let dir = tempdir().unwrap();
@@ -264,9 +539,18 @@ mod tests {
             last_iteration_at: Some(Utc::now()),
             error_count: 3,
             consecutive_errors: 2,
+            circuit_breaker_cooled_down: false,
             last_error: Some("Test error".to_string()),
             last_commit: None,
             idle_iterations: 0,
+            commit_count: None,
+            total_input_tokens: 0,
+            total_output_tokens: 0,
+            initial_commit: None,
+            pid: None,
+            session_id: None,
+            error_history: Vec::new(),
+            settings: LoopSettings::default(),
         };
 
         state.save(dir.path()).unwrap();
@@ -296,6 +580,97 @@ last_iteration_at = "2024-01-01T12:05:00Z"
         assert!(state.last_error.is_none()); // Should default to None
         assert!(state.last_commit.is_none()); // Should default to None
         assert_eq!(state.idle_iterations, 0); // Should default to 0
+        assert_eq!(state.total_input_tokens, 0); // Should default to 0
+        assert_eq!(state.total_output_tokens, 0); // Should default to 0
+        assert!(state.initial_commit.is_none()); // Should default to None
+        assert!(state.error_history.is_empty()); // Should default to empty
+        assert_eq!(state.settings, LoopSettings::default()); // Should default to no options
+    }
+
+    #[test]
+    fn test_state_roundtrip_with_settings_and_history() {
+        let dir = tempdir().unwrap();
+        let mut state = make_state(true, Mode::Build);
+        state.record_error(ErrorKind::Validation, "cargo test failed".to_string());
+        state.settings = LoopSettings {
+            prompt: Some("PROMPT_custom.md".to_string()),
+            prompt_append: vec![PathBuf::from("notes.md")],
+            validation_cmd: Some("make check".to_string()),
+            max_errors: Some(3),
+            sequential: true,
+            ..LoopSettings::default()
+        };
+
+        state.save(dir.path()).unwrap();
+        let loaded = RalphState::load(dir.path()).unwrap().unwrap();
+
+        assert_eq!(loaded.settings, state.settings);
+        assert_eq!(loaded.error_history, state.error_history);
+    }
+
+    #[test]
+    fn test_record_error_updates_counters_and_history() {
+        let mut state = make_state(true, Mode::Build);
+        state.record_error(ErrorKind::Agent, "Agent timeout: took too long".to_string());
+
+        assert_eq!(state.error_count, 1);
+        assert_eq!(state.consecutive_errors, 1);
+        assert_eq!(
+            state.last_error.as_deref(),
+            Some("Agent timeout: took too long")
+        );
+        assert_eq!(state.error_history.len(), 1);
+        let entry = &state.error_history[0];
+        assert_eq!(entry.iteration, 5);
+        assert_eq!(entry.kind, ErrorKind::Agent);
+        assert_eq!(entry.message, "Agent timeout: took too long");
+    }
+
+    #[test]
+    fn test_error_history_keeps_most_recent_entries() {
+        let mut state = make_state(true, Mode::Build);
+        for iteration in 1..=u32::try_from(ERROR_HISTORY_LIMIT).unwrap() + 5 {
+            state.iteration = iteration;
+            state.push_error_history(ErrorKind::Validation, &format!("error {iteration}"));
+        }
+
+        assert_eq!(state.error_history.len(), ERROR_HISTORY_LIMIT);
+        assert_eq!(state.error_history[0].iteration, 6);
+        assert_eq!(state.error_history[0].message, "error 6");
+        assert_eq!(
+            state.error_history.last().unwrap().message,
+            format!("error {}", ERROR_HISTORY_LIMIT + 5)
+        );
+    }
+
+    #[test]
+    fn test_error_history_truncates_long_messages() {
+        let mut state = make_state(true, Mode::Build);
+        let message = "é".repeat(ERROR_HISTORY_MESSAGE_CHARS + 100);
+        state.record_error(ErrorKind::Validation, message.clone());
+
+        let stored = &state.error_history[0].message;
+        assert_eq!(stored.chars().count(), ERROR_HISTORY_MESSAGE_CHARS + 3);
+        assert!(stored.ends_with("..."));
+        // The last error keeps the full message
+        assert_eq!(state.last_error, Some(message));
+    }
+
+    #[test]
+    fn test_error_history_roundtrip() {
+        let dir = tempdir().unwrap();
+        let mut state = make_state(true, Mode::Build);
+        state.record_error(
+            ErrorKind::Hook,
+            "Pre-iteration hook error:exit 1".to_string(),
+        );
+        state.iteration += 1;
+        state.push_error_history(ErrorKind::Push, "Git push failed: rejected");
+        state.save(dir.path()).unwrap();
+
+        let loaded = RalphState::load(dir.path()).unwrap().unwrap();
+        assert_eq!(loaded.error_history, state.error_history);
+        assert_eq!(loaded.error_history[1].kind, ErrorKind::Push);
     }
 
     #[test]
@@ -310,9 +685,18 @@ last_iteration_at = "2024-01-01T12:05:00Z"
             last_iteration_at: Some(Utc::now()),
             error_count: 0,
             consecutive_errors: 0,
+            circuit_breaker_cooled_down: false,
             last_error: None,
             last_commit: Some("abc123def456".to_string()),
             idle_iterations: 1,
+            commit_count: None,
+            total_input_tokens: 0,
+            total_output_tokens: 0,
+            initial_commit: None,
+            pid: None,
+            session_id: None,
+            error_history: Vec::new(),
+            settings: LoopSettings::default(),
         };
 
         state.save(dir.path()).unwrap();
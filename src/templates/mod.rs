@@ -15,6 +15,53 @@ pub(crate) const RULES_MDC: &str = include_str!("rules.mdc");
 /// `AGENTS.md` template.
 pub(crate) const AGENTS_MD: &str = include_str!("agents.md");
 
+/// Validation command line in [`RALPH_TOML`], replaced per project template.
+const DEFAULT_VALIDATION_LINE: &str = "command = \"nix flake check --quiet\"";
+
+/// Language-specific variant of the init templates (`ralph init --template`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ProjectTemplate {
+    /// Default `validation.command` written to `ralph.toml`.
+    pub validation_command: &'static str,
+    /// `PROMPT_build.md` content.
+    pub prompt_build: &'static str,
+}
+
+impl ProjectTemplate {
+    /// Renders `ralph.toml` with this template's validation command.
+    pub(crate) fn ralph_toml(&self) -> String {
+        let line = format!(
+            "command = {}",
+            toml::Value::String(self.validation_command.to_string())
+        );
+        RALPH_TOML.replacen(DEFAULT_VALIDATION_LINE, &line, 1)
+    }
+}
+
+/// Nix projects (default): `nix flake check`.
+pub(crate) const NIX: ProjectTemplate = ProjectTemplate {
+    validation_command: "nix flake check --quiet",
+    prompt_build: PROMPT_BUILD,
+};
+
+/// Rust projects: clippy with warnings denied, then tests.
+pub(crate) const RUST: ProjectTemplate = ProjectTemplate {
+    validation_command: "sh -c \"cargo clippy --all-targets -- -D warnings && cargo test\"",
+    prompt_build: include_str!("prompt_build_rust.md"),
+};
+
+/// Node.js projects: `npm test`.
+pub(crate) const NODE: ProjectTemplate = ProjectTemplate {
+    validation_command: "npm test",
+    prompt_build: include_str!("prompt_build_node.md"),
+};
+
+/// Python projects: pytest.
+pub(crate) const PYTHON: ProjectTemplate = ProjectTemplate {
+    validation_command: "python -m pytest",
+    prompt_build: include_str!("prompt_build_python.md"),
+};
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -44,6 +91,21 @@ mod tests {
         assert_eq!(config.monitoring.max_consecutive_errors, 5);
     }
 
+    /// Every project template renders a config that parses and carries its
+    /// validation command.
+    #[test]
+    fn project_templates_parse_into_config() {
+        for template in [NIX, RUST, NODE, PYTHON] {
+            let toml = template.ralph_toml();
+            let config: Config = toml::from_str(&toml).unwrap_or_else(|e| {
+                panic!("{} template should parse: {e}", template.validation_command)
+            });
+            assert_eq!(config.validation.command, template.validation_command);
+            assert!(!template.prompt_build.is_empty());
+        }
+        assert_eq!(NIX.ralph_toml(), RALPH_TOML);
+    }
+
     /// Validates that `ralph.toml` template is non-empty and reasonable size.
     #[test]
     fn ralph_toml_template_has_content() {
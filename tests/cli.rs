@@ -38,10 +38,15 @@ fn test_help_shows_all_commands() {
         .stdout(predicate::str::contains("ralph"))
         .stdout(predicate::str::contains("init"))
         .stdout(predicate::str::contains("loop"))
+        .stdout(predicate::str::contains("resume"))
         .stdout(predicate::str::contains("status"))
         .stdout(predicate::str::contains("cancel"))
+        .stdout(predicate::str::contains("logs"))
         .stdout(predicate::str::contains("revert"))
+        .stdout(predicate::str::contains("diff"))
         .stdout(predicate::str::contains("clean"))
+        .stdout(predicate::str::contains("doctor"))
+        .stdout(predicate::str::contains("config"))
         .stdout(predicate::str::contains("image"));
 }
 
@@ -73,7 +78,12 @@ fn test_loop_help_shows_all_options() {
         .stdout(predicate::str::contains("--unlimited"))
         .stdout(predicate::str::contains("--no-sandbox"))
         .stdout(predicate::str::contains("--prompt"))
-        .stdout(predicate::str::contains("--provider"));
+        .stdout(predicate::str::contains("--provider"))
+        .stdout(predicate::str::contains("--dry-run"))
+        .stdout(predicate::str::contains("--no-validation"))
+        .stdout(predicate::str::contains("--validation-cmd"))
+        .stdout(predicate::str::contains("--tail-errors"))
+        .stdout(predicate::str::contains("--simulate"));
 }
 
 #[test]
@@ -113,6 +123,32 @@ fn test_init_creates_all_files() {
     assert!(toml_content.contains("[agent]"));
 }
 
+#[test]
+fn test_init_template_sets_validation_command() {
+    let dir = TempDir::new().unwrap();
+
+    ralph_in(&dir)
+        .args(["init", "--template", "node"])
+        .assert()
+        .success();
+
+    let toml_content = fs::read_to_string(dir.path().join("ralph.toml")).unwrap();
+    assert!(toml_content.contains("command = \"npm test\""));
+    let prompt = fs::read_to_string(dir.path().join("PROMPT_build.md")).unwrap();
+    assert!(prompt.contains("npm test"));
+}
+
+#[test]
+fn test_init_rejects_unknown_template() {
+    let dir = TempDir::new().unwrap();
+
+    ralph_in(&dir)
+        .args(["init", "--template", "cobol"])
+        .assert()
+        .failure();
+    assert!(!dir.path().join("ralph.toml").exists());
+}
+
 #[test]
 fn test_init_skips_existing_without_force() {
     let dir = TempDir::new().unwrap();
@@ -168,6 +204,40 @@ fn test_status_no_active_loop() {
         .stdout(predicate::str::contains("No active Ralph loop"));
 }
 
+#[test]
+fn test_status_watch_exits_without_state() {
+    let dir = TempDir::new().unwrap();
+
+    ralph_in(&dir)
+        .args(["status", "--watch"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No active Ralph loop"))
+        .stdout(predicate::str::contains("Stopped watching"));
+}
+
+#[test]
+fn test_status_watch_rejects_zero_interval() {
+    let dir = TempDir::new().unwrap();
+
+    ralph_in(&dir)
+        .args(["status", "--watch", "0"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("at least 1 second"));
+}
+
+#[test]
+fn test_status_rejects_zero_commits() {
+    let dir = TempDir::new().unwrap();
+
+    ralph_in(&dir)
+        .args(["status", "--commits", "0"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--commits"));
+}
+
 #[test]
 fn test_status_with_state_file() {
     let dir = TempDir::new().unwrap();
@@ -200,6 +270,44 @@ idle_iterations = 0
         .stdout(predicate::str::contains("5"));
 }
 
+#[test]
+fn test_status_json_no_active_loop() {
+    let dir = TempDir::new().unwrap();
+
+    ralph_in(&dir)
+        .args(["status", "--json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"exists\": false"))
+        .stdout(predicate::str::contains("\"active\": false"));
+}
+
+#[test]
+fn test_status_json_with_state_file() {
+    let dir = TempDir::new().unwrap();
+
+    fs::create_dir_all(dir.path().join(".ralph")).unwrap();
+    fs::write(
+        dir.path().join(".ralph/state.toml"),
+        r#"
+active = true
+iteration = 5
+mode = "build"
+started_at = "2024-01-01T00:00:00Z"
+error_count = 1
+"#,
+    )
+    .unwrap();
+
+    ralph_in(&dir)
+        .args(["status", "--json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"exists\": true"))
+        .stdout(predicate::str::contains("\"iteration\": 5"))
+        .stdout(predicate::str::contains("\"elapsed_seconds\""));
+}
+
 // -----------------------------------------------------------------------------
 // Cancel command tests
 // -----------------------------------------------------------------------------
@@ -253,6 +361,111 @@ idle_iterations = 0
     assert!(state.contains("active = false"));
 }
 
+#[test]
+fn test_cancel_help_shows_force() {
+    ralph()
+        .args(["cancel", "--help"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("--force"));
+}
+
+// -----------------------------------------------------------------------------
+// Logs command tests
+// -----------------------------------------------------------------------------
+
+#[test]
+fn test_logs_no_log_file() {
+    let dir = TempDir::new().unwrap();
+
+    ralph_in(&dir)
+        .arg("logs")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No log file found"));
+}
+
+#[test]
+fn test_logs_filters_by_event() {
+    let dir = TempDir::new().unwrap();
+
+    fs::create_dir_all(dir.path().join(".ralph")).unwrap();
+    fs::write(
+        dir.path().join(".ralph/loop.log.2024-01-01"),
+        concat!(
+            r#"{"timestamp":"2024-01-01T00:00:00Z","level":"INFO","fields":{"event":"iteration_start","iteration":1}}"#,
+            "\n",
+            r#"{"timestamp":"2024-01-01T00:05:00Z","level":"INFO","fields":{"event":"loop_end","reason":"completion_detected"}}"#,
+            "\n",
+        ),
+    )
+    .unwrap();
+
+    ralph_in(&dir)
+        .args(["logs", "--event", "loop_end"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("completion_detected"))
+        .stdout(predicate::str::contains("iteration_start").not());
+}
+
+// -----------------------------------------------------------------------------
+// Resume command tests
+// -----------------------------------------------------------------------------
+
+#[test]
+fn test_resume_help_shows_options() {
+    ralph()
+        .args(["resume", "--help"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("--max-iterations"))
+        .stdout(predicate::str::contains("--no-sandbox"))
+        .stdout(predicate::str::contains("--provider"));
+}
+
+#[test]
+fn test_resume_without_state_fails() {
+    let dir = TempDir::new().unwrap();
+
+    ralph_in(&dir)
+        .arg("resume")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No saved Ralph loop state"))
+        .stderr(predicate::str::contains("ralph loop"));
+}
+
+#[test]
+fn test_resume_while_loop_running_leaves_state_untouched() {
+    use fs2::FileExt;
+
+    let dir = TempDir::new().unwrap();
+    fs::create_dir_all(dir.path().join(".ralph")).unwrap();
+    let state = r#"
+active = false
+mode = "build"
+iteration = 4
+max_iterations = 10
+started_at = "2024-01-01T12:00:00Z"
+consecutive_errors = 3
+"#;
+    fs::write(dir.path().join(".ralph/state.toml"), state).unwrap();
+
+    // Stand in for a running loop
+    let lock = fs::File::create(dir.path().join(".ralph/loop.lock")).unwrap();
+    lock.try_lock_exclusive().unwrap();
+
+    ralph_in(&dir)
+        .args(["resume", "-m", "5"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("already running"));
+
+    let after = fs::read_to_string(dir.path().join(".ralph/state.toml")).unwrap();
+    assert_eq!(after, state);
+}
+
 // -----------------------------------------------------------------------------
 // Clean command tests
 // -----------------------------------------------------------------------------
@@ -276,6 +489,26 @@ fn test_clean_removes_state() {
     assert!(dir.path().join("ralph.toml").exists());
 }
 
+#[test]
+fn test_clean_dry_run_keeps_files() {
+    let dir = TempDir::new().unwrap();
+    ralph_in(&dir).arg("init").assert().success();
+    fs::create_dir_all(dir.path().join(".ralph")).unwrap();
+    fs::write(dir.path().join(".ralph/state.toml"), "active = false").unwrap();
+
+    ralph_in(&dir)
+        .args(["clean", "--all", "--worktrees", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Dry run"))
+        .stdout(predicate::str::contains(".ralph/state.toml"))
+        .stdout(predicate::str::contains("ralph.toml"))
+        .stdout(predicate::str::contains("No worktrees found"));
+
+    assert!(dir.path().join(".ralph/state.toml").exists());
+    assert!(dir.path().join("ralph.toml").exists());
+}
+
 #[test]
 fn test_clean_all_removes_config_files() {
     let dir = TempDir::new().unwrap();
@@ -346,10 +579,147 @@ fn test_revert_not_a_git_repo() {
         .stderr(predicate::str::contains("git"));
 }
 
+// -----------------------------------------------------------------------------
+// Diff command tests
+// -----------------------------------------------------------------------------
+
+#[test]
+fn test_diff_without_state() {
+    let dir = TempDir::new().unwrap();
+
+    ralph_in(&dir)
+        .arg("diff")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No saved Ralph loop state"));
+}
+
+#[test]
+fn test_diff_name_only_conflicts_with_full() {
+    let dir = TempDir::new().unwrap();
+
+    ralph_in(&dir)
+        .args(["diff", "--name-only", "--full"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
 // -----------------------------------------------------------------------------
 // Loop command tests (without running actual loop)
 // -----------------------------------------------------------------------------
 
+#[test]
+fn test_loop_dry_run_prints_prompt() {
+    let dir = TempDir::new().unwrap();
+    ralph_in(&dir).arg("init").assert().success();
+
+    fs::create_dir_all(dir.path().join(".ralph")).unwrap();
+    fs::write(
+        dir.path().join(".ralph/state.toml"),
+        r#"
+active = true
+iteration = 4
+mode = "plan"
+started_at = "2024-01-01T00:00:00Z"
+last_error = "Validation error:cargo check failed"
+"#,
+    )
+    .unwrap();
+
+    ralph_in(&dir)
+        .args(["loop", "plan", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Dry run"))
+        .stdout(predicate::str::contains("PROMPT_plan.md"))
+        .stdout(predicate::str::contains(
+            "VALIDATION ERROR FROM PREVIOUS ITERATION",
+        ))
+        .stdout(predicate::str::contains("cargo check failed"));
+
+    // State is left untouched
+    let state = fs::read_to_string(dir.path().join(".ralph/state.toml")).unwrap();
+    assert!(state.contains("iteration = 4"));
+    assert!(!state.contains("initial_commit"));
+}
+
+#[test]
+fn test_loop_dry_run_json_events() {
+    let dir = TempDir::new().unwrap();
+    ralph_in(&dir).arg("init").assert().success();
+
+    let output = ralph_in(&dir)
+        .args(["loop", "plan", "--dry-run", "--events", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Every stdout line is a JSON event
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let events: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["event"], "dry_run");
+    assert!(events[0]["prompt_file"]
+        .as_str()
+        .unwrap()
+        .ends_with("PROMPT_plan.md"));
+}
+
+#[test]
+fn test_loop_simulate_runs_to_completion() {
+    let dir = TempDir::new().unwrap();
+    ralph_in(&dir).arg("init").assert().success();
+
+    let output = ralph_in(&dir)
+        .args(["loop", "build", "--simulate", "--events", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let events: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let names: Vec<&str> = events
+        .iter()
+        .map(|e| e["event"].as_str().unwrap())
+        .collect();
+
+    assert_eq!(names.first(), Some(&"loop_start"));
+    assert_eq!(events[0]["provider"], "simulated");
+    assert_eq!(events[0]["sandbox_enabled"], false);
+    assert!(names.contains(&"iteration_start"));
+    assert!(names.contains(&"completion_detected"));
+    let finished = events.last().unwrap();
+    assert_eq!(finished["event"], "loop_finished");
+    assert_eq!(finished["reason"], "agent_idle");
+
+    // The loop leaves state inactive, like a real run
+    let state = fs::read_to_string(dir.path().join(".ralph/state.toml")).unwrap();
+    assert!(state.contains("active = false"));
+}
+
+#[test]
+fn test_loop_output_writes_summary() {
+    let dir = TempDir::new().unwrap();
+    ralph_in(&dir).arg("init").assert().success();
+
+    ralph_in(&dir)
+        .args(["loop", "build", "--simulate", "--output", "reports/run.md"])
+        .assert()
+        .success();
+
+    let summary = fs::read_to_string(dir.path().join("reports/run.md")).unwrap();
+    assert!(summary.starts_with("# Ralph Run Summary"));
+    assert!(summary.contains("| Termination reason | `agent_idle` |"));
+    assert!(summary.contains("## Errors"));
+}
+
 #[test]
 fn test_loop_without_init() {
     let dir = TempDir::new().unwrap();
@@ -369,6 +739,122 @@ fn test_loop_without_init() {
         .stderr(predicate::str::contains("ralph init"));
 }
 
+#[test]
+fn test_loop_refuses_protected_branch() {
+    let dir = TempDir::new().unwrap();
+    let git_ok = std::process::Command::new("git")
+        .current_dir(dir.path())
+        .args(["init", "-q", "-b", "main"])
+        .status()
+        .is_ok_and(|s| s.success());
+    if !git_ok {
+        return;
+    }
+    ralph_in(&dir).arg("init").assert().success();
+
+    ralph_in(&dir)
+        .args(["loop", "build", "--no-sandbox"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("protected branch 'main'"))
+        .stderr(predicate::str::contains("git.auto_branch"));
+}
+
+#[test]
+fn test_env_file_sets_provider() {
+    let dir = TempDir::new().unwrap();
+    ralph_in(&dir).arg("init").assert().success();
+    fs::write(
+        dir.path().join(".env"),
+        "# secrets\nexport RALPH_PROVIDER=bogus\n",
+    )
+    .unwrap();
+
+    ralph_in(&dir)
+        .args(["--env-file", ".env", "loop", "build", "--no-sandbox"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("bogus"));
+}
+
+#[test]
+fn test_env_file_errors() {
+    let dir = TempDir::new().unwrap();
+
+    ralph_in(&dir)
+        .args(["--env-file", "missing.env", "status"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Failed to read env file"));
+
+    fs::write(dir.path().join(".env"), "OK=1\nnot a variable\n").unwrap();
+    ralph_in(&dir)
+        .args(["--env-file", ".env", "status"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("line 2"));
+}
+
+#[test]
+fn test_config_flag_loads_custom_path() {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("ci.toml"),
+        "[sandbox]\nimage = \"ralph:ci\"\n",
+    )
+    .unwrap();
+
+    ralph_in(&dir)
+        .args(["--config", "ci.toml", "config", "show"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("image = \"ralph:ci\""));
+
+    ralph_in(&dir)
+        .args(["config", "show", "--config", "missing.toml"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Failed to read config file"));
+}
+
+#[test]
+fn test_plan_list_json() {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("IMPLEMENTATION_PLAN.md"),
+        "## Branch: done\nGoal: Finished\nBase: main\n\n- [x] Task\n\n\
+         ## Branch: todo\nGoal: Pending\nBase: main\n\n- [ ] Task\n",
+    )
+    .unwrap();
+
+    ralph_in(&dir)
+        .args(["plan", "list", "--json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"status\": \"complete\""))
+        .stdout(predicate::str::contains("\"status\": \"incomplete\""))
+        .stdout(predicate::str::contains("\"incomplete\": 1"));
+
+    ralph_in(&dir)
+        .args(["plan", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "2 branch(es): 1 complete, 1 incomplete",
+        ));
+}
+
+#[test]
+fn test_plan_list_without_plan_fails() {
+    let dir = TempDir::new().unwrap();
+
+    ralph_in(&dir)
+        .args(["plan", "list"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("IMPLEMENTATION_PLAN.md"));
+}
+
 #[test]
 fn test_loop_invalid_provider() {
     let dir = TempDir::new().unwrap();
@@ -383,6 +869,140 @@ fn test_loop_invalid_provider() {
         .stderr(predicate::str::contains("provider"));
 }
 
+// -----------------------------------------------------------------------------
+// Doctor command tests
+// -----------------------------------------------------------------------------
+
+#[test]
+fn test_doctor_passes_without_sandbox() {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("ralph.toml"),
+        "[agent]\nprovider = \"claude\"\n[agent.claude]\npath = \"sh\"\n\
+         [sandbox]\nenabled = false\n[git]\nauto_pr = false\n",
+    )
+    .unwrap();
+
+    ralph_in(&dir)
+        .arg("doctor")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Config: ralph.toml parses"))
+        .stdout(predicate::str::contains("Agent binary: sh found"));
+}
+
+#[test]
+fn test_doctor_fails_on_missing_agent() {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("ralph.toml"),
+        "[agent.cursor]\npath = \"ralph-no-such-agent\"\n[sandbox]\nenabled = false\n",
+    )
+    .unwrap();
+
+    ralph_in(&dir)
+        .arg("doctor")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("ralph-no-such-agent not found"))
+        .stderr(predicate::str::contains("check(s) failed"));
+}
+
+#[test]
+fn test_doctor_reports_invalid_config() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("ralph.toml"), "[agent\n").unwrap();
+
+    ralph_in(&dir)
+        .arg("doctor")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("Failed to parse config file"));
+}
+
+// -----------------------------------------------------------------------------
+// Config command tests
+// -----------------------------------------------------------------------------
+
+#[test]
+fn test_config_validate_accepts_init_template() {
+    let dir = TempDir::new().unwrap();
+    ralph_in(&dir).arg("init").assert().success();
+
+    ralph_in(&dir)
+        .args(["config", "validate"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ralph.toml is valid"));
+}
+
+#[test]
+fn test_config_validate_reports_all_problems() {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("ralph.toml"),
+        "[sandbox.resources]\nmemory = \"lots\"\ncpus = \"four\"\n",
+    )
+    .unwrap();
+
+    ralph_in(&dir)
+        .args(["config", "validate"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("sandbox.resources.memory"))
+        .stdout(predicate::str::contains("sandbox.resources.cpus"))
+        .stderr(predicate::str::contains("2 problem(s)"));
+
+    // Agent settings are reported with the rest instead of failing the load
+    fs::write(
+        dir.path().join("ralph.toml"),
+        "[agent.cursor]\noutput_format = \"jsn\"\n[agent.claude]\noutput_format = \"JSON\"\n",
+    )
+    .unwrap();
+    ralph_in(&dir)
+        .args(["config", "validate"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("agent.cursor.output_format"))
+        .stdout(predicate::str::contains("agent.claude.output_format"))
+        .stderr(predicate::str::contains("2 problem(s)"));
+}
+
+#[test]
+fn test_config_show_prints_defaults() {
+    let dir = TempDir::new().unwrap();
+
+    ralph_in(&dir)
+        .args(["config", "show"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[agent]"))
+        .stdout(predicate::str::contains("provider = \"cursor\""));
+}
+
+#[test]
+fn test_config_show_redacts_secrets() {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("ralph.toml"),
+        "[sandbox]\nimage = \"${RALPH_TEST_IMAGE}\"\n[sandbox.env.vars]\nAPI_TOKEN = \"s3cret\"\n\
+         [monitoring.notifications]\non_error = \"discord:https://discord.com/api/webhooks/1/abc\"\n",
+    )
+    .unwrap();
+
+    ralph_in(&dir)
+        .env("RALPH_TEST_IMAGE", "registry.corp/ralph:token-abc")
+        .args(["config", "show"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("API_TOKEN = \"***\""))
+        .stdout(predicate::str::contains("image = \"***\""))
+        .stdout(predicate::str::contains("on_error = \"discord:***\""))
+        .stdout(predicate::str::contains("s3cret").not())
+        .stdout(predicate::str::contains("token-abc").not())
+        .stdout(predicate::str::contains("webhooks").not());
+}
+
 // -----------------------------------------------------------------------------
 // Error message tests
 // -----------------------------------------------------------------------------